@@ -4,7 +4,7 @@ RED='\033[0;31m'
 NC='\033[0m' # No Color
 
 # Start the db with in memory option
-cargo run -- -m memory > /dev/null 2>&1 &
+cargo run -- serve --backend memory > /dev/null 2>&1 &
 DB_PID=$!
 sleep 2 # wait for the db to start
 
@@ -18,18 +18,78 @@ else
     echo -e "${RED}Health check failed with status code $HTTP_CODE${NC}"
 fi
 
+echo -e "\n=== GET /api/openapi.json ==="
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" http://localhost:8080/api/openapi.json)
+if [ "$HTTP_CODE" -eq 200 ]; then
+    echo -e "${GREEN}OpenAPI document served!${NC}"
+else
+    echo -e "${RED}OpenAPI document failed with status code $HTTP_CODE${NC}"
+fi
+
+echo -e "\n=== GET /api/v1/health - deprecated ==="
+if curl -s -D - -o /dev/null http://localhost:8080/api/v1/health | grep -qi "^sunset:"; then
+    echo -e "${GREEN}v1 announces its sunset!${NC}"
+else
+    echo -e "${RED}v1 sent no Sunset header${NC}"
+fi
+
+# Register and log in, all todo routes need the token
+echo -e "\n=== POST /api/auth/register and /api/auth/login ==="
+CREDENTIALS='{"username":"integration","password":"integration-test"}'
+curl -s -o /dev/null -X POST http://localhost:8080/api/auth/register -H "Content-Type: application/json" -d "$CREDENTIALS"
+TOKEN=$(curl -s -X POST http://localhost:8080/api/auth/login -H "Content-Type: application/json" -d "$CREDENTIALS" | sed -n 's/.*"token":"\([^"]*\)".*/\1/p')
+if [ -n "$TOKEN" ]; then
+    echo -e "${GREEN}Login passed!${NC}"
+else
+    echo -e "${RED}Login failed${NC}"
+fi
+AUTH="Authorization: Bearer $TOKEN"
+
+echo -e "\n=== GET /api/todos without token ==="
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" http://localhost:8080/api/todos)
+if [ "$HTTP_CODE" -eq 401 ]; then
+    echo -e "${GREEN}Unauthenticated request rejected!${NC}"
+else
+    echo -e "${RED}Unauthenticated request returned status code $HTTP_CODE${NC}"
+fi
+
+echo -e "\n=== POST /api/keys and a read-only key ==="
+KEY=$(curl -s -H "$AUTH" -X POST http://localhost:8080/api/keys -H "Content-Type: application/json" -d '{"name":"integration","scopes":["read"]}' | sed -n 's/.*"secret":"\([^"]*\)".*/\1/p')
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -H "Authorization: Bearer $KEY" -X POST http://localhost:8080/api/todos -H "Content-Type: application/json" -d '{"title":"Not allowed"}')
+if [ -n "$KEY" ] && [ "$HTTP_CODE" -eq 403 ]; then
+    echo -e "${GREEN}Read-only key cannot write!${NC}"
+else
+    echo -e "${RED}Read-only key write returned status code $HTTP_CODE${NC}"
+fi
+
+# Follow the event stream while the todo is added
+EVENTS_FILE=$(mktemp)
+curl -s -N -H "$AUTH" http://localhost:8080/api/events > "$EVENTS_FILE" &
+EVENTS_PID=$!
+sleep 1
+
 # Add a todo
 echo -e "\n=== POST /api/todos ==="
-HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -X POST http://localhost:8080/api/todos -H "Content-Type: application/json" -d '{"title":"Test Todo","completed":false}')
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -H "$AUTH" -X POST http://localhost:8080/api/todos -H "Content-Type: application/json" -d '{"title":"Test Todo","completed":false}')
 if [ "$HTTP_CODE" -eq 200 ]; then
     echo -e "${GREEN}Todo creation passed!${NC}"
 else
     echo -e "${RED}Todo creation failed with status code $HTTP_CODE${NC}"
 fi
 
+echo -e "\n=== GET /api/events ==="
+sleep 1
+kill $EVENTS_PID 2>/dev/null
+if grep -q "^event: created" "$EVENTS_FILE"; then
+    echo -e "${GREEN}Creation was streamed!${NC}"
+else
+    echo -e "${RED}No created event was streamed${NC}"
+fi
+rm -f "$EVENTS_FILE"
+
 # Get Todo
 echo -e "\n=== GET /api/todos/{id} ==="
-HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -X GET http://localhost:8080/api/todos/1)
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -H "$AUTH" -X GET http://localhost:8080/api/todos/1)
 if [ "$HTTP_CODE" -eq  200 ]; then
   echo -e "${GREEN}GET todo passed${NC}"
 else
@@ -38,7 +98,7 @@ fi
 
 # Get all Todos
 echo -e "\n=== GET /api/todos ==="
-HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -X GET http://localhost:8080/api/todos)
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -H "$AUTH" -X GET http://localhost:8080/api/todos)
 if [ "$HTTP_CODE" -eq  200 ]; then
   echo -e "${GREEN}GET todos passed${NC}"
 else
@@ -48,16 +108,34 @@ fi
 
 # PUT Todo
 echo -e "\n=== PUT /api/todos ==="
-HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -X PUT http://localhost:8080/api/todos/1 -H "Content-Type: application/json" -d '{"todo_id": 1,"title":"Updated Test Todo","completed":true}')
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -H "$AUTH" -X PUT http://localhost:8080/api/todos/1 -H "Content-Type: application/json" -d '{"todo_id": 1,"title":"Updated Test Todo","completed":true}')
 if [ "$HTTP_CODE" -eq  200 ]; then
   echo -e "${GREEN}PUT todo passed${NC}"
 else
   echo -e "${RED}PUT todo failed with status code $HTTP_CODE${NC}"
 fi
 
+# PATCH Todo
+echo -e "\n=== PATCH /api/todos/{id} ==="
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -H "$AUTH" -X PATCH http://localhost:8080/api/todos/1 -H "Content-Type: application/json" -d '{"completed":false}')
+if [ "$HTTP_CODE" -eq  200 ]; then
+  echo -e "${GREEN}PATCH todo passed${NC}"
+else
+  echo -e "${RED}PATCH todo failed with status code $HTTP_CODE${NC}"
+fi
+
+# PATCH with an outdated ETag - should return 412
+echo -e "\n=== PATCH /api/todos/{id} with If-Match ==="
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -H "$AUTH" -H 'If-Match: "outdated"' -X PATCH http://localhost:8080/api/todos/1 -H "Content-Type: application/json" -d '{"completed":true}')
+if [ "$HTTP_CODE" -eq 412 ]; then
+  echo -e "${GREEN}Outdated If-Match passed${NC}"
+else
+  echo -e "${RED}Outdated If-Match failed with status code $HTTP_CODE${NC}"
+fi
+
 # DELETE Todo
 echo -e "\n=== DELETE /api/todos/{id} ==="
-HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -X DELETE http://localhost:8080/api/todos/1)
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -H "$AUTH" -X DELETE http://localhost:8080/api/todos/1)
 if [ "$HTTP_CODE" -eq 200 ]; then
   echo -e "${GREEN}DELETE todo passed${NC}"
 else
@@ -66,13 +144,40 @@ fi
 
 # Verify deletion - should return 404
 echo -e "\n=== Verify deletion (GET /api/todos/1 should fail) ==="
-HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -X GET http://localhost:8080/api/todos/1)
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -H "$AUTH" -X GET http://localhost:8080/api/todos/1)
 if [ "$HTTP_CODE" -eq 404 ]; then
   echo -e "${GREEN}Deletion verified - todo not found${NC}"
 else
   echo -e "${RED}Deletion verification failed - expected 404, got $HTTP_CODE${NC}"
 fi
 
+# Restore from the trash
+echo -e "\n=== POST /api/todos/{id}/restore ==="
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -H "$AUTH" -X POST http://localhost:8080/api/todos/1/restore)
+if [ "$HTTP_CODE" -eq 200 ]; then
+  echo -e "${GREEN}Restore todo passed${NC}"
+else
+  echo -e "${RED}Restore todo failed with status code $HTTP_CODE${NC}"
+fi
+
+# History of the deleted and restored todo
+echo -e "\n=== GET /api/todos/{id}/history ==="
+HISTORY=$(curl -s -H "$AUTH" -X GET http://localhost:8080/api/todos/1/history)
+if echo "$HISTORY" | grep -q '"action":"restored"'; then
+  echo -e "${GREEN}History passed${NC}"
+else
+  echo -e "${RED}History failed: $HISTORY${NC}"
+fi
+
+# Bulk operations
+echo -e "\n=== POST /api/todos/bulk ==="
+HTTP_CODE=$(curl -s -o /dev/null -w "%{http_code}" -H "$AUTH" -X POST http://localhost:8080/api/todos/bulk -H "Content-Type: application/json" -d '{"operations":[{"op":"create","todo":{"title":"Bulk Todo"}},{"op":"complete","id":2}]}')
+if [ "$HTTP_CODE" -eq 200 ]; then
+  echo -e "${GREEN}Bulk operations passed${NC}"
+else
+  echo -e "${RED}Bulk operations failed with status code $HTTP_CODE${NC}"
+fi
+
 echo -e "\n${GREEN}=== All integration tests completed ===${NC}"
 
 # Stop the db
@@ -0,0 +1,15 @@
+CREATE TABLE tags (
+  tag_id INT AUTO_INCREMENT PRIMARY KEY,
+  name VARCHAR(50) NOT NULL,
+  -- The default collation makes names unique regardless of case.
+  UNIQUE KEY tags_name (name)
+);
+
+CREATE TABLE todo_tags (
+  todo_id INT NOT NULL,
+  tag_id INT NOT NULL,
+  PRIMARY KEY (todo_id, tag_id),
+  KEY todo_tags_tag_id (tag_id),
+  CONSTRAINT todo_tags_todo FOREIGN KEY (todo_id) REFERENCES todos (todo_id) ON DELETE CASCADE,
+  CONSTRAINT todo_tags_tag FOREIGN KEY (tag_id) REFERENCES tags (tag_id) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+ALTER TABLE todos DROP FOREIGN KEY todos_list;
+ALTER TABLE todos DROP COLUMN list_id;
+DROP TABLE lists;
@@ -0,0 +1,12 @@
+CREATE TABLE lists (
+  list_id INT AUTO_INCREMENT PRIMARY KEY,
+  name VARCHAR(100) NOT NULL
+);
+
+-- The default list, which owns all existing todos and everything created
+-- through the flat `/api/todos` routes.
+INSERT INTO lists (list_id, name) VALUES (1, 'Inbox');
+
+ALTER TABLE todos
+  ADD COLUMN list_id INT NOT NULL DEFAULT 1,
+  ADD CONSTRAINT todos_list FOREIGN KEY (list_id) REFERENCES lists (list_id);
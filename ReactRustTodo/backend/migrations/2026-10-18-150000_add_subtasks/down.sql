@@ -0,0 +1,3 @@
+ALTER TABLE todos DROP FOREIGN KEY todos_parent;
+ALTER TABLE todos DROP COLUMN auto_complete;
+ALTER TABLE todos DROP COLUMN parent_id;
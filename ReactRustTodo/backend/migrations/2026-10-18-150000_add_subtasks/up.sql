@@ -0,0 +1,4 @@
+ALTER TABLE todos
+  ADD COLUMN parent_id INT NULL,
+  ADD COLUMN auto_complete BOOLEAN NOT NULL DEFAULT FALSE,
+  ADD CONSTRAINT todos_parent FOREIGN KEY (parent_id) REFERENCES todos (todo_id) ON DELETE CASCADE;
@@ -0,0 +1,9 @@
+-- `blocker_id` has to be completed before `blocked_id` can be.
+CREATE TABLE todo_dependencies (
+  blocked_id INT NOT NULL,
+  blocker_id INT NOT NULL,
+  PRIMARY KEY (blocked_id, blocker_id),
+  KEY todo_dependencies_blocker_id (blocker_id),
+  CONSTRAINT todo_dependencies_blocked FOREIGN KEY (blocked_id) REFERENCES todos (todo_id) ON DELETE CASCADE,
+  CONSTRAINT todo_dependencies_blocker FOREIGN KEY (blocker_id) REFERENCES todos (todo_id) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+ALTER TABLE todos DROP FOREIGN KEY todos_owner;
+ALTER TABLE todos DROP COLUMN owner_id;
+DROP TABLE users;
@@ -0,0 +1,14 @@
+-- The default collation compares case-insensitively, so `Alice` and `alice`
+-- cannot both register.
+CREATE TABLE users (
+  user_id INT AUTO_INCREMENT PRIMARY KEY,
+  username VARCHAR(50) NOT NULL,
+  password_hash VARCHAR(255) NOT NULL,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE KEY users_username (username)
+);
+
+-- Todos created before there were accounts have no owner.
+ALTER TABLE todos
+  ADD COLUMN owner_id INT,
+  ADD CONSTRAINT todos_owner FOREIGN KEY (owner_id) REFERENCES users (user_id) ON DELETE CASCADE;
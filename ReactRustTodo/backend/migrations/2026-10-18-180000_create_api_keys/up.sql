@@ -0,0 +1,14 @@
+-- Only a SHA-256 of each secret is stored; `scopes` is a comma separated list
+-- like `read,write`.
+CREATE TABLE api_keys (
+  key_id INT AUTO_INCREMENT PRIMARY KEY,
+  user_id INT NOT NULL,
+  name VARCHAR(100) NOT NULL,
+  prefix VARCHAR(20) NOT NULL,
+  key_hash VARCHAR(64) NOT NULL,
+  scopes VARCHAR(50) NOT NULL,
+  expires_at DATETIME,
+  created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE KEY api_keys_key_hash (key_hash),
+  CONSTRAINT api_keys_user FOREIGN KEY (user_id) REFERENCES users (user_id) ON DELETE CASCADE
+);
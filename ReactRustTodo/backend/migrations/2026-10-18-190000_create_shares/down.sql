@@ -0,0 +1,4 @@
+DROP TABLE list_shares;
+DROP TABLE todo_shares;
+ALTER TABLE lists DROP FOREIGN KEY lists_owner;
+ALTER TABLE lists DROP COLUMN owner_id;
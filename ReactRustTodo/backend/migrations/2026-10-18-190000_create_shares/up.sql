@@ -0,0 +1,23 @@
+-- Lists created before there were accounts have no owner and stay open to everyone.
+ALTER TABLE lists
+  ADD COLUMN owner_id INT,
+  ADD CONSTRAINT lists_owner FOREIGN KEY (owner_id) REFERENCES users (user_id) ON DELETE CASCADE;
+
+-- `role` is one of `viewer`, `editor` and `owner`.
+CREATE TABLE todo_shares (
+  todo_id INT NOT NULL,
+  user_id INT NOT NULL,
+  role VARCHAR(10) NOT NULL,
+  PRIMARY KEY (todo_id, user_id),
+  CONSTRAINT todo_shares_todo FOREIGN KEY (todo_id) REFERENCES todos (todo_id) ON DELETE CASCADE,
+  CONSTRAINT todo_shares_user FOREIGN KEY (user_id) REFERENCES users (user_id) ON DELETE CASCADE
+);
+
+CREATE TABLE list_shares (
+  list_id INT NOT NULL,
+  user_id INT NOT NULL,
+  role VARCHAR(10) NOT NULL,
+  PRIMARY KEY (list_id, user_id),
+  CONSTRAINT list_shares_list FOREIGN KEY (list_id) REFERENCES lists (list_id) ON DELETE CASCADE,
+  CONSTRAINT list_shares_user FOREIGN KEY (user_id) REFERENCES users (user_id) ON DELETE CASCADE
+);
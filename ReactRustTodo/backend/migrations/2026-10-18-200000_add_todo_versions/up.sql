@@ -0,0 +1,2 @@
+-- Increased by every update, compared against `If-Match` to detect concurrent edits.
+ALTER TABLE todos ADD COLUMN version INT NOT NULL DEFAULT 1;
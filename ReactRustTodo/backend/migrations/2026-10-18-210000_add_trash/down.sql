@@ -0,0 +1,3 @@
+DELETE FROM todos WHERE deleted_at IS NOT NULL;
+DROP INDEX todos_deleted_at ON todos;
+ALTER TABLE todos DROP COLUMN deleted_at;
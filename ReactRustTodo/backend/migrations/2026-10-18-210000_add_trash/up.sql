@@ -0,0 +1,3 @@
+-- Set when a todo is moved to the trash, purged after the retention period.
+ALTER TABLE todos ADD COLUMN deleted_at DATETIME NULL;
+CREATE INDEX todos_deleted_at ON todos (deleted_at);
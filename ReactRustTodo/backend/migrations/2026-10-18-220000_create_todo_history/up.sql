@@ -0,0 +1,15 @@
+-- Append-only audit log of todo changes. There is no foreign key on
+-- `todo_id`, so the entries outlive their todo; `before_todo` and
+-- `after_todo` hold the todo as JSON.
+CREATE TABLE todo_history (
+  history_id INT AUTO_INCREMENT PRIMARY KEY,
+  todo_id INT NOT NULL,
+  owner_id INT,
+  user_id INT,
+  action VARCHAR(16) NOT NULL,
+  changed_at DATETIME NOT NULL,
+  before_todo MEDIUMTEXT,
+  after_todo MEDIUMTEXT,
+  KEY todo_history_todo_id (todo_id),
+  KEY todo_history_changed_at (changed_at)
+);
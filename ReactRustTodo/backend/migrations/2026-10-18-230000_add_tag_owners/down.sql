@@ -0,0 +1,5 @@
+ALTER TABLE tags
+  DROP INDEX tags_owner_name,
+  ADD UNIQUE KEY tags_name (name);
+ALTER TABLE tags DROP FOREIGN KEY tags_owner;
+ALTER TABLE tags DROP COLUMN owner_id;
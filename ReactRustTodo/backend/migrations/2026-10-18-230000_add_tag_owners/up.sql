@@ -0,0 +1,9 @@
+-- Tags created before they had owners cannot be renamed or deleted through
+-- the API. A tag stays attached to other users' todos when its owner is deleted.
+-- Names are unique per owner; tags without owner never clash, which keeps
+-- deleting an owner from failing on a name taken by an orphaned tag.
+ALTER TABLE tags
+  ADD COLUMN owner_id INT,
+  ADD CONSTRAINT tags_owner FOREIGN KEY (owner_id) REFERENCES users (user_id) ON DELETE SET NULL,
+  DROP INDEX tags_name,
+  ADD UNIQUE KEY tags_owner_name (owner_id, name);
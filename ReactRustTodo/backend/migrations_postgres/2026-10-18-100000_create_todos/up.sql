@@ -0,0 +1,8 @@
+-- PostgreSQL counterpart of migrations/2023-07-25-164054_create_todos
+CREATE TABLE todos (
+  todo_id SERIAL PRIMARY KEY,
+  title VARCHAR(255) NOT NULL,
+  description TEXT,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  completed BOOLEAN DEFAULT FALSE
+)
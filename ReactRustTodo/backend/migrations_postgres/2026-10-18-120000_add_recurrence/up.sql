@@ -0,0 +1,2 @@
+-- PostgreSQL counterpart of migrations/2026-10-18-120000_add_recurrence
+ALTER TABLE todos ADD COLUMN recurrence VARCHAR(255);
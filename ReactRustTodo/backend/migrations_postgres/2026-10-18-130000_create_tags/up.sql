@@ -0,0 +1,13 @@
+-- PostgreSQL counterpart of migrations/2026-10-18-130000_create_tags
+CREATE TABLE tags (
+  tag_id SERIAL PRIMARY KEY,
+  name VARCHAR(50) NOT NULL
+);
+CREATE UNIQUE INDEX tags_name ON tags (lower(name));
+
+CREATE TABLE todo_tags (
+  todo_id INTEGER NOT NULL REFERENCES todos (todo_id) ON DELETE CASCADE,
+  tag_id INTEGER NOT NULL REFERENCES tags (tag_id) ON DELETE CASCADE,
+  PRIMARY KEY (todo_id, tag_id)
+);
+CREATE INDEX todo_tags_tag_id ON todo_tags (tag_id);
@@ -0,0 +1,12 @@
+-- PostgreSQL counterpart of migrations/2026-10-18-140000_create_lists
+CREATE TABLE lists (
+  list_id SERIAL PRIMARY KEY,
+  name VARCHAR(100) NOT NULL
+);
+
+INSERT INTO lists (list_id, name) VALUES (1, 'Inbox');
+-- The explicit id does not advance the sequence.
+SELECT setval('lists_list_id_seq', 1);
+
+ALTER TABLE todos ADD COLUMN list_id INTEGER NOT NULL DEFAULT 1 REFERENCES lists (list_id);
+CREATE INDEX todos_list_id ON todos (list_id);
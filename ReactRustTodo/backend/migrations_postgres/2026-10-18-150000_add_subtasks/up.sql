@@ -0,0 +1,5 @@
+-- PostgreSQL counterpart of migrations/2026-10-18-150000_add_subtasks
+ALTER TABLE todos
+  ADD COLUMN parent_id INTEGER REFERENCES todos (todo_id) ON DELETE CASCADE,
+  ADD COLUMN auto_complete BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE INDEX todos_parent_id ON todos (parent_id);
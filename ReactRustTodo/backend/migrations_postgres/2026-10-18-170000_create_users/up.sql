@@ -0,0 +1,11 @@
+-- PostgreSQL counterpart of migrations/2026-10-18-170000_create_users
+CREATE TABLE users (
+  user_id SERIAL PRIMARY KEY,
+  username VARCHAR(50) NOT NULL,
+  password_hash VARCHAR(255) NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE UNIQUE INDEX users_username ON users (lower(username));
+
+ALTER TABLE todos ADD COLUMN owner_id INTEGER REFERENCES users (user_id) ON DELETE CASCADE;
+CREATE INDEX todos_owner_id ON todos (owner_id);
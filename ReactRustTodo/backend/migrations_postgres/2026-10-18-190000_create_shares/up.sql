@@ -0,0 +1,18 @@
+-- PostgreSQL counterpart of migrations/2026-10-18-190000_create_shares
+ALTER TABLE lists ADD COLUMN owner_id INTEGER REFERENCES users (user_id) ON DELETE CASCADE;
+
+CREATE TABLE todo_shares (
+  todo_id INTEGER NOT NULL REFERENCES todos (todo_id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  role VARCHAR(10) NOT NULL,
+  PRIMARY KEY (todo_id, user_id)
+);
+CREATE INDEX todo_shares_user_id ON todo_shares (user_id);
+
+CREATE TABLE list_shares (
+  list_id INTEGER NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+  user_id INTEGER NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  role VARCHAR(10) NOT NULL,
+  PRIMARY KEY (list_id, user_id)
+);
+CREATE INDEX list_shares_user_id ON list_shares (user_id);
@@ -0,0 +1,3 @@
+DROP INDEX tags_owner_name;
+CREATE UNIQUE INDEX tags_name ON tags (lower(name));
+ALTER TABLE tags DROP COLUMN owner_id;
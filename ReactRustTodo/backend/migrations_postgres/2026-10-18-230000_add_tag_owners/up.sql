@@ -0,0 +1,4 @@
+-- PostgreSQL counterpart of migrations/2026-10-18-230000_add_tag_owners
+ALTER TABLE tags ADD COLUMN owner_id INTEGER REFERENCES users (user_id) ON DELETE SET NULL;
+DROP INDEX tags_name;
+CREATE UNIQUE INDEX tags_owner_name ON tags (owner_id, lower(name));
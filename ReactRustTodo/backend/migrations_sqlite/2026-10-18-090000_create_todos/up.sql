@@ -0,0 +1,8 @@
+-- SQLite counterpart of migrations/2023-07-25-164054_create_todos
+CREATE TABLE todos (
+  todo_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  title VARCHAR(255) NOT NULL,
+  description TEXT,
+  created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+  completed BOOLEAN DEFAULT FALSE
+)
@@ -0,0 +1,4 @@
+-- SQLite counterpart of migrations/2026-10-18-110000_add_due_dates
+ALTER TABLE todos ADD COLUMN due_at TIMESTAMP;
+ALTER TABLE todos ADD COLUMN remind_at TIMESTAMP;
+CREATE INDEX todos_due_at ON todos (due_at);
@@ -0,0 +1,2 @@
+-- SQLite counterpart of migrations/2026-10-18-120000_add_recurrence
+ALTER TABLE todos ADD COLUMN recurrence TEXT;
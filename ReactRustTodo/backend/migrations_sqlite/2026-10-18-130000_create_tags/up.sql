@@ -0,0 +1,14 @@
+-- SQLite counterpart of migrations/2026-10-18-130000_create_tags
+CREATE TABLE tags (
+  tag_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  name VARCHAR(50) NOT NULL COLLATE NOCASE
+);
+-- An index rather than a column constraint, so it can be dropped again.
+CREATE UNIQUE INDEX tags_name ON tags (name);
+
+CREATE TABLE todo_tags (
+  todo_id INTEGER NOT NULL REFERENCES todos (todo_id) ON DELETE CASCADE,
+  tag_id INTEGER NOT NULL REFERENCES tags (tag_id) ON DELETE CASCADE,
+  PRIMARY KEY (todo_id, tag_id)
+);
+CREATE INDEX todo_tags_tag_id ON todo_tags (tag_id);
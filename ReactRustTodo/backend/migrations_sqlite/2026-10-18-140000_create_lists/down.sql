@@ -0,0 +1,3 @@
+DROP INDEX todos_list_id;
+ALTER TABLE todos DROP COLUMN list_id;
+DROP TABLE lists;
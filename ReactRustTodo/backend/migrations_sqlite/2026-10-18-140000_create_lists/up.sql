@@ -0,0 +1,12 @@
+-- SQLite counterpart of migrations/2026-10-18-140000_create_lists
+CREATE TABLE lists (
+  list_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  name VARCHAR(100) NOT NULL
+);
+
+INSERT INTO lists (list_id, name) VALUES (1, 'Inbox');
+
+-- SQLite cannot add a column with both a REFERENCES clause and a non-NULL
+-- default, so the repository checks the list itself.
+ALTER TABLE todos ADD COLUMN list_id INTEGER NOT NULL DEFAULT 1;
+CREATE INDEX todos_list_id ON todos (list_id);
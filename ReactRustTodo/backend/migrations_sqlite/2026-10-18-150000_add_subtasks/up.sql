@@ -0,0 +1,6 @@
+-- SQLite counterpart of migrations/2026-10-18-150000_add_subtasks
+-- Without foreign key, like `list_id`: SQLite could not drop the column
+-- again, and the repository deletes subtrees itself.
+ALTER TABLE todos ADD COLUMN parent_id INTEGER;
+ALTER TABLE todos ADD COLUMN auto_complete BOOLEAN NOT NULL DEFAULT 0;
+CREATE INDEX todos_parent_id ON todos (parent_id);
@@ -0,0 +1,7 @@
+-- SQLite counterpart of migrations/2026-10-18-160000_create_todo_dependencies
+CREATE TABLE todo_dependencies (
+  blocked_id INTEGER NOT NULL REFERENCES todos (todo_id) ON DELETE CASCADE,
+  blocker_id INTEGER NOT NULL REFERENCES todos (todo_id) ON DELETE CASCADE,
+  PRIMARY KEY (blocked_id, blocker_id)
+);
+CREATE INDEX todo_dependencies_blocker_id ON todo_dependencies (blocker_id);
@@ -0,0 +1,3 @@
+DROP INDEX todos_owner_id;
+ALTER TABLE todos DROP COLUMN owner_id;
+DROP TABLE users;
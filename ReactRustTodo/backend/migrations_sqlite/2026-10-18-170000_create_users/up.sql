@@ -0,0 +1,11 @@
+-- SQLite counterpart of migrations/2026-10-18-170000_create_users
+CREATE TABLE users (
+  user_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  username VARCHAR(50) NOT NULL UNIQUE COLLATE NOCASE,
+  password_hash VARCHAR(255) NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Without foreign key, like `list_id`, so the column can be dropped again.
+ALTER TABLE todos ADD COLUMN owner_id INTEGER;
+CREATE INDEX todos_owner_id ON todos (owner_id);
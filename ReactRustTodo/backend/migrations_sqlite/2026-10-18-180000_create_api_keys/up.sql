@@ -0,0 +1,12 @@
+-- SQLite counterpart of migrations/2026-10-18-180000_create_api_keys
+CREATE TABLE api_keys (
+  key_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  user_id INTEGER NOT NULL REFERENCES users (user_id) ON DELETE CASCADE,
+  name VARCHAR(100) NOT NULL,
+  prefix VARCHAR(20) NOT NULL,
+  key_hash VARCHAR(64) NOT NULL UNIQUE,
+  scopes VARCHAR(50) NOT NULL,
+  expires_at TIMESTAMP,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX api_keys_user_id ON api_keys (user_id);
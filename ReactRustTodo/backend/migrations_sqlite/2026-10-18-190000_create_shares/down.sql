@@ -0,0 +1,3 @@
+DROP TABLE list_shares;
+DROP TABLE todo_shares;
+ALTER TABLE lists DROP COLUMN owner_id;
@@ -0,0 +1,2 @@
+-- SQLite counterpart of migrations/2026-10-18-200000_add_todo_versions
+ALTER TABLE todos ADD COLUMN version INT NOT NULL DEFAULT 1;
@@ -0,0 +1,3 @@
+-- SQLite counterpart of migrations/2026-10-18-210000_add_trash
+ALTER TABLE todos ADD COLUMN deleted_at TIMESTAMP;
+CREATE INDEX todos_deleted_at ON todos (deleted_at);
@@ -0,0 +1,13 @@
+-- SQLite counterpart of migrations/2026-10-18-220000_create_todo_history
+CREATE TABLE todo_history (
+  history_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  todo_id INTEGER NOT NULL,
+  owner_id INTEGER,
+  user_id INTEGER,
+  action VARCHAR(16) NOT NULL,
+  changed_at TIMESTAMP NOT NULL,
+  before_todo TEXT,
+  after_todo TEXT
+);
+CREATE INDEX todo_history_todo_id ON todo_history (todo_id);
+CREATE INDEX todo_history_changed_at ON todo_history (changed_at);
@@ -0,0 +1,13 @@
+-- SQLite cannot drop a column with a foreign key, so the table is rebuilt.
+-- Dropping it would cascade to `todo_tags`, whose rows are put back afterwards.
+CREATE TEMP TABLE todo_tags_kept AS SELECT todo_id, tag_id FROM todo_tags;
+CREATE TABLE tags_without_owners (
+  tag_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  name VARCHAR(50) NOT NULL COLLATE NOCASE
+);
+INSERT INTO tags_without_owners (tag_id, name) SELECT tag_id, name FROM tags;
+DROP TABLE tags;
+ALTER TABLE tags_without_owners RENAME TO tags;
+CREATE UNIQUE INDEX tags_name ON tags (name);
+INSERT INTO todo_tags (todo_id, tag_id) SELECT todo_id, tag_id FROM todo_tags_kept;
+DROP TABLE todo_tags_kept;
@@ -1,83 +1,622 @@
+use super::auth::{self, scope, UserRepo};
+use super::error::{bad_request, ErrorResponse};
+use super::etag::{expected_version, tagged, tagged_unless_cached};
+use super::version::{self, ApiVersion};
+use super::{events, history, keys, lists, openapi, shares, tags, trash};
 use crate::{
-    models::todo::{NewTodo, Todo},
-    repository::RepoBox,
+    models::{
+        batch::{BatchOp, BatchOutcome, MAX_BATCH_SIZE},
+        list::DEFAULT_LIST_ID,
+        query::{SortField, TodoQuery},
+        todo::{NewTodo, Todo, TodoPatch},
+        tree::TodoTree,
+    },
+    repository::{error::RepoError, todo_repo::TodoRepo},
+    validation::{FieldError, Validate},
 };
-use actix_web::{delete, get, post, put, web, HttpResponse, Responder, Result};
-use serde::Serialize;
+use actix_web::{
+    delete,
+    dev::{HttpServiceFactory, Service as _},
+    get,
+    http::StatusCode,
+    patch, post, put, web, HttpRequest, HttpResponse, Responder, ResponseError as _, Result,
+};
+use chrono::{Duration, Local};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_swagger_ui::{Config, SwaggerUi};
 
+/// Answers 200 rather than 201 like the other create endpoints, existing
+/// clients of this route predate them and check for 200.
+#[utoipa::path(
+    tag = "todos",
+    responses(
+        (status = 200, description = "The created todo", body = Todo),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown list or parent", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
 #[post("/todos")]
-pub async fn create_todo(db: web::Data<RepoBox>, new_todo: web::Json<NewTodo>) -> HttpResponse {
-    match db.create_todo(new_todo.into_inner()).await {
-        Ok(todo) => HttpResponse::Ok().json(todo),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+pub async fn create_todo(
+    db: UserRepo<scope::Write>,
+    new_todo: web::Json<NewTodo>,
+) -> Result<HttpResponse, RepoError> {
+    let new_todo = new_todo.into_inner();
+    new_todo.validate()?;
+    let todo = db.create_todo(new_todo).await?;
+    Ok(HttpResponse::Ok().json(todo))
+}
+
+/// Body of `POST /api/todos/bulk`.
+#[derive(Deserialize, ToSchema)]
+pub struct BulkRequest {
+    pub operations: Vec<BatchOp>,
+}
+
+/// Result of one operation of a bulk request.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct BulkItem {
+    pub index: usize,
+    /// The status the operation would have had on its own.
+    pub status: u16,
+    /// The created, changed or completed todo.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub todo: Option<Todo>,
+    /// Number of deleted todos, subtasks included.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorResponse>,
+}
+
+impl BulkItem {
+    fn new(index: usize, result: Result<BatchOutcome, RepoError>) -> Self {
+        let mut item = BulkItem {
+            index,
+            status: StatusCode::OK.as_u16(),
+            todo: None,
+            deleted: None,
+            error: None,
+        };
+        match result {
+            Ok(BatchOutcome::Todo(todo)) => item.todo = Some(todo),
+            Ok(BatchOutcome::Deleted(deleted)) => item.deleted = Some(deleted),
+            Err(err) => {
+                item.status = err.status_code().as_u16();
+                err.log();
+                item.error = Some(err.body());
+            }
+        }
+        item
     }
 }
 
-#[get("/todos/{id}")]
-pub async fn get_todo_by_id(db: web::Data<RepoBox>, path: web::Path<(i32,)>) -> HttpResponse {
-    match db.get_todo_by_id(path.into_inner().0).await {
-        Some(todo) => HttpResponse::Ok().json(todo),
-        None => HttpResponse::NotFound().body("Not found"),
+/// Response of `POST /api/todos/bulk`.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct BulkResponse {
+    /// The operations were applied in one transaction.
+    pub atomic: bool,
+    pub results: Vec<BulkItem>,
+}
+
+/// Applies the operations in order. Backends with transactions apply all or
+/// none of them, the others apply them one by one and report each result.
+#[utoipa::path(
+    tag = "todos",
+    responses(
+        (status = 200, description = "Every operation succeeded", body = BulkResponse),
+        (status = 207, description = "Applied one by one, some operations failed", body = BulkResponse),
+        (status = 403, description = "An operation of an atomic batch is not allowed, nothing was applied", body = ErrorResponse),
+        (status = 404, description = "An operation of an atomic batch names an unknown todo, list or parent, nothing was applied", body = ErrorResponse),
+        (status = 409, description = "An operation of an atomic batch conflicts, nothing was applied", body = ErrorResponse),
+        (status = 412, description = "A todo of an atomic batch was changed since the given version, nothing was applied", body = ErrorResponse),
+        (status = 422, description = "Too many operations, or an invalid one in an atomic batch", body = ErrorResponse),
+    )
+)]
+#[post("/todos/bulk")]
+pub async fn bulk_todos(
+    db: UserRepo<scope::Write>,
+    request: web::Json<BulkRequest>,
+) -> Result<HttpResponse, RepoError> {
+    let operations = request.into_inner().operations;
+    if operations.len() > MAX_BATCH_SIZE {
+        return Err(vec![FieldError::new(
+            "operations",
+            "length",
+            format!("at most {MAX_BATCH_SIZE} operations per request"),
+        )]
+        .into());
     }
+    let atomic = db.atomic_batches();
+    let results: Vec<BulkItem> = db
+        .apply_batch(operations)
+        .await?
+        .into_iter()
+        .enumerate()
+        .map(|(index, result)| BulkItem::new(index, result))
+        .collect();
+    let status = if results.iter().all(|item| item.error.is_none()) {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+    Ok(HttpResponse::build(status).json(BulkResponse { atomic, results }))
+}
+
+/// Deletes the user's completed todos with their subtasks, of all lists or of
+/// `?list_id=`. Todos shared with the user are left alone.
+#[utoipa::path(
+    tag = "todos",
+    params(ListParams),
+    responses(
+        (status = 200, description = "Number of deleted todos, subtasks included", body = usize, content_type = "application/json"),
+        (status = 412, description = "A todo changed meanwhile, nothing was deleted", body = ErrorResponse),
+    )
+)]
+#[delete("/todos/completed")]
+pub async fn delete_completed_todos(
+    db: UserRepo<scope::Write>,
+    params: web::Query<ListParams>,
+) -> Result<HttpResponse, RepoError> {
+    let deleted = db.delete_completed(params.list_id).await?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+/// Completes the user's open todos, of all lists or of `?list_id=`, blockers
+/// first. Todos shared with the user are left alone.
+#[utoipa::path(
+    tag = "todos",
+    params(ListParams),
+    responses(
+        (status = 200, description = "The completed todos", body = Vec<Todo>),
+        (status = 409, description = "A todo is blocked by an open todo outside the selection", body = ErrorResponse),
+    )
+)]
+#[post("/todos/complete-all")]
+pub async fn complete_all_todos(
+    db: UserRepo<scope::Write>,
+    params: web::Query<ListParams>,
+) -> Result<HttpResponse, RepoError> {
+    let completed = db.complete_all(params.list_id).await?;
+    Ok(HttpResponse::Ok().json(completed))
 }
 
+/// The todo with its `ETag`, `304 Not Modified` if `If-None-Match` still matches.
+#[utoipa::path(
+    tag = "todos",
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a cached copy"),
+    ),
+    responses(
+        (status = 200, description = "The todo", body = Todo, headers(("ETag" = String, description = "Entity tag of the todo"))),
+        (status = 304, description = "The cached copy is still current"),
+        (status = 404, description = "Unknown todo", body = ErrorResponse),
+    )
+)]
+#[get("/todos/{id}")]
+pub async fn get_todo_by_id(
+    req: HttpRequest,
+    db: UserRepo<scope::Read>,
+    path: web::Path<(i32,)>,
+) -> Result<HttpResponse, RepoError> {
+    let todo = db.get_todo_by_id(path.into_inner().0).await?;
+    Ok(tagged_unless_cached(&req, &todo))
+}
+
+/// The todo with all its subtasks nested below it.
+#[utoipa::path(
+    tag = "todos",
+    responses(
+        (status = 200, description = "The todo and its subtasks", body = TodoTree),
+        (status = 404, description = "Unknown todo", body = ErrorResponse),
+    )
+)]
+#[get("/todos/{id}/subtree")]
+pub async fn get_todo_subtree(
+    db: UserRepo<scope::Read>,
+    path: web::Path<(i32,)>,
+) -> Result<HttpResponse, RepoError> {
+    let tree = db.get_subtree(path.into_inner().0).await?;
+    Ok(HttpResponse::Ok().json(tree))
+}
+
+/// Makes the todo `blocker_id` a blocker of the todo and returns the todo.
+#[utoipa::path(
+    tag = "todos",
+    responses(
+        (status = 200, description = "The blocked todo", body = Todo),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown todo or blocker", body = ErrorResponse),
+        (status = 422, description = "The blocker would create a cycle", body = ErrorResponse),
+    )
+)]
+#[put("/todos/{id}/blockers/{blocker_id}")]
+pub async fn add_blocker(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, RepoError> {
+    let (id, blocker_id) = path.into_inner();
+    let todo = db.add_blocker(id, blocker_id).await?;
+    Ok(HttpResponse::Ok().json(todo))
+}
+
+/// Removes the blocker `blocker_id` from the todo and returns the todo.
+#[utoipa::path(
+    tag = "todos",
+    responses(
+        (status = 200, description = "The formerly blocked todo", body = Todo),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown todo or blocker", body = ErrorResponse),
+    )
+)]
+#[delete("/todos/{id}/blockers/{blocker_id}")]
+pub async fn remove_blocker(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, RepoError> {
+    let (id, blocker_id) = path.into_inner();
+    let todo = db.remove_blocker(id, blocker_id).await?;
+    Ok(HttpResponse::Ok().json(todo))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ListParams {
+    pub list_id: Option<i32>,
+}
+
+/// Open todos that are not blocked by an open todo, of all lists or of `?list_id=`.
+#[utoipa::path(
+    tag = "todos",
+    params(ListParams),
+    responses(
+        (status = 200, description = "Todos that can be started", body = Vec<Todo>),
+    )
+)]
+#[get("/todos/next")]
+pub async fn get_next_todos(
+    db: UserRepo<scope::Read>,
+    params: web::Query<ListParams>,
+) -> Result<HttpResponse, RepoError> {
+    let todos = db.get_next_todos(params.list_id).await?;
+    Ok(HttpResponse::Ok().json(todos))
+}
+
+/// Todos of the default list, or of `?list_id=`, each after its blockers.
+#[utoipa::path(
+    tag = "todos",
+    params(ListParams),
+    responses(
+        (status = 200, description = "The todos of the list", body = Vec<Todo>),
+        (status = 404, description = "Unknown list", body = ErrorResponse),
+    )
+)]
+#[get("/todos/ordered")]
+pub async fn get_ordered_todos(
+    db: UserRepo<scope::Read>,
+    params: web::Query<ListParams>,
+) -> Result<HttpResponse, RepoError> {
+    let list_id = params.list_id.unwrap_or(DEFAULT_LIST_ID);
+    let todos = db.get_ordered_todos(list_id).await?;
+    Ok(HttpResponse::Ok().json(todos))
+}
+
+/// Header carrying the number of todos matching the filters, independent of `limit`/`offset`.
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+/// An item of `GET /api/todos`.
+#[derive(Serialize, ToSchema)]
+pub struct ListedTodo {
+    #[serde(flatten)]
+    pub todo: Todo,
+    /// Whether the todo belongs to another user and is shared with the caller.
+    pub shared: bool,
+}
+
+/// Todos of the default list, or of the list named by `?list_id=`.
+#[utoipa::path(
+    tag = "todos",
+    params(TodoQuery),
+    responses(
+        (status = 200, description = "The matching todos", body = Vec<ListedTodo>, headers(("X-Total-Count" = i64, description = "Number of matching todos"))),
+        (status = 400, description = "Malformed query", body = ErrorResponse),
+    )
+)]
 #[get("/todos")]
-pub async fn get_todos(db: web::Data<RepoBox>) -> HttpResponse {
-    HttpResponse::Ok().json(db.get_todos().await)
+pub async fn get_todos(
+    db: UserRepo<scope::Read>,
+    query: web::Query<TodoQuery>,
+) -> Result<HttpResponse, RepoError> {
+    let mut query = query.into_inner();
+    query.list_id.get_or_insert(DEFAULT_LIST_ID);
+    let page = db.query_todos(query).await?;
+    let items: Vec<ListedTodo> = page
+        .items
+        .into_iter()
+        .map(|todo| ListedTodo {
+            shared: todo.owner_id != Some(db.owner_id()),
+            todo,
+        })
+        .collect();
+    Ok(HttpResponse::Ok()
+        .insert_header((TOTAL_COUNT_HEADER, page.total.to_string()))
+        .json(items))
 }
 
-#[delete("/todos/{id}")]
-pub async fn delete_todo_by_id(db: web::Data<RepoBox>, path: web::Path<(i32,)>) -> HttpResponse {
-    match db.delete_todo_by_id(path.into_inner().0).await {
-        Some(deleted) => HttpResponse::Ok().json(deleted),
-        None => HttpResponse::NotFound().body("Not found"),
+/// Default window of `GET /api/todos/due`.
+pub const DEFAULT_DUE_DAYS: u32 = 7;
+/// Largest window accepted by `GET /api/todos/due`.
+pub const MAX_DUE_DAYS: u32 = 3650;
+
+#[derive(Deserialize, IntoParams)]
+pub struct DueParams {
+    pub days: Option<u32>,
+}
+
+/// Open todos of all lists whose due date has passed, most overdue first.
+#[utoipa::path(
+    tag = "todos",
+    responses(
+        (status = 200, description = "The overdue todos", body = Vec<Todo>, headers(("X-Total-Count" = i64, description = "Number of matching todos"))),
+    )
+)]
+#[get("/todos/overdue")]
+pub async fn get_overdue_todos(db: UserRepo<scope::Read>) -> Result<HttpResponse, RepoError> {
+    let query = TodoQuery {
+        completed: Some(false),
+        due_before: Some(Local::now().naive_local()),
+        sort: SortField::DueAt,
+        ..Default::default()
+    };
+    let page = db.query_todos(query).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((TOTAL_COUNT_HEADER, page.total.to_string()))
+        .json(page.items))
+}
+
+/// Open todos of all lists due within the next `days` days (default 7), soonest first.
+#[utoipa::path(
+    tag = "todos",
+    params(DueParams),
+    responses(
+        (status = 200, description = "The todos due soon", body = Vec<Todo>, headers(("X-Total-Count" = i64, description = "Number of matching todos"))),
+        (status = 400, description = "`days` is out of range", body = ErrorResponse),
+    )
+)]
+#[get("/todos/due")]
+pub async fn get_due_todos(
+    db: UserRepo<scope::Read>,
+    version: web::Data<ApiVersion>,
+    params: web::Query<DueParams>,
+) -> actix_web::Result<HttpResponse> {
+    let days = params.days.unwrap_or(DEFAULT_DUE_DAYS);
+    if days > MAX_DUE_DAYS {
+        return Err(bad_request(
+            **version,
+            format!("days must be at most {MAX_DUE_DAYS}"),
+        ));
     }
+    let now = Local::now().naive_local();
+    let query = TodoQuery {
+        completed: Some(false),
+        due_after: Some(now),
+        due_before: Some(now + Duration::days(i64::from(days))),
+        sort: SortField::DueAt,
+        ..Default::default()
+    };
+    let page = db.query_todos(query).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((TOTAL_COUNT_HEADER, page.total.to_string()))
+        .json(page.items))
 }
 
+/// Moves a todo and its subtasks to the trash. With `If-Match` this fails with
+/// `412` once someone else changed it.
+#[utoipa::path(
+    tag = "todos",
+    params(
+        ("If-Match" = Option<String>, Header, description = "Only change the todo if it still has this `ETag`"),
+    ),
+    responses(
+        (status = 200, description = "Number of trashed todos, subtasks included", body = usize, content_type = "application/json"),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown todo", body = ErrorResponse),
+        (status = 412, description = "Changed since the `ETag` sent as `If-Match`", body = ErrorResponse),
+    )
+)]
+#[delete("/todos/{id}")]
+pub async fn delete_todo_by_id(
+    req: HttpRequest,
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32,)>,
+) -> Result<HttpResponse, RepoError> {
+    let id = path.into_inner().0;
+    let expected = expected_version(&req, &db, id).await?;
+    let deleted = db.delete_todo_if_version(id, expected).await?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+#[utoipa::path(
+    tag = "todos",
+    params(
+        ("If-Match" = Option<String>, Header, description = "Only change the todo if it still has this `ETag`"),
+    ),
+    responses(
+        (status = 200, description = "The updated todo", body = Todo, headers(("ETag" = String, description = "Entity tag of the todo"))),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown todo", body = ErrorResponse),
+        (status = 409, description = "The todo has open blockers", body = ErrorResponse),
+        (status = 412, description = "Changed since the `ETag` sent as `If-Match`", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
 #[put("/todos/{id}")]
 pub async fn update_todo_by_id(
-    db: web::Data<RepoBox>,
+    req: HttpRequest,
+    db: UserRepo<scope::Write>,
     path: web::Path<(i32,)>,
     updated_todo: web::Json<Todo>,
-) -> HttpResponse {
-    match db
-        .update_todo_by_id(path.into_inner().0, updated_todo.into_inner())
-        .await
-    {
-        Some(updated) => HttpResponse::Ok().json(updated),
-        None => HttpResponse::NotFound().body("Not found"),
-    }
+) -> Result<HttpResponse, RepoError> {
+    let id = path.into_inner().0;
+    let updated_todo = updated_todo.into_inner();
+    updated_todo.validate()?;
+    let expected = expected_version(&req, &db, id).await?;
+    let updated = db
+        .update_todo_if_version(id, updated_todo, expected)
+        .await?;
+    Ok(tagged(&updated))
+}
+
+#[utoipa::path(
+    tag = "todos",
+    params(
+        ("If-Match" = Option<String>, Header, description = "Only change the todo if it still has this `ETag`"),
+    ),
+    responses(
+        (status = 200, description = "The updated todo", body = Todo, headers(("ETag" = String, description = "Entity tag of the todo"))),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown todo", body = ErrorResponse),
+        (status = 409, description = "The todo has open blockers", body = ErrorResponse),
+        (status = 412, description = "Changed since the `ETag` sent as `If-Match`", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[patch("/todos/{id}")]
+pub async fn patch_todo_by_id(
+    req: HttpRequest,
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32,)>,
+    patch: web::Json<TodoPatch>,
+) -> Result<HttpResponse, RepoError> {
+    let id = path.into_inner().0;
+    let patch = patch.into_inner();
+    patch.validate()?;
+    let expected = expected_version(&req, &db, id).await?;
+    let patched = db.patch_todo_if_version(id, patch, expected).await?;
+    Ok(tagged(&patched))
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct Response {
     pub message: String,
 }
 
+#[utoipa::path(
+    tag = "meta",
+    security(()),
+    responses(
+        (status = 200, description = "The backend is up", body = Response),
+    )
+)]
 #[get("/health")]
-async fn health() -> impl Responder {
+pub async fn health() -> impl Responder {
     let response = Response {
         message: "Everything is working fine".to_string(),
     };
     HttpResponse::Ok().json(response)
 }
 
-async fn not_found() -> Result<HttpResponse> {
-    let response = Response {
-        message: "Resource not found".to_string(),
-    };
-    Ok(HttpResponse::NotFound().json(response))
+async fn not_found(version: web::Data<ApiVersion>) -> Result<HttpResponse> {
+    let message = "Resource not found".to_string();
+    Ok(match **version {
+        ApiVersion::V1 => HttpResponse::NotFound().json(Response { message }),
+        version => ErrorResponse {
+            code: "not_found".to_string(),
+            message,
+            details: Vec::new(),
+        }
+        .respond(StatusCode::NOT_FOUND, version),
+    })
 }
 
+/// Mounts every version at its prefix and `/api` as alias of v1 for older
+/// clients, last as it would otherwise claim the paths of the versions.
 pub fn config(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/api")
-            .service(create_todo)
-            .service(get_todo_by_id)
-            .service(get_todos)
-            .service(delete_todo_by_id)
-            .service(update_todo_by_id)
-            .service(health)
-            .default_service(web::route().to(not_found)),
-    );
+    cfg.service(mount(ApiVersion::V1.prefix(), ApiVersion::V1))
+        .service(mount(ApiVersion::V2.prefix(), ApiVersion::V2))
+        .service(mount("/api", ApiVersion::V1));
+}
+
+/// The routes of `version` below `prefix`.
+fn mount(prefix: &'static str, version: ApiVersion) -> impl HttpServiceFactory {
+    web::scope(prefix)
+        .wrap_fn(|req, srv| {
+            auth::resolve_user(&req);
+            srv.call(req)
+        })
+        .wrap_fn(move |req, srv| {
+            let res = srv.call(req);
+            async move { Ok(version::finish(version, prefix, res.await?)) }
+        })
+        .app_data(web::Data::new(version))
+        .app_data(web::JsonConfig::default().error_handler(move |err, _| bad_request(version, err)))
+        .app_data(web::PathConfig::default().error_handler(move |err, _| bad_request(version, err)))
+        .app_data(
+            web::QueryConfig::default().error_handler(move |err, _| bad_request(version, err)),
+        )
+        .configure(routes)
+        .service(
+            SwaggerUi::new("/docs/{_:.*}").config(Config::from(format!("{prefix}/openapi.json"))),
+        )
+        .default_service(web::route().to(not_found))
+}
+
+/// The handlers of every version.
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(auth::register)
+        .service(auth::login)
+        .service(auth::me)
+        .service(keys::create_api_key)
+        .service(keys::get_api_keys)
+        .service(keys::delete_api_key)
+        .service(create_todo)
+        // Registered before `/todos/{id}`, which would otherwise claim these paths.
+        .service(bulk_todos)
+        .service(delete_completed_todos)
+        .service(complete_all_todos)
+        .service(get_overdue_todos)
+        .service(get_due_todos)
+        .service(get_next_todos)
+        .service(get_ordered_todos)
+        .service(get_todo_by_id)
+        .service(get_todo_subtree)
+        .service(add_blocker)
+        .service(remove_blocker)
+        .service(get_todos)
+        .service(delete_todo_by_id)
+        .service(update_todo_by_id)
+        .service(patch_todo_by_id)
+        .service(trash::get_trash)
+        .service(trash::restore_todo)
+        .service(trash::purge_todo)
+        .service(trash::empty_trash)
+        .service(history::get_todo_history)
+        .service(history::get_audit)
+        .service(tags::attach_tag)
+        .service(tags::detach_tag)
+        .service(tags::get_tags)
+        .service(tags::create_tag)
+        .service(tags::get_tag_by_id)
+        .service(tags::update_tag_by_id)
+        .service(tags::delete_tag_by_id)
+        .service(lists::get_lists)
+        .service(lists::create_list)
+        .service(lists::get_list_by_id)
+        .service(lists::update_list_by_id)
+        .service(lists::delete_list_by_id)
+        .service(lists::get_list_todos)
+        .service(lists::create_list_todo)
+        .service(lists::get_list_todo_by_id)
+        .service(lists::update_list_todo_by_id)
+        .service(lists::patch_list_todo_by_id)
+        .service(lists::delete_list_todo_by_id)
+        .service(shares::get_todo_shares)
+        .service(shares::share_todo)
+        .service(shares::unshare_todo)
+        .service(shares::get_list_shares)
+        .service(shares::share_list)
+        .service(shares::unshare_list)
+        .service(events::events)
+        .service(health)
+        .service(openapi::openapi_json);
 }
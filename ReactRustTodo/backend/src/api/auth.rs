@@ -0,0 +1,366 @@
+use std::future::{ready, Future};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::error::ErrorResponse;
+use crate::{
+    auth::{
+        hash_api_key, hash_password, verify_password, AuthKeys, API_KEY_PREFIX, DUMMY_PASSWORD_HASH,
+    },
+    models::api_key::Scope,
+    models::user::{Credentials, NewUser, User},
+    repository::todo_repo::TodoRepo,
+    repository::{audit_repo::AuditRepo, error::RepoError, owned_repo::OwnedRepo, RepoBox},
+    validation::Validate,
+};
+use actix_web::{
+    dev::{Payload, ServiceRequest},
+    get,
+    http::header,
+    post, web, FromRequest, HttpMessage, HttpRequest, HttpResponse,
+};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+type LocalFuture<T> = Pin<Box<dyn Future<Output = Result<T, RepoError>>>>;
+
+/// The user a request was made by, resolved from its bearer token by [`resolve_user`].
+///
+/// As extractor it rejects requests without a valid token with 401.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrentUser {
+    pub user_id: i32,
+    /// The widest scope of the API key, [`Scope::Admin`] for session tokens.
+    pub scope: Scope,
+}
+
+impl CurrentUser {
+    /// `RepoError::Forbidden` unless the request may use `scope`.
+    pub fn require(&self, scope: Scope) -> Result<(), RepoError> {
+        if self.scope >= scope {
+            Ok(())
+        } else {
+            Err(RepoError::Forbidden(format!(
+                "the api key lacks the `{scope}` scope"
+            )))
+        }
+    }
+}
+
+/// Outcome of [`resolve_user`], kept in the request extensions.
+#[derive(Debug, Clone)]
+enum Authentication {
+    /// A session token and when it expires.
+    User(CurrentUser, DateTime<Utc>),
+    /// Hash of an API key, looked up by the extractor as that needs the repository.
+    ApiKey(String),
+    Rejected(&'static str),
+}
+
+/// Middleware step that checks the `Authorization: Bearer <token>` header of
+/// `req` against the [`AuthKeys`] of the app. Tokens starting with
+/// [`API_KEY_PREFIX`] are API keys instead.
+///
+/// Nothing is rejected here, so routes without a user like `/api/health` stay
+/// reachable; handlers that need one fail in the [`CurrentUser`] extractor.
+pub fn resolve_user(req: &ServiceRequest) {
+    let token = match req.headers().get(header::AUTHORIZATION) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string()),
+        None => match query_token(req) {
+            Some(token) => Some(token),
+            None => return,
+        },
+    };
+    let authentication = match (token.as_deref(), req.app_data::<web::Data<AuthKeys>>()) {
+        (None, _) => Authentication::Rejected("the authorization header is not a bearer token"),
+        (Some(token), _) if token.starts_with(API_KEY_PREFIX) => {
+            Authentication::ApiKey(hash_api_key(token))
+        }
+        (Some(_), None) => Authentication::Rejected("authentication is not configured"),
+        (Some(token), Some(keys)) => match keys.verify_until(token) {
+            Some((user_id, expires_at)) => Authentication::User(
+                CurrentUser {
+                    user_id,
+                    scope: Scope::Admin,
+                },
+                expires_at,
+            ),
+            None => Authentication::Rejected("the token is invalid or expired"),
+        },
+    };
+    req.extensions_mut().insert(authentication);
+}
+
+#[derive(Deserialize)]
+struct TokenParams {
+    access_token: Option<String>,
+}
+
+/// A browser's `EventSource` cannot send headers, so the event stream also
+/// takes the token as `?access_token=`.
+fn query_token(req: &ServiceRequest) -> Option<String> {
+    if !req.path().ends_with("/events") {
+        return None;
+    }
+    web::Query::<TokenParams>::from_query(req.query_string())
+        .ok()?
+        .into_inner()
+        .access_token
+}
+
+/// The user of the API key with `key_hash`, if the key exists and has not expired.
+async fn key_user(repo: Option<RepoBox>, key_hash: String) -> Result<CurrentUser, RepoError> {
+    let repo = repo.ok_or_else(|| RepoError::Internal("no repository configured".to_string()))?;
+    let key = match repo.get_api_key_by_hash(&key_hash).await {
+        Err(RepoError::Missing(_)) => {
+            return Err(RepoError::Unauthorized(
+                "the api key is invalid or revoked".to_string(),
+            ))
+        }
+        other => other?,
+    };
+    if key.is_expired(Local::now().naive_local()) {
+        return Err(RepoError::Unauthorized(
+            "the api key has expired".to_string(),
+        ));
+    }
+    let scope = key
+        .scopes
+        .iter()
+        .copied()
+        .max()
+        .ok_or_else(|| RepoError::Forbidden("the api key has no scopes".to_string()))?;
+    Ok(CurrentUser {
+        user_id: key.user_id,
+        scope,
+    })
+}
+
+impl FromRequest for CurrentUser {
+    type Error = RepoError;
+    type Future = LocalFuture<Self>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user = match req.extensions().get::<Authentication>() {
+            Some(Authentication::User(user, _)) => Ok(*user),
+            Some(Authentication::ApiKey(key_hash)) => {
+                let repo = req
+                    .app_data::<web::Data<RepoBox>>()
+                    .map(|repo| repo.get_ref().clone());
+                return Box::pin(key_user(repo, key_hash.clone()));
+            }
+            Some(Authentication::Rejected(reason)) => {
+                Err(RepoError::Unauthorized(reason.to_string()))
+            }
+            None => Err(RepoError::Unauthorized(
+                "log in and send the token as `Authorization: Bearer <token>`".to_string(),
+            )),
+        };
+        Box::pin(ready(user))
+    }
+}
+
+/// The credential a request was authenticated with, checked again while a
+/// long-lived response like the event stream is open.
+#[derive(Clone)]
+pub enum Session {
+    /// A session token, valid until `expires_at`.
+    Token { expires_at: DateTime<Utc> },
+    /// An API key, looked up again as it may be revoked or expire.
+    ApiKey { repo: RepoBox, key_hash: String },
+}
+
+impl Session {
+    /// The credential of `req`, `None` unless it has a token or API key.
+    pub fn of(req: &HttpRequest) -> Option<Session> {
+        match req.extensions().get::<Authentication>()? {
+            Authentication::User(_, expires_at) => Some(Session::Token {
+                expires_at: *expires_at,
+            }),
+            Authentication::ApiKey(key_hash) => Some(Session::ApiKey {
+                repo: req.app_data::<web::Data<RepoBox>>()?.get_ref().clone(),
+                key_hash: key_hash.clone(),
+            }),
+            Authentication::Rejected(_) => None,
+        }
+    }
+
+    /// Whether the credential would still be accepted.
+    pub async fn is_valid(&self) -> bool {
+        match self {
+            Session::Token { expires_at } => Utc::now() < *expires_at,
+            Session::ApiKey { repo, key_hash } => {
+                key_user(Some(repo.clone()), key_hash.clone()).await.is_ok()
+            }
+        }
+    }
+
+    /// How long to wait at most before checking the credential again: `max`,
+    /// or less if a session token expires earlier.
+    pub fn next_check(&self, max: Duration) -> Duration {
+        match self {
+            Session::Token { expires_at } => (*expires_at - Utc::now())
+                .to_std()
+                .map_or(Duration::ZERO, |left| left.min(max)),
+            Session::ApiKey { .. } => max,
+        }
+    }
+}
+
+/// The scopes a handler can ask for through [`UserRepo`].
+pub mod scope {
+    use crate::models::api_key::Scope;
+
+    /// Maps a marker type to the [`Scope`] it stands for.
+    pub trait RequiredScope {
+        const SCOPE: Scope;
+    }
+
+    /// Needs [`Scope::Read`].
+    pub struct Read;
+    /// Needs [`Scope::Write`].
+    pub struct Write;
+    /// Needs [`Scope::Admin`].
+    pub struct Admin;
+
+    impl RequiredScope for Read {
+        const SCOPE: Scope = Scope::Read;
+    }
+
+    impl RequiredScope for Write {
+        const SCOPE: Scope = Scope::Write;
+    }
+
+    impl RequiredScope for Admin {
+        const SCOPE: Scope = Scope::Admin;
+    }
+}
+
+/// The repository restricted to the todos of the [`CurrentUser`], see [`OwnedRepo`].
+/// Their changes are recorded in the audit log, see [`AuditRepo`].
+///
+/// `S` is the scope the handler needs, e.g. `UserRepo<scope::Write>`; API keys
+/// without it are rejected with 403.
+pub struct UserRepo<S>(pub OwnedRepo, PhantomData<S>);
+
+impl<S> Deref for UserRepo<S> {
+    type Target = OwnedRepo;
+
+    fn deref(&self) -> &OwnedRepo {
+        &self.0
+    }
+}
+
+impl<S: scope::RequiredScope + 'static> FromRequest for UserRepo<S> {
+    type Error = RepoError;
+    type Future = LocalFuture<Self>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let user = CurrentUser::from_request(req, payload);
+        let repo = req
+            .app_data::<web::Data<RepoBox>>()
+            .map(|repo| repo.get_ref().clone());
+        Box::pin(async move {
+            let user = user.await?;
+            user.require(S::SCOPE)?;
+            let repo =
+                repo.ok_or_else(|| RepoError::Internal("no repository configured".to_string()))?;
+            let repo = Arc::new(AuditRepo::new(repo, Some(user.user_id)));
+            Ok(UserRepo(OwnedRepo::new(repo, user.user_id), PhantomData))
+        })
+    }
+}
+
+/// Body of a successful `POST /api/auth/login`.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct LoginResponse {
+    pub token: String,
+    /// Always `Bearer`.
+    pub token_type: String,
+    /// Seconds until the token expires.
+    pub expires_in: i64,
+    pub user: User,
+}
+
+/// Creates an account; the username must not be taken, ignoring case.
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    responses(
+        (status = 201, description = "The new user", body = User),
+        (status = 409, description = "The name is taken", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[post("/auth/register")]
+pub async fn register(
+    db: web::Data<RepoBox>,
+    credentials: web::Json<Credentials>,
+) -> Result<HttpResponse, RepoError> {
+    let credentials = credentials.into_inner();
+    credentials.validate()?;
+    let password_hash = hash_password(&credentials.password)?;
+    let user = db
+        .create_user(NewUser {
+            username: credentials.username,
+            password_hash,
+        })
+        .await?;
+    Ok(HttpResponse::Created().json(user))
+}
+
+/// Checks the credentials and hands out a session token.
+#[utoipa::path(
+    tag = "auth",
+    security(()),
+    responses(
+        (status = 200, description = "A session token", body = LoginResponse),
+        (status = 401, description = "Wrong name or password", body = ErrorResponse),
+    )
+)]
+#[post("/auth/login")]
+pub async fn login(
+    db: web::Data<RepoBox>,
+    keys: web::Data<AuthKeys>,
+    credentials: web::Json<Credentials>,
+) -> Result<HttpResponse, RepoError> {
+    let rejected = || RepoError::Unauthorized("invalid username or password".to_string());
+    let record = match db.get_user_record(&credentials.username).await {
+        Err(RepoError::Missing(_)) => {
+            // Hash anyway, so the response time does not tell which names exist.
+            verify_password(&credentials.password, DUMMY_PASSWORD_HASH);
+            return Err(rejected());
+        }
+        other => other?,
+    };
+    if !verify_password(&credentials.password, &record.password_hash) {
+        return Err(rejected());
+    }
+    Ok(HttpResponse::Ok().json(LoginResponse {
+        token: keys.issue(record.user.user_id)?,
+        token_type: "Bearer".to_string(),
+        expires_in: keys.ttl().num_seconds(),
+        user: record.user,
+    }))
+}
+
+/// The logged in user.
+#[utoipa::path(
+    tag = "auth",
+    responses(
+        (status = 200, description = "The current user", body = User),
+    )
+)]
+#[get("/auth/me")]
+pub async fn me(db: UserRepo<scope::Read>) -> Result<HttpResponse, RepoError> {
+    let user = db.get_user_by_id(db.owner_id()).await?;
+    Ok(HttpResponse::Ok().json(user))
+}
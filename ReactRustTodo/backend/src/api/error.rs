@@ -0,0 +1,149 @@
+use actix_web::{
+    http::{
+        header::{self, HeaderValue},
+        StatusCode,
+    },
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::version::ApiVersion;
+use crate::{repository::error::RepoError, validation::FieldError};
+
+/// JSON body returned for every failed request under `/api` and `/api/v1`.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ErrorResponse {
+    pub code: String,
+    pub message: String,
+    /// Per-field violations, only present for validation errors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+}
+
+/// Content type of [`Problem`] bodies.
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Error body of `/api/v2`, RFC 9457 problem details.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct Problem {
+    /// Always `about:blank`, `code` tells the errors apart.
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// Reason phrase of `status`.
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: String,
+    /// Per-field violations, only present for validation errors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl ErrorResponse {
+    /// A `status` response with this error in the format of `version`.
+    pub fn respond(self, status: StatusCode, version: ApiVersion) -> HttpResponse {
+        let mut response = HttpResponse::build(status);
+        match version {
+            ApiVersion::V1 => response.json(self),
+            ApiVersion::V2 => response.content_type(PROBLEM_CONTENT_TYPE).json(Problem {
+                problem_type: "about:blank".to_string(),
+                title: status.canonical_reason().unwrap_or_default().to_string(),
+                status: status.as_u16(),
+                detail: self.message,
+                code: self.code,
+                errors: self.details,
+            }),
+        }
+    }
+}
+
+impl RepoError {
+    /// Stable, machine readable identifier of the error kind.
+    pub fn code(&self) -> &'static str {
+        match self.cause() {
+            RepoError::NotFound | RepoError::Missing(_) => "not_found",
+            RepoError::Conflict(_) => "conflict",
+            RepoError::Validation(_) => "validation_failed",
+            RepoError::Unauthorized(_) => "unauthorized",
+            RepoError::Forbidden(_) => "forbidden",
+            RepoError::PreconditionFailed(_) => "precondition_failed",
+            RepoError::Unavailable(_) => "unavailable",
+            RepoError::Internal(_) => "internal",
+            RepoError::Batch(..) => unreachable!("cause() unwraps batches"),
+        }
+    }
+}
+
+impl ResponseError for RepoError {
+    fn status_code(&self) -> StatusCode {
+        match self.cause() {
+            RepoError::NotFound | RepoError::Missing(_) => StatusCode::NOT_FOUND,
+            RepoError::Conflict(_) => StatusCode::CONFLICT,
+            RepoError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            RepoError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            RepoError::Forbidden(_) => StatusCode::FORBIDDEN,
+            RepoError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            RepoError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            RepoError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            RepoError::Batch(..) => unreachable!("cause() unwraps batches"),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        self.response(ApiVersion::V1)
+    }
+}
+
+impl RepoError {
+    /// The JSON body of this error.
+    pub fn body(&self) -> ErrorResponse {
+        // Internal details stay in the logs, clients only get a generic message.
+        let message = match self.cause() {
+            RepoError::Internal(_) => "Internal server error".to_string(),
+            RepoError::Unavailable(_) => "storage unavailable".to_string(),
+            _ => self.to_string(),
+        };
+        let details = match self.cause() {
+            RepoError::Validation(errors) => errors.clone(),
+            _ => Vec::new(),
+        };
+        ErrorResponse {
+            code: self.code().to_string(),
+            message,
+            details,
+        }
+    }
+
+    /// Logs the details that [`RepoError::body`] hides from clients.
+    pub fn log(&self) {
+        if let RepoError::Internal(_) | RepoError::Unavailable(_) = self.cause() {
+            log::error!("{}", self.cause());
+        }
+    }
+
+    /// The response for this error in the format of `version`.
+    pub fn response(&self, version: ApiVersion) -> HttpResponse {
+        self.log();
+        let mut response = self.body().respond(self.status_code(), version);
+        if let RepoError::Unauthorized(_) = self.cause() {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+/// Turns extractor failures (malformed JSON, non-numeric ids, ...) into the
+/// same shape as repository errors of `version` instead of actix' plain text
+/// bodies.
+pub fn bad_request(version: ApiVersion, err: impl std::fmt::Display) -> actix_web::Error {
+    let body = ErrorResponse {
+        code: "bad_request".to_string(),
+        message: err.to_string(),
+        details: Vec::new(),
+    };
+    let response = body.respond(StatusCode::BAD_REQUEST, version);
+    actix_web::error::InternalError::from_response(err.to_string(), response).into()
+}
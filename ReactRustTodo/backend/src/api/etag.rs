@@ -0,0 +1,74 @@
+use crate::{
+    models::todo::Todo,
+    repository::{error::RepoError, owned_repo::OwnedRepo, todo_repo::TodoRepo},
+};
+use actix_web::{
+    http::header::{ETag, EntityTag, IfMatch, IfNoneMatch, IF_MATCH},
+    HttpMessage, HttpRequest, HttpResponse,
+};
+use sha2::{Digest, Sha256};
+
+/// The entity tag of `todo`, a hash of its JSON so tags and progress count too.
+pub fn entity_tag(todo: &Todo) -> EntityTag {
+    let json = serde_json::to_vec(todo).unwrap_or_default();
+    let hash = format!("{:x}", Sha256::digest(&json));
+    EntityTag::new_strong(hash[..16].to_string())
+}
+
+/// `200 OK` with `todo` and its `ETag`.
+pub fn tagged(todo: &Todo) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(ETag(entity_tag(todo)))
+        .json(todo)
+}
+
+/// Like [`tagged`], but `304 Not Modified` if the client sent the current tag
+/// in `If-None-Match`.
+pub fn tagged_unless_cached(req: &HttpRequest, todo: &Todo) -> HttpResponse {
+    let tag = entity_tag(todo);
+    let cached = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(items)) => items.iter().any(|item| item.weak_eq(&tag)),
+        None => false,
+    };
+    if cached {
+        return HttpResponse::NotModified()
+            .insert_header(ETag(tag))
+            .finish();
+    }
+    tagged(todo)
+}
+
+/// The version a write to `current` has to find, see
+/// [`TodoRepo::update_todo_if_version`]: `None` without `If-Match`, otherwise
+/// the version of `current` if the header names its tag.
+pub fn if_match(req: &HttpRequest, current: &Todo) -> Result<Option<i32>, RepoError> {
+    let matches = match req.get_header::<IfMatch>() {
+        Some(IfMatch::Any) => true,
+        Some(IfMatch::Items(items)) => {
+            let tag = entity_tag(current);
+            items.iter().any(|item| item.strong_eq(&tag))
+        }
+        None => return Ok(None),
+    };
+    if !matches {
+        return Err(RepoError::PreconditionFailed(format!(
+            "todo {} does not match If-Match",
+            current.todo_id
+        )));
+    }
+    Ok(Some(current.version))
+}
+
+/// [`if_match`] for the todo `id`, which is only loaded if the header is there.
+pub async fn expected_version(
+    req: &HttpRequest,
+    db: &OwnedRepo,
+    id: i32,
+) -> Result<Option<i32>, RepoError> {
+    if !req.headers().contains_key(IF_MATCH) {
+        return Ok(None);
+    }
+    let current = db.get_todo_by_id(id).await?;
+    if_match(req, &current)
+}
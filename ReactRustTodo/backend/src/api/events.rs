@@ -0,0 +1,105 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use super::auth::{scope, Session, UserRepo};
+use crate::{
+    events::{Delivery, EventBus, Subscription},
+    models::event::TodoEvent,
+    repository::error::RepoError,
+};
+use actix_web::{get, http::header, web, HttpRequest, HttpResponse};
+use futures_util::stream;
+use tokio::time::Instant;
+
+/// Header of a reconnecting `EventSource` with the id of the last event it got.
+pub const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+/// Idle time after which a comment is sent so proxies keep the stream open.
+/// The credential of the stream is checked again at least this often.
+pub const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// `event` as a server-sent event named after its kind.
+fn frame(event: &TodoEvent) -> web::Bytes {
+    let data = serde_json::to_string(event).unwrap_or_default();
+    let kind = event.kind.as_str();
+    web::Bytes::from(format!(
+        "id: {}\nevent: {kind}\ndata: {data}\n\n",
+        event.event_id
+    ))
+}
+
+/// Tells the client that it missed events and has to load the todos again.
+const RESET_FRAME: &[u8] = b"event: reset\ndata: {}\n\n";
+
+const KEEP_ALIVE_FRAME: &[u8] = b": keep-alive\n\n";
+
+/// An open event stream of one user.
+struct Stream {
+    subscription: Subscription,
+    session: Session,
+    /// When the credential is checked next.
+    next_check: Instant,
+}
+
+/// Server-sent events for the todos the current user can see. A client sending
+/// `Last-Event-ID` first gets the events it missed, or a `reset` event if they
+/// are no longer known.
+///
+/// The stream ends once the session token expires or the API key is revoked
+/// or expires.
+#[utoipa::path(
+    tag = "events",
+    params(
+        ("access_token" = Option<String>, Query, description = "The token, for clients that cannot send headers"),
+        ("Last-Event-ID" = Option<u64>, Header, description = "Resume after this event"),
+    ),
+    responses(
+        (status = 200, description = "`created`, `updated` and `deleted` events carrying a `TodoEvent`, or `reset`", body = String, content_type = "text/event-stream"),
+    )
+)]
+#[get("/events")]
+pub async fn events(
+    req: HttpRequest,
+    db: UserRepo<scope::Read>,
+    bus: web::Data<EventBus>,
+) -> Result<HttpResponse, RepoError> {
+    let last_event_id = req
+        .headers()
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok());
+    let user_id = db.owner_id();
+    let session = Session::of(&req).ok_or_else(|| {
+        RepoError::Unauthorized("the event stream needs a token or api key".to_string())
+    })?;
+    let stream = Stream {
+        subscription: bus.subscribe(last_event_id),
+        session,
+        next_check: Instant::now(),
+    };
+    let body = stream::unfold(stream, move |mut stream| async move {
+        loop {
+            if Instant::now() >= stream.next_check {
+                if !stream.session.is_valid().await {
+                    return None;
+                }
+                stream.next_check = Instant::now() + stream.session.next_check(KEEP_ALIVE);
+            }
+            let next = stream.subscription.next();
+            let bytes = match tokio::time::timeout_at(stream.next_check, next).await {
+                Err(_) => web::Bytes::from_static(KEEP_ALIVE_FRAME),
+                Ok(None) => return None,
+                Ok(Some(Delivery::Reset)) => web::Bytes::from_static(RESET_FRAME),
+                Ok(Some(Delivery::Event(event))) if event.audience.contains(&user_id) => {
+                    frame(&event)
+                }
+                Ok(Some(Delivery::Event(_))) => continue,
+            };
+            return Some((Ok::<_, Infallible>(bytes), stream));
+        }
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(body))
+}
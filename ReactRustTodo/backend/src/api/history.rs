@@ -0,0 +1,55 @@
+use super::auth::{scope, UserRepo};
+use super::error::ErrorResponse;
+use crate::{
+    models::history::{HistoryEntry, HistoryQuery},
+    repository::{error::RepoError, todo_repo::TodoRepo},
+};
+use actix_web::{get, web, HttpResponse};
+
+/// The changes of a todo in the order they were made, also after it was deleted.
+#[utoipa::path(
+    tag = "history",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "The changes of the todo", body = Vec<HistoryEntry>),
+        (status = 400, description = "Malformed query", body = ErrorResponse),
+        (status = 404, description = "Unknown todo", body = ErrorResponse),
+    )
+)]
+#[get("/todos/{id}/history")]
+pub async fn get_todo_history(
+    db: UserRepo<scope::Read>,
+    path: web::Path<(i32,)>,
+    query: web::Query<HistoryQuery>,
+) -> Result<HttpResponse, RepoError> {
+    let id = path.into_inner().0;
+    let query = HistoryQuery {
+        todo_id: Some(id),
+        ..query.into_inner()
+    };
+    let entries = db.get_history(query).await?;
+    if entries.is_empty() {
+        // A visible todo may have no changes in the log yet.
+        db.get_todo_by_id(id).await?;
+    }
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+/// The changes of the user's own todos and the changes made by the user, in
+/// the order they were made.
+#[utoipa::path(
+    tag = "history",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "The matching changes", body = Vec<HistoryEntry>),
+        (status = 400, description = "Malformed query", body = ErrorResponse),
+    )
+)]
+#[get("/audit")]
+pub async fn get_audit(
+    db: UserRepo<scope::Read>,
+    query: web::Query<HistoryQuery>,
+) -> Result<HttpResponse, RepoError> {
+    let entries = db.get_history(query.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(entries))
+}
@@ -0,0 +1,74 @@
+use super::auth::{scope, UserRepo};
+use super::error::ErrorResponse;
+use crate::{
+    auth::{generate_api_key, hash_api_key, API_KEY_SHOWN_LEN},
+    models::api_key::{ApiKey, IssuedApiKey, NewApiKey, NewApiKeyRecord},
+    repository::{error::RepoError, todo_repo::TodoRepo},
+    validation::Validate,
+};
+use actix_web::{delete, get, post, web, HttpResponse};
+
+/// Issues an API key acting for the current user. The secret is only part of
+/// this response, the repository keeps its hash.
+#[utoipa::path(
+    tag = "keys",
+    responses(
+        (status = 201, description = "The key with its secret, shown only once", body = IssuedApiKey),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[post("/keys")]
+pub async fn create_api_key(
+    db: UserRepo<scope::Admin>,
+    new_key: web::Json<NewApiKey>,
+) -> Result<HttpResponse, RepoError> {
+    let mut new_key = new_key.into_inner();
+    new_key.validate()?;
+    new_key.scopes.sort();
+    new_key.scopes.dedup();
+    let secret = generate_api_key();
+    let key = db
+        .create_api_key(NewApiKeyRecord {
+            user_id: db.owner_id(),
+            key: new_key,
+            prefix: secret[..API_KEY_SHOWN_LEN].to_string(),
+            key_hash: hash_api_key(&secret),
+        })
+        .await?;
+    Ok(HttpResponse::Created().json(IssuedApiKey { key, secret }))
+}
+
+/// The keys of the current user, without their secrets.
+#[utoipa::path(
+    tag = "keys",
+    responses(
+        (status = 200, description = "The keys of the current user, without secrets", body = Vec<ApiKey>),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+    )
+)]
+#[get("/keys")]
+pub async fn get_api_keys(db: UserRepo<scope::Admin>) -> Result<HttpResponse, RepoError> {
+    let keys = db.get_api_keys(db.owner_id()).await?;
+    Ok(HttpResponse::Ok().json(keys))
+}
+
+/// Revokes a key, requests with it are rejected right away.
+#[utoipa::path(
+    tag = "keys",
+    responses(
+        (status = 200, description = "Number of revoked keys", body = usize, content_type = "application/json"),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown key", body = ErrorResponse),
+    )
+)]
+#[delete("/keys/{id}")]
+pub async fn delete_api_key(
+    db: UserRepo<scope::Admin>,
+    path: web::Path<(i32,)>,
+) -> Result<HttpResponse, RepoError> {
+    let deleted = db
+        .delete_api_key(db.owner_id(), path.into_inner().0)
+        .await?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
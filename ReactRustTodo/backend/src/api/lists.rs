@@ -0,0 +1,285 @@
+use super::api::{ListedTodo, TOTAL_COUNT_HEADER};
+use super::auth::{scope, UserRepo};
+use super::error::ErrorResponse;
+use super::etag::{if_match, tagged, tagged_unless_cached};
+use crate::{
+    models::{
+        list::{NewList, TodoList},
+        query::TodoQuery,
+        todo::{NewTodo, Todo, TodoPatch},
+    },
+    repository::{error::RepoError, owned_repo::OwnedRepo, todo_repo::TodoRepo},
+    validation::Validate,
+};
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[derive(Deserialize, Default, IntoParams)]
+#[serde(default)]
+pub struct DeleteListParams {
+    /// Delete the todos of the list too instead of refusing while it has any.
+    pub cascade: bool,
+}
+
+#[utoipa::path(
+    tag = "lists",
+    responses(
+        (status = 200, description = "The visible lists", body = Vec<TodoList>),
+    )
+)]
+#[get("/lists")]
+pub async fn get_lists(db: UserRepo<scope::Read>) -> Result<HttpResponse, RepoError> {
+    let lists = db.get_lists().await?;
+    Ok(HttpResponse::Ok().json(lists))
+}
+
+#[utoipa::path(
+    tag = "lists",
+    responses(
+        (status = 201, description = "The created list", body = TodoList),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[post("/lists")]
+pub async fn create_list(
+    db: UserRepo<scope::Write>,
+    new_list: web::Json<NewList>,
+) -> Result<HttpResponse, RepoError> {
+    let new_list = new_list.into_inner();
+    new_list.validate()?;
+    let list = db.create_list(new_list).await?;
+    Ok(HttpResponse::Created().json(list))
+}
+
+#[utoipa::path(
+    tag = "lists",
+    responses(
+        (status = 200, description = "The list", body = TodoList),
+        (status = 404, description = "Unknown list", body = ErrorResponse),
+    )
+)]
+#[get("/lists/{id}")]
+pub async fn get_list_by_id(
+    db: UserRepo<scope::Read>,
+    path: web::Path<(i32,)>,
+) -> Result<HttpResponse, RepoError> {
+    let list = db.get_list_by_id(path.into_inner().0).await?;
+    Ok(HttpResponse::Ok().json(list))
+}
+
+/// Renames a list.
+#[utoipa::path(
+    tag = "lists",
+    responses(
+        (status = 200, description = "The renamed list", body = TodoList),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown list", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[put("/lists/{id}")]
+pub async fn update_list_by_id(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32,)>,
+    list: web::Json<NewList>,
+) -> Result<HttpResponse, RepoError> {
+    let list = list.into_inner();
+    list.validate()?;
+    let updated = db.update_list_by_id(path.into_inner().0, list).await?;
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+/// Deletes a list; one that still has todos only with `?cascade=true`.
+#[utoipa::path(
+    tag = "lists",
+    params(DeleteListParams),
+    responses(
+        (status = 200, description = "Number of deleted lists and todos", body = usize, content_type = "application/json"),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown list", body = ErrorResponse),
+        (status = 409, description = "The list still has todos or is the default list", body = ErrorResponse),
+    )
+)]
+#[delete("/lists/{id}")]
+pub async fn delete_list_by_id(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32,)>,
+    params: web::Query<DeleteListParams>,
+) -> Result<HttpResponse, RepoError> {
+    let deleted = db
+        .delete_list_by_id(path.into_inner().0, params.cascade)
+        .await?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+/// The todo `id`, as long as it belongs to the list `list_id`.
+async fn todo_in_list(db: &OwnedRepo, list_id: i32, id: i32) -> Result<Todo, RepoError> {
+    db.get_list_by_id(list_id).await?;
+    let todo = db.get_todo_by_id(id).await?;
+    if todo.list_id != list_id {
+        return Err(RepoError::NotFound);
+    }
+    Ok(todo)
+}
+
+/// `GET /api/todos` restricted to one list.
+#[utoipa::path(
+    tag = "lists",
+    params(TodoQuery),
+    responses(
+        (status = 200, description = "The matching todos of the list", body = Vec<ListedTodo>, headers(("X-Total-Count" = i64, description = "Number of matching todos"))),
+        (status = 404, description = "Unknown list", body = ErrorResponse),
+    )
+)]
+#[get("/lists/{list_id}/todos")]
+pub async fn get_list_todos(
+    db: UserRepo<scope::Read>,
+    path: web::Path<(i32,)>,
+    query: web::Query<TodoQuery>,
+) -> Result<HttpResponse, RepoError> {
+    let list_id = path.into_inner().0;
+    db.get_list_by_id(list_id).await?;
+    let query = TodoQuery {
+        list_id: Some(list_id),
+        ..query.into_inner()
+    };
+    let page = db.query_todos(query).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((TOTAL_COUNT_HEADER, page.total.to_string()))
+        .json(page.items))
+}
+
+/// Creates a todo in the list, whatever `list_id` the body names.
+#[utoipa::path(
+    tag = "lists",
+    responses(
+        (status = 201, description = "The created todo", body = Todo),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown list or parent", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[post("/lists/{list_id}/todos")]
+pub async fn create_list_todo(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32,)>,
+    new_todo: web::Json<NewTodo>,
+) -> Result<HttpResponse, RepoError> {
+    let mut new_todo = new_todo.into_inner();
+    new_todo.list_id = path.into_inner().0;
+    new_todo.validate()?;
+    let todo = db.create_todo(new_todo).await?;
+    Ok(HttpResponse::Created().json(todo))
+}
+
+#[utoipa::path(
+    tag = "lists",
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a cached copy"),
+    ),
+    responses(
+        (status = 200, description = "The todo", body = Todo, headers(("ETag" = String, description = "Entity tag of the todo"))),
+        (status = 304, description = "The cached copy is still current"),
+        (status = 404, description = "Unknown list or todo", body = ErrorResponse),
+    )
+)]
+#[get("/lists/{list_id}/todos/{id}")]
+pub async fn get_list_todo_by_id(
+    req: HttpRequest,
+    db: UserRepo<scope::Read>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, RepoError> {
+    let (list_id, id) = path.into_inner();
+    let todo = todo_in_list(&db, list_id, id).await?;
+    Ok(tagged_unless_cached(&req, &todo))
+}
+
+/// Replaces a todo of the list, which stays in the list; moving goes through `PATCH`.
+#[utoipa::path(
+    tag = "lists",
+    params(
+        ("If-Match" = Option<String>, Header, description = "Only change the todo if it still has this `ETag`"),
+    ),
+    responses(
+        (status = 200, description = "The updated todo", body = Todo, headers(("ETag" = String, description = "Entity tag of the todo"))),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown list or todo", body = ErrorResponse),
+        (status = 409, description = "The todo has open blockers", body = ErrorResponse),
+        (status = 412, description = "Changed since the `ETag` sent as `If-Match`", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[put("/lists/{list_id}/todos/{id}")]
+pub async fn update_list_todo_by_id(
+    req: HttpRequest,
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32, i32)>,
+    updated_todo: web::Json<Todo>,
+) -> Result<HttpResponse, RepoError> {
+    let (list_id, id) = path.into_inner();
+    let mut updated_todo = updated_todo.into_inner();
+    updated_todo.list_id = list_id;
+    updated_todo.validate()?;
+    let current = todo_in_list(&db, list_id, id).await?;
+    let expected = if_match(&req, &current)?;
+    let updated = db
+        .update_todo_if_version(id, updated_todo, expected)
+        .await?;
+    Ok(tagged(&updated))
+}
+
+#[utoipa::path(
+    tag = "lists",
+    params(
+        ("If-Match" = Option<String>, Header, description = "Only change the todo if it still has this `ETag`"),
+    ),
+    responses(
+        (status = 200, description = "The updated todo", body = Todo, headers(("ETag" = String, description = "Entity tag of the todo"))),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown list or todo", body = ErrorResponse),
+        (status = 409, description = "The todo has open blockers", body = ErrorResponse),
+        (status = 412, description = "Changed since the `ETag` sent as `If-Match`", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[patch("/lists/{list_id}/todos/{id}")]
+pub async fn patch_list_todo_by_id(
+    req: HttpRequest,
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32, i32)>,
+    patch: web::Json<TodoPatch>,
+) -> Result<HttpResponse, RepoError> {
+    let (list_id, id) = path.into_inner();
+    let patch = patch.into_inner();
+    patch.validate()?;
+    let current = todo_in_list(&db, list_id, id).await?;
+    let expected = if_match(&req, &current)?;
+    let patched = db.patch_todo_if_version(id, patch, expected).await?;
+    Ok(tagged(&patched))
+}
+
+#[utoipa::path(
+    tag = "lists",
+    params(
+        ("If-Match" = Option<String>, Header, description = "Only change the todo if it still has this `ETag`"),
+    ),
+    responses(
+        (status = 200, description = "Number of deleted todos, subtasks included", body = usize, content_type = "application/json"),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown list or todo", body = ErrorResponse),
+        (status = 412, description = "Changed since the `ETag` sent as `If-Match`", body = ErrorResponse),
+    )
+)]
+#[delete("/lists/{list_id}/todos/{id}")]
+pub async fn delete_list_todo_by_id(
+    req: HttpRequest,
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, RepoError> {
+    let (list_id, id) = path.into_inner();
+    let current = todo_in_list(&db, list_id, id).await?;
+    let expected = if_match(&req, &current)?;
+    let deleted = db.delete_todo_if_version(id, expected).await?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
@@ -1 +1,14 @@
-pub mod api;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod api;
+pub mod auth;
+pub mod error;
+pub mod etag;
+pub mod events;
+pub mod history;
+pub mod keys;
+pub mod lists;
+pub mod openapi;
+pub mod shares;
+pub mod tags;
+pub mod trash;
+pub mod version;
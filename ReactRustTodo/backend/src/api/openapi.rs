@@ -0,0 +1,173 @@
+use super::error::{Problem, PROBLEM_CONTENT_TYPE};
+use super::version::ApiVersion;
+use super::{api, auth, events, history, keys, lists, shares, tags, trash};
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use utoipa::openapi::path::Operation;
+use utoipa::openapi::schema::Ref;
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::openapi::{Deprecated, OpenApi as Spec, Paths, RefOr};
+use utoipa::{Modify, OpenApi, PartialSchema, ToSchema};
+
+/// The OpenAPI 3 description of every route of a version, without its prefix.
+/// [`document`] turns it into the one of a mount.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "ReactRustTodo API",
+        description = "Todos with lists, tags, subtasks, blockers and sharing. \
+            Apart from health, registration, login and this document every route \
+            needs `Authorization: Bearer <token or API key>` and answers `401` without it."
+    ),
+    paths(
+        auth::register,
+        auth::login,
+        auth::me,
+        keys::create_api_key,
+        keys::get_api_keys,
+        keys::delete_api_key,
+        api::create_todo,
+        api::bulk_todos,
+        api::delete_completed_todos,
+        api::complete_all_todos,
+        api::get_overdue_todos,
+        api::get_due_todos,
+        api::get_next_todos,
+        api::get_ordered_todos,
+        api::get_todo_by_id,
+        api::get_todo_subtree,
+        api::add_blocker,
+        api::remove_blocker,
+        api::get_todos,
+        api::delete_todo_by_id,
+        api::update_todo_by_id,
+        api::patch_todo_by_id,
+        trash::get_trash,
+        trash::restore_todo,
+        trash::purge_todo,
+        trash::empty_trash,
+        history::get_todo_history,
+        history::get_audit,
+        tags::attach_tag,
+        tags::detach_tag,
+        tags::get_tags,
+        tags::create_tag,
+        tags::get_tag_by_id,
+        tags::update_tag_by_id,
+        tags::delete_tag_by_id,
+        lists::get_lists,
+        lists::create_list,
+        lists::get_list_by_id,
+        lists::update_list_by_id,
+        lists::delete_list_by_id,
+        lists::get_list_todos,
+        lists::create_list_todo,
+        lists::get_list_todo_by_id,
+        lists::update_list_todo_by_id,
+        lists::patch_list_todo_by_id,
+        lists::delete_list_todo_by_id,
+        shares::get_todo_shares,
+        shares::share_todo,
+        shares::unshare_todo,
+        shares::get_list_shares,
+        shares::share_list,
+        shares::unshare_list,
+        events::events,
+        api::health,
+        openapi_json,
+    ),
+    components(schemas(crate::models::event::TodoEvent)),
+    modifiers(&BearerAuth),
+    security(("bearer" = [])),
+    tags(
+        (name = "auth", description = "Accounts and session tokens"),
+        (name = "keys", description = "API keys for scripts and bots"),
+        (name = "todos", description = "Todos, their subtasks and blockers"),
+        (name = "trash", description = "Deleted todos until they are purged"),
+        (name = "history", description = "Audit log of the changes of todos"),
+        (name = "tags", description = "Tags shared by all users"),
+        (name = "lists", description = "Lists and the todos in them"),
+        (name = "shares", description = "Sharing todos and lists with other users"),
+        (name = "events", description = "Server-sent events about changed todos"),
+        (name = "meta", description = "Health and this document"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Adds the `bearer` scheme the routes refer to.
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut Spec) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+/// The document of `version` mounted at `prefix`, served at
+/// `{prefix}/openapi.json` and shown by the UI at `{prefix}/docs/`.
+///
+/// Routes of deprecated versions are marked as such, from v2 on the errors
+/// are [`Problem`]s.
+pub fn document(prefix: &str, version: ApiVersion) -> Spec {
+    let mut spec = ApiDoc::openapi();
+    let paths = std::mem::replace(&mut spec.paths, Paths::new());
+    for (path, mut item) in paths.paths {
+        let operations = [
+            &mut item.get,
+            &mut item.put,
+            &mut item.post,
+            &mut item.delete,
+            &mut item.patch,
+        ];
+        for operation in operations.into_iter().flatten() {
+            if version.deprecation().is_some() {
+                operation.deprecated = Some(Deprecated::True);
+            }
+            if version != ApiVersion::V1 {
+                problem_errors(operation);
+            }
+        }
+        spec.paths.paths.insert(format!("{prefix}{path}"), item);
+    }
+    if version != ApiVersion::V1 {
+        let schemas = &mut spec.components.get_or_insert_with(Default::default).schemas;
+        schemas.remove("ErrorResponse");
+        schemas.insert(Problem::name().into_owned(), Problem::schema());
+    }
+    spec
+}
+
+/// Swaps the `ErrorResponse` bodies of `operation` for [`Problem`]s.
+fn problem_errors(operation: &mut Operation) {
+    let error_response = Ref::from_schema_name("ErrorResponse");
+    for response in operation.responses.responses.values_mut() {
+        let RefOr::T(response) = response else {
+            continue;
+        };
+        let is_error = response.content.get("application/json").is_some_and(
+            |content| matches!(&content.schema, Some(RefOr::Ref(r)) if *r == error_response),
+        );
+        if is_error {
+            let mut content = response.content.shift_remove("application/json").unwrap();
+            content.schema = Some(RefOr::Ref(Ref::from_schema_name(Problem::name())));
+            response
+                .content
+                .insert(PROBLEM_CONTENT_TYPE.to_string(), content);
+        }
+    }
+}
+
+/// This document.
+#[utoipa::path(
+    tag = "meta",
+    security(()),
+    responses((status = 200, description = "The OpenAPI 3 document", content_type = "application/json"))
+)]
+#[get("/openapi.json")]
+pub async fn openapi_json(req: HttpRequest, version: web::Data<ApiVersion>) -> HttpResponse {
+    let prefix = req.path().trim_end_matches("/openapi.json");
+    HttpResponse::Ok().json(document(prefix, **version))
+}
@@ -0,0 +1,129 @@
+use super::auth::{scope, UserRepo};
+use super::error::ErrorResponse;
+use crate::{
+    models::share::{NewShare, Share},
+    repository::{error::RepoError, todo_repo::TodoRepo},
+};
+use actix_web::{delete, get, put, web, HttpResponse};
+
+/// The users the todo is shared with and their roles.
+#[utoipa::path(
+    tag = "shares",
+    responses(
+        (status = 200, description = "The shares of the todo", body = Vec<Share>),
+        (status = 404, description = "Unknown todo", body = ErrorResponse),
+    )
+)]
+#[get("/todos/{id}/shares")]
+pub async fn get_todo_shares(
+    db: UserRepo<scope::Read>,
+    path: web::Path<(i32,)>,
+) -> Result<HttpResponse, RepoError> {
+    let shares = db.get_todo_shares(path.into_inner().0).await?;
+    Ok(HttpResponse::Ok().json(shares))
+}
+
+/// Shares the todo with the user `user_id`, only its owner may do so.
+#[utoipa::path(
+    tag = "shares",
+    responses(
+        (status = 200, description = "The share", body = Share),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown todo or user", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[put("/todos/{id}/shares/{user_id}")]
+pub async fn share_todo(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32, i32)>,
+    new_share: web::Json<NewShare>,
+) -> Result<HttpResponse, RepoError> {
+    let (id, user_id) = path.into_inner();
+    let share = Share {
+        user_id,
+        role: new_share.role,
+    };
+    let share = db.share_todo(id, share).await?;
+    Ok(HttpResponse::Ok().json(share))
+}
+
+/// Stops sharing the todo with the user `user_id`.
+#[utoipa::path(
+    tag = "shares",
+    responses(
+        (status = 200, description = "Number of ended shares", body = usize, content_type = "application/json"),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown todo", body = ErrorResponse),
+    )
+)]
+#[delete("/todos/{id}/shares/{user_id}")]
+pub async fn unshare_todo(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, RepoError> {
+    let (id, user_id) = path.into_inner();
+    let deleted = db.unshare_todo(id, user_id).await?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+/// The users the list is shared with and their roles.
+#[utoipa::path(
+    tag = "shares",
+    responses(
+        (status = 200, description = "The shares of the list", body = Vec<Share>),
+        (status = 404, description = "Unknown list", body = ErrorResponse),
+    )
+)]
+#[get("/lists/{id}/shares")]
+pub async fn get_list_shares(
+    db: UserRepo<scope::Read>,
+    path: web::Path<(i32,)>,
+) -> Result<HttpResponse, RepoError> {
+    let shares = db.get_list_shares(path.into_inner().0).await?;
+    Ok(HttpResponse::Ok().json(shares))
+}
+
+/// Shares the list and all its todos with the user `user_id`.
+#[utoipa::path(
+    tag = "shares",
+    responses(
+        (status = 200, description = "The share", body = Share),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown list or user", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[put("/lists/{id}/shares/{user_id}")]
+pub async fn share_list(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32, i32)>,
+    new_share: web::Json<NewShare>,
+) -> Result<HttpResponse, RepoError> {
+    let (id, user_id) = path.into_inner();
+    let share = Share {
+        user_id,
+        role: new_share.role,
+    };
+    let share = db.share_list(id, share).await?;
+    Ok(HttpResponse::Ok().json(share))
+}
+
+/// Stops sharing the list with the user `user_id`.
+#[utoipa::path(
+    tag = "shares",
+    responses(
+        (status = 200, description = "Number of ended shares", body = usize, content_type = "application/json"),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown list", body = ErrorResponse),
+    )
+)]
+#[delete("/lists/{id}/shares/{user_id}")]
+pub async fn unshare_list(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, RepoError> {
+    let (id, user_id) = path.into_inner();
+    let deleted = db.unshare_list(id, user_id).await?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
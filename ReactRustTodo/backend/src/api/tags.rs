@@ -0,0 +1,137 @@
+use super::auth::{scope, UserRepo};
+use super::error::ErrorResponse;
+use crate::{
+    models::{
+        tag::{NewTag, Tag},
+        todo::Todo,
+    },
+    repository::{error::RepoError, todo_repo::TodoRepo},
+    validation::Validate,
+};
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+#[utoipa::path(
+    tag = "tags",
+    responses(
+        (status = 200, description = "The user's tags and those on todos they can see", body = Vec<Tag>),
+    )
+)]
+#[get("/tags")]
+pub async fn get_tags(db: UserRepo<scope::Read>) -> Result<HttpResponse, RepoError> {
+    let tags = db.get_tags(None).await?;
+    Ok(HttpResponse::Ok().json(tags))
+}
+
+#[utoipa::path(
+    tag = "tags",
+    responses(
+        (status = 201, description = "The created tag", body = Tag),
+        (status = 409, description = "The name is taken", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[post("/tags")]
+pub async fn create_tag(
+    db: UserRepo<scope::Write>,
+    new_tag: web::Json<NewTag>,
+) -> Result<HttpResponse, RepoError> {
+    let new_tag = new_tag.into_inner();
+    new_tag.validate()?;
+    let tag = db.create_tag(new_tag).await?;
+    Ok(HttpResponse::Created().json(tag))
+}
+
+#[utoipa::path(
+    tag = "tags",
+    responses(
+        (status = 200, description = "The tag", body = Tag),
+        (status = 404, description = "Unknown tag", body = ErrorResponse),
+    )
+)]
+#[get("/tags/{id}")]
+pub async fn get_tag_by_id(
+    db: UserRepo<scope::Read>,
+    path: web::Path<(i32,)>,
+) -> Result<HttpResponse, RepoError> {
+    let tag = db.get_tag_by_id(path.into_inner().0).await?;
+    Ok(HttpResponse::Ok().json(tag))
+}
+
+/// Renames a tag, only its owner may.
+#[utoipa::path(
+    tag = "tags",
+    responses(
+        (status = 200, description = "The renamed tag", body = Tag),
+        (status = 403, description = "The tag belongs to another user", body = ErrorResponse),
+        (status = 404, description = "Unknown tag", body = ErrorResponse),
+        (status = 409, description = "The name is taken", body = ErrorResponse),
+        (status = 422, description = "Invalid payload", body = ErrorResponse),
+    )
+)]
+#[put("/tags/{id}")]
+pub async fn update_tag_by_id(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32,)>,
+    tag: web::Json<NewTag>,
+) -> Result<HttpResponse, RepoError> {
+    let tag = tag.into_inner();
+    tag.validate()?;
+    let updated = db.update_tag_by_id(path.into_inner().0, tag).await?;
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+/// Deletes a tag, detaching it from all todos; only its owner may.
+#[utoipa::path(
+    tag = "tags",
+    responses(
+        (status = 200, description = "Number of deleted tags", body = usize, content_type = "application/json"),
+        (status = 403, description = "The tag belongs to another user", body = ErrorResponse),
+        (status = 404, description = "Unknown tag", body = ErrorResponse),
+    )
+)]
+#[delete("/tags/{id}")]
+pub async fn delete_tag_by_id(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32,)>,
+) -> Result<HttpResponse, RepoError> {
+    let deleted = db.delete_tag_by_id(path.into_inner().0).await?;
+    Ok(HttpResponse::Ok().json(deleted))
+}
+
+/// Attaches a tag to a todo and returns the todo.
+#[utoipa::path(
+    tag = "tags",
+    responses(
+        (status = 200, description = "The tagged todo", body = Todo),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown todo or tag", body = ErrorResponse),
+    )
+)]
+#[put("/todos/{id}/tags/{tag_id}")]
+pub async fn attach_tag(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, RepoError> {
+    let (id, tag_id) = path.into_inner();
+    let todo = db.attach_tag(id, tag_id).await?;
+    Ok(HttpResponse::Ok().json(todo))
+}
+
+/// Detaches a tag from a todo and returns the todo.
+#[utoipa::path(
+    tag = "tags",
+    responses(
+        (status = 200, description = "The todo without the tag", body = Todo),
+        (status = 403, description = "The role or the scope does not allow this", body = ErrorResponse),
+        (status = 404, description = "Unknown todo or tag", body = ErrorResponse),
+    )
+)]
+#[delete("/todos/{id}/tags/{tag_id}")]
+pub async fn detach_tag(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, RepoError> {
+    let (id, tag_id) = path.into_inner();
+    let todo = db.detach_tag(id, tag_id).await?;
+    Ok(HttpResponse::Ok().json(todo))
+}
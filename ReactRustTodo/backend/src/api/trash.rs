@@ -0,0 +1,70 @@
+use super::auth::{scope, UserRepo};
+use super::error::ErrorResponse;
+use super::etag::tagged;
+use crate::{
+    models::todo::Todo,
+    repository::{error::RepoError, todo_repo::TodoRepo},
+};
+use actix_web::{delete, get, post, web, HttpResponse};
+use chrono::Local;
+
+/// The user's trashed todos, most recently deleted first.
+#[utoipa::path(
+    tag = "trash",
+    responses(
+        (status = 200, description = "The trashed todos", body = Vec<Todo>),
+    )
+)]
+#[get("/trash")]
+pub async fn get_trash(db: UserRepo<scope::Read>) -> Result<HttpResponse, RepoError> {
+    let todos = db.get_trash(None).await?;
+    Ok(HttpResponse::Ok().json(todos))
+}
+
+/// Takes a todo out of the trash together with the subtasks deleted with it.
+#[utoipa::path(
+    tag = "trash",
+    responses(
+        (status = 200, description = "The restored todo", body = Todo, headers(("ETag" = String, description = "Entity tag of the todo"))),
+        (status = 404, description = "The todo is not in the trash", body = ErrorResponse),
+        (status = 409, description = "The parent todo is in the trash as well", body = ErrorResponse),
+    )
+)]
+#[post("/todos/{id}/restore")]
+pub async fn restore_todo(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32,)>,
+) -> Result<HttpResponse, RepoError> {
+    let todo = db.restore_todo(path.into_inner().0).await?;
+    Ok(tagged(&todo))
+}
+
+/// Deletes a trashed todo and its subtasks for good.
+#[utoipa::path(
+    tag = "trash",
+    responses(
+        (status = 200, description = "Number of deleted todos, subtasks included", body = usize, content_type = "application/json"),
+        (status = 404, description = "The todo is not in the trash", body = ErrorResponse),
+    )
+)]
+#[delete("/trash/{id}")]
+pub async fn purge_todo(
+    db: UserRepo<scope::Write>,
+    path: web::Path<(i32,)>,
+) -> Result<HttpResponse, RepoError> {
+    let purged = db.purge_todo(path.into_inner().0).await?;
+    Ok(HttpResponse::Ok().json(purged))
+}
+
+/// Empties the user's trash.
+#[utoipa::path(
+    tag = "trash",
+    responses(
+        (status = 200, description = "Number of deleted todos", body = usize, content_type = "application/json"),
+    )
+)]
+#[delete("/trash")]
+pub async fn empty_trash(db: UserRepo<scope::Write>) -> Result<HttpResponse, RepoError> {
+    let purged = db.purge_trash(Local::now().naive_local()).await?;
+    Ok(HttpResponse::Ok().json(purged))
+}
@@ -0,0 +1,82 @@
+use actix_web::{
+    dev::ServiceResponse,
+    http::header::{HeaderName, HeaderValue, LINK},
+};
+
+use crate::repository::error::RepoError;
+
+/// `Deprecation` header (RFC 9745) on responses of deprecated versions.
+pub const DEPRECATION_HEADER: &str = "Deprecation";
+/// `Sunset` header (RFC 8594) on responses of deprecated versions.
+pub const SUNSET_HEADER: &str = "Sunset";
+
+/// A version of the API. All versions share the handlers and the
+/// [`TodoRepo`](crate::repository::todo_repo::TodoRepo) layer, they differ in
+/// how the responses look.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    /// The original API, also mounted at `/api`. Deprecated.
+    V1,
+    /// Errors are RFC 9457 problem details.
+    V2,
+}
+
+impl ApiVersion {
+    /// The version clients should move to.
+    pub const LATEST: ApiVersion = ApiVersion::V2;
+
+    /// Where the version is mounted.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/api/v1",
+            ApiVersion::V2 => "/api/v2",
+        }
+    }
+
+    /// `(Deprecation, Sunset)` header values while the version is deprecated:
+    /// since when, as structured date, and the HTTP date it goes away.
+    pub fn deprecation(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            // 2026-10-18, when v2 came out.
+            ApiVersion::V1 => Some(("@1792281600", "Fri, 30 Apr 2027 00:00:00 GMT")),
+            ApiVersion::V2 => None,
+        }
+    }
+}
+
+/// Last step of every response of `version` mounted at `prefix`: handler errors
+/// get the error format of the version, deprecated versions get `Deprecation`,
+/// `Sunset` and a `Link` to the same route in [`ApiVersion::LATEST`].
+pub fn finish(version: ApiVersion, prefix: &str, res: ServiceResponse) -> ServiceResponse {
+    let reformatted = res
+        .response()
+        .error()
+        .and_then(|err| err.as_error::<RepoError>())
+        .filter(|_| version != ApiVersion::V1)
+        .map(|err| err.response(version));
+    let mut res = match reformatted {
+        Some(response) => res.into_response(response),
+        None => res,
+    };
+
+    if let Some((since, sunset)) = version.deprecation() {
+        let route = res.request().path().strip_prefix(prefix).unwrap_or("");
+        let successor = format!(
+            "<{}{route}>; rel=\"successor-version\"",
+            ApiVersion::LATEST.prefix()
+        );
+        let headers = res.headers_mut();
+        headers.insert(
+            HeaderName::from_static("deprecation"),
+            HeaderValue::from_static(since),
+        );
+        headers.insert(
+            HeaderName::from_static("sunset"),
+            HeaderValue::from_static(sunset),
+        );
+        if let Ok(link) = HeaderValue::from_str(&successor) {
+            headers.insert(LINK, link);
+        }
+    }
+    res
+}
@@ -0,0 +1,132 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::repository::error::{RepoError, RepoResult};
+
+/// Lifetime of a session token unless configured otherwise.
+pub const DEFAULT_TOKEN_TTL_HOURS: u32 = 24;
+
+/// Hashes `password` with Argon2id and a random salt, in PHC string format.
+pub fn hash_password(password: &str) -> RepoResult<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| RepoError::Internal(format!("cannot hash password: {err}")))
+}
+
+/// Whether `password` matches `hash`; a malformed hash matches nothing.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// An Argon2id hash with the default parameters that no password matches.
+/// Login checks it for unknown usernames, so they take as long as known ones.
+pub const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$VspbxBruaNvbH5B6Hab6iw$qbjqVOjVVxG2DBYBK3xOy9Yx0Ixv+0qEye3MiuBZLbQ";
+
+/// Start of every API key secret, tells them apart from session tokens.
+pub const API_KEY_PREFIX: &str = "tdk_";
+
+/// Characters of a secret kept in [`ApiKey::prefix`](crate::models::api_key::ApiKey::prefix).
+pub const API_KEY_SHOWN_LEN: usize = 12;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// A new API key secret, [`API_KEY_PREFIX`] followed by 32 random bytes in hex.
+pub fn generate_api_key() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    format!("{API_KEY_PREFIX}{}", hex(&bytes))
+}
+
+/// The hash an API key is stored and looked up by.
+///
+/// The secrets are random, so a plain SHA-256 is enough and, unlike a salted
+/// hash, allows the lookup.
+pub fn hash_api_key(secret: &str) -> String {
+    hex(&Sha256::digest(secret.as_bytes()))
+}
+
+/// Payload of a session token.
+#[derive(Serialize, Deserialize, Debug)]
+struct Claims {
+    /// Id of the user, as a string like JWT wants it.
+    sub: String,
+    /// Expiry as Unix timestamp.
+    exp: i64,
+}
+
+/// Issues and checks the session tokens, JWTs signed with HMAC-SHA256.
+#[derive(Clone)]
+pub struct AuthKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    ttl: Duration,
+}
+
+impl AuthKeys {
+    /// Keys derived from `secret`; tokens stay valid across restarts with the same secret.
+    pub fn new(secret: &[u8]) -> Self {
+        AuthKeys {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            ttl: Duration::hours(i64::from(DEFAULT_TOKEN_TTL_HOURS)),
+        }
+    }
+
+    /// Keys from a random secret, all tokens become invalid on restart.
+    pub fn random() -> Self {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        Self::new(&secret)
+    }
+
+    /// Sets how long issued tokens are valid.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// A token for the user `user_id`, valid for [`AuthKeys::ttl`].
+    pub fn issue(&self, user_id: i32) -> RepoResult<String> {
+        let claims = Claims {
+            sub: user_id.to_string(),
+            exp: (Utc::now() + self.ttl).timestamp(),
+        };
+        encode(&Header::new(Algorithm::HS256), &claims, &self.encoding)
+            .map_err(|err| RepoError::Internal(format!("cannot sign token: {err}")))
+    }
+
+    /// The user id of a token issued by these keys, `None` if it is forged,
+    /// malformed or expired.
+    pub fn verify(&self, token: &str) -> Option<i32> {
+        self.verify_until(token).map(|(user_id, _)| user_id)
+    }
+
+    /// Like [`AuthKeys::verify`], together with the time the token expires.
+    pub fn verify_until(&self, token: &str) -> Option<(i32, DateTime<Utc>)> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        let claims = decode::<Claims>(token, &self.decoding, &validation)
+            .ok()?
+            .claims;
+        let expires_at = Utc.timestamp_opt(claims.exp, 0).single()?;
+        Some((claims.sub.parse().ok()?, expires_at))
+    }
+}
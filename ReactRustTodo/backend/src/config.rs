@@ -0,0 +1,372 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{AuthKeys, DEFAULT_TOKEN_TTL_HOURS};
+use crate::repository::{
+    self, error::RepoError, error::RepoResult, file_repo::FileRepo, mem_repo::MemRepo, RepoBox,
+    RepoOptions, DEFAULT_POOL_SIZE,
+};
+
+/// Days deleted todos stay in the trash unless configured otherwise.
+pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+
+/// Config file read when `--config` and `TODO_CONFIG` are not given, if present.
+pub const DEFAULT_CONFIG_FILE: &str = "todo.toml";
+
+/// Command line of the server binary.
+#[derive(Parser, Debug, Clone)]
+#[command(
+    name = "todo-backend",
+    version,
+    about = "REST backend of the todo app",
+    after_help = "Settings are layered: built-in defaults, then the TOML config file, \
+                  then environment variables, then command line flags."
+)]
+pub struct Cli {
+    /// TOML config file [env: TODO_CONFIG] [default: ./todo.toml if it exists]
+    #[arg(short, long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub overrides: PartialConfig,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Start the HTTP server (the default)
+    Serve,
+    /// Print the effective configuration as TOML and exit
+    Config,
+}
+
+/// Where the todos are stored.
+#[derive(Serialize, Deserialize, ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// The SQL database `database_url` points to
+    #[default]
+    Database,
+    /// In memory only, lost on restart
+    Memory,
+    /// Write-ahead log and snapshots in `data_dir`
+    File,
+}
+
+/// Effective settings of the server after all layers are merged.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub backend: Backend,
+    pub database_url: Option<String>,
+    pub data_dir: PathBuf,
+    pub host: String,
+    pub port: u16,
+    pub cors_origins: Vec<String>,
+    pub static_dir: PathBuf,
+    pub pool_size: u32,
+    pub log_level: String,
+    pub block_completion: bool,
+    /// Kept out of the `config` output.
+    #[serde(skip_serializing)]
+    pub jwt_secret: Option<String>,
+    pub token_ttl_hours: u32,
+    pub trash_retention_days: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            backend: Backend::Database,
+            database_url: None,
+            data_dir: PathBuf::from("./data"),
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            cors_origins: vec!["http://localhost:5173".to_string()],
+            static_dir: PathBuf::from("./static"),
+            pool_size: DEFAULT_POOL_SIZE,
+            log_level: "info".to_string(),
+            block_completion: true,
+            jwt_secret: None,
+            token_ttl_hours: DEFAULT_TOKEN_TTL_HOURS,
+            trash_retention_days: DEFAULT_TRASH_RETENTION_DAYS,
+        }
+    }
+}
+
+/// One configuration layer; unset fields leave the lower layers untouched.
+///
+/// The same struct is read from the config file, the environment and the
+/// command line flags.
+#[derive(Args, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PartialConfig {
+    /// Storage backend [env: TODO_BACKEND] [default: database]
+    #[arg(short, long, global = true, value_enum)]
+    pub backend: Option<Backend>,
+
+    /// URL of the database backend (mysql://, sqlite://, postgres://, file://) [env: DATABASE_URL]
+    #[arg(long, global = true, value_name = "URL")]
+    pub database_url: Option<String>,
+
+    /// Data directory of the file backend [env: DATA_DIR] [default: ./data]
+    #[arg(long, global = true, value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Address to bind to [env: TODO_HOST] [default: 127.0.0.1]
+    #[arg(long, global = true)]
+    pub host: Option<String>,
+
+    /// Port to listen on [env: TODO_PORT] [default: 8080]
+    #[arg(short, long, global = true)]
+    pub port: Option<u16>,
+
+    /// Allowed CORS origin, repeatable or comma separated, `*` allows any
+    /// [env: TODO_CORS_ORIGINS] [default: http://localhost:5173]
+    #[arg(
+        long = "cors-origin",
+        global = true,
+        value_name = "ORIGIN",
+        value_delimiter = ','
+    )]
+    pub cors_origins: Option<Vec<String>>,
+
+    /// Directory with the frontend files [env: TODO_STATIC_DIR] [default: ./static]
+    #[arg(long, global = true, value_name = "DIR")]
+    pub static_dir: Option<PathBuf>,
+
+    /// Maximum number of database connections [env: TODO_POOL_SIZE] [default: 10]
+    #[arg(long, global = true)]
+    pub pool_size: Option<u32>,
+
+    /// Log filter, e.g. `info` or `warn,actix_web=debug` [env: TODO_LOG_LEVEL] [default: info]
+    #[arg(long, global = true, value_name = "FILTER")]
+    pub log_level: Option<String>,
+
+    /// Reject completing a todo while it has open blockers
+    /// [env: TODO_BLOCK_COMPLETION] [default: true]
+    #[arg(long, global = true, value_name = "BOOL")]
+    pub block_completion: Option<bool>,
+
+    /// Secret the session tokens are signed with, random (tokens end with the
+    /// process) if unset [env: TODO_JWT_SECRET]
+    #[arg(long, global = true, value_name = "SECRET")]
+    pub jwt_secret: Option<String>,
+
+    /// Hours a session token stays valid [env: TODO_TOKEN_TTL_HOURS] [default: 24]
+    #[arg(long, global = true, value_name = "HOURS")]
+    pub token_ttl_hours: Option<u32>,
+
+    /// Days deleted todos stay in the trash before they are purged, 0 keeps
+    /// them until the trash is emptied [env: TODO_TRASH_RETENTION_DAYS] [default: 30]
+    #[arg(long, global = true, value_name = "DAYS")]
+    pub trash_retention_days: Option<u32>,
+}
+
+/// A setting that could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The config file is missing or malformed.
+    File { path: PathBuf, message: String },
+    /// An environment variable holds an invalid value.
+    Env { var: String, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::File { path, message } => {
+                write!(f, "invalid config file {}: {message}", path.display())
+            }
+            ConfigError::Env { var, message } => write!(f, "invalid value for {var}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn env_value<T: std::str::FromStr>(
+    env: &impl Fn(&str) -> Option<String>,
+    var: &str,
+) -> Result<Option<T>, ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    env(var)
+        .map(|value| {
+            value.parse().map_err(|err: T::Err| ConfigError::Env {
+                var: var.to_string(),
+                message: err.to_string(),
+            })
+        })
+        .transpose()
+}
+
+impl PartialConfig {
+    /// Reads the layer from a TOML file.
+    pub fn from_file(path: &Path) -> Result<PartialConfig, ConfigError> {
+        let file_error = |message: String| ConfigError::File {
+            path: path.to_path_buf(),
+            message,
+        };
+        let content = std::fs::read_to_string(path).map_err(|err| file_error(err.to_string()))?;
+        toml::from_str(&content).map_err(|err| file_error(err.to_string()))
+    }
+
+    /// Reads the layer from environment variables, looked up through `env`.
+    pub fn from_env(env: impl Fn(&str) -> Option<String>) -> Result<PartialConfig, ConfigError> {
+        let backend = env("TODO_BACKEND")
+            .map(|value| {
+                Backend::from_str(&value, true).map_err(|message| ConfigError::Env {
+                    var: "TODO_BACKEND".to_string(),
+                    message,
+                })
+            })
+            .transpose()?;
+        let cors_origins = env("TODO_CORS_ORIGINS").map(|value| {
+            value
+                .split(',')
+                .map(|origin| origin.trim().to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect()
+        });
+
+        Ok(PartialConfig {
+            backend,
+            database_url: env("DATABASE_URL"),
+            data_dir: env("DATA_DIR").map(PathBuf::from),
+            host: env("TODO_HOST"),
+            port: env_value(&env, "TODO_PORT")?,
+            cors_origins,
+            static_dir: env("TODO_STATIC_DIR").map(PathBuf::from),
+            pool_size: env_value(&env, "TODO_POOL_SIZE")?,
+            log_level: env("TODO_LOG_LEVEL"),
+            block_completion: env_value(&env, "TODO_BLOCK_COMPLETION")?,
+            jwt_secret: env("TODO_JWT_SECRET"),
+            token_ttl_hours: env_value(&env, "TODO_TOKEN_TTL_HOURS")?,
+            trash_retention_days: env_value(&env, "TODO_TRASH_RETENTION_DAYS")?,
+        })
+    }
+}
+
+impl Config {
+    /// Merges defaults, config file, environment and `cli` flags, in that order.
+    ///
+    /// The environment is passed in as a lookup function so callers (and tests)
+    /// do not depend on the process environment.
+    pub fn load(cli: &Cli, env: impl Fn(&str) -> Option<String>) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+
+        let path = cli
+            .config
+            .clone()
+            .or_else(|| env("TODO_CONFIG").map(PathBuf::from));
+        match path {
+            Some(path) => config.merge(PartialConfig::from_file(&path)?),
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => {
+                config.merge(PartialConfig::from_file(Path::new(DEFAULT_CONFIG_FILE))?)
+            }
+            None => {}
+        }
+        config.merge(PartialConfig::from_env(env)?);
+        config.merge(cli.overrides.clone());
+        Ok(config)
+    }
+
+    /// Overwrites every setting that is set in `layer`.
+    pub fn merge(&mut self, layer: PartialConfig) {
+        if let Some(backend) = layer.backend {
+            self.backend = backend;
+        }
+        if let Some(database_url) = layer.database_url {
+            self.database_url = Some(database_url);
+        }
+        if let Some(data_dir) = layer.data_dir {
+            self.data_dir = data_dir;
+        }
+        if let Some(host) = layer.host {
+            self.host = host;
+        }
+        if let Some(port) = layer.port {
+            self.port = port;
+        }
+        if let Some(cors_origins) = layer.cors_origins {
+            self.cors_origins = cors_origins;
+        }
+        if let Some(static_dir) = layer.static_dir {
+            self.static_dir = static_dir;
+        }
+        if let Some(pool_size) = layer.pool_size {
+            self.pool_size = pool_size;
+        }
+        if let Some(log_level) = layer.log_level {
+            self.log_level = log_level;
+        }
+        if let Some(block_completion) = layer.block_completion {
+            self.block_completion = block_completion;
+        }
+        if let Some(jwt_secret) = layer.jwt_secret {
+            self.jwt_secret = Some(jwt_secret);
+        }
+        if let Some(token_ttl_hours) = layer.token_ttl_hours {
+            self.token_ttl_hours = token_ttl_hours;
+        }
+        if let Some(trash_retention_days) = layer.trash_retention_days {
+            self.trash_retention_days = trash_retention_days;
+        }
+    }
+
+    /// The settings handed to the repository.
+    pub fn repo_options(&self) -> RepoOptions {
+        RepoOptions {
+            pool_size: self.pool_size,
+            block_completion: self.block_completion,
+        }
+    }
+
+    /// The keys for the session tokens, from a random secret if `jwt_secret` is unset.
+    pub fn auth_keys(&self) -> AuthKeys {
+        let keys = match &self.jwt_secret {
+            Some(secret) => AuthKeys::new(secret.as_bytes()),
+            None => AuthKeys::random(),
+        };
+        keys.with_ttl(chrono::Duration::hours(i64::from(self.token_ttl_hours)))
+    }
+
+    /// How long deleted todos stay in the trash, `None` if they stay until the
+    /// trash is emptied.
+    pub fn trash_retention(&self) -> Option<chrono::Duration> {
+        (self.trash_retention_days > 0)
+            .then(|| chrono::Duration::days(i64::from(self.trash_retention_days)))
+    }
+
+    /// Opens the repository selected by `backend`.
+    pub fn open_repository(&self) -> RepoResult<RepoBox> {
+        match self.backend {
+            Backend::Memory => Ok(Arc::new(
+                MemRepo::new().with_block_completion(self.block_completion),
+            )),
+            Backend::File => Ok(Arc::new(
+                FileRepo::new(&self.data_dir)?.with_block_completion(self.block_completion),
+            )),
+            Backend::Database => {
+                let database_url = self.database_url.as_deref().ok_or_else(|| {
+                    RepoError::Unavailable(
+                        "no database configured, set DATABASE_URL or --database-url".to_string(),
+                    )
+                })?;
+                repository::connect_with_options(database_url, self.repo_options())
+            }
+        }
+    }
+
+    /// The settings as TOML, in the format accepted by the config file.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("config is always representable as TOML")
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::models::event::{EventKind, TodoEvent};
+use crate::models::todo::Todo;
+
+/// Number of past events kept for clients that reconnect.
+pub const DEFAULT_EVENT_BUFFER: usize = 1000;
+
+/// Fan-out of [`TodoEvent`]s to the open `/api/events` streams, keeping the
+/// latest ones so a client can resume where its connection dropped.
+///
+/// Event ids start at 1 whenever the server starts.
+#[derive(Clone)]
+pub struct EventBus {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    capacity: usize,
+    recent: Mutex<Recent>,
+    sender: broadcast::Sender<Arc<TodoEvent>>,
+}
+
+struct Recent {
+    events: VecDeque<Arc<TodoEvent>>,
+    last_id: u64,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::with_capacity(DEFAULT_EVENT_BUFFER)
+    }
+}
+
+impl EventBus {
+    /// A bus keeping the last `capacity` events, also the most a slow stream
+    /// may fall behind before it is reset.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (sender, _) = broadcast::channel(capacity);
+        EventBus {
+            shared: Arc::new(Shared {
+                capacity,
+                recent: Mutex::new(Recent {
+                    events: VecDeque::with_capacity(capacity),
+                    last_id: 0,
+                }),
+                sender,
+            }),
+        }
+    }
+
+    fn recent(&self) -> MutexGuard<'_, Recent> {
+        // The buffer stays consistent even if a publisher panicked.
+        self.shared
+            .recent
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Records an event for the users in `audience` and returns it.
+    pub fn publish(
+        &self,
+        kind: EventKind,
+        todo_id: i32,
+        todo: Option<Todo>,
+        audience: Vec<i32>,
+    ) -> Arc<TodoEvent> {
+        let mut recent = self.recent();
+        recent.last_id += 1;
+        let event = Arc::new(TodoEvent {
+            event_id: recent.last_id,
+            kind,
+            todo_id,
+            todo,
+            audience,
+        });
+        if recent.events.len() == self.shared.capacity {
+            recent.events.pop_front();
+        }
+        recent.events.push_back(event.clone());
+        // Sent under the lock so streams see the events in id order; having
+        // no stream open is not an error.
+        let _ = self.shared.sender.send(event.clone());
+        event
+    }
+
+    /// Id of the latest event, 0 before the first one.
+    pub fn last_event_id(&self) -> u64 {
+        self.recent().last_id
+    }
+
+    /// The events after `last_event_id` followed by all new ones; `None` only
+    /// delivers new events.
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> Subscription {
+        let recent = self.recent();
+        // Subscribing under the lock, the receiver gets exactly the events
+        // that are not in the backlog.
+        let receiver = self.shared.sender.subscribe();
+        let (backlog, reset) = match last_event_id {
+            None => (VecDeque::new(), false),
+            // From before a restart of the server, or already dropped.
+            Some(last) if last > recent.last_id => (VecDeque::new(), true),
+            Some(last) => {
+                let oldest = recent
+                    .events
+                    .front()
+                    .map_or(recent.last_id + 1, |event| event.event_id);
+                if last + 1 < oldest {
+                    (VecDeque::new(), true)
+                } else {
+                    let backlog = recent
+                        .events
+                        .iter()
+                        .filter(|event| event.event_id > last)
+                        .cloned()
+                        .collect();
+                    (backlog, false)
+                }
+            }
+        };
+        Subscription {
+            backlog,
+            receiver,
+            reset,
+        }
+    }
+}
+
+/// What a [`Subscription`] delivers next.
+#[derive(Debug, Clone)]
+pub enum Delivery {
+    Event(Arc<TodoEvent>),
+    /// Events were lost on the way, the client has to load the todos again.
+    Reset,
+}
+
+/// The events of an [`EventBus`] for one stream, see [`EventBus::subscribe`].
+pub struct Subscription {
+    backlog: VecDeque<Arc<TodoEvent>>,
+    receiver: broadcast::Receiver<Arc<TodoEvent>>,
+    reset: bool,
+}
+
+impl Subscription {
+    /// Waits for the next delivery, `None` once the bus is gone.
+    pub async fn next(&mut self) -> Option<Delivery> {
+        if std::mem::take(&mut self.reset) {
+            return Some(Delivery::Reset);
+        }
+        if let Some(event) = self.backlog.pop_front() {
+            return Some(Delivery::Event(event));
+        }
+        match self.receiver.recv().await {
+            Ok(event) => Some(Delivery::Event(event)),
+            Err(RecvError::Lagged(_)) => Some(Delivery::Reset),
+            Err(RecvError::Closed) => None,
+        }
+    }
+}
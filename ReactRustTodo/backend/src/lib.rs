@@ -1,3 +1,9 @@
+#![allow(non_snake_case)]
 pub mod api;
+pub mod auth;
+pub mod config;
+pub mod events;
 pub mod models;
+pub mod recurrence;
 pub mod repository;
+pub mod validation;
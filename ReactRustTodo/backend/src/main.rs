@@ -1,77 +1,116 @@
 #![allow(non_snake_case)]
-use std::{
-    iter,
-    sync::{Arc, Mutex},
-};
+use std::sync::Arc;
+use std::time::Duration;
 
 use actix_cors::Cors;
 use actix_files::Files;
-use actix_web::{
-    get, http::header, middleware::Logger, web, App, HttpResponse, HttpServer, Responder, Result,
-};
-use diesel::{
-    r2d2::{self, ConnectionManager},
-    MysqlConnection,
-};
+use actix_web::{http::header, middleware::Logger, rt, web, App, HttpServer};
+use chrono::Local;
+use clap::Parser;
 use dotenvy::dotenv;
-use serde::Serialize;
 use TodoRustBackend::{
     api,
-    repository::{mem_repo::MemRepo, mysql_repo::MysqlRepo, RepoBox},
+    config::{Cli, Command, Config},
+    events::EventBus,
+    repository::{audit_repo::AuditRepo, event_repo::EventRepo, RepoBox},
 };
 
-fn parse_arg(arg: String) -> String {
-    if arg.starts_with("-") {
-        return arg.replace("-", "");
-    } else {
-        return "".to_string();
-    }
-}
-
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    let args = std::env::args();
-    let mut setup_mem = false;
-    args.into_iter().skip(1).for_each(|x| {
-        if parse_arg(x) == "m" {
-            setup_mem = true;
-        }
+    let cli = Cli::parse();
+    let config = Config::load(&cli, |var| std::env::var(var).ok()).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
     });
 
-    let repo: RepoBox;
-    if (!setup_mem) {
-        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let manager = ConnectionManager::<MysqlConnection>::new(database_url);
-        let pool = r2d2::Pool::builder()
-            .build(manager)
-            .expect("Failed to create pool.");
-        let mysql_repo = MysqlRepo { pool };
-        repo = Arc::new(mysql_repo);
-    } else {
-        repo = Arc::new(MemRepo {
-            inner: Arc::new(Mutex::new(Vec::new())),
-        })
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Config => {
+            print!("{}", config.to_toml());
+            Ok(())
+        }
+        Command::Serve => serve(config).await,
+    }
+}
+
+/// How often todos past the retention are purged from the trash.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+async fn serve(config: Config) -> std::io::Result<()> {
+    env_logger::Builder::new()
+        .parse_filters(&config.log_level)
+        .init();
+
+    let repo = config
+        .open_repository()
+        .unwrap_or_else(|err| panic!("Failed to open the {:?} backend: {err}", config.backend));
+    let events = EventBus::default();
+    let repo: RepoBox = Arc::new(EventRepo::new(repo, events.clone()));
+
+    if config.jwt_secret.is_none() {
+        log::warn!("No jwt_secret configured, sessions end when the server stops");
+    }
+    let auth_keys = config.auth_keys();
+    if let Some(retention) = config.trash_retention() {
+        // Recorded in the audit log as purged by the server itself.
+        let repo = Arc::new(AuditRepo::new(repo.clone(), None));
+        actix_web::rt::spawn(purge_trash(repo, retention));
     }
 
+    let cors_origins = config.cors_origins.clone();
+    let static_dir = config.static_dir.clone();
     HttpServer::new(move || {
-        let cors = Cors::default()
-            .allowed_origin("http://localhost:5173")
-            .allowed_origin("http://localhost:5173/")
-            .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
+        let mut cors = Cors::default()
+            .allowed_methods(vec!["GET", "POST", "PUT", "PATCH", "DELETE"])
             .allowed_headers(vec![
                 header::CONTENT_TYPE,
                 header::AUTHORIZATION,
                 header::ACCEPT,
+                header::HeaderName::from_static("last-event-id"),
+                header::IF_MATCH,
+                header::IF_NONE_MATCH,
+            ])
+            .expose_headers(vec![
+                api::api::TOTAL_COUNT_HEADER,
+                header::ETAG.as_str(),
+                api::version::DEPRECATION_HEADER,
+                api::version::SUNSET_HEADER,
+                header::LINK.as_str(),
             ]);
+        for origin in &cors_origins {
+            cors = if origin == "*" {
+                cors.allow_any_origin()
+            } else {
+                cors.allowed_origin(origin)
+            };
+        }
         App::new()
             .app_data(web::Data::new(repo.clone()))
+            .app_data(web::Data::new(auth_keys.clone()))
+            .app_data(web::Data::new(events.clone()))
             .configure(api::api::config)
-            .service(Files::new("/", "./static").index_file("index.html"))
+            .service(Files::new("/", &static_dir).index_file("index.html"))
             .wrap(cors)
             .wrap(Logger::default())
     })
-    .bind(("127.0.0.1", 8080))?
+    .bind((config.host.as_str(), config.port))?
     .run()
     .await
 }
+
+/// Purges the todos that have been in the trash for longer than `retention`,
+/// on startup and then every [`PURGE_INTERVAL`].
+async fn purge_trash(repo: RepoBox, retention: chrono::Duration) {
+    let mut interval = rt::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match repo
+            .purge_trash(Local::now().naive_local() - retention)
+            .await
+        {
+            Ok(0) => {}
+            Ok(purged) => log::info!("Purged {purged} todos from the trash"),
+            Err(err) => log::warn!("Cannot purge the trash: {err}"),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::{backend::Backend, deserialize, Queryable};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// What an API key may do. Each scope includes the ones before it, so a
+/// `write` key can read as well.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Reading todos, tags and lists.
+    Read,
+    /// Creating, changing and deleting them.
+    Write,
+    /// Managing API keys.
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+
+    /// Joins `scopes` the way the SQL backends store them, e.g. `read,write`.
+    pub fn join(scopes: &[Scope]) -> String {
+        let names: Vec<&str> = scopes.iter().map(|scope| scope.as_str()).collect();
+        names.join(",")
+    }
+
+    /// Parses the output of [`Scope::join`].
+    pub fn split(scopes: &str) -> Result<Vec<Scope>, UnknownScope> {
+        scopes
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error of parsing a [`Scope`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownScope(pub String);
+
+impl fmt::Display for UnknownScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown scope `{}`", self.0)
+    }
+}
+
+impl std::error::Error for UnknownScope {}
+
+impl FromStr for Scope {
+    type Err = UnknownScope;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "admin" => Ok(Scope::Admin),
+            other => Err(UnknownScope(other.to_string())),
+        }
+    }
+}
+
+/// A key for machine clients, acting for the user that issued it. Only a hash
+/// of the secret is stored, see [`ApiKeyRecord`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct ApiKey {
+    pub key_id: i32,
+    pub user_id: i32,
+    pub name: String,
+    /// The first characters of the secret, to tell keys apart.
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    /// The key is rejected from then on, `None` for keys that do not expire.
+    pub expires_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl ApiKey {
+    /// Whether one of the scopes of the key includes `scope`.
+    pub fn grants(&self, scope: Scope) -> bool {
+        self.scopes.iter().any(|granted| *granted >= scope)
+    }
+
+    pub fn is_expired(&self, now: NaiveDateTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// The columns of an `api_keys` row without the hash, scopes are stored as
+/// [`Scope::join`] does.
+#[derive(Queryable)]
+pub struct ApiKeyRow {
+    pub key_id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: String,
+    pub expires_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl<ST, DB> Queryable<ST, DB> for ApiKey
+where
+    DB: Backend,
+    ApiKeyRow: Queryable<ST, DB>,
+{
+    type Row = <ApiKeyRow as Queryable<ST, DB>>::Row;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        let row = ApiKeyRow::build(row)?;
+        Ok(ApiKey {
+            key_id: row.key_id,
+            user_id: row.user_id,
+            name: row.name,
+            prefix: row.prefix,
+            scopes: Scope::split(&row.scopes)?,
+            expires_at: row.expires_at,
+            created_at: row.created_at,
+        })
+    }
+}
+
+/// A key together with the hash of its secret, as kept by the repositories.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyRecord {
+    #[serde(flatten)]
+    pub key: ApiKey,
+    /// Hex encoded SHA-256 of the secret.
+    pub key_hash: String,
+}
+
+/// Body of `POST /api/keys`.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+/// A key to be stored, the secret is already hashed.
+#[derive(Debug, Clone)]
+pub struct NewApiKeyRecord {
+    pub user_id: i32,
+    pub key: NewApiKey,
+    pub prefix: String,
+    pub key_hash: String,
+}
+
+impl NewApiKeyRecord {
+    /// The stored key, for repositories that assign ids themselves.
+    pub fn with_id(self, key_id: i32, created_at: NaiveDateTime) -> ApiKeyRecord {
+        ApiKeyRecord {
+            key: ApiKey {
+                key_id,
+                user_id: self.user_id,
+                name: self.key.name,
+                prefix: self.prefix,
+                scopes: self.key.scopes,
+                expires_at: self.key.expires_at,
+                created_at,
+            },
+            key_hash: self.key_hash,
+        }
+    }
+}
+
+/// Body of a successful `POST /api/keys`, the only time the secret is shown.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct IssuedApiKey {
+    #[serde(flatten)]
+    pub key: ApiKey,
+    pub secret: String,
+}
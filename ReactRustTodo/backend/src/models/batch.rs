@@ -0,0 +1,109 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use super::dependency::topological_order;
+use super::todo::{NewTodo, Todo, TodoPatch};
+
+/// One operation of `POST /api/todos/bulk`, tagged by `op`.
+#[derive(Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOp {
+    /// Creates a todo like `POST /api/todos`.
+    Create { todo: NewTodo },
+    /// Changes the present fields like `PATCH /api/todos/{id}`.
+    Update {
+        id: i32,
+        patch: TodoPatch,
+        /// Only change the todo if it still has this version.
+        #[serde(default)]
+        version: Option<i32>,
+    },
+    /// Deletes the todo with its subtasks.
+    Delete {
+        id: i32,
+        #[serde(default)]
+        version: Option<i32>,
+    },
+    /// Marks the todo as completed.
+    Complete {
+        id: i32,
+        #[serde(default)]
+        version: Option<i32>,
+    },
+}
+
+/// What a successful [`BatchOp`] did.
+// Nearly every outcome is a todo, boxing it would only add allocations.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum BatchOutcome {
+    /// The created or changed todo.
+    Todo(Todo),
+    /// The number of deleted todos, subtasks included.
+    Deleted(usize),
+}
+
+/// Largest number of operations in one batch.
+pub const MAX_BATCH_SIZE: usize = 1000;
+
+impl BatchOp {
+    /// The patch of [`BatchOp::Update`] and [`BatchOp::Complete`].
+    pub fn patch(&self) -> Option<TodoPatch> {
+        match self {
+            BatchOp::Update { patch, .. } => Some(patch.clone()),
+            BatchOp::Complete { .. } => Some(TodoPatch {
+                completed: Some(true),
+                ..TodoPatch::default()
+            }),
+            _ => None,
+        }
+    }
+
+    /// Deletes the completed ones of `todos`, each with its subtasks. Todos
+    /// below another deleted one go with it and get no operation of their own.
+    pub fn delete_completed(todos: &[Todo]) -> Vec<BatchOp> {
+        let parents: HashMap<i32, Option<i32>> = todos
+            .iter()
+            .map(|todo| (todo.todo_id, todo.parent_id))
+            .collect();
+        let doomed: HashSet<i32> = todos
+            .iter()
+            .filter(|todo| todo.completed == Some(true))
+            .map(|todo| todo.todo_id)
+            .collect();
+        let below_doomed = |todo: &Todo| {
+            let mut ancestor = todo.parent_id;
+            let mut seen = HashSet::new();
+            while let Some(id) = ancestor.filter(|id| seen.insert(*id)) {
+                if doomed.contains(&id) {
+                    return true;
+                }
+                ancestor = parents.get(&id).copied().flatten();
+            }
+            false
+        };
+        todos
+            .iter()
+            .filter(|todo| doomed.contains(&todo.todo_id) && !below_doomed(todo))
+            .map(|todo| BatchOp::Delete {
+                id: todo.todo_id,
+                version: None,
+            })
+            .collect()
+    }
+
+    /// Completes the open ones of `todos`, blockers first so completing the
+    /// todos they block is not refused.
+    pub fn complete_open(todos: &[Todo]) -> Vec<BatchOp> {
+        topological_order(todos.to_vec())
+            .into_iter()
+            .filter(|todo| todo.completed != Some(true))
+            .map(|todo| BatchOp::Complete {
+                id: todo.todo_id,
+                version: None,
+            })
+            .collect()
+    }
+}
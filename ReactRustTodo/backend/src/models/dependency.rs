@@ -0,0 +1,48 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::todo::Todo;
+
+/// Orders `todos` so that every todo comes after its blockers.
+///
+/// Only blockers among `todos` are taken into account. Of the todos that are
+/// ready at the same time the one with the lowest id comes first, so the order
+/// is stable. Todos on a cycle, which the repositories do not let happen, are
+/// appended by id.
+pub fn topological_order(todos: Vec<Todo>) -> Vec<Todo> {
+    let mut by_id: HashMap<i32, Todo> = todos.into_iter().map(|t| (t.todo_id, t)).collect();
+    let mut waiting_for: HashMap<i32, usize> = HashMap::new();
+    let mut blocking: HashMap<i32, Vec<i32>> = HashMap::new();
+    for todo in by_id.values() {
+        let blockers = todo
+            .blocked_by
+            .iter()
+            .filter(|blocker| by_id.contains_key(blocker));
+        for blocker in blockers {
+            *waiting_for.entry(todo.todo_id).or_default() += 1;
+            blocking.entry(*blocker).or_default().push(todo.todo_id);
+        }
+    }
+
+    let mut ready: BTreeSet<i32> = by_id
+        .keys()
+        .filter(|id| !waiting_for.contains_key(id))
+        .copied()
+        .collect();
+    let mut ordered = Vec::with_capacity(by_id.len());
+    while let Some(id) = ready.pop_first() {
+        for blocked in blocking.remove(&id).unwrap_or_default() {
+            let count = waiting_for.get_mut(&blocked).expect("counted above");
+            *count -= 1;
+            if *count == 0 {
+                waiting_for.remove(&blocked);
+                ready.insert(blocked);
+            }
+        }
+        ordered.extend(by_id.remove(&id));
+    }
+
+    let mut rest: Vec<Todo> = by_id.into_values().collect();
+    rest.sort_by_key(|todo| todo.todo_id);
+    ordered.extend(rest);
+    ordered
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::todo::Todo;
+
+/// What happened to the todo of a [`TodoEvent`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Created => "created",
+            EventKind::Updated => "updated",
+            EventKind::Deleted => "deleted",
+        }
+    }
+}
+
+/// A change of a todo as sent by `GET /api/events`.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TodoEvent {
+    /// Increases by one per event, clients resume after it with `Last-Event-ID`.
+    pub event_id: u64,
+    pub kind: EventKind,
+    pub todo_id: i32,
+    /// The todo after the change, `None` for deleted todos.
+    pub todo: Option<Todo>,
+    /// The users who could see the todo, the feed skips the event for everyone else.
+    #[serde(skip)]
+    pub audience: Vec<i32>,
+}
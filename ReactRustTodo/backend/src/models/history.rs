@@ -0,0 +1,249 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use diesel::{backend::Backend, deserialize, Queryable};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::todo::Todo;
+
+/// What happened to the todo of a [`HistoryEntry`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryAction {
+    Created,
+    Updated,
+    /// Moved to the trash.
+    Deleted,
+    /// Taken out of the trash.
+    Restored,
+    /// Deleted for good.
+    Purged,
+}
+
+impl HistoryAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HistoryAction::Created => "created",
+            HistoryAction::Updated => "updated",
+            HistoryAction::Deleted => "deleted",
+            HistoryAction::Restored => "restored",
+            HistoryAction::Purged => "purged",
+        }
+    }
+}
+
+impl fmt::Display for HistoryAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error of parsing a [`HistoryAction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownAction(pub String);
+
+impl fmt::Display for UnknownAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown history action `{}`", self.0)
+    }
+}
+
+impl std::error::Error for UnknownAction {}
+
+impl FromStr for HistoryAction {
+    type Err = UnknownAction;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "created" => Ok(HistoryAction::Created),
+            "updated" => Ok(HistoryAction::Updated),
+            "deleted" => Ok(HistoryAction::Deleted),
+            "restored" => Ok(HistoryAction::Restored),
+            "purged" => Ok(HistoryAction::Purged),
+            other => Err(UnknownAction(other.to_string())),
+        }
+    }
+}
+
+/// One change of a todo in the audit log. Entries are never changed and
+/// outlive their todo.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct HistoryEntry {
+    pub history_id: i32,
+    pub todo_id: i32,
+    /// The owner of the todo at the time of the change.
+    pub owner_id: Option<i32>,
+    /// The user who made the change, `None` for changes of the server itself
+    /// like purging old todos from the trash.
+    pub user_id: Option<i32>,
+    pub action: HistoryAction,
+    pub changed_at: NaiveDateTime,
+    /// The todo before the change, `None` if it was created or restored.
+    pub before: Option<Todo>,
+    /// The todo after the change, `None` if it was deleted or purged.
+    pub after: Option<Todo>,
+}
+
+/// The columns of a `todo_history` row, the todos are stored as JSON.
+#[derive(Queryable)]
+pub struct HistoryRow {
+    pub history_id: i32,
+    pub todo_id: i32,
+    pub owner_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub action: String,
+    pub changed_at: NaiveDateTime,
+    pub before_todo: Option<String>,
+    pub after_todo: Option<String>,
+}
+
+fn parse_snapshot(json: Option<String>) -> serde_json::Result<Option<Todo>> {
+    json.map(|json| serde_json::from_str(&json)).transpose()
+}
+
+impl<ST, DB> Queryable<ST, DB> for HistoryEntry
+where
+    DB: Backend,
+    HistoryRow: Queryable<ST, DB>,
+{
+    type Row = <HistoryRow as Queryable<ST, DB>>::Row;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        let row = HistoryRow::build(row)?;
+        Ok(HistoryEntry {
+            history_id: row.history_id,
+            todo_id: row.todo_id,
+            owner_id: row.owner_id,
+            user_id: row.user_id,
+            action: row.action.parse()?,
+            changed_at: row.changed_at,
+            before: parse_snapshot(row.before_todo)?,
+            after: parse_snapshot(row.after_todo)?,
+        })
+    }
+}
+
+/// A change to be appended to the audit log.
+#[derive(Debug, Clone)]
+pub struct NewHistoryEntry {
+    pub todo_id: i32,
+    pub owner_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub action: HistoryAction,
+    pub changed_at: NaiveDateTime,
+    pub before: Option<Todo>,
+    pub after: Option<Todo>,
+}
+
+impl NewHistoryEntry {
+    /// The change `action` of a todo made by `user_id`; the todo and its
+    /// owner are taken from `after`, or from `before` if it is gone.
+    pub fn new(
+        action: HistoryAction,
+        user_id: Option<i32>,
+        changed_at: NaiveDateTime,
+        before: Option<Todo>,
+        after: Option<Todo>,
+    ) -> Option<Self> {
+        let todo = after.as_ref().or(before.as_ref())?;
+        Some(NewHistoryEntry {
+            todo_id: todo.todo_id,
+            owner_id: todo.owner_id,
+            user_id,
+            action,
+            changed_at,
+            before,
+            after,
+        })
+    }
+
+    /// `before` and `after` as JSON, the way the SQL backends store them.
+    pub fn snapshots(&self) -> serde_json::Result<(Option<String>, Option<String>)> {
+        let json = |todo: &Option<Todo>| todo.as_ref().map(serde_json::to_string).transpose();
+        Ok((json(&self.before)?, json(&self.after)?))
+    }
+
+    /// The stored entry, for repositories that assign ids themselves.
+    pub fn with_id(self, history_id: i32) -> HistoryEntry {
+        HistoryEntry {
+            history_id,
+            todo_id: self.todo_id,
+            owner_id: self.owner_id,
+            user_id: self.user_id,
+            action: self.action,
+            changed_at: self.changed_at,
+            before: self.before,
+            after: self.after,
+        }
+    }
+}
+
+/// Filters and pagination for `GET /api/audit` and `GET /api/todos/{id}/history`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoParams)]
+#[serde(default)]
+pub struct HistoryQuery {
+    /// Only changes of this todo, set from the path of `/api/todos/{id}/history`.
+    pub todo_id: Option<i32>,
+    /// Only changes made by this user.
+    pub user_id: Option<i32>,
+    /// Inclusive lower bound for `changed_at`.
+    pub changed_after: Option<NaiveDateTime>,
+    /// Exclusive upper bound for `changed_at`.
+    pub changed_before: Option<NaiveDateTime>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    /// Only changes of todos this user owned or made by them, set by the API
+    /// and never taken from the query string.
+    #[serde(skip)]
+    pub visible_to: Option<i32>,
+}
+
+impl HistoryQuery {
+    /// Whether `entry` passes all filters of this query.
+    ///
+    /// Used by repositories that cannot push the filters down to a database.
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        if self.todo_id.is_some_and(|id| entry.todo_id != id) {
+            return false;
+        }
+        if self.user_id.is_some() && entry.user_id != self.user_id {
+            return false;
+        }
+        if self
+            .changed_after
+            .is_some_and(|after| entry.changed_at < after)
+        {
+            return false;
+        }
+        if self
+            .changed_before
+            .is_some_and(|before| entry.changed_at >= before)
+        {
+            return false;
+        }
+        if let Some(user) = self.visible_to {
+            if entry.owner_id != Some(user) && entry.user_id != Some(user) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Filters and paginates entries kept in memory in the order they were recorded.
+    pub fn apply<'a>(
+        &self,
+        entries: impl IntoIterator<Item = &'a HistoryEntry>,
+    ) -> Vec<HistoryEntry> {
+        let offset = self.offset.unwrap_or(0) as usize;
+        let limit = self.limit.map(|l| l as usize).unwrap_or(usize::MAX);
+        entries
+            .into_iter()
+            .filter(|entry| self.matches(entry))
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
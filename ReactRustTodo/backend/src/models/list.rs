@@ -0,0 +1,53 @@
+use diesel::Queryable;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Id of the list created by the migrations. It owns the todos of the flat
+/// `/api/todos` routes and cannot be deleted.
+pub const DEFAULT_LIST_ID: i32 = 1;
+
+/// Name of the default list in a fresh store.
+pub const DEFAULT_LIST_NAME: &str = "Inbox";
+
+/// A project that owns todos.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Queryable, ToSchema)]
+pub struct TodoList {
+    pub list_id: i32,
+    pub name: String,
+    /// The user who created the list, `None` for the default list and lists
+    /// created before there were accounts; those are open to every user.
+    #[serde(default)]
+    pub owner_id: Option<i32>,
+}
+
+impl TodoList {
+    /// The default list as the migrations create it.
+    pub fn default_list() -> TodoList {
+        TodoList {
+            list_id: DEFAULT_LIST_ID,
+            name: DEFAULT_LIST_NAME.to_string(),
+            owner_id: None,
+        }
+    }
+}
+
+/// Body of `POST /api/lists` and `PUT /api/lists/{id}`.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct NewList {
+    pub name: String,
+    /// Set by the API to the current user, never taken from the payload.
+    /// Ignored on updates.
+    #[serde(skip_deserializing)]
+    pub owner_id: Option<i32>,
+}
+
+impl NewList {
+    /// The stored list, for repositories that assign ids themselves.
+    pub fn with_id(self, list_id: i32) -> TodoList {
+        TodoList {
+            list_id,
+            name: self.name,
+            owner_id: self.owner_id,
+        }
+    }
+}
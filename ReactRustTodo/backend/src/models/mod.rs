@@ -1 +1,12 @@
-pub mod todo;
\ No newline at end of file
+pub mod api_key;
+pub mod batch;
+pub mod dependency;
+pub mod event;
+pub mod history;
+pub mod list;
+pub mod query;
+pub mod share;
+pub mod tag;
+pub mod todo;
+pub mod tree;
+pub mod user;
@@ -0,0 +1,195 @@
+use std::cmp::Ordering;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::todo::Todo;
+
+/// Field the todo list can be sorted by.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    TodoId,
+    Title,
+    CreatedAt,
+    Completed,
+    DueAt,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// How the names in [`TodoQuery::tag`] are combined.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMode {
+    /// The todo has at least one of the tags.
+    #[default]
+    Or,
+    /// The todo has all of the tags.
+    And,
+}
+
+/// Filters, sorting and pagination for `GET /api/todos`.
+///
+/// Every field is optional; the default query returns all todos ordered by id,
+/// which is what the endpoint did before the parameters existed.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoParams)]
+#[serde(default)]
+pub struct TodoQuery {
+    /// Only todos of this list, set from the path of `/api/lists/{list_id}/todos`.
+    pub list_id: Option<i32>,
+    pub completed: Option<bool>,
+    /// Case-insensitive substring of the title.
+    pub title: Option<String>,
+    /// Case-insensitive substring of the description.
+    pub description: Option<String>,
+    /// Inclusive lower bound for `created_at`.
+    pub created_after: Option<NaiveDateTime>,
+    /// Exclusive upper bound for `created_at`.
+    pub created_before: Option<NaiveDateTime>,
+    /// Inclusive lower bound for `due_at`, todos without due date never match.
+    pub due_after: Option<NaiveDateTime>,
+    /// Exclusive upper bound for `due_at`, todos without due date never match.
+    pub due_before: Option<NaiveDateTime>,
+    /// Comma separated tag names (case-insensitive), combined according to `tag_mode`.
+    pub tag: Option<String>,
+    pub tag_mode: TagMode,
+    pub sort: SortField,
+    pub order: SortOrder,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    /// Only todos of this user, set by the API and never taken from the query string.
+    #[serde(skip)]
+    pub owner_id: Option<i32>,
+    /// Only todos this user has a role on, see [`todo_role`](super::share::todo_role).
+    /// Set by the API like `owner_id`.
+    #[serde(skip)]
+    pub visible_to: Option<i32>,
+}
+
+/// One page of todos together with the number of todos matching the filters.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TodoPage {
+    pub items: Vec<Todo>,
+    pub total: i64,
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+impl TodoQuery {
+    /// The names listed in `tag`, empty if the todos are not filtered by tag.
+    pub fn tag_names(&self) -> Vec<&str> {
+        self.tag
+            .iter()
+            .flat_map(|tags| tags.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+
+    /// Whether `todo` passes all filters of this query.
+    ///
+    /// Used by repositories that cannot push the filters down to a database.
+    pub fn matches(&self, todo: &Todo) -> bool {
+        if self.owner_id.is_some() && todo.owner_id != self.owner_id {
+            return false;
+        }
+        if let Some(list_id) = self.list_id {
+            if todo.list_id != list_id {
+                return false;
+            }
+        }
+        if let Some(completed) = self.completed {
+            if todo.completed.unwrap_or(false) != completed {
+                return false;
+            }
+        }
+        if let Some(title) = &self.title {
+            if !contains_ignore_case(&todo.title, title) {
+                return false;
+            }
+        }
+        if let Some(description) = &self.description {
+            match &todo.description {
+                Some(d) if contains_ignore_case(d, description) => {}
+                _ => return false,
+            }
+        }
+        if let Some(after) = self.created_after {
+            match todo.created_at {
+                Some(created) if created >= after => {}
+                _ => return false,
+            }
+        }
+        if let Some(before) = self.created_before {
+            match todo.created_at {
+                Some(created) if created < before => {}
+                _ => return false,
+            }
+        }
+        if let Some(after) = self.due_after {
+            match todo.due_at {
+                Some(due) if due >= after => {}
+                _ => return false,
+            }
+        }
+        if let Some(before) = self.due_before {
+            match todo.due_at {
+                Some(due) if due < before => {}
+                _ => return false,
+            }
+        }
+        let names = self.tag_names();
+        if !names.is_empty() {
+            let has_tag = |name: &&str| {
+                let name = name.to_lowercase();
+                todo.tags.iter().any(|tag| tag.name.to_lowercase() == name)
+            };
+            let tagged = match self.tag_mode {
+                TagMode::Or => names.iter().any(has_tag),
+                TagMode::And => names.iter().all(has_tag),
+            };
+            if !tagged {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Orders two todos according to `sort` and `order`, ties broken by id.
+    pub fn compare(&self, a: &Todo, b: &Todo) -> Ordering {
+        let ordering = match self.sort {
+            SortField::TodoId => Ordering::Equal,
+            SortField::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            SortField::CreatedAt => a.created_at.cmp(&b.created_at),
+            SortField::Completed => a.completed.cmp(&b.completed),
+            SortField::DueAt => a.due_at.cmp(&b.due_at),
+        }
+        .then(a.todo_id.cmp(&b.todo_id));
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+
+    /// Filters, sorts and paginates an in-memory list of todos.
+    pub fn apply(&self, todos: impl IntoIterator<Item = Todo>) -> TodoPage {
+        let mut items: Vec<Todo> = todos.into_iter().filter(|t| self.matches(t)).collect();
+        let total = items.len() as i64;
+        items.sort_by(|a, b| self.compare(a, b));
+        let offset = self.offset.unwrap_or(0) as usize;
+        let limit = self.limit.map(|l| l as usize).unwrap_or(usize::MAX);
+        let items = items.into_iter().skip(offset).take(limit).collect();
+        TodoPage { items, total }
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+use std::str::FromStr;
+
+use diesel::{backend::Backend, deserialize, Queryable};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::list::TodoList;
+use super::todo::Todo;
+
+/// What a user may do with a todo or list. Each role includes the ones before it.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Reading.
+    Viewer,
+    /// Changing, completing, tagging and adding todos to a list.
+    Editor,
+    /// Deleting, sharing and moving to another list.
+    Owner,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error of parsing a [`Role`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownRole(pub String);
+
+impl fmt::Display for UnknownRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown role `{}`", self.0)
+    }
+}
+
+impl std::error::Error for UnknownRole {}
+
+impl FromStr for Role {
+    type Err = UnknownRole;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "owner" => Ok(Role::Owner),
+            other => Err(UnknownRole(other.to_string())),
+        }
+    }
+}
+
+/// Access of one user to a todo or list besides its owner.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub struct Share {
+    pub user_id: i32,
+    pub role: Role,
+}
+
+/// The columns of a `todo_shares` or `list_shares` row without the shared id.
+#[derive(Queryable)]
+pub struct ShareRow {
+    pub user_id: i32,
+    pub role: String,
+}
+
+impl<ST, DB> Queryable<ST, DB> for Share
+where
+    DB: Backend,
+    ShareRow: Queryable<ST, DB>,
+{
+    type Row = <ShareRow as Queryable<ST, DB>>::Row;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        let row = ShareRow::build(row)?;
+        Ok(Share {
+            user_id: row.user_id,
+            role: row.role.parse()?,
+        })
+    }
+}
+
+/// Body of `PUT /api/todos/{id}/shares/{user_id}` and `PUT /api/lists/{id}/shares/{user_id}`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ToSchema)]
+pub struct NewShare {
+    pub role: Role,
+}
+
+fn shared_role(user_id: i32, shares: &[Share]) -> Option<Role> {
+    shares
+        .iter()
+        .find(|share| share.user_id == user_id)
+        .map(|share| share.role)
+}
+
+/// The role of `user_id` on `list` with the shares `shares`, `None` without access.
+///
+/// Lists without owner give no role, every user may use them but they do not
+/// make the todos of others visible.
+pub fn list_role(user_id: i32, list: &TodoList, shares: &[Share]) -> Option<Role> {
+    if list.owner_id == Some(user_id) {
+        return Some(Role::Owner);
+    }
+    shared_role(user_id, shares)
+}
+
+/// The role of `user_id` on `todo`: owners of the todo or of its list own it,
+/// otherwise the better of the roles the todo and its list are shared with.
+pub fn todo_role(
+    user_id: i32,
+    todo: &Todo,
+    todo_shares: &[Share],
+    list: &TodoList,
+    list_shares: &[Share],
+) -> Option<Role> {
+    if todo.owner_id == Some(user_id) {
+        return Some(Role::Owner);
+    }
+    shared_role(user_id, todo_shares).max(list_role(user_id, list, list_shares))
+}
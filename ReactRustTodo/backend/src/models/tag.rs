@@ -0,0 +1,37 @@
+use diesel::Queryable;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A label that can be attached to any number of todos.
+///
+/// Names are unique, compared case-insensitively.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Queryable, ToSchema)]
+pub struct Tag {
+    pub tag_id: i32,
+    pub name: String,
+    /// The user who created the tag and may rename or delete it, `None` for
+    /// tags created before tags had owners.
+    #[serde(default)]
+    pub owner_id: Option<i32>,
+}
+
+/// Body of `POST /api/tags` and `PUT /api/tags/{id}`.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct NewTag {
+    pub name: String,
+    /// Set by the API to the current user, never taken from the payload.
+    /// Ignored on updates.
+    #[serde(skip_deserializing)]
+    pub owner_id: Option<i32>,
+}
+
+impl NewTag {
+    /// The stored tag, for repositories that assign ids themselves.
+    pub fn with_id(self, tag_id: i32) -> Tag {
+        Tag {
+            tag_id,
+            name: self.name,
+            owner_id: self.owner_id,
+        }
+    }
+}
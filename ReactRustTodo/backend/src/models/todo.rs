@@ -1,23 +1,279 @@
-use serde::{Deserialize, Serialize};
-use diesel::{Queryable, Insertable, AsChangeset};
+use serde::{Deserialize, Deserializer, Serialize};
+use diesel::{backend::Backend, deserialize, Queryable, Insertable, AsChangeset};
 use chrono::{self, NaiveDateTime};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Queryable, Insertable, AsChangeset)]
+use super::list::DEFAULT_LIST_ID;
+use super::tag::Tag;
+use super::tree::Progress;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Insertable, AsChangeset, ToSchema)]
 #[diesel(table_name = crate::repository::schema::todos)]
+// A PUT replaces the whole todo, so `None` has to clear the column.
+#[diesel(treat_none_as_null = true)]
 pub struct Todo {
     pub todo_id: i32,
     pub title: String,
     pub description: Option<String>,
     pub created_at: Option<NaiveDateTime>,
     pub completed: Option<bool>,
+    /// Deadline of the todo, absent in payloads of older clients.
+    #[serde(default)]
+    pub due_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub remind_at: Option<NaiveDateTime>,
+    /// Recurrence rule, see [`Recurrence`](crate::recurrence::Recurrence).
+    #[serde(default)]
+    pub recurrence: Option<String>,
+    /// The owning list, the default list when absent.
+    #[serde(default = "default_list_id")]
+    pub list_id: i32,
+    /// The todo this one is a subtask of, in the same list.
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+    /// Complete the todo as soon as all its subtasks are completed.
+    #[serde(default)]
+    pub auto_complete: bool,
+    /// The user the todo belongs to, `None` for todos created before there
+    /// were accounts. Ignored on updates.
+    #[serde(default)]
+    #[diesel(skip_update)]
+    pub owner_id: Option<i32>,
+    /// Counts the updates of the todo, starting at 1. Ignored on updates, the
+    /// repositories increase it themselves.
+    #[serde(default = "first_version")]
+    #[diesel(skip_insertion, skip_update)]
+    pub version: i32,
+    /// When the todo was moved to the trash, absent for todos in use. Ignored
+    /// on updates, see `/api/trash`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[diesel(skip_insertion, skip_update)]
+    pub deleted_at: Option<NaiveDateTime>,
+    /// Attached tags ordered by name. Ignored on updates, tags are attached
+    /// and detached through `/api/todos/{id}/tags/{tag_id}`.
+    #[serde(default)]
+    #[diesel(skip_insertion, skip_update)]
+    pub tags: Vec<Tag>,
+    /// Ids of the todos that have to be completed first, ordered by id. Ignored
+    /// on updates, see `/api/todos/{id}/blockers/{blocker_id}`.
+    #[serde(default)]
+    #[diesel(skip_insertion, skip_update)]
+    pub blocked_by: Vec<i32>,
+    /// Completed and total number of direct subtasks, absent without subtasks.
+    /// Computed by the repositories and ignored on updates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[diesel(skip_insertion, skip_update)]
+    pub progress: Option<Progress>,
 }
 
-#[derive(Deserialize, Insertable)]
+/// The columns of a `todos` row, i.e. a [`Todo`] without its tags, blockers and progress.
+#[derive(Queryable)]
+pub struct TodoRow {
+    pub todo_id: i32,
+    pub title: String,
+    pub description: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+    pub completed: Option<bool>,
+    pub due_at: Option<NaiveDateTime>,
+    pub remind_at: Option<NaiveDateTime>,
+    pub recurrence: Option<String>,
+    pub list_id: i32,
+    pub parent_id: Option<i32>,
+    pub auto_complete: bool,
+    pub owner_id: Option<i32>,
+    pub version: i32,
+    pub deleted_at: Option<NaiveDateTime>,
+}
+
+// Tags and blockers live in their own tables and the progress is derived from
+// the subtasks, so a todo is loaded without them and the repositories fill
+// them in afterwards.
+impl<ST, DB> Queryable<ST, DB> for Todo
+where
+    DB: Backend,
+    TodoRow: Queryable<ST, DB>,
+{
+    type Row = <TodoRow as Queryable<ST, DB>>::Row;
+
+    fn build(row: Self::Row) -> deserialize::Result<Self> {
+        let row = TodoRow::build(row)?;
+        Ok(Todo {
+            todo_id: row.todo_id,
+            title: row.title,
+            description: row.description,
+            created_at: row.created_at,
+            completed: row.completed,
+            due_at: row.due_at,
+            remind_at: row.remind_at,
+            recurrence: row.recurrence,
+            list_id: row.list_id,
+            parent_id: row.parent_id,
+            auto_complete: row.auto_complete,
+            owner_id: row.owner_id,
+            version: row.version,
+            deleted_at: row.deleted_at,
+            tags: Vec::new(),
+            blocked_by: Vec::new(),
+            progress: None,
+        })
+    }
+}
+
+#[derive(Deserialize, Insertable, ToSchema)]
 #[diesel(table_name = crate::repository::schema::todos)]
 pub struct NewTodo {
     pub title: String,
     pub description: Option<String>,
     pub created_at: Option<NaiveDateTime>,
     pub completed: Option<bool>,
+    #[serde(default)]
+    pub due_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub remind_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub recurrence: Option<String>,
+    #[serde(default = "default_list_id")]
+    pub list_id: i32,
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+    #[serde(default)]
+    pub auto_complete: bool,
+    /// Set by the API to the current user, never taken from the payload.
+    #[serde(skip_deserializing)]
+    pub owner_id: Option<i32>,
+}
+
+fn default_list_id() -> i32 {
+    DEFAULT_LIST_ID
 }
 
+/// Version of a new todo, also of todos stored before there were versions.
+pub fn first_version() -> i32 {
+    1
+}
+
+impl NewTodo {
+    /// The stored todo, for repositories that assign ids themselves.
+    pub fn with_id(self, todo_id: i32) -> Todo {
+        Todo {
+            todo_id,
+            title: self.title,
+            description: self.description,
+            created_at: self.created_at,
+            completed: self.completed,
+            due_at: self.due_at,
+            remind_at: self.remind_at,
+            recurrence: self.recurrence,
+            list_id: self.list_id,
+            parent_id: self.parent_id,
+            auto_complete: self.auto_complete,
+            owner_id: self.owner_id,
+            version: first_version(),
+            deleted_at: None,
+            tags: Vec::new(),
+            blocked_by: Vec::new(),
+            progress: None,
+        }
+    }
+}
+
+/// Partial update of a todo, the body of `PATCH /api/todos/{id}`.
+///
+/// Absent fields keep their stored value. The nullable fields can be cleared
+/// by sending an explicit `null`, which is why they are wrapped twice.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, AsChangeset, ToSchema)]
+#[diesel(table_name = crate::repository::schema::todos)]
+pub struct TodoPatch {
+    pub title: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_present"
+    )]
+    pub description: Option<Option<String>>,
+    pub completed: Option<bool>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_present"
+    )]
+    pub due_at: Option<Option<NaiveDateTime>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_present"
+    )]
+    pub remind_at: Option<Option<NaiveDateTime>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_present"
+    )]
+    pub recurrence: Option<Option<String>>,
+    /// Moves the todo to another list.
+    pub list_id: Option<i32>,
+    /// Makes the todo a subtask of another one, or a top-level todo with `null`.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_present"
+    )]
+    pub parent_id: Option<Option<i32>>,
+    pub auto_complete: Option<bool>,
+}
+
+/// Maps a present JSON field (even `null`) to `Some`, so that together with
+/// `#[serde(default)]` an absent field stays `None`.
+fn deserialize_present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl TodoPatch {
+    /// True if the patch would not change anything.
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.description.is_none()
+            && self.completed.is_none()
+            && self.due_at.is_none()
+            && self.remind_at.is_none()
+            && self.recurrence.is_none()
+            && self.list_id.is_none()
+            && self.parent_id.is_none()
+            && self.auto_complete.is_none()
+    }
+
+    /// Applies the present fields to `todo`, leaving id and `created_at` untouched.
+    pub fn apply_to(self, todo: &mut Todo) {
+        if let Some(title) = self.title {
+            todo.title = title;
+        }
+        if let Some(description) = self.description {
+            todo.description = description;
+        }
+        if let Some(completed) = self.completed {
+            todo.completed = Some(completed);
+        }
+        if let Some(due_at) = self.due_at {
+            todo.due_at = due_at;
+        }
+        if let Some(remind_at) = self.remind_at {
+            todo.remind_at = remind_at;
+        }
+        if let Some(recurrence) = self.recurrence {
+            todo.recurrence = recurrence;
+        }
+        if let Some(list_id) = self.list_id {
+            todo.list_id = list_id;
+        }
+        if let Some(parent_id) = self.parent_id {
+            todo.parent_id = parent_id;
+        }
+        if let Some(auto_complete) = self.auto_complete {
+            todo.auto_complete = auto_complete;
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::todo::Todo;
+
+/// How many direct subtasks of a todo are completed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+pub struct Progress {
+    pub completed: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    /// Progress over the `completed` flags of the subtasks, `None` without subtasks.
+    pub fn of(subtasks: impl IntoIterator<Item = Option<bool>>) -> Option<Progress> {
+        let progress = subtasks
+            .into_iter()
+            .fold(Progress::default(), |progress, completed| Progress {
+                completed: progress.completed + usize::from(completed.unwrap_or(false)),
+                total: progress.total + 1,
+            });
+        (progress.total > 0).then_some(progress)
+    }
+
+    /// True if there are subtasks and all of them are completed.
+    pub fn is_done(&self) -> bool {
+        self.total > 0 && self.completed == self.total
+    }
+}
+
+/// A todo with its subtasks, the body of `GET /api/todos/{id}/subtree`.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TodoTree {
+    #[serde(flatten)]
+    pub todo: Todo,
+    #[schema(no_recursion)]
+    pub children: Vec<TodoTree>,
+}
+
+impl TodoTree {
+    /// Nests `descendants` below `root`, children ordered by id.
+    ///
+    /// Todos whose parent is neither `root` nor among `descendants` are dropped.
+    pub fn build(root: Todo, descendants: Vec<Todo>) -> TodoTree {
+        let mut children: HashMap<i32, Vec<Todo>> = HashMap::new();
+        for todo in descendants {
+            if let Some(parent_id) = todo.parent_id {
+                children.entry(parent_id).or_default().push(todo);
+            }
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by_key(|todo| todo.todo_id);
+        }
+        Self::nest(root, &mut children)
+    }
+
+    fn nest(todo: Todo, children: &mut HashMap<i32, Vec<Todo>>) -> TodoTree {
+        let nested = children
+            .remove(&todo.todo_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| Self::nest(child, children))
+            .collect();
+        TodoTree {
+            todo,
+            children: nested,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use chrono::NaiveDateTime;
+use diesel::Queryable;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A registered user, without the password hash.
+///
+/// Usernames are unique, compared case-insensitively.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Queryable, ToSchema)]
+pub struct User {
+    pub user_id: i32,
+    pub username: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// A user together with its password hash, as kept by the repositories.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UserRecord {
+    #[serde(flatten)]
+    pub user: User,
+    /// Argon2 hash in PHC string format.
+    pub password_hash: String,
+}
+
+/// A user to be stored, the password is already hashed.
+#[derive(Debug, Clone)]
+pub struct NewUser {
+    pub username: String,
+    pub password_hash: String,
+}
+
+impl NewUser {
+    /// The stored user, for repositories that assign ids themselves.
+    pub fn with_id(self, user_id: i32, created_at: NaiveDateTime) -> UserRecord {
+        UserRecord {
+            user: User {
+                user_id,
+                username: self.username,
+                created_at,
+            },
+            password_hash: self.password_hash,
+        }
+    }
+}
+
+/// Body of `POST /api/auth/register` and `POST /api/auth/login`.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
@@ -0,0 +1,254 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+
+use crate::models::todo::{NewTodo, Todo};
+
+/// Largest `INTERVAL` accepted, about ten years.
+pub const MAX_INTERVAL: u32 = 3650;
+
+/// When a recurring todo comes back, parsed from an RRULE-style string.
+///
+/// | Rule                                | Next due date                                  |
+/// |-------------------------------------|------------------------------------------------|
+/// | `FREQ=DAILY[;INTERVAL=n]`           | every n days (default 1), counted from `due_at` |
+/// | `FREQ=WEEKLY;BYDAY=MO,WE`           | the next of the given weekdays                 |
+/// | `FREQ=MONTHLY;BYMONTHDAY=n`         | day n of the month, the last day if shorter    |
+/// | `FREQ=AFTER_COMPLETION;INTERVAL=n`  | n days after the todo was completed            |
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recurrence {
+    Daily { interval: u32 },
+    Weekly { weekdays: Vec<Weekday> },
+    Monthly { day: u32 },
+    AfterCompletion { days: u32 },
+}
+
+/// Why a recurrence rule could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceError(pub String);
+
+impl fmt::Display for RecurrenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid recurrence rule: {}", self.0)
+    }
+}
+
+impl std::error::Error for RecurrenceError {}
+
+fn error(message: impl Into<String>) -> RecurrenceError {
+    RecurrenceError(message.into())
+}
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("MO", Weekday::Mon),
+    ("TU", Weekday::Tue),
+    ("WE", Weekday::Wed),
+    ("TH", Weekday::Thu),
+    ("FR", Weekday::Fri),
+    ("SA", Weekday::Sat),
+    ("SU", Weekday::Sun),
+];
+
+fn parse_weekday(value: &str) -> Result<Weekday, RecurrenceError> {
+    WEEKDAYS
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(value.trim()))
+        .map(|(_, weekday)| *weekday)
+        .ok_or_else(|| error(format!("unknown weekday `{value}`, expected MO..SU")))
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    WEEKDAYS[weekday.num_days_from_monday() as usize].0
+}
+
+fn parse_number(key: &str, value: &str, max: u32) -> Result<u32, RecurrenceError> {
+    match value.trim().parse::<u32>() {
+        Ok(n) if (1..=max).contains(&n) => Ok(n),
+        _ => Err(error(format!("{key} must be a number from 1 to {max}"))),
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = RecurrenceError;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let mut freq = None;
+        let mut interval = None;
+        let mut by_day = None;
+        let mut by_month_day = None;
+        for part in rule.split(';').filter(|part| !part.trim().is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected KEY=VALUE, got `{part}`")))?;
+            let key = key.trim().to_ascii_uppercase();
+            let slot = match key.as_str() {
+                "FREQ" => &mut freq,
+                "INTERVAL" => &mut interval,
+                "BYDAY" => &mut by_day,
+                "BYMONTHDAY" => &mut by_month_day,
+                _ => return Err(error(format!("unsupported key `{key}`"))),
+            };
+            if slot.replace(value.trim()).is_some() {
+                return Err(error(format!("{key} given twice")));
+            }
+        }
+
+        let freq = freq
+            .ok_or_else(|| error("FREQ is missing"))?
+            .to_ascii_uppercase();
+        let allowed: &[&str] = match freq.as_str() {
+            "DAILY" | "AFTER_COMPLETION" => &["INTERVAL"],
+            "WEEKLY" => &["BYDAY"],
+            "MONTHLY" => &["BYMONTHDAY"],
+            _ => return Err(error(format!("unsupported FREQ `{freq}`"))),
+        };
+        let present = [
+            ("INTERVAL", interval.is_some()),
+            ("BYDAY", by_day.is_some()),
+            ("BYMONTHDAY", by_month_day.is_some()),
+        ];
+        if let Some((key, _)) = present
+            .iter()
+            .find(|(key, set)| *set && !allowed.contains(key))
+        {
+            return Err(error(format!("{key} cannot be used with FREQ={freq}")));
+        }
+
+        match freq.as_str() {
+            "DAILY" => Ok(Recurrence::Daily {
+                interval: interval
+                    .map(|value| parse_number("INTERVAL", value, MAX_INTERVAL))
+                    .transpose()?
+                    .unwrap_or(1),
+            }),
+            "WEEKLY" => {
+                let by_day = by_day.ok_or_else(|| error("FREQ=WEEKLY needs BYDAY"))?;
+                let mut weekdays = by_day
+                    .split(',')
+                    .map(parse_weekday)
+                    .collect::<Result<Vec<_>, _>>()?;
+                weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+                weekdays.dedup();
+                Ok(Recurrence::Weekly { weekdays })
+            }
+            "MONTHLY" => {
+                let day = by_month_day.ok_or_else(|| error("FREQ=MONTHLY needs BYMONTHDAY"))?;
+                Ok(Recurrence::Monthly {
+                    day: parse_number("BYMONTHDAY", day, 31)?,
+                })
+            }
+            _ => {
+                let days = interval.ok_or_else(|| error("FREQ=AFTER_COMPLETION needs INTERVAL"))?;
+                Ok(Recurrence::AfterCompletion {
+                    days: parse_number("INTERVAL", days, MAX_INTERVAL)?,
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recurrence::Daily { interval: 1 } => write!(f, "FREQ=DAILY"),
+            Recurrence::Daily { interval } => write!(f, "FREQ=DAILY;INTERVAL={interval}"),
+            Recurrence::Weekly { weekdays } => {
+                let days: Vec<&str> = weekdays.iter().map(|w| weekday_code(*w)).collect();
+                write!(f, "FREQ=WEEKLY;BYDAY={}", days.join(","))
+            }
+            Recurrence::Monthly { day } => write!(f, "FREQ=MONTHLY;BYMONTHDAY={day}"),
+            Recurrence::AfterCompletion { days } => {
+                write!(f, "FREQ=AFTER_COMPLETION;INTERVAL={days}")
+            }
+        }
+    }
+}
+
+/// Day `day` of the given month, or the month's last day if it is shorter.
+fn clamped_month_day(year: i32, month: u32, day: u32) -> NaiveDate {
+    (1..=day)
+        .rev()
+        .find_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+        .expect("every month has a first day")
+}
+
+impl Recurrence {
+    /// Due date of the next instance of a todo due at `due_at` and completed at `completed_at`.
+    ///
+    /// Scheduled rules keep the time of day of `due_at` and skip occurrences
+    /// that already passed, so completing a todo late does not produce an
+    /// instance that is overdue right away.
+    pub fn next_due(
+        &self,
+        due_at: Option<NaiveDateTime>,
+        completed_at: NaiveDateTime,
+    ) -> NaiveDateTime {
+        let anchor = due_at.unwrap_or(completed_at);
+        let after = anchor.max(completed_at);
+        let time = anchor.time();
+        match self {
+            Recurrence::Daily { interval } => {
+                let step = Duration::days(i64::from(*interval));
+                let mut next = anchor + step;
+                while next <= after {
+                    next += step;
+                }
+                next
+            }
+            Recurrence::Weekly { weekdays } => (0..=7)
+                .map(|offset| (after.date() + Duration::days(offset)).and_time(time))
+                .find(|next| *next > after && weekdays.contains(&next.weekday()))
+                .expect("a weekday recurs within eight days"),
+            Recurrence::Monthly { day } => {
+                let (mut year, mut month) = (after.year(), after.month());
+                loop {
+                    let next = clamped_month_day(year, month, *day).and_time(time);
+                    if next > after {
+                        return next;
+                    }
+                    (year, month) = if month == 12 {
+                        (year + 1, 1)
+                    } else {
+                        (year, month + 1)
+                    };
+                }
+            }
+            Recurrence::AfterCompletion { days } => {
+                (completed_at.date() + Duration::days(i64::from(*days))).and_time(time)
+            }
+        }
+    }
+}
+
+/// The instance that follows `updated` if this update completed a recurring todo.
+///
+/// `previous` is the stored state before the update. The reminder keeps its
+/// distance to the due date. Repositories create the returned todo and clear
+/// the rule on `updated`, so the rule moves on to the new instance.
+pub fn follow_up(previous: &Todo, updated: &Todo, now: NaiveDateTime) -> Option<NewTodo> {
+    let completed_now = updated.completed.unwrap_or(false) && !previous.completed.unwrap_or(false);
+    if !completed_now {
+        return None;
+    }
+    let rule = updated.recurrence.as_deref()?.parse::<Recurrence>().ok()?;
+    let due_at = rule.next_due(updated.due_at, now);
+    let remind_at = match (updated.remind_at, updated.due_at) {
+        (Some(remind_at), Some(previous_due)) => Some(due_at - (previous_due - remind_at)),
+        _ => None,
+    };
+
+    Some(NewTodo {
+        title: updated.title.clone(),
+        description: updated.description.clone(),
+        created_at: Some(now),
+        completed: Some(false),
+        due_at: Some(due_at),
+        remind_at,
+        recurrence: Some(rule.to_string()),
+        list_id: updated.list_id,
+        parent_id: updated.parent_id,
+        auto_complete: updated.auto_complete,
+        owner_id: updated.owner_id,
+    })
+}
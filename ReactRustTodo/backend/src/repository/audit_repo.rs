@@ -0,0 +1,165 @@
+use async_trait::async_trait;
+
+use super::error::RepoResult;
+use super::mutation::{Actor, Mutated, Mutation};
+use super::todo_repo::TodoRepo;
+use super::RepoBox;
+use crate::models::api_key::{ApiKey, NewApiKeyRecord};
+use crate::models::history::{HistoryEntry, HistoryQuery, NewHistoryEntry};
+use crate::models::list::{NewList, TodoList};
+use crate::models::query::{TodoPage, TodoQuery};
+use crate::models::share::Share;
+use crate::models::tag::{NewTag, Tag};
+use crate::models::todo::Todo;
+use crate::models::user::{NewUser, User, UserRecord};
+
+/// Records every todo another repository successfully creates, changes,
+/// trashes, restores or purges in the audit log, see
+/// [`TodoRepo::record_history`], as changed by one user.
+///
+/// Tags and blockers count as changes of their todo, deleting a list with its
+/// todos purges each of them. Side effects are recorded as the inner
+/// repository reports them in [`Mutated::changes`]; the inner repository
+/// writes the entries in the transaction of the change, see [`Actor`].
+#[derive(Clone)]
+pub struct AuditRepo {
+    inner: RepoBox,
+    user_id: Option<i32>,
+}
+
+impl AuditRepo {
+    /// Records the changes as made by `user_id`, `None` for the server itself.
+    pub fn new(inner: RepoBox, user_id: Option<i32>) -> Self {
+        AuditRepo { inner, user_id }
+    }
+}
+
+#[async_trait]
+impl TodoRepo for AuditRepo {
+    async fn query_todos(&self, query: TodoQuery) -> RepoResult<TodoPage> {
+        self.inner.query_todos(query).await
+    }
+
+    async fn mutate(&self, mutation: Mutation, _actor: Option<Actor>) -> RepoResult<Mutated> {
+        let actor = Actor {
+            user_id: self.user_id,
+        };
+        self.inner.mutate(mutation, Some(actor)).await
+    }
+
+    async fn get_todo_by_id(&self, id: i32) -> RepoResult<Todo> {
+        self.inner.get_todo_by_id(id).await
+    }
+
+    async fn get_trash(&self, owner_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        self.inner.get_trash(owner_id).await
+    }
+
+    fn atomic_batches(&self) -> bool {
+        self.inner.atomic_batches()
+    }
+
+    async fn get_descendants(&self, id: i32) -> RepoResult<Vec<Todo>> {
+        self.inner.get_descendants(id).await
+    }
+
+    async fn get_next_todos(&self, list_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        self.inner.get_next_todos(list_id).await
+    }
+
+    async fn get_tags(&self, visible_to: Option<i32>) -> RepoResult<Vec<Tag>> {
+        self.inner.get_tags(visible_to).await
+    }
+
+    async fn create_tag(&self, new: NewTag) -> RepoResult<Tag> {
+        self.inner.create_tag(new).await
+    }
+
+    async fn get_tag_by_id(&self, id: i32) -> RepoResult<Tag> {
+        self.inner.get_tag_by_id(id).await
+    }
+
+    async fn update_tag_by_id(&self, id: i32, tag: NewTag) -> RepoResult<Tag> {
+        self.inner.update_tag_by_id(id, tag).await
+    }
+
+    async fn delete_tag_by_id(&self, id: i32) -> RepoResult<usize> {
+        self.inner.delete_tag_by_id(id).await
+    }
+
+    async fn get_lists(&self) -> RepoResult<Vec<TodoList>> {
+        self.inner.get_lists().await
+    }
+
+    async fn create_list(&self, new: NewList) -> RepoResult<TodoList> {
+        self.inner.create_list(new).await
+    }
+
+    async fn get_list_by_id(&self, id: i32) -> RepoResult<TodoList> {
+        self.inner.get_list_by_id(id).await
+    }
+
+    async fn update_list_by_id(&self, id: i32, list: NewList) -> RepoResult<TodoList> {
+        self.inner.update_list_by_id(id, list).await
+    }
+
+    async fn get_todo_shares(&self, todo_id: i32) -> RepoResult<Vec<Share>> {
+        self.inner.get_todo_shares(todo_id).await
+    }
+
+    async fn share_todo(&self, todo_id: i32, share: Share) -> RepoResult<Share> {
+        self.inner.share_todo(todo_id, share).await
+    }
+
+    async fn unshare_todo(&self, todo_id: i32, user_id: i32) -> RepoResult<usize> {
+        self.inner.unshare_todo(todo_id, user_id).await
+    }
+
+    async fn get_list_shares(&self, list_id: i32) -> RepoResult<Vec<Share>> {
+        self.inner.get_list_shares(list_id).await
+    }
+
+    async fn share_list(&self, list_id: i32, share: Share) -> RepoResult<Share> {
+        self.inner.share_list(list_id, share).await
+    }
+
+    async fn unshare_list(&self, list_id: i32, user_id: i32) -> RepoResult<usize> {
+        self.inner.unshare_list(list_id, user_id).await
+    }
+
+    async fn create_user(&self, new: NewUser) -> RepoResult<User> {
+        self.inner.create_user(new).await
+    }
+
+    async fn get_user_by_id(&self, id: i32) -> RepoResult<User> {
+        self.inner.get_user_by_id(id).await
+    }
+
+    async fn get_user_record(&self, username: &str) -> RepoResult<UserRecord> {
+        self.inner.get_user_record(username).await
+    }
+
+    async fn create_api_key(&self, key: NewApiKeyRecord) -> RepoResult<ApiKey> {
+        self.inner.create_api_key(key).await
+    }
+
+    async fn get_api_keys(&self, user_id: i32) -> RepoResult<Vec<ApiKey>> {
+        self.inner.get_api_keys(user_id).await
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> RepoResult<ApiKey> {
+        self.inner.get_api_key_by_hash(key_hash).await
+    }
+
+    async fn delete_api_key(&self, user_id: i32, key_id: i32) -> RepoResult<usize> {
+        self.inner.delete_api_key(user_id, key_id).await
+    }
+
+    async fn record_history(&self, entries: Vec<NewHistoryEntry>) -> RepoResult<()> {
+        self.inner.record_history(entries).await
+    }
+
+    async fn get_history(&self, query: HistoryQuery) -> RepoResult<Vec<HistoryEntry>> {
+        self.inner.get_history(query).await
+    }
+}
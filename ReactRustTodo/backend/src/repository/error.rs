@@ -0,0 +1,117 @@
+use std::fmt;
+
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+
+use crate::validation::FieldError;
+
+/// Error returned by every [`TodoRepo`](super::todo_repo::TodoRepo) method.
+///
+/// The variants describe *what* went wrong independently of the storage
+/// backend, so the API layer can map them to HTTP statuses without knowing
+/// whether it talks to MySQL or to the in-memory repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepoError {
+    /// The requested todo does not exist.
+    NotFound,
+    /// Some other referenced resource, e.g. a tag, does not exist.
+    Missing(&'static str),
+    /// The operation clashes with existing data (e.g. a unique constraint).
+    Conflict(String),
+    /// The input breaks one or more validation rules.
+    Validation(Vec<FieldError>),
+    /// The request lacks a valid session token or the credentials are wrong.
+    Unauthorized(String),
+    /// The caller is known but not allowed to do this, e.g. an API key
+    /// without the needed scope.
+    Forbidden(String),
+    /// The todo was changed since the version the caller based its change on.
+    PreconditionFailed(String),
+    /// The backend cannot be reached right now (pool exhausted, connection lost).
+    Unavailable(String),
+    /// Anything else; the message is meant for logs, not for clients.
+    Internal(String),
+    /// The operation at the index failed, so a batch applied in one
+    /// transaction was rolled back.
+    Batch(usize, Box<RepoError>),
+}
+
+pub type RepoResult<T> = Result<T, RepoError>;
+
+impl fmt::Display for RepoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepoError::NotFound => write!(f, "todo not found"),
+            RepoError::Missing(what) => write!(f, "{what} not found"),
+            RepoError::Conflict(msg) => write!(f, "conflict: {msg}"),
+            RepoError::Validation(errors) => {
+                let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+                write!(f, "validation failed for: {}", fields.join(", "))
+            }
+            RepoError::Unauthorized(msg) => write!(f, "unauthorized: {msg}"),
+            RepoError::Forbidden(msg) => write!(f, "forbidden: {msg}"),
+            RepoError::PreconditionFailed(msg) => write!(f, "precondition failed: {msg}"),
+            RepoError::Unavailable(msg) => write!(f, "storage unavailable: {msg}"),
+            RepoError::Internal(msg) => write!(f, "internal storage error: {msg}"),
+            RepoError::Batch(index, cause) => write!(f, "operation {index}: {cause}"),
+        }
+    }
+}
+
+impl std::error::Error for RepoError {}
+
+impl From<DieselError> for RepoError {
+    fn from(err: DieselError) -> Self {
+        match err {
+            DieselError::NotFound => RepoError::NotFound,
+            DieselError::DatabaseError(kind, info) => match kind {
+                DatabaseErrorKind::UniqueViolation | DatabaseErrorKind::ForeignKeyViolation => {
+                    RepoError::Conflict(info.message().to_string())
+                }
+                DatabaseErrorKind::NotNullViolation | DatabaseErrorKind::CheckViolation => {
+                    RepoError::Validation(vec![FieldError::new(
+                        info.column_name().unwrap_or_default(),
+                        "constraint",
+                        info.message(),
+                    )])
+                }
+                DatabaseErrorKind::ClosedConnection => {
+                    RepoError::Unavailable(info.message().to_string())
+                }
+                _ => RepoError::Internal(info.message().to_string()),
+            },
+            other => RepoError::Internal(other.to_string()),
+        }
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for RepoError {
+    fn from(err: diesel::r2d2::PoolError) -> Self {
+        RepoError::Unavailable(err.to_string())
+    }
+}
+
+impl From<Vec<FieldError>> for RepoError {
+    fn from(errors: Vec<FieldError>) -> Self {
+        RepoError::Validation(errors)
+    }
+}
+
+impl RepoError {
+    /// Marks the error as the one of the operation `index` of a batch.
+    pub fn in_batch(self, index: usize) -> Self {
+        RepoError::Batch(index, Box::new(self))
+    }
+
+    /// The error itself, or for [`RepoError::Batch`] the one of the operation.
+    pub fn cause(&self) -> &RepoError {
+        match self {
+            RepoError::Batch(_, cause) => cause.cause(),
+            other => other,
+        }
+    }
+
+    /// The todo `id` no longer has the version `expected`.
+    pub fn stale(id: i32, expected: i32) -> Self {
+        RepoError::PreconditionFailed(format!("todo {id} was changed since version {expected}"))
+    }
+}
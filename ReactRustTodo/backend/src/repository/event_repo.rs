@@ -0,0 +1,286 @@
+use async_trait::async_trait;
+
+use super::error::RepoResult;
+use super::mutation::{Actor, Mutated, Mutation};
+use super::todo_repo::TodoRepo;
+use super::RepoBox;
+use crate::events::EventBus;
+use crate::models::api_key::{ApiKey, NewApiKeyRecord};
+use crate::models::batch::BatchOp;
+use crate::models::event::EventKind;
+use crate::models::history::{HistoryAction, HistoryEntry, HistoryQuery, NewHistoryEntry};
+use crate::models::list::{NewList, TodoList};
+use crate::models::query::{TodoPage, TodoQuery};
+use crate::models::share::Share;
+use crate::models::tag::{NewTag, Tag};
+use crate::models::todo::Todo;
+use crate::models::user::{NewUser, User, UserRecord};
+
+/// Publishes a [`TodoEvent`](crate::models::event::TodoEvent) on an
+/// [`EventBus`] for every todo another repository successfully creates,
+/// changes or deletes. Moving a todo to the trash counts as deleting it,
+/// restoring it as creating it.
+///
+/// Tags, blockers and shares count as changes of their todo. Side effects are
+/// reported as the inner repository reports them in [`Mutated::changes`]:
+/// parents completed along with their last subtask as updated, the next
+/// instance of a completed recurring todo as created.
+#[derive(Clone)]
+pub struct EventRepo {
+    inner: RepoBox,
+    events: EventBus,
+}
+
+impl EventRepo {
+    pub fn new(inner: RepoBox, events: EventBus) -> Self {
+        EventRepo { inner, events }
+    }
+
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// The users who can see `todo`: its owner, the owner of its list and
+    /// everyone either is shared with.
+    async fn shared_with(&self, todo: &Todo) -> RepoResult<Vec<i32>> {
+        let list = self.inner.get_list_by_id(todo.list_id).await?;
+        let mut users: Vec<i32> = todo.owner_id.into_iter().chain(list.owner_id).collect();
+        let todo_shares = self.inner.get_todo_shares(todo.todo_id).await?;
+        let list_shares = self.inner.get_list_shares(todo.list_id).await?;
+        users.extend(
+            todo_shares
+                .iter()
+                .chain(&list_shares)
+                .map(|share| share.user_id),
+        );
+        users.sort_unstable();
+        users.dedup();
+        Ok(users)
+    }
+
+    /// Like [`EventRepo::shared_with`]; the change already happened, so
+    /// failing to look the shares up only narrows the event to the owner.
+    async fn audience(&self, todo: &Todo) -> Vec<i32> {
+        self.shared_with(todo).await.unwrap_or_else(|err| {
+            log::warn!("Reporting todo {} to its owner only: {err}", todo.todo_id);
+            todo.owner_id.into_iter().collect()
+        })
+    }
+
+    async fn publish(&self, kind: EventKind, todo: &Todo) {
+        let audience = self.audience(todo).await;
+        self.events
+            .publish(kind, todo.todo_id, Some(todo.clone()), audience);
+    }
+
+    /// Publishes the todo `id` as updated after a change that does not return it.
+    async fn touched(&self, id: i32) {
+        match self.inner.get_todo_by_id(id).await {
+            Ok(todo) => self.publish(EventKind::Updated, &todo).await,
+            Err(err) => log::warn!("Cannot report the change of todo {id}: {err}"),
+        }
+    }
+
+    /// The todo `id` and its subtasks with their audiences, to be reported
+    /// once they are deleted.
+    async fn doomed(&self, id: i32) -> RepoResult<Vec<(i32, Vec<i32>)>> {
+        let mut todos = vec![self.inner.get_todo_by_id(id).await?];
+        todos.extend(self.inner.get_descendants(id).await?);
+        let mut doomed = Vec::with_capacity(todos.len());
+        for todo in &todos {
+            doomed.push((todo.todo_id, self.audience(todo).await));
+        }
+        Ok(doomed)
+    }
+
+    /// The todos `mutation` may delete with their audiences: once they are
+    /// gone, so are their shares.
+    async fn doomed_by(&self, mutation: &Mutation) -> RepoResult<Vec<(i32, Vec<i32>)>> {
+        match mutation {
+            Mutation::Delete { id, .. } => self.doomed(*id).await,
+            Mutation::Batch { ops } => {
+                let mut doomed = Vec::new();
+                for op in ops {
+                    if let BatchOp::Delete { id, .. } = op {
+                        // An unknown todo fails its operation anyway.
+                        doomed.extend(self.doomed(*id).await.unwrap_or_default());
+                    }
+                }
+                Ok(doomed)
+            }
+            Mutation::DeleteList { id, cascade: true } => {
+                let query = TodoQuery {
+                    list_id: Some(*id),
+                    ..TodoQuery::default()
+                };
+                let mut doomed = Vec::new();
+                for todo in self.inner.query_todos(query).await?.items {
+                    doomed.push((todo.todo_id, self.audience(&todo).await));
+                }
+                Ok(doomed)
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Publishes the todo `todo` as deleted to the audience it had before,
+    /// see [`EventRepo::doomed_by`], or else to its owner.
+    fn deleted(&self, todo: &Todo, doomed: &[(i32, Vec<i32>)]) {
+        let audience = doomed
+            .iter()
+            .find(|(id, _)| *id == todo.todo_id)
+            .map(|(_, audience)| audience.clone())
+            .unwrap_or_else(|| todo.owner_id.into_iter().collect());
+        self.events
+            .publish(EventKind::Deleted, todo.todo_id, None, audience);
+    }
+}
+
+#[async_trait]
+impl TodoRepo for EventRepo {
+    async fn query_todos(&self, query: TodoQuery) -> RepoResult<TodoPage> {
+        self.inner.query_todos(query).await
+    }
+
+    /// Trashed todos were already reported as deleted when they are purged.
+    async fn mutate(&self, mutation: Mutation, actor: Option<Actor>) -> RepoResult<Mutated> {
+        let doomed = self.doomed_by(&mutation).await?;
+        let mutated = self.inner.mutate(mutation, actor).await?;
+        for change in &mutated.changes {
+            match (change.action, &change.before, &change.after) {
+                (HistoryAction::Created | HistoryAction::Restored, _, Some(todo)) => {
+                    self.publish(EventKind::Created, todo).await
+                }
+                (HistoryAction::Updated, _, Some(todo)) => {
+                    self.publish(EventKind::Updated, todo).await
+                }
+                (HistoryAction::Deleted, Some(todo), _) => self.deleted(todo, &doomed),
+                (HistoryAction::Purged, Some(todo), _) if todo.deleted_at.is_none() => {
+                    self.deleted(todo, &doomed)
+                }
+                _ => {}
+            }
+        }
+        Ok(mutated)
+    }
+
+    async fn get_todo_by_id(&self, id: i32) -> RepoResult<Todo> {
+        self.inner.get_todo_by_id(id).await
+    }
+
+    async fn get_trash(&self, owner_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        self.inner.get_trash(owner_id).await
+    }
+
+    fn atomic_batches(&self) -> bool {
+        self.inner.atomic_batches()
+    }
+
+    async fn get_descendants(&self, id: i32) -> RepoResult<Vec<Todo>> {
+        self.inner.get_descendants(id).await
+    }
+
+    async fn get_next_todos(&self, list_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        self.inner.get_next_todos(list_id).await
+    }
+
+    async fn get_tags(&self, visible_to: Option<i32>) -> RepoResult<Vec<Tag>> {
+        self.inner.get_tags(visible_to).await
+    }
+
+    async fn create_tag(&self, new: NewTag) -> RepoResult<Tag> {
+        self.inner.create_tag(new).await
+    }
+
+    async fn get_tag_by_id(&self, id: i32) -> RepoResult<Tag> {
+        self.inner.get_tag_by_id(id).await
+    }
+
+    async fn update_tag_by_id(&self, id: i32, tag: NewTag) -> RepoResult<Tag> {
+        self.inner.update_tag_by_id(id, tag).await
+    }
+
+    async fn delete_tag_by_id(&self, id: i32) -> RepoResult<usize> {
+        self.inner.delete_tag_by_id(id).await
+    }
+
+    async fn get_lists(&self) -> RepoResult<Vec<TodoList>> {
+        self.inner.get_lists().await
+    }
+
+    async fn create_list(&self, new: NewList) -> RepoResult<TodoList> {
+        self.inner.create_list(new).await
+    }
+
+    async fn get_list_by_id(&self, id: i32) -> RepoResult<TodoList> {
+        self.inner.get_list_by_id(id).await
+    }
+
+    async fn update_list_by_id(&self, id: i32, list: NewList) -> RepoResult<TodoList> {
+        self.inner.update_list_by_id(id, list).await
+    }
+
+    async fn get_todo_shares(&self, todo_id: i32) -> RepoResult<Vec<Share>> {
+        self.inner.get_todo_shares(todo_id).await
+    }
+
+    async fn share_todo(&self, todo_id: i32, share: Share) -> RepoResult<Share> {
+        let share = self.inner.share_todo(todo_id, share).await?;
+        self.touched(todo_id).await;
+        Ok(share)
+    }
+
+    async fn unshare_todo(&self, todo_id: i32, user_id: i32) -> RepoResult<usize> {
+        let removed = self.inner.unshare_todo(todo_id, user_id).await?;
+        self.touched(todo_id).await;
+        Ok(removed)
+    }
+
+    async fn get_list_shares(&self, list_id: i32) -> RepoResult<Vec<Share>> {
+        self.inner.get_list_shares(list_id).await
+    }
+
+    async fn share_list(&self, list_id: i32, share: Share) -> RepoResult<Share> {
+        self.inner.share_list(list_id, share).await
+    }
+
+    async fn unshare_list(&self, list_id: i32, user_id: i32) -> RepoResult<usize> {
+        self.inner.unshare_list(list_id, user_id).await
+    }
+
+    async fn create_user(&self, new: NewUser) -> RepoResult<User> {
+        self.inner.create_user(new).await
+    }
+
+    async fn get_user_by_id(&self, id: i32) -> RepoResult<User> {
+        self.inner.get_user_by_id(id).await
+    }
+
+    async fn get_user_record(&self, username: &str) -> RepoResult<UserRecord> {
+        self.inner.get_user_record(username).await
+    }
+
+    async fn create_api_key(&self, key: NewApiKeyRecord) -> RepoResult<ApiKey> {
+        self.inner.create_api_key(key).await
+    }
+
+    async fn get_api_keys(&self, user_id: i32) -> RepoResult<Vec<ApiKey>> {
+        self.inner.get_api_keys(user_id).await
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> RepoResult<ApiKey> {
+        self.inner.get_api_key_by_hash(key_hash).await
+    }
+
+    async fn delete_api_key(&self, user_id: i32, key_id: i32) -> RepoResult<usize> {
+        self.inner.delete_api_key(user_id, key_id).await
+    }
+
+    async fn record_history(&self, entries: Vec<NewHistoryEntry>) -> RepoResult<()> {
+        self.inner.record_history(entries).await
+    }
+
+    async fn get_history(&self, query: HistoryQuery) -> RepoResult<Vec<HistoryEntry>> {
+        self.inner.get_history(query).await
+    }
+}
@@ -0,0 +1,966 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use super::error::{RepoError, RepoResult};
+use super::mem_repo::{put_share, remove_share, shares_of, MemStore};
+use super::mutation::{Actor, Change, Mutated, Mutation, Outcome};
+use super::todo_repo::{apply_one_by_one, check_version, TodoRepo};
+use crate::models::api_key::{ApiKey, ApiKeyRecord, NewApiKeyRecord};
+use crate::models::history::{HistoryEntry, HistoryQuery, NewHistoryEntry};
+use crate::models::list::{NewList, TodoList};
+use crate::models::query::{TodoPage, TodoQuery};
+use crate::models::share::Share;
+use crate::models::tag::{NewTag, Tag};
+use crate::models::todo::{NewTodo, Todo};
+use crate::models::user::{NewUser, User, UserRecord};
+use crate::recurrence::follow_up;
+use crate::validation::Validate;
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const WAL_FILE: &str = "wal.log";
+
+/// Number of log entries after which the log is folded into a new snapshot.
+pub const DEFAULT_COMPACT_EVERY: usize = 1000;
+
+/// One line of the write-ahead log.
+///
+/// Entries describe the resulting state rather than the request, so replaying
+/// an entry twice (after a crash during compaction) is harmless. Todos are
+/// logged together with their tags, which replace the attached ones. Trashing
+/// a todo trashes its subtasks and drops their dependencies, deleting a list
+/// deletes its todos as well. `Delete` is only written by older versions.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum WalEntry {
+    Put {
+        todo: Todo,
+    },
+    /// Several todos written together, e.g. a completed recurring todo and its next instance.
+    PutAll {
+        todos: Vec<Todo>,
+    },
+    Delete {
+        todo_id: i32,
+    },
+    Trash {
+        todo_id: i32,
+        deleted_at: NaiveDateTime,
+    },
+    Restore {
+        todo_ids: Vec<i32>,
+    },
+    Purge {
+        todo_ids: Vec<i32>,
+    },
+    PutTag {
+        tag: Tag,
+    },
+    DeleteTag {
+        tag_id: i32,
+    },
+    PutList {
+        list: TodoList,
+    },
+    DeleteList {
+        list_id: i32,
+    },
+    Block {
+        blocked_id: i32,
+        blocker_id: i32,
+    },
+    Unblock {
+        blocked_id: i32,
+        blocker_id: i32,
+    },
+    ShareTodo {
+        todo_id: i32,
+        share: Share,
+    },
+    UnshareTodo {
+        todo_id: i32,
+        user_id: i32,
+    },
+    ShareList {
+        list_id: i32,
+        share: Share,
+    },
+    UnshareList {
+        list_id: i32,
+        user_id: i32,
+    },
+    PutUser {
+        user: UserRecord,
+    },
+    PutApiKey {
+        key: ApiKeyRecord,
+    },
+    DeleteApiKey {
+        user_id: i32,
+        key_id: i32,
+    },
+    /// Entries of the audit log, those already in the snapshot are skipped.
+    History {
+        entries: Vec<HistoryEntry>,
+    },
+    /// Everything one mutation of todos wrote, with its audit log entries, so
+    /// it is replayed in full or not at all.
+    Mutation {
+        entries: Vec<WalEntry>,
+    },
+}
+
+fn put(store: &mut MemStore, mut todo: Todo) {
+    store.last_todo_id = store.last_todo_id.max(todo.todo_id);
+    if store.trash.iter().any(|t| t.todo_id == todo.todo_id) {
+        // Only replayed entries from before the todo was trashed get here.
+        return;
+    }
+    store.set_tags(todo.todo_id, &std::mem::take(&mut todo.tags));
+    todo.blocked_by = Vec::new();
+    todo.progress = None;
+    match store.todos.iter_mut().find(|t| t.todo_id == todo.todo_id) {
+        Some(stored) => *stored = todo,
+        None => store.todos.push(todo),
+    }
+}
+
+impl WalEntry {
+    fn apply(self, store: &mut MemStore) {
+        match self {
+            WalEntry::Put { todo } => put(store, todo),
+            WalEntry::PutAll { todos } => todos.into_iter().for_each(|todo| put(store, todo)),
+            WalEntry::Delete { todo_id } => {
+                store.remove(todo_id);
+            }
+            WalEntry::Trash {
+                todo_id,
+                deleted_at,
+            } => {
+                store.trash(todo_id, deleted_at);
+            }
+            WalEntry::Restore { todo_ids } => store.restore(&todo_ids),
+            WalEntry::Purge { todo_ids } => {
+                store.purge(&todo_ids);
+            }
+            WalEntry::PutTag { tag } => store.put_tag(tag),
+            WalEntry::DeleteTag { tag_id } => {
+                store.remove_tag(tag_id);
+            }
+            WalEntry::PutList { list } => store.put_list(list),
+            WalEntry::DeleteList { list_id } => {
+                store.remove_list(list_id);
+            }
+            WalEntry::Block {
+                blocked_id,
+                blocker_id,
+            } => store.set_blocker(blocked_id, blocker_id, true),
+            WalEntry::Unblock {
+                blocked_id,
+                blocker_id,
+            } => store.set_blocker(blocked_id, blocker_id, false),
+            WalEntry::ShareTodo { todo_id, share } => {
+                put_share(&mut store.todo_shares, todo_id, share)
+            }
+            WalEntry::UnshareTodo { todo_id, user_id } => {
+                remove_share(&mut store.todo_shares, todo_id, user_id);
+            }
+            WalEntry::ShareList { list_id, share } => {
+                put_share(&mut store.list_shares, list_id, share)
+            }
+            WalEntry::UnshareList { list_id, user_id } => {
+                remove_share(&mut store.list_shares, list_id, user_id);
+            }
+            WalEntry::PutUser { user } => store.put_user(user),
+            WalEntry::PutApiKey { key } => store.put_api_key(key),
+            WalEntry::DeleteApiKey { user_id, key_id } => {
+                store.remove_api_key(user_id, key_id);
+            }
+            WalEntry::History { entries } => store.put_history(entries),
+            WalEntry::Mutation { entries } => {
+                entries.into_iter().for_each(|entry| entry.apply(store))
+            }
+        }
+    }
+}
+
+struct FileState {
+    store: MemStore,
+    wal: File,
+    wal_entries: usize,
+    /// Entries of the running mutation, applied but not yet logged.
+    staged: Vec<WalEntry>,
+}
+
+impl FileState {
+    /// Applies `entry` to the store; it is logged once the mutation is done,
+    /// see [`FileRepo::commit_staged`].
+    fn stage(&mut self, entry: WalEntry) {
+        entry.clone().apply(&mut self.store);
+        self.staged.push(entry);
+    }
+}
+
+/// Repository that keeps the todos in memory and persists them to `dir`.
+///
+/// Every mutation is appended to `wal.log` and synced before it becomes
+/// visible. The log is periodically compacted into `snapshot.json`; on
+/// startup the snapshot is loaded and the log replayed on top of it.
+#[derive(Clone)]
+pub struct FileRepo {
+    dir: PathBuf,
+    compact_every: usize,
+    block_completion: bool,
+    inner: Arc<Mutex<FileState>>,
+}
+
+fn io_error(context: &str, path: &Path, err: impl std::fmt::Display) -> RepoError {
+    RepoError::Internal(format!("{context} {}: {err}", path.display()))
+}
+
+impl FileRepo {
+    /// Opens (or creates) the data directory `dir`.
+    pub fn new(dir: impl AsRef<Path>) -> RepoResult<FileRepo> {
+        Self::with_compact_every(dir, DEFAULT_COMPACT_EVERY)
+    }
+
+    /// Like [`FileRepo::new`], compacting after `compact_every` log entries.
+    pub fn with_compact_every(dir: impl AsRef<Path>, compact_every: usize) -> RepoResult<FileRepo> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|err| io_error("cannot create", &dir, err))?;
+
+        let mut store = load_snapshot(&dir.join(SNAPSHOT_FILE))?;
+        let wal_path = dir.join(WAL_FILE);
+        let wal_entries = replay_wal(&wal_path, &mut store)?;
+        let wal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&wal_path)
+            .map_err(|err| io_error("cannot open", &wal_path, err))?;
+
+        let repo = FileRepo {
+            dir,
+            compact_every: compact_every.max(1),
+            block_completion: true,
+            inner: Arc::new(Mutex::new(FileState {
+                store,
+                wal,
+                wal_entries,
+                staged: Vec::new(),
+            })),
+        };
+        if wal_entries > 0 {
+            repo.compact()?;
+        }
+        Ok(repo)
+    }
+
+    /// Allows or rejects completing todos with open blockers.
+    pub fn with_block_completion(mut self, block_completion: bool) -> Self {
+        self.block_completion = block_completion;
+        self
+    }
+
+    /// Writes the current state to a new snapshot and empties the log.
+    pub fn compact(&self) -> RepoResult<()> {
+        let mut state = self.lock()?;
+        self.compact_locked(&mut state)
+    }
+
+    fn compact_locked(&self, state: &mut FileState) -> RepoResult<()> {
+        let snapshot = self.dir.join(SNAPSHOT_FILE);
+        let tmp = self.dir.join(format!("{SNAPSHOT_FILE}.tmp"));
+        let json =
+            serde_json::to_vec(&state.store).map_err(|err| RepoError::Internal(err.to_string()))?;
+
+        // Write aside and rename, so a crash leaves either the old or the new
+        // snapshot behind, never a half-written one.
+        let mut file = File::create(&tmp).map_err(|err| io_error("cannot create", &tmp, err))?;
+        file.write_all(&json)
+            .and_then(|_| file.sync_all())
+            .map_err(|err| io_error("cannot write", &tmp, err))?;
+        fs::rename(&tmp, &snapshot).map_err(|err| io_error("cannot replace", &snapshot, err))?;
+        sync_dir(&self.dir)?;
+
+        // Entries still in the log are already part of the snapshot; should we
+        // crash before this point they are simply replayed again.
+        state
+            .wal
+            .set_len(0)
+            .and_then(|_| state.wal.sync_all())
+            .map_err(|err| io_error("cannot truncate", &self.dir.join(WAL_FILE), err))?;
+        state.wal_entries = 0;
+        Ok(())
+    }
+
+    /// Appends `entry` to the log, then applies it to the in-memory state.
+    fn commit(&self, state: &mut FileState, entry: WalEntry) -> RepoResult<()> {
+        self.append(state, &entry)?;
+        entry.apply(&mut state.store);
+        self.logged(state);
+        Ok(())
+    }
+
+    /// Logs the entries staged by a mutation as one entry. If that fails they
+    /// are dropped from the in-memory state again.
+    fn commit_staged(&self, state: &mut FileState) -> RepoResult<()> {
+        let entries = std::mem::take(&mut state.staged);
+        if entries.is_empty() {
+            return Ok(());
+        }
+        if let Err(err) = self.append(state, &WalEntry::Mutation { entries }) {
+            self.reload(state);
+            return Err(err);
+        }
+        self.logged(state);
+        Ok(())
+    }
+
+    /// Drops the entries staged by a failed mutation.
+    fn discard_staged(&self, state: &mut FileState) {
+        if !std::mem::take(&mut state.staged).is_empty() {
+            self.reload(state);
+        }
+    }
+
+    /// Loads the in-memory state from disk again, dropping whatever was
+    /// applied to it but not logged.
+    fn reload(&self, state: &mut FileState) {
+        let reloaded = load_snapshot(&self.dir.join(SNAPSHOT_FILE)).and_then(|mut store| {
+            let wal_entries = replay_wal(&self.dir.join(WAL_FILE), &mut store)?;
+            Ok((store, wal_entries))
+        });
+        match reloaded {
+            Ok((store, wal_entries)) => {
+                state.store = store;
+                state.wal_entries = wal_entries;
+            }
+            Err(err) => log::error!("Cannot reload {}: {err}", self.dir.display()),
+        }
+    }
+
+    /// Appends `entry` to the log and syncs it.
+    ///
+    /// A failed append is cut off the log again, so the next entry does not
+    /// follow a partial line.
+    fn append(&self, state: &mut FileState, entry: &WalEntry) -> RepoResult<()> {
+        let wal_path = self.dir.join(WAL_FILE);
+        let mut line =
+            serde_json::to_vec(entry).map_err(|err| RepoError::Internal(err.to_string()))?;
+        line.push(b'\n');
+        let len = state
+            .wal
+            .metadata()
+            .map_err(|err| io_error("cannot read", &wal_path, err))?
+            .len();
+        if let Err(err) = state
+            .wal
+            .write_all(&line)
+            .and_then(|_| state.wal.sync_data())
+        {
+            if let Err(err) = state.wal.set_len(len).and_then(|_| state.wal.sync_data()) {
+                log::error!(
+                    "Cannot cut a failed append off {}: {err}",
+                    wal_path.display()
+                );
+            }
+            return Err(io_error("cannot append to", &wal_path, err));
+        }
+        Ok(())
+    }
+
+    /// Counts an appended entry. Once the entry is durable the mutation counts
+    /// as done, a failed compaction is only logged and retried with the next entry.
+    fn logged(&self, state: &mut FileState) {
+        state.wal_entries += 1;
+        if state.wal_entries >= self.compact_every {
+            if let Err(err) = self.compact_locked(state) {
+                log::error!("Cannot compact {}: {err}", self.dir.display());
+            }
+        }
+    }
+
+    /// Replaces the todo `id`; completing a recurring todo also logs its next
+    /// instance. Both are added to `changes`.
+    ///
+    /// The tags of `todo` are ignored, both keep the stored tags.
+    fn save(
+        &self,
+        state: &mut FileState,
+        id: i32,
+        mut todo: Todo,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Todo> {
+        let previous = state.store.find(id)?;
+        state
+            .store
+            .check_placement(Some(id), todo.list_id, todo.parent_id)?;
+        todo.todo_id = id;
+        todo.owner_id = previous.owner_id;
+        todo.version = previous.version + 1;
+        todo.tags = previous.tags.clone();
+        let next = follow_up(&previous, &todo, Local::now().naive_local()).map(|next| {
+            todo.recurrence = None;
+            let mut next = next.with_id(state.store.last_todo_id + 1);
+            next.tags = todo.tags.clone();
+            next
+        });
+        let entry = match &next {
+            Some(next) => WalEntry::PutAll {
+                todos: vec![todo.clone(), next.clone()],
+            },
+            None => WalEntry::Put { todo: todo.clone() },
+        };
+        state.stage(entry);
+        changes.push(Change::updated(previous, state.store.find(id)?));
+        if let Some(next) = next {
+            changes.push(Change::created(state.store.find(next.todo_id)?));
+        }
+        Ok(todo)
+    }
+
+    /// Completes the parents of `start` that are done, see [`MemStore::roll_up`].
+    fn roll_up(
+        &self,
+        state: &mut FileState,
+        mut start: Option<i32>,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<()> {
+        while let Some(id) = state.store.next_to_complete(start, self.block_completion) {
+            let mut todo = state.store.find(id)?;
+            todo.completed = Some(true);
+            self.save(state, id, todo, changes)?;
+            start = Some(id);
+        }
+        Ok(())
+    }
+
+    fn insert_todo(
+        &self,
+        state: &mut FileState,
+        todo: NewTodo,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Todo> {
+        todo.validate()?;
+        state
+            .store
+            .check_placement(None, todo.list_id, todo.parent_id)?;
+        let todo = todo.with_id(state.store.last_todo_id + 1);
+        state.stage(WalEntry::Put { todo: todo.clone() });
+        let todo = state.store.with_details(todo);
+        changes.push(Change::created(todo.clone()));
+        Ok(todo)
+    }
+
+    /// Replaces the todo `id` with what `change` makes of it, see
+    /// [`TodoRepo::update_todo_if_version`].
+    fn replace_todo(
+        &self,
+        state: &mut FileState,
+        id: i32,
+        version: Option<i32>,
+        changes: &mut Vec<Change>,
+        change: impl FnOnce(&Todo) -> RepoResult<Todo>,
+    ) -> RepoResult<Todo> {
+        let previous = state.store.find(id)?;
+        check_version(&previous, version)?;
+        let todo = change(&previous)?;
+        if self.block_completion {
+            state.store.check_unblocked(id, todo.completed)?;
+        }
+        let todo = self.save(state, id, todo, changes)?;
+        self.roll_up(state, previous.parent_id, changes)?;
+        self.roll_up(state, todo.parent_id, changes)?;
+        state.store.find(id)
+    }
+
+    fn trash_todo(
+        &self,
+        state: &mut FileState,
+        id: i32,
+        version: Option<i32>,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<usize> {
+        let todo = state.store.find(id)?;
+        check_version(&todo, version)?;
+        let parent_id = todo.parent_id;
+        let trashed = state
+            .store
+            .subtree_ids(id)
+            .into_iter()
+            .map(|todo_id| state.store.find(todo_id))
+            .collect::<RepoResult<Vec<_>>>()?;
+        let count = trashed.len();
+        let entry = WalEntry::Trash {
+            todo_id: id,
+            deleted_at: Local::now().naive_local(),
+        };
+        state.stage(entry);
+        changes.extend(trashed.into_iter().map(Change::deleted));
+        self.roll_up(state, parent_id, changes)?;
+        Ok(count)
+    }
+
+    fn restore(
+        &self,
+        state: &mut FileState,
+        id: i32,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Todo> {
+        let todo_ids = state.store.restorable(id)?;
+        state.stage(WalEntry::Restore {
+            todo_ids: todo_ids.clone(),
+        });
+        for todo_id in todo_ids {
+            changes.push(Change::restored(state.store.find(todo_id)?));
+        }
+        let todo = state.store.find(id)?;
+        self.roll_up(state, todo.parent_id, changes)?;
+        state.store.find(id)
+    }
+
+    /// Purges the trashed todos `select` picks and adds them to `changes`.
+    fn purge(
+        &self,
+        state: &mut FileState,
+        changes: &mut Vec<Change>,
+        select: impl FnOnce(&MemStore) -> RepoResult<Vec<i32>>,
+    ) -> RepoResult<usize> {
+        let todo_ids = select(&state.store)?;
+        let purged = state.store.trashed_todos(&todo_ids);
+        let count = todo_ids.len();
+        if count > 0 {
+            state.stage(WalEntry::Purge { todo_ids });
+        }
+        changes.extend(purged.into_iter().map(Change::purged));
+        Ok(count)
+    }
+
+    fn set_blocker(
+        &self,
+        state: &mut FileState,
+        id: i32,
+        blocker_id: i32,
+        present: bool,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Todo> {
+        let previous = state.store.find(id)?;
+        let entry = if present {
+            state.store.check_blocker(id, blocker_id)?;
+            WalEntry::Block {
+                blocked_id: id,
+                blocker_id,
+            }
+        } else {
+            state
+                .store
+                .find(blocker_id)
+                .map_err(|_| RepoError::Missing("blocker"))?;
+            WalEntry::Unblock {
+                blocked_id: id,
+                blocker_id,
+            }
+        };
+        state.stage(entry);
+        let todo = state.store.find(id)?;
+        changes.push(Change::updated(previous, todo.clone()));
+        Ok(todo)
+    }
+
+    fn retag(
+        &self,
+        state: &mut FileState,
+        todo_id: i32,
+        tag_id: i32,
+        attach: bool,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Todo> {
+        let previous = state.store.find(todo_id)?;
+        let todo = state.store.retagged(todo_id, tag_id, attach)?;
+        state.stage(WalEntry::Put { todo: todo.clone() });
+        changes.push(Change::updated(previous, todo.clone()));
+        Ok(todo)
+    }
+
+    fn remove_list(
+        &self,
+        state: &mut FileState,
+        id: i32,
+        cascade: bool,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<usize> {
+        state.store.check_list_deletable(id, cascade)?;
+        let store = &state.store;
+        let purged: Vec<Todo> = store
+            .todos
+            .iter()
+            .chain(&store.trash)
+            .filter(|t| t.list_id == id)
+            .map(|t| store.with_details(t.clone()))
+            .collect();
+        state.stage(WalEntry::DeleteList { list_id: id });
+        changes.extend(purged.into_iter().map(Change::purged));
+        Ok(1)
+    }
+
+    /// Applies `mutation` to the locked `state` and stages its log entries,
+    /// batches go through [`apply_one_by_one`] before.
+    fn write(
+        &self,
+        state: &mut FileState,
+        mutation: Mutation,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Outcome> {
+        let outcome = match mutation {
+            Mutation::Create { todo } => Outcome::Todo(self.insert_todo(state, todo, changes)?),
+            Mutation::Update { id, todo, version } => {
+                todo.validate()?;
+                Outcome::Todo(self.replace_todo(state, id, version, changes, |_| Ok(todo))?)
+            }
+            Mutation::Patch { id, patch, version } => {
+                patch.validate()?;
+                let todo = self.replace_todo(state, id, version, changes, |previous| {
+                    patch.validate_against(previous)?;
+                    let mut todo = previous.clone();
+                    patch.apply_to(&mut todo);
+                    Ok(todo)
+                })?;
+                Outcome::Todo(todo)
+            }
+            Mutation::Delete { id, version } => {
+                Outcome::Count(self.trash_todo(state, id, version, changes)?)
+            }
+            Mutation::Restore { id } => Outcome::Todo(self.restore(state, id, changes)?),
+            Mutation::Purge { id } => {
+                Outcome::Count(self.purge(state, changes, |store| store.trashed_ids(id, None))?)
+            }
+            Mutation::PurgeTrash { before } => {
+                Outcome::Count(
+                    self.purge(state, changes, |store| Ok(store.trashed_before(before)))?,
+                )
+            }
+            Mutation::Batch { .. } => unreachable!("batches are applied one by one"),
+            Mutation::AddBlocker { id, blocker_id } => {
+                Outcome::Todo(self.set_blocker(state, id, blocker_id, true, changes)?)
+            }
+            Mutation::RemoveBlocker { id, blocker_id } => {
+                Outcome::Todo(self.set_blocker(state, id, blocker_id, false, changes)?)
+            }
+            Mutation::AttachTag { todo_id, tag_id } => {
+                Outcome::Todo(self.retag(state, todo_id, tag_id, true, changes)?)
+            }
+            Mutation::DetachTag { todo_id, tag_id } => {
+                Outcome::Todo(self.retag(state, todo_id, tag_id, false, changes)?)
+            }
+            Mutation::DeleteList { id, cascade } => {
+                Outcome::Count(self.remove_list(state, id, cascade, changes)?)
+            }
+        };
+        Ok(outcome)
+    }
+
+    fn lock(&self) -> RepoResult<MutexGuard<'_, FileState>> {
+        self.inner
+            .lock()
+            .map_err(|_| RepoError::Internal("file store is poisoned".to_string()))
+    }
+}
+
+fn load_snapshot(path: &Path) -> RepoResult<MemStore> {
+    match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(|err| io_error("corrupt", path, err)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(MemStore::default()),
+        Err(err) => Err(io_error("cannot read", path, err)),
+    }
+}
+
+/// Replays the log at `path` into `store` and returns the number of entries.
+///
+/// A torn last line (the process died while appending it) was never
+/// acknowledged to a client, so it is cut off. Anything unreadable before the
+/// last line means the log is damaged and opening fails.
+fn replay_wal(path: &Path, store: &mut MemStore) -> RepoResult<usize> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(io_error("cannot read", path, err)),
+    };
+    let mut reader = BufReader::new(file);
+    let mut entries = 0;
+    let mut valid_len = 0u64;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|err| io_error("cannot read", path, err))?;
+        if read == 0 {
+            break;
+        }
+        if !line.ends_with(b"\n") {
+            break;
+        }
+        let entry = serde_json::from_slice::<WalEntry>(&line)
+            .map_err(|err| io_error("corrupt", path, err))?;
+        entry.apply(store);
+        entries += 1;
+        valid_len += read as u64;
+    }
+
+    let file_len = fs::metadata(path)
+        .map_err(|err| io_error("cannot read", path, err))?
+        .len();
+    if valid_len < file_len {
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_len(valid_len).and_then(|_| file.sync_all()))
+            .map_err(|err| io_error("cannot truncate", path, err))?;
+    }
+    Ok(entries)
+}
+
+/// Makes a rename inside `dir` durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> RepoResult<()> {
+    File::open(dir)
+        .and_then(|d| d.sync_all())
+        .map_err(|err| io_error("cannot sync", dir, err))
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> RepoResult<()> {
+    Ok(())
+}
+
+#[async_trait]
+impl TodoRepo for FileRepo {
+    async fn query_todos(&self, query: TodoQuery) -> RepoResult<TodoPage> {
+        Ok(self.lock()?.store.query(&query))
+    }
+
+    async fn mutate(&self, mutation: Mutation, actor: Option<Actor>) -> RepoResult<Mutated> {
+        let mutation = match mutation {
+            Mutation::Batch { ops } => return Ok(apply_one_by_one(self, ops, actor).await),
+            mutation => mutation,
+        };
+        let mut state = self.lock()?;
+        let mut changes = Vec::new();
+        let outcome = match self.write(&mut state, mutation, &mut changes) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                self.discard_staged(&mut state);
+                return Err(err);
+            }
+        };
+        if let Some(actor) = actor {
+            let entries = state.store.new_history(actor.history(&changes));
+            if !entries.is_empty() {
+                state.stage(WalEntry::History { entries });
+            }
+        }
+        self.commit_staged(&mut state)?;
+        Ok(Mutated::new(outcome, changes))
+    }
+
+    async fn get_todo_by_id(&self, id: i32) -> RepoResult<Todo> {
+        self.lock()?.store.find(id)
+    }
+
+    async fn get_trash(&self, owner_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        Ok(self.lock()?.store.trashed(owner_id))
+    }
+
+    async fn get_descendants(&self, id: i32) -> RepoResult<Vec<Todo>> {
+        self.lock()?.store.descendants(id)
+    }
+
+    async fn get_next_todos(&self, list_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        let state = self.lock()?;
+        if let Some(list_id) = list_id {
+            state.store.find_list(list_id)?;
+        }
+        Ok(state.store.next_todos(list_id))
+    }
+
+    async fn get_tags(&self, visible_to: Option<i32>) -> RepoResult<Vec<Tag>> {
+        Ok(self.lock()?.store.visible_tags(visible_to))
+    }
+
+    async fn create_tag(&self, tag: NewTag) -> RepoResult<Tag> {
+        tag.validate()?;
+        let mut state = self.lock()?;
+        state.store.check_tag_name(&tag.name, tag.owner_id, None)?;
+        let tag = tag.with_id(state.store.last_tag_id + 1);
+        self.commit(&mut state, WalEntry::PutTag { tag: tag.clone() })?;
+        Ok(tag)
+    }
+
+    async fn get_tag_by_id(&self, id: i32) -> RepoResult<Tag> {
+        self.lock()?.store.find_tag(id)
+    }
+
+    async fn update_tag_by_id(&self, id: i32, tag: NewTag) -> RepoResult<Tag> {
+        tag.validate()?;
+        let mut state = self.lock()?;
+        let owner_id = state.store.find_tag(id)?.owner_id;
+        state.store.check_tag_name(&tag.name, owner_id, Some(id))?;
+        let tag = NewTag { owner_id, ..tag }.with_id(id);
+        self.commit(&mut state, WalEntry::PutTag { tag: tag.clone() })?;
+        Ok(tag)
+    }
+
+    async fn delete_tag_by_id(&self, id: i32) -> RepoResult<usize> {
+        let mut state = self.lock()?;
+        state.store.find_tag(id)?;
+        self.commit(&mut state, WalEntry::DeleteTag { tag_id: id })?;
+        Ok(1)
+    }
+
+    async fn get_lists(&self) -> RepoResult<Vec<TodoList>> {
+        let mut lists = self.lock()?.store.lists.clone();
+        lists.sort_by_key(|list| list.list_id);
+        Ok(lists)
+    }
+
+    async fn create_list(&self, list: NewList) -> RepoResult<TodoList> {
+        list.validate()?;
+        let mut state = self.lock()?;
+        let list = list.with_id(state.store.last_list_id + 1);
+        self.commit(&mut state, WalEntry::PutList { list: list.clone() })?;
+        Ok(list)
+    }
+
+    async fn get_list_by_id(&self, id: i32) -> RepoResult<TodoList> {
+        self.lock()?.store.find_list(id)
+    }
+
+    async fn update_list_by_id(&self, id: i32, list: NewList) -> RepoResult<TodoList> {
+        list.validate()?;
+        let mut state = self.lock()?;
+        let owner_id = state.store.find_list(id)?.owner_id;
+        let list = NewList { owner_id, ..list }.with_id(id);
+        self.commit(&mut state, WalEntry::PutList { list: list.clone() })?;
+        Ok(list)
+    }
+
+    async fn get_todo_shares(&self, todo_id: i32) -> RepoResult<Vec<Share>> {
+        let state = self.lock()?;
+        state.store.find(todo_id)?;
+        Ok(shares_of(&state.store.todo_shares, todo_id).to_vec())
+    }
+
+    async fn share_todo(&self, todo_id: i32, share: Share) -> RepoResult<Share> {
+        let mut state = self.lock()?;
+        state.store.find(todo_id)?;
+        state.store.find_user_by_id(share.user_id)?;
+        self.commit(&mut state, WalEntry::ShareTodo { todo_id, share })?;
+        Ok(share)
+    }
+
+    async fn unshare_todo(&self, todo_id: i32, user_id: i32) -> RepoResult<usize> {
+        let mut state = self.lock()?;
+        state.store.find(todo_id)?;
+        let shared = shares_of(&state.store.todo_shares, todo_id)
+            .iter()
+            .any(|share| share.user_id == user_id);
+        if !shared {
+            return Ok(0);
+        }
+        self.commit(&mut state, WalEntry::UnshareTodo { todo_id, user_id })?;
+        Ok(1)
+    }
+
+    async fn get_list_shares(&self, list_id: i32) -> RepoResult<Vec<Share>> {
+        let state = self.lock()?;
+        state.store.find_list(list_id)?;
+        Ok(shares_of(&state.store.list_shares, list_id).to_vec())
+    }
+
+    async fn share_list(&self, list_id: i32, share: Share) -> RepoResult<Share> {
+        let mut state = self.lock()?;
+        state.store.find_list(list_id)?;
+        state.store.find_user_by_id(share.user_id)?;
+        self.commit(&mut state, WalEntry::ShareList { list_id, share })?;
+        Ok(share)
+    }
+
+    async fn unshare_list(&self, list_id: i32, user_id: i32) -> RepoResult<usize> {
+        let mut state = self.lock()?;
+        state.store.find_list(list_id)?;
+        let shared = shares_of(&state.store.list_shares, list_id)
+            .iter()
+            .any(|share| share.user_id == user_id);
+        if !shared {
+            return Ok(0);
+        }
+        self.commit(&mut state, WalEntry::UnshareList { list_id, user_id })?;
+        Ok(1)
+    }
+
+    async fn create_user(&self, user: NewUser) -> RepoResult<User> {
+        let mut state = self.lock()?;
+        let user = state.store.new_user_record(user)?;
+        self.commit(&mut state, WalEntry::PutUser { user: user.clone() })?;
+        Ok(user.user)
+    }
+
+    async fn get_user_by_id(&self, id: i32) -> RepoResult<User> {
+        self.lock()?.store.find_user_by_id(id)
+    }
+
+    async fn get_user_record(&self, username: &str) -> RepoResult<UserRecord> {
+        self.lock()?
+            .store
+            .find_user(username)
+            .cloned()
+            .ok_or(RepoError::Missing("user"))
+    }
+
+    async fn create_api_key(&self, key: NewApiKeyRecord) -> RepoResult<ApiKey> {
+        let mut state = self.lock()?;
+        let key = state.store.new_api_key_record(key)?;
+        self.commit(&mut state, WalEntry::PutApiKey { key: key.clone() })?;
+        Ok(key.key)
+    }
+
+    async fn get_api_keys(&self, user_id: i32) -> RepoResult<Vec<ApiKey>> {
+        Ok(self.lock()?.store.api_keys_of(user_id))
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> RepoResult<ApiKey> {
+        self.lock()?.store.find_api_key(key_hash)
+    }
+
+    async fn delete_api_key(&self, user_id: i32, key_id: i32) -> RepoResult<usize> {
+        let mut state = self.lock()?;
+        let known = state
+            .store
+            .api_keys
+            .iter()
+            .any(|record| record.key.key_id == key_id && record.key.user_id == user_id);
+        if !known {
+            return Err(RepoError::NotFound);
+        }
+        self.commit(&mut state, WalEntry::DeleteApiKey { user_id, key_id })?;
+        Ok(1)
+    }
+
+    async fn record_history(&self, entries: Vec<NewHistoryEntry>) -> RepoResult<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut state = self.lock()?;
+        let entries = state.store.new_history(entries);
+        self.commit(&mut state, WalEntry::History { entries })
+    }
+
+    async fn get_history(&self, query: HistoryQuery) -> RepoResult<Vec<HistoryEntry>> {
+        Ok(query.apply(&self.lock()?.store.history))
+    }
+}
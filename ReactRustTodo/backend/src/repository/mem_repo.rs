@@ -1,62 +1,1191 @@
-use super::todo_repo::TodoRepo;
+use super::error::{RepoError, RepoResult};
+use super::mutation::{Actor, Change, Mutated, Mutation, Outcome};
+use super::todo_repo::{apply_one_by_one, check_version, TodoRepo};
+use super::{
+    blocked, check_dependency, check_parent, default_list_kept, duplicate_tag, duplicate_user,
+    list_not_empty, parent_trashed, subtasks_stay,
+};
+use crate::models::api_key::{ApiKey, ApiKeyRecord, NewApiKeyRecord};
+use crate::models::history::{HistoryEntry, HistoryQuery, NewHistoryEntry};
+use crate::models::list::{NewList, TodoList, DEFAULT_LIST_ID};
+use crate::models::query::{TodoPage, TodoQuery};
+use crate::models::share::{self, Role, Share};
+use crate::models::tag::{NewTag, Tag};
 use crate::models::todo::{NewTodo, Todo};
+use crate::models::tree::Progress;
+use crate::models::user::{NewUser, User, UserRecord};
+use crate::recurrence::follow_up;
+use crate::validation::Validate;
 use async_trait::async_trait;
-use std::{
-    fmt::Error,
-    sync::{Arc, Mutex},
-};
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex, MutexGuard};
 
-#[derive(Clone, Default)]
-pub struct MemRepo {
-    pub inner: Arc<Mutex<Vec<Todo>>>,
+/// Everything the in-memory repository holds, guarded by a single mutex.
+///
+/// Todos are stored without their tags and blockers, which are kept in
+/// `todo_tags` and `dependencies` like in the join tables of the SQL backends,
+/// and without their progress.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MemStore {
+    pub todos: Vec<Todo>,
+    /// Highest id handed out so far. Ids are never reused, even after deletes.
+    pub last_todo_id: i32,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub last_tag_id: i32,
+    /// `(todo_id, tag_id)` pairs of attached tags.
+    #[serde(default)]
+    pub todo_tags: BTreeSet<(i32, i32)>,
+    /// Stores written before lists existed get the default list.
+    #[serde(default = "default_lists")]
+    pub lists: Vec<TodoList>,
+    #[serde(default = "default_last_list_id")]
+    pub last_list_id: i32,
+    /// `(blocked_id, blocker_id)` pairs, the first todo waits for the second.
+    #[serde(default)]
+    pub dependencies: BTreeSet<(i32, i32)>,
+    #[serde(default)]
+    pub users: Vec<UserRecord>,
+    #[serde(default)]
+    pub last_user_id: i32,
+    /// Users each todo is shared with, by todo id.
+    #[serde(default)]
+    pub todo_shares: BTreeMap<i32, Vec<Share>>,
+    /// Users each list is shared with, by list id.
+    #[serde(default)]
+    pub list_shares: BTreeMap<i32, Vec<Share>>,
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyRecord>,
+    #[serde(default)]
+    pub last_api_key_id: i32,
+    /// Trashed todos, kept apart from `todos` so that nothing else sees them.
+    /// Their tag links and shares stay until they are purged.
+    #[serde(default)]
+    pub trash: Vec<Todo>,
+    /// The audit log in the order it was recorded.
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    #[serde(default)]
+    pub last_history_id: i32,
 }
 
-#[async_trait]
-impl TodoRepo for MemRepo {
-    async fn get_todos(&self) -> Vec<Todo> {
-        self.inner.lock().unwrap().clone()
-    }
-
-    async fn create_todo(&self, todo: NewTodo) -> Result<Todo, Error> {
-        let mut v = self.inner.lock().unwrap();
-        let id = v.last().map(|t| t.todo_id).unwrap_or(0) + 1;
-        let t = Todo {
-            todo_id: id,
-            title: todo.title,
-            description: todo.description,
-            created_at: todo.created_at,
-            completed: todo.completed,
-        };
-        v.push(t.clone());
-        Ok(t)
+fn default_lists() -> Vec<TodoList> {
+    vec![TodoList::default_list()]
+}
+
+fn default_last_list_id() -> i32 {
+    DEFAULT_LIST_ID
+}
+
+impl Default for MemStore {
+    fn default() -> Self {
+        MemStore {
+            todos: Vec::new(),
+            last_todo_id: 0,
+            tags: Vec::new(),
+            last_tag_id: 0,
+            todo_tags: BTreeSet::new(),
+            lists: default_lists(),
+            last_list_id: default_last_list_id(),
+            dependencies: BTreeSet::new(),
+            users: Vec::new(),
+            last_user_id: 0,
+            todo_shares: BTreeMap::new(),
+            list_shares: BTreeMap::new(),
+            api_keys: Vec::new(),
+            last_api_key_id: 0,
+            trash: Vec::new(),
+            history: Vec::new(),
+            last_history_id: 0,
+        }
     }
+}
 
-    async fn get_todo_by_id(&self, id: i32) -> Option<Todo> {
-        self.inner
-            .lock()
-            .unwrap()
+impl MemStore {
+    /// Stores `todo` under the next free id.
+    pub fn insert(&mut self, todo: NewTodo) -> Todo {
+        self.last_todo_id += 1;
+        let todo = todo.with_id(self.last_todo_id);
+        self.todos.push(todo.clone());
+        todo
+    }
+
+    /// Replaces the todo `id`; completing a recurring todo also inserts its next instance.
+    ///
+    /// The tags of `todo` are ignored, the next instance gets the tags of the
+    /// completed one. Both are added to `changes`.
+    pub fn replace(
+        &mut self,
+        id: i32,
+        mut todo: Todo,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Todo> {
+        let pos = self
+            .todos
+            .iter()
+            .position(|t| t.todo_id == id)
+            .ok_or(RepoError::NotFound)?;
+        self.check_placement(Some(id), todo.list_id, todo.parent_id)?;
+        let previous = self.with_details(self.todos[pos].clone());
+        todo.todo_id = id;
+        todo.owner_id = previous.owner_id;
+        todo.version = previous.version + 1;
+        todo.tags = Vec::new();
+        todo.blocked_by = Vec::new();
+        todo.progress = None;
+        let next = follow_up(&previous, &todo, Local::now().naive_local()).map(|next| {
+            todo.recurrence = None;
+            let next = self.insert(next);
+            self.set_tags(next.todo_id, &previous.tags);
+            next
+        });
+        self.todos[pos] = todo.clone();
+        let todo = self.with_details(todo);
+        changes.push(Change::updated(previous, todo.clone()));
+        if let Some(next) = next {
+            changes.push(Change::created(self.with_details(next)));
+        }
+        Ok(todo)
+    }
+
+    /// Removes the todo `id` with all its subtasks, their tag links and
+    /// dependencies, returns the number of removed todos.
+    pub fn remove(&mut self, id: i32) -> usize {
+        let ids = self.subtree_ids(id);
+        self.todos.retain(|t| !ids.contains(&t.todo_id));
+        self.todo_tags.retain(|(todo_id, _)| !ids.contains(todo_id));
+        self.dependencies.retain(|(blocked_id, blocker_id)| {
+            !ids.contains(blocked_id) && !ids.contains(blocker_id)
+        });
+        self.todo_shares.retain(|todo_id, _| !ids.contains(todo_id));
+        ids.len()
+    }
+
+    /// Moves the todo `id` with all its subtasks to the trash and drops their
+    /// dependencies, returns the number of trashed todos.
+    pub fn trash(&mut self, id: i32, deleted_at: NaiveDateTime) -> usize {
+        let ids = self.subtree_ids(id);
+        let (trashed, kept): (Vec<Todo>, Vec<Todo>) = std::mem::take(&mut self.todos)
+            .into_iter()
+            .partition(|t| ids.contains(&t.todo_id));
+        self.todos = kept;
+        self.trash.extend(trashed.into_iter().map(|mut todo| {
+            todo.deleted_at = Some(deleted_at);
+            todo
+        }));
+        self.dependencies.retain(|(blocked_id, blocker_id)| {
+            !ids.contains(blocked_id) && !ids.contains(blocker_id)
+        });
+        ids.len()
+    }
+
+    /// Ids of the trashed todo `id` and the trashed todos below it, only
+    /// following those trashed at `deleted_at` if given. Fails with
+    /// `RepoError::NotFound` unless the todo is in the trash.
+    pub fn trashed_ids(&self, id: i32, deleted_at: Option<NaiveDateTime>) -> RepoResult<Vec<i32>> {
+        if !self.trash.iter().any(|t| t.todo_id == id) {
+            return Err(RepoError::NotFound);
+        }
+        let mut ids = vec![id];
+        let mut next = 0;
+        while next < ids.len() {
+            let parent = ids[next];
+            ids.extend(
+                self.trash
+                    .iter()
+                    .filter(|t| t.parent_id == Some(parent))
+                    .filter(|t| deleted_at.is_none() || t.deleted_at == deleted_at)
+                    .map(|t| t.todo_id),
+            );
+            next += 1;
+        }
+        Ok(ids)
+    }
+
+    /// Ids of the todos to restore with the trashed todo `id`, see
+    /// [`TodoRepo::restore_todo`].
+    pub fn restorable(&self, id: i32) -> RepoResult<Vec<i32>> {
+        let todo = self
+            .trash
+            .iter()
+            .find(|t| t.todo_id == id)
+            .ok_or(RepoError::NotFound)?;
+        if let Some(parent_id) = todo.parent_id {
+            if self.trash.iter().any(|t| t.todo_id == parent_id) {
+                return Err(parent_trashed());
+            }
+        }
+        self.trashed_ids(id, todo.deleted_at)
+    }
+
+    /// Moves the todos `ids` from the trash back to the todos in use.
+    pub fn restore(&mut self, ids: &[i32]) {
+        let (restored, kept): (Vec<Todo>, Vec<Todo>) = std::mem::take(&mut self.trash)
+            .into_iter()
+            .partition(|t| ids.contains(&t.todo_id));
+        self.trash = kept;
+        self.todos.extend(restored.into_iter().map(|mut todo| {
+            todo.deleted_at = None;
+            todo
+        }));
+        self.todos.sort_by_key(|t| t.todo_id);
+    }
+
+    /// The trashed todos `ids` with their tags, in the order of `ids`.
+    pub fn trashed_todos(&self, ids: &[i32]) -> Vec<Todo> {
+        ids.iter()
+            .filter_map(|id| self.trash.iter().find(|t| t.todo_id == *id))
+            .map(|todo| self.with_details(todo.clone()))
+            .collect()
+    }
+
+    /// Ids of the todos trashed before `before`.
+    pub fn trashed_before(&self, before: NaiveDateTime) -> Vec<i32> {
+        self.trash
+            .iter()
+            .filter(|t| t.deleted_at.is_some_and(|at| at < before))
+            .map(|t| t.todo_id)
+            .collect()
+    }
+
+    /// Removes the trashed todos `ids` with their tag links and shares,
+    /// returns the number of removed todos.
+    pub fn purge(&mut self, ids: &[i32]) -> usize {
+        let len = self.trash.len();
+        self.trash.retain(|t| !ids.contains(&t.todo_id));
+        self.todo_tags.retain(|(todo_id, _)| !ids.contains(todo_id));
+        self.todo_shares.retain(|todo_id, _| !ids.contains(todo_id));
+        len - self.trash.len()
+    }
+
+    /// The trashed todos, only those of `owner_id` if given, with their tags,
+    /// most recently trashed first.
+    pub fn trashed(&self, owner_id: Option<i32>) -> Vec<Todo> {
+        let mut todos: Vec<Todo> = self
+            .trash
+            .iter()
+            .filter(|t| owner_id.is_none() || t.owner_id == owner_id)
+            .cloned()
+            .map(|todo| self.with_details(todo))
+            .collect();
+        todos.sort_by(|a, b| {
+            b.deleted_at
+                .cmp(&a.deleted_at)
+                .then(a.todo_id.cmp(&b.todo_id))
+        });
+        todos
+    }
+
+    /// The todo `id` with its tags and progress.
+    pub fn find(&self, id: i32) -> RepoResult<Todo> {
+        self.todos
             .iter()
             .find(|t| t.todo_id == id)
             .cloned()
+            .map(|todo| self.with_details(todo))
+            .ok_or(RepoError::NotFound)
+    }
+
+    /// Ids of the todo `id` and all todos below it, empty if it does not exist.
+    pub fn subtree_ids(&self, id: i32) -> Vec<i32> {
+        let mut ids: Vec<i32> = self
+            .todos
+            .iter()
+            .filter(|t| t.todo_id == id)
+            .map(|t| t.todo_id)
+            .collect();
+        let mut next = 0;
+        while next < ids.len() {
+            let parent = ids[next];
+            ids.extend(
+                self.todos
+                    .iter()
+                    .filter(|t| t.parent_id == Some(parent))
+                    .map(|t| t.todo_id),
+            );
+            next += 1;
+        }
+        ids
+    }
+
+    /// All todos below the todo `id` with tags and progress, ordered by id.
+    pub fn descendants(&self, id: i32) -> RepoResult<Vec<Todo>> {
+        self.find(id)?;
+        let mut ids = self.subtree_ids(id);
+        ids.sort_unstable();
+        ids.into_iter()
+            .filter(|todo_id| *todo_id != id)
+            .map(|todo_id| self.find(todo_id))
+            .collect()
+    }
+
+    /// Progress over the direct subtasks of the todo `id`.
+    pub fn progress_of(&self, id: i32) -> Option<Progress> {
+        Progress::of(
+            self.todos
+                .iter()
+                .filter(|t| t.parent_id == Some(id))
+                .map(|t| t.completed),
+        )
+    }
+
+    /// Checks the list and parent of the todo `id` (`None` for a new todo).
+    pub fn check_placement(
+        &self,
+        id: Option<i32>,
+        list_id: i32,
+        parent_id: Option<i32>,
+    ) -> RepoResult<()> {
+        self.find_list(list_id)?;
+        if let Some(id) = id {
+            let moved = self
+                .todos
+                .iter()
+                .any(|t| t.todo_id == id && t.list_id != list_id);
+            if moved && self.progress_of(id).is_some() {
+                return Err(subtasks_stay());
+            }
+        }
+        match parent_id {
+            Some(parent_id) => check_parent(id, list_id, parent_id, |todo_id| {
+                Ok(self
+                    .todos
+                    .iter()
+                    .find(|t| t.todo_id == todo_id)
+                    .map(|t| (t.parent_id, t.list_id)))
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// The first todo from `start` upwards that should be completed because
+    /// all its subtasks are, see [`Todo::auto_complete`].
+    ///
+    /// Completed todos are passed on to their parent, the search stops at the
+    /// first open todo that cannot be completed yet, which includes todos with
+    /// open blockers if `block_completion` is set.
+    pub fn next_to_complete(&self, start: Option<i32>, block_completion: bool) -> Option<i32> {
+        let mut current = start;
+        while let Some(id) = current {
+            let todo = self.todos.iter().find(|t| t.todo_id == id)?;
+            if todo.completed != Some(true) {
+                let done = self.progress_of(id).is_some_and(|p| p.is_done());
+                let blocked = block_completion && !self.open_blockers(id).is_empty();
+                return (todo.auto_complete && done && !blocked).then_some(id);
+            }
+            current = todo.parent_id;
+        }
+        None
+    }
+
+    /// Completes the todos found by [`MemStore::next_to_complete`] and adds
+    /// them to `changes`.
+    pub fn roll_up(
+        &mut self,
+        mut start: Option<i32>,
+        block_completion: bool,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<()> {
+        while let Some(id) = self.next_to_complete(start, block_completion) {
+            let mut todo = self.find(id)?;
+            todo.completed = Some(true);
+            self.replace(id, todo, changes)?;
+            start = Some(id);
+        }
+        Ok(())
+    }
+
+    /// Tags attached to the todo `id`, ordered by name.
+    pub fn tags_of(&self, id: i32) -> Vec<Tag> {
+        let mut tags: Vec<Tag> = self
+            .tags
+            .iter()
+            .filter(|tag| self.todo_tags.contains(&(id, tag.tag_id)))
+            .cloned()
+            .collect();
+        sort_by_name(&mut tags);
+        tags
+    }
+
+    /// Blockers of the todo `id`, ordered by id.
+    pub fn blockers_of(&self, id: i32) -> Vec<i32> {
+        self.dependencies
+            .range((id, i32::MIN)..=(id, i32::MAX))
+            .map(|(_, blocker_id)| *blocker_id)
+            .collect()
+    }
+
+    /// Blockers of the todo `id` that are not completed, ordered by id.
+    pub fn open_blockers(&self, id: i32) -> Vec<i32> {
+        self.blockers_of(id)
+            .into_iter()
+            .filter(|blocker_id| {
+                self.todos
+                    .iter()
+                    .any(|t| t.todo_id == *blocker_id && t.completed != Some(true))
+            })
+            .collect()
+    }
+
+    /// Fails if setting `completed` completes the stored todo `id` while it
+    /// has open blockers.
+    pub fn check_unblocked(&self, id: i32, completed: Option<bool>) -> RepoResult<()> {
+        let completes = completed == Some(true)
+            && self
+                .todos
+                .iter()
+                .any(|t| t.todo_id == id && t.completed != Some(true));
+        let open = self.open_blockers(id);
+        if completes && !open.is_empty() {
+            return Err(blocked(&open));
+        }
+        Ok(())
+    }
+
+    /// Checks that the todo `blocker_id` may block the todo `blocked_id`, see
+    /// [`TodoRepo::add_blocker`].
+    pub fn check_blocker(&self, blocked_id: i32, blocker_id: i32) -> RepoResult<()> {
+        self.find(blocked_id)?;
+        self.find(blocker_id)
+            .map_err(|_| RepoError::Missing("blocker"))?;
+        check_dependency(blocked_id, blocker_id, |id| Ok(self.blockers_of(id)))
+    }
+
+    /// Adds or removes the dependency of the todo `blocked_id` on `blocker_id`.
+    pub fn set_blocker(&mut self, blocked_id: i32, blocker_id: i32, present: bool) {
+        if present {
+            self.dependencies.insert((blocked_id, blocker_id));
+        } else {
+            self.dependencies.remove(&(blocked_id, blocker_id));
+        }
+    }
+
+    /// Open todos without open blockers, of all lists or only of `list_id`,
+    /// ordered by id.
+    pub fn next_todos(&self, list_id: Option<i32>) -> Vec<Todo> {
+        let mut todos: Vec<Todo> = self
+            .todos
+            .iter()
+            .filter(|t| t.completed != Some(true))
+            .filter(|t| list_id.is_none_or(|list_id| t.list_id == list_id))
+            .filter(|t| self.open_blockers(t.todo_id).is_empty())
+            .map(|t| self.with_details(t.clone()))
+            .collect();
+        todos.sort_by_key(|todo| todo.todo_id);
+        todos
+    }
+
+    /// The page of todos selected by `query`.
+    pub fn query(&self, query: &TodoQuery) -> TodoPage {
+        let visible = |todo: &&Todo| {
+            query
+                .visible_to
+                .is_none_or(|user_id| self.todo_role(user_id, todo).is_some())
+        };
+        query.apply(
+            self.todos
+                .iter()
+                .filter(visible)
+                .map(|t| self.with_details(t.clone())),
+        )
+    }
+
+    /// `todo` with its tags, blockers and progress filled in.
+    pub fn with_details(&self, mut todo: Todo) -> Todo {
+        todo.tags = self.tags_of(todo.todo_id);
+        todo.blocked_by = self.blockers_of(todo.todo_id);
+        todo.progress = self.progress_of(todo.todo_id);
+        todo
+    }
+
+    /// Makes `tags` the tags of the todo `id`.
+    pub fn set_tags(&mut self, id: i32, tags: &[Tag]) {
+        self.todo_tags.retain(|(todo_id, _)| *todo_id != id);
+        self.todo_tags
+            .extend(tags.iter().map(|tag| (id, tag.tag_id)));
+    }
+
+    pub fn find_tag(&self, id: i32) -> RepoResult<Tag> {
+        self.tags
+            .iter()
+            .find(|tag| tag.tag_id == id)
+            .cloned()
+            .ok_or(RepoError::Missing("tag"))
+    }
+
+    /// Fails with a conflict if another tag than `id` of `owner_id` is called
+    /// `name`. Like `NULL`s in the unique index of the SQL backends, tags
+    /// without owner never conflict.
+    pub fn check_tag_name(
+        &self,
+        name: &str,
+        owner_id: Option<i32>,
+        id: Option<i32>,
+    ) -> RepoResult<()> {
+        if owner_id.is_none() {
+            return Ok(());
+        }
+        let name = name.to_lowercase();
+        match self.tags.iter().find(|tag| {
+            tag.owner_id == owner_id && tag.name.to_lowercase() == name && Some(tag.tag_id) != id
+        }) {
+            Some(tag) => Err(duplicate_tag(&tag.name)),
+            None => Ok(()),
+        }
+    }
+
+    /// All tags ordered by name, see [`TodoRepo::get_tags`] for `visible_to`.
+    pub fn visible_tags(&self, visible_to: Option<i32>) -> Vec<Tag> {
+        let mut tags: Vec<Tag> = match visible_to {
+            Some(user_id) => {
+                let attached: BTreeSet<i32> = self
+                    .todos
+                    .iter()
+                    .filter(|todo| self.todo_role(user_id, todo).is_some())
+                    .flat_map(|todo| {
+                        self.todo_tags
+                            .range((todo.todo_id, i32::MIN)..=(todo.todo_id, i32::MAX))
+                            .map(|(_, tag_id)| *tag_id)
+                    })
+                    .collect();
+                self.tags
+                    .iter()
+                    .filter(|tag| tag.owner_id == Some(user_id) || attached.contains(&tag.tag_id))
+                    .cloned()
+                    .collect()
+            }
+            None => self.tags.clone(),
+        };
+        sort_by_name(&mut tags);
+        tags
+    }
+
+    /// Adds or replaces `tag`.
+    pub fn put_tag(&mut self, tag: Tag) {
+        self.last_tag_id = self.last_tag_id.max(tag.tag_id);
+        match self.tags.iter_mut().find(|t| t.tag_id == tag.tag_id) {
+            Some(stored) => *stored = tag,
+            None => self.tags.push(tag),
+        }
+    }
+
+    /// Removes the tag `id` and detaches it everywhere, returns whether it existed.
+    pub fn remove_tag(&mut self, id: i32) -> bool {
+        let len = self.tags.len();
+        self.tags.retain(|tag| tag.tag_id != id);
+        self.todo_tags.retain(|(_, tag_id)| *tag_id != id);
+        self.tags.len() != len
+    }
+
+    /// The todo `todo_id` with the tag `tag_id` attached or detached, not yet stored.
+    pub fn retagged(&self, todo_id: i32, tag_id: i32, attach: bool) -> RepoResult<Todo> {
+        let mut todo = self.find(todo_id)?;
+        let tag = self.find_tag(tag_id)?;
+        todo.tags.retain(|t| t.tag_id != tag_id);
+        if attach {
+            todo.tags.push(tag);
+            sort_by_name(&mut todo.tags);
+        }
+        Ok(todo)
+    }
+
+    pub fn find_list(&self, id: i32) -> RepoResult<TodoList> {
+        self.lists
+            .iter()
+            .find(|list| list.list_id == id)
+            .cloned()
+            .ok_or(RepoError::Missing("list"))
+    }
+
+    /// Adds or replaces `list`.
+    pub fn put_list(&mut self, list: TodoList) {
+        self.last_list_id = self.last_list_id.max(list.list_id);
+        match self.lists.iter_mut().find(|l| l.list_id == list.list_id) {
+            Some(stored) => *stored = list,
+            None => self.lists.push(list),
+        }
+    }
+
+    /// Fails unless the list `id` may be deleted, see [`TodoRepo::delete_list_by_id`].
+    pub fn check_list_deletable(&self, id: i32, cascade: bool) -> RepoResult<()> {
+        if id == DEFAULT_LIST_ID {
+            return Err(default_list_kept());
+        }
+        self.find_list(id)?;
+        let count = self.todos.iter().filter(|t| t.list_id == id).count();
+        if count > 0 && !cascade {
+            return Err(list_not_empty(count as i64));
+        }
+        Ok(())
+    }
+
+    /// Removes the list `id` with all its todos, trashed ones included, returns
+    /// whether it existed.
+    pub fn remove_list(&mut self, id: i32) -> bool {
+        let todo_ids: Vec<i32> = self
+            .todos
+            .iter()
+            .filter(|t| t.list_id == id)
+            .map(|t| t.todo_id)
+            .collect();
+        for todo_id in todo_ids {
+            self.remove(todo_id);
+        }
+        let trashed: Vec<i32> = self
+            .trash
+            .iter()
+            .filter(|t| t.list_id == id)
+            .map(|t| t.todo_id)
+            .collect();
+        self.purge(&trashed);
+        self.list_shares.remove(&id);
+        let len = self.lists.len();
+        self.lists.retain(|list| list.list_id != id);
+        self.lists.len() != len
+    }
+
+    /// The role of the user `user_id` on `todo`, see [`share::todo_role`].
+    pub fn todo_role(&self, user_id: i32, todo: &Todo) -> Option<Role> {
+        let list = self.find_list(todo.list_id).ok()?;
+        share::todo_role(
+            user_id,
+            todo,
+            shares_of(&self.todo_shares, todo.todo_id),
+            &list,
+            shares_of(&self.list_shares, todo.list_id),
+        )
+    }
+
+    /// The user called `username`, ignoring case.
+    pub fn find_user(&self, username: &str) -> Option<&UserRecord> {
+        let username = username.to_lowercase();
+        self.users
+            .iter()
+            .find(|record| record.user.username.to_lowercase() == username)
+    }
+
+    pub fn find_user_by_id(&self, id: i32) -> RepoResult<User> {
+        self.users
+            .iter()
+            .find(|record| record.user.user_id == id)
+            .map(|record| record.user.clone())
+            .ok_or(RepoError::Missing("user"))
+    }
+
+    /// `user` with the next free id, fails if the username is taken.
+    pub fn new_user_record(&self, user: NewUser) -> RepoResult<UserRecord> {
+        if let Some(record) = self.find_user(&user.username) {
+            return Err(duplicate_user(&record.user.username));
+        }
+        Ok(user.with_id(self.last_user_id + 1, Local::now().naive_local()))
+    }
+
+    /// Adds `record`.
+    pub fn put_user(&mut self, record: UserRecord) {
+        self.last_user_id = self.last_user_id.max(record.user.user_id);
+        self.users.push(record);
+    }
+
+    /// `key` with the next free id, fails if its user does not exist.
+    pub fn new_api_key_record(&self, key: NewApiKeyRecord) -> RepoResult<ApiKeyRecord> {
+        self.find_user_by_id(key.user_id)?;
+        Ok(key.with_id(self.last_api_key_id + 1, Local::now().naive_local()))
+    }
+
+    /// Adds `record`.
+    pub fn put_api_key(&mut self, record: ApiKeyRecord) {
+        self.last_api_key_id = self.last_api_key_id.max(record.key.key_id);
+        self.api_keys.push(record);
+    }
+
+    /// The keys of the user `user_id` ordered by id.
+    pub fn api_keys_of(&self, user_id: i32) -> Vec<ApiKey> {
+        self.api_keys
+            .iter()
+            .filter(|record| record.key.user_id == user_id)
+            .map(|record| record.key.clone())
+            .collect()
+    }
+
+    pub fn find_api_key(&self, key_hash: &str) -> RepoResult<ApiKey> {
+        self.api_keys
+            .iter()
+            .find(|record| record.key_hash == key_hash)
+            .map(|record| record.key.clone())
+            .ok_or(RepoError::Missing("api key"))
+    }
+
+    /// Removes the key `key_id` of the user `user_id`, returns whether there was one.
+    pub fn remove_api_key(&mut self, user_id: i32, key_id: i32) -> bool {
+        let len = self.api_keys.len();
+        self.api_keys
+            .retain(|record| !(record.key.key_id == key_id && record.key.user_id == user_id));
+        self.api_keys.len() != len
+    }
+
+    /// `entries` with the next free ids.
+    pub fn new_history(&self, entries: Vec<NewHistoryEntry>) -> Vec<HistoryEntry> {
+        (self.last_history_id + 1..)
+            .zip(entries)
+            .map(|(id, entry)| entry.with_id(id))
+            .collect()
+    }
+
+    /// Appends `entries`, skipping the ones already recorded.
+    pub fn put_history(&mut self, entries: Vec<HistoryEntry>) {
+        for entry in entries {
+            if entry.history_id > self.last_history_id {
+                self.last_history_id = entry.history_id;
+                self.history.push(entry);
+            }
+        }
+    }
+}
+
+/// The shares of the todo or list `id` in `shares`, ordered by user id.
+pub fn shares_of(shares: &BTreeMap<i32, Vec<Share>>, id: i32) -> &[Share] {
+    shares.get(&id).map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Adds `share` to the shares of `id`, replacing an earlier role of the same user.
+pub fn put_share(shares: &mut BTreeMap<i32, Vec<Share>>, id: i32, share: Share) {
+    let entries = shares.entry(id).or_default();
+    entries.retain(|entry| entry.user_id != share.user_id);
+    entries.push(share);
+    entries.sort_by_key(|entry| entry.user_id);
+}
+
+/// Removes the share of `user_id` from `id`, returns the number of removed shares.
+pub fn remove_share(shares: &mut BTreeMap<i32, Vec<Share>>, id: i32, user_id: i32) -> usize {
+    let Some(entries) = shares.get_mut(&id) else {
+        return 0;
+    };
+    let len = entries.len();
+    entries.retain(|entry| entry.user_id != user_id);
+    let removed = len - entries.len();
+    if entries.is_empty() {
+        shares.remove(&id);
+    }
+    removed
+}
+
+/// Orders tags by name ignoring case, like the SQL backends do.
+pub fn sort_by_name(tags: &mut [Tag]) {
+    tags.sort_by_cached_key(|tag| (tag.name.to_lowercase(), tag.tag_id));
+}
+
+#[derive(Clone)]
+pub struct MemRepo {
+    pub inner: Arc<Mutex<MemStore>>,
+    /// Whether completing a todo with open blockers is rejected.
+    pub block_completion: bool,
+}
+
+impl Default for MemRepo {
+    fn default() -> Self {
+        MemRepo {
+            inner: Arc::default(),
+            block_completion: true,
+        }
     }
+}
 
-    async fn delete_todo_by_id(&self, id: i32) -> Option<usize> {
-        let mut v = self.inner.lock().unwrap();
-        if let Some(pos) = v.iter().position(|t| t.todo_id == id) {
-            v.remove(pos);
-            Some(pos)
+impl MemRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows or rejects completing todos with open blockers.
+    pub fn with_block_completion(mut self, block_completion: bool) -> Self {
+        self.block_completion = block_completion;
+        self
+    }
+
+    fn lock(&self) -> RepoResult<MutexGuard<'_, MemStore>> {
+        self.inner
+            .lock()
+            .map_err(|_| RepoError::Internal("in-memory store is poisoned".to_string()))
+    }
+
+    fn insert_todo(
+        &self,
+        store: &mut MemStore,
+        todo: NewTodo,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Todo> {
+        todo.validate()?;
+        store.check_placement(None, todo.list_id, todo.parent_id)?;
+        let todo = store.insert(todo);
+        let todo = store.with_details(todo);
+        changes.push(Change::created(todo.clone()));
+        Ok(todo)
+    }
+
+    /// Replaces the todo `id` with what `change` makes of it, see
+    /// [`TodoRepo::update_todo_if_version`].
+    fn replace_todo(
+        &self,
+        store: &mut MemStore,
+        id: i32,
+        version: Option<i32>,
+        changes: &mut Vec<Change>,
+        change: impl FnOnce(&Todo) -> RepoResult<Todo>,
+    ) -> RepoResult<Todo> {
+        let previous = store.find(id)?;
+        check_version(&previous, version)?;
+        let todo = change(&previous)?;
+        if self.block_completion {
+            store.check_unblocked(id, todo.completed)?;
+        }
+        let todo = store.replace(id, todo, changes)?;
+        store.roll_up(previous.parent_id, self.block_completion, changes)?;
+        store.roll_up(todo.parent_id, self.block_completion, changes)?;
+        store.find(id)
+    }
+
+    fn trash_todo(
+        &self,
+        store: &mut MemStore,
+        id: i32,
+        version: Option<i32>,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<usize> {
+        let todo = store.find(id)?;
+        check_version(&todo, version)?;
+        let parent_id = todo.parent_id;
+        let trashed = store
+            .subtree_ids(id)
+            .into_iter()
+            .map(|todo_id| store.find(todo_id))
+            .collect::<RepoResult<Vec<_>>>()?;
+        let count = store.trash(id, Local::now().naive_local());
+        changes.extend(trashed.into_iter().map(Change::deleted));
+        store.roll_up(parent_id, self.block_completion, changes)?;
+        Ok(count)
+    }
+
+    fn restore(
+        &self,
+        store: &mut MemStore,
+        id: i32,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Todo> {
+        let ids = store.restorable(id)?;
+        store.restore(&ids);
+        for todo_id in &ids {
+            changes.push(Change::restored(store.find(*todo_id)?));
+        }
+        let todo = store.find(id)?;
+        store.roll_up(todo.parent_id, self.block_completion, changes)?;
+        store.find(id)
+    }
+
+    fn set_blocker(
+        &self,
+        store: &mut MemStore,
+        id: i32,
+        blocker_id: i32,
+        present: bool,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Todo> {
+        let previous = store.find(id)?;
+        if present {
+            store.check_blocker(id, blocker_id)?;
         } else {
-            None
+            store
+                .find(blocker_id)
+                .map_err(|_| RepoError::Missing("blocker"))?;
+        }
+        store.set_blocker(id, blocker_id, present);
+        let todo = store.find(id)?;
+        changes.push(Change::updated(previous, todo.clone()));
+        Ok(todo)
+    }
+
+    fn retag(
+        &self,
+        store: &mut MemStore,
+        todo_id: i32,
+        tag_id: i32,
+        attach: bool,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Todo> {
+        let previous = store.find(todo_id)?;
+        let todo = store.retagged(todo_id, tag_id, attach)?;
+        store.set_tags(todo_id, &todo.tags);
+        changes.push(Change::updated(previous, todo.clone()));
+        Ok(todo)
+    }
+
+    /// Applies `mutation` to the locked `store`, batches go through
+    /// [`apply_one_by_one`] before.
+    fn write(
+        &self,
+        store: &mut MemStore,
+        mutation: Mutation,
+        changes: &mut Vec<Change>,
+    ) -> RepoResult<Outcome> {
+        let outcome = match mutation {
+            Mutation::Create { todo } => Outcome::Todo(self.insert_todo(store, todo, changes)?),
+            Mutation::Update { id, todo, version } => {
+                todo.validate()?;
+                Outcome::Todo(self.replace_todo(store, id, version, changes, |_| Ok(todo))?)
+            }
+            Mutation::Patch { id, patch, version } => {
+                patch.validate()?;
+                let todo = self.replace_todo(store, id, version, changes, |previous| {
+                    patch.validate_against(previous)?;
+                    let mut todo = previous.clone();
+                    patch.apply_to(&mut todo);
+                    Ok(todo)
+                })?;
+                Outcome::Todo(todo)
+            }
+            Mutation::Delete { id, version } => {
+                Outcome::Count(self.trash_todo(store, id, version, changes)?)
+            }
+            Mutation::Restore { id } => Outcome::Todo(self.restore(store, id, changes)?),
+            Mutation::Purge { id } => {
+                let ids = store.trashed_ids(id, None)?;
+                Outcome::Count(purge(store, &ids, changes))
+            }
+            Mutation::PurgeTrash { before } => {
+                let ids = store.trashed_before(before);
+                Outcome::Count(purge(store, &ids, changes))
+            }
+            Mutation::Batch { .. } => unreachable!("batches are applied one by one"),
+            Mutation::AddBlocker { id, blocker_id } => {
+                Outcome::Todo(self.set_blocker(store, id, blocker_id, true, changes)?)
+            }
+            Mutation::RemoveBlocker { id, blocker_id } => {
+                Outcome::Todo(self.set_blocker(store, id, blocker_id, false, changes)?)
+            }
+            Mutation::AttachTag { todo_id, tag_id } => {
+                Outcome::Todo(self.retag(store, todo_id, tag_id, true, changes)?)
+            }
+            Mutation::DetachTag { todo_id, tag_id } => {
+                Outcome::Todo(self.retag(store, todo_id, tag_id, false, changes)?)
+            }
+            Mutation::DeleteList { id, cascade } => {
+                store.check_list_deletable(id, cascade)?;
+                let purged: Vec<Todo> = store
+                    .todos
+                    .iter()
+                    .chain(&store.trash)
+                    .filter(|t| t.list_id == id)
+                    .map(|t| store.with_details(t.clone()))
+                    .collect();
+                store.remove_list(id);
+                changes.extend(purged.into_iter().map(Change::purged));
+                Outcome::Count(1)
+            }
+        };
+        Ok(outcome)
+    }
+}
+
+/// Purges the trashed todos `ids` from `store` and adds them to `changes`.
+fn purge(store: &mut MemStore, ids: &[i32], changes: &mut Vec<Change>) -> usize {
+    let purged = store.trashed_todos(ids);
+    let count = store.purge(ids);
+    changes.extend(purged.into_iter().map(Change::purged));
+    count
+}
+
+#[async_trait]
+impl TodoRepo for MemRepo {
+    async fn query_todos(&self, query: TodoQuery) -> RepoResult<TodoPage> {
+        Ok(self.lock()?.query(&query))
+    }
+
+    async fn mutate(&self, mutation: Mutation, actor: Option<Actor>) -> RepoResult<Mutated> {
+        let mutation = match mutation {
+            Mutation::Batch { ops } => return Ok(apply_one_by_one(self, ops, actor).await),
+            mutation => mutation,
+        };
+        let mut store = self.lock()?;
+        let mut changes = Vec::new();
+        let outcome = self.write(&mut store, mutation, &mut changes)?;
+        if let Some(actor) = actor {
+            let entries = store.new_history(actor.history(&changes));
+            store.put_history(entries);
         }
+        Ok(Mutated::new(outcome, changes))
+    }
+
+    async fn get_todo_by_id(&self, id: i32) -> RepoResult<Todo> {
+        self.lock()?.find(id)
+    }
+
+    async fn get_trash(&self, owner_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        Ok(self.lock()?.trashed(owner_id))
+    }
+
+    async fn get_descendants(&self, id: i32) -> RepoResult<Vec<Todo>> {
+        self.lock()?.descendants(id)
+    }
+
+    async fn get_next_todos(&self, list_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        let store = self.lock()?;
+        if let Some(list_id) = list_id {
+            store.find_list(list_id)?;
+        }
+        Ok(store.next_todos(list_id))
+    }
+
+    async fn get_tags(&self, visible_to: Option<i32>) -> RepoResult<Vec<Tag>> {
+        Ok(self.lock()?.visible_tags(visible_to))
+    }
+
+    async fn create_tag(&self, tag: NewTag) -> RepoResult<Tag> {
+        tag.validate()?;
+        let mut store = self.lock()?;
+        store.check_tag_name(&tag.name, tag.owner_id, None)?;
+        let tag = tag.with_id(store.last_tag_id + 1);
+        store.put_tag(tag.clone());
+        Ok(tag)
+    }
+
+    async fn get_tag_by_id(&self, id: i32) -> RepoResult<Tag> {
+        self.lock()?.find_tag(id)
+    }
+
+    async fn update_tag_by_id(&self, id: i32, tag: NewTag) -> RepoResult<Tag> {
+        tag.validate()?;
+        let mut store = self.lock()?;
+        let owner_id = store.find_tag(id)?.owner_id;
+        store.check_tag_name(&tag.name, owner_id, Some(id))?;
+        let tag = NewTag { owner_id, ..tag }.with_id(id);
+        store.put_tag(tag.clone());
+        Ok(tag)
+    }
+
+    async fn delete_tag_by_id(&self, id: i32) -> RepoResult<usize> {
+        if !self.lock()?.remove_tag(id) {
+            return Err(RepoError::Missing("tag"));
+        }
+        Ok(1)
+    }
+
+    async fn get_lists(&self) -> RepoResult<Vec<TodoList>> {
+        let mut lists = self.lock()?.lists.clone();
+        lists.sort_by_key(|list| list.list_id);
+        Ok(lists)
+    }
+
+    async fn create_list(&self, list: NewList) -> RepoResult<TodoList> {
+        list.validate()?;
+        let mut store = self.lock()?;
+        let list = list.with_id(store.last_list_id + 1);
+        store.put_list(list.clone());
+        Ok(list)
     }
 
-    async fn update_todo_by_id(&self, id: i32, todo: Todo) -> Option<Todo> {
-        let mut v = self.inner.lock().unwrap();
-        if let Some(pos) = v.iter().position(|t| t.todo_id == id) {
-            v[pos] = todo.clone();
-            Some(todo)
+    async fn get_list_by_id(&self, id: i32) -> RepoResult<TodoList> {
+        self.lock()?.find_list(id)
+    }
+
+    async fn update_list_by_id(&self, id: i32, list: NewList) -> RepoResult<TodoList> {
+        list.validate()?;
+        let mut store = self.lock()?;
+        let owner_id = store.find_list(id)?.owner_id;
+        let list = NewList { owner_id, ..list }.with_id(id);
+        store.put_list(list.clone());
+        Ok(list)
+    }
+
+    async fn get_todo_shares(&self, todo_id: i32) -> RepoResult<Vec<Share>> {
+        let store = self.lock()?;
+        store.find(todo_id)?;
+        Ok(shares_of(&store.todo_shares, todo_id).to_vec())
+    }
+
+    async fn share_todo(&self, todo_id: i32, share: Share) -> RepoResult<Share> {
+        let mut store = self.lock()?;
+        store.find(todo_id)?;
+        store.find_user_by_id(share.user_id)?;
+        put_share(&mut store.todo_shares, todo_id, share);
+        Ok(share)
+    }
+
+    async fn unshare_todo(&self, todo_id: i32, user_id: i32) -> RepoResult<usize> {
+        let mut store = self.lock()?;
+        store.find(todo_id)?;
+        Ok(remove_share(&mut store.todo_shares, todo_id, user_id))
+    }
+
+    async fn get_list_shares(&self, list_id: i32) -> RepoResult<Vec<Share>> {
+        let store = self.lock()?;
+        store.find_list(list_id)?;
+        Ok(shares_of(&store.list_shares, list_id).to_vec())
+    }
+
+    async fn share_list(&self, list_id: i32, share: Share) -> RepoResult<Share> {
+        let mut store = self.lock()?;
+        store.find_list(list_id)?;
+        store.find_user_by_id(share.user_id)?;
+        put_share(&mut store.list_shares, list_id, share);
+        Ok(share)
+    }
+
+    async fn unshare_list(&self, list_id: i32, user_id: i32) -> RepoResult<usize> {
+        let mut store = self.lock()?;
+        store.find_list(list_id)?;
+        Ok(remove_share(&mut store.list_shares, list_id, user_id))
+    }
+
+    async fn create_user(&self, user: NewUser) -> RepoResult<User> {
+        let mut store = self.lock()?;
+        let record = store.new_user_record(user)?;
+        store.put_user(record.clone());
+        Ok(record.user)
+    }
+
+    async fn get_user_by_id(&self, id: i32) -> RepoResult<User> {
+        self.lock()?.find_user_by_id(id)
+    }
+
+    async fn get_user_record(&self, username: &str) -> RepoResult<UserRecord> {
+        self.lock()?
+            .find_user(username)
+            .cloned()
+            .ok_or(RepoError::Missing("user"))
+    }
+
+    async fn create_api_key(&self, key: NewApiKeyRecord) -> RepoResult<ApiKey> {
+        let mut store = self.lock()?;
+        let record = store.new_api_key_record(key)?;
+        store.put_api_key(record.clone());
+        Ok(record.key)
+    }
+
+    async fn get_api_keys(&self, user_id: i32) -> RepoResult<Vec<ApiKey>> {
+        Ok(self.lock()?.api_keys_of(user_id))
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> RepoResult<ApiKey> {
+        self.lock()?.find_api_key(key_hash)
+    }
+
+    async fn delete_api_key(&self, user_id: i32, key_id: i32) -> RepoResult<usize> {
+        if self.lock()?.remove_api_key(user_id, key_id) {
+            Ok(1)
         } else {
-            None
+            Err(RepoError::NotFound)
         }
     }
+
+    async fn record_history(&self, entries: Vec<NewHistoryEntry>) -> RepoResult<()> {
+        let mut store = self.lock()?;
+        let entries = store.new_history(entries);
+        store.put_history(entries);
+        Ok(())
+    }
+
+    async fn get_history(&self, query: HistoryQuery) -> RepoResult<Vec<HistoryEntry>> {
+        Ok(query.apply(&self.lock()?.history))
+    }
 }
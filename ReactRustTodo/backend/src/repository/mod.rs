@@ -1,8 +1,251 @@
+pub mod audit_repo;
+pub mod error;
+pub mod event_repo;
+pub mod file_repo;
 pub mod mem_repo;
+pub mod mutation;
 pub mod mysql_repo;
+pub mod owned_repo;
+#[cfg(feature = "postgres")]
+pub mod postgres_repo;
+#[cfg(feature = "postgres")]
+pub mod postgres_schema;
 pub mod schema;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_repo;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_schema;
 pub mod todo_repo;
 
 use std::sync::Arc;
+
+use diesel::{
+    r2d2::{self, ConnectionManager},
+    MysqlConnection,
+};
+
+use crate::validation::FieldError;
+use error::{RepoError, RepoResult};
+use mysql_repo::MysqlRepo;
 use todo_repo::TodoRepo;
 pub type RepoBox = Arc<dyn TodoRepo>;
+
+/// Connections per pool unless configured otherwise, same as r2d2's default.
+pub const DEFAULT_POOL_SIZE: u32 = 10;
+
+/// Settings every backend understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepoOptions {
+    /// Maximum number of connections for SQL backends.
+    pub pool_size: u32,
+    /// Whether completing a todo with open blockers is rejected.
+    pub block_completion: bool,
+}
+
+impl Default for RepoOptions {
+    fn default() -> Self {
+        RepoOptions {
+            pool_size: DEFAULT_POOL_SIZE,
+            block_completion: true,
+        }
+    }
+}
+
+/// Opens the repository `database_url` points to, chosen by its scheme.
+///
+/// `mysql://` and `file://` (a data directory for [`file_repo::FileRepo`]) are
+/// always available, `sqlite://` needs the `sqlite` feature and `postgres://`
+/// (or `postgresql://`) the `postgres` feature.
+pub fn connect(database_url: &str) -> RepoResult<RepoBox> {
+    connect_with_options(database_url, RepoOptions::default())
+}
+
+/// Like [`connect`], with `options` instead of the defaults.
+pub fn connect_with_options(database_url: &str, options: RepoOptions) -> RepoResult<RepoBox> {
+    let RepoOptions {
+        pool_size,
+        block_completion,
+    } = options;
+    let scheme = database_url.split("://").next().unwrap_or_default();
+    match scheme {
+        "mysql" => {
+            let manager = ConnectionManager::<MysqlConnection>::new(database_url);
+            let pool = r2d2::Pool::builder().max_size(pool_size).build(manager)?;
+            Ok(Arc::new(MysqlRepo {
+                pool,
+                block_completion,
+            }))
+        }
+        "file" => Ok(Arc::new(
+            file_repo::FileRepo::new(database_url.trim_start_matches("file://"))?
+                .with_block_completion(block_completion),
+        )),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Ok(Arc::new(
+            sqlite_repo::SqliteRepo::with_pool_size(database_url, pool_size)?
+                .with_block_completion(block_completion),
+        )),
+        #[cfg(not(feature = "sqlite"))]
+        "sqlite" => Err(RepoError::Unavailable(
+            "SQLite support is not compiled in, rebuild with `--features sqlite`".to_string(),
+        )),
+        #[cfg(feature = "postgres")]
+        "postgres" | "postgresql" => Ok(Arc::new(
+            postgres_repo::PostgresRepo::with_pool_size(database_url, pool_size)?
+                .with_block_completion(block_completion),
+        )),
+        #[cfg(not(feature = "postgres"))]
+        "postgres" | "postgresql" => Err(RepoError::Unavailable(
+            "PostgreSQL support is not compiled in, rebuild with `--features postgres`".to_string(),
+        )),
+        other => Err(RepoError::Unavailable(format!(
+            "unsupported database scheme `{other}`"
+        ))),
+    }
+}
+
+/// Escapes `%`, `_` and `\` so user input is matched literally inside `LIKE`.
+///
+/// The SQL backends use `\` as escape character for their `LIKE` filters.
+pub(crate) fn like_pattern(needle: &str) -> String {
+    let escaped = needle
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+/// Conflict returned when a tag name is taken.
+pub(crate) fn duplicate_tag(name: &str) -> RepoError {
+    RepoError::Conflict(format!("a tag named `{name}` already exists"))
+}
+
+/// Maps the unique violation of an insert or rename of tag `name` to [`duplicate_tag`].
+pub(crate) fn tag_error(name: &str) -> impl FnOnce(diesel::result::Error) -> RepoError + '_ {
+    move |err| match RepoError::from(err) {
+        RepoError::Conflict(_) => duplicate_tag(name),
+        other => other,
+    }
+}
+
+/// Conflict returned when a username is taken.
+pub(crate) fn duplicate_user(username: &str) -> RepoError {
+    RepoError::Conflict(format!("a user named `{username}` already exists"))
+}
+
+/// Maps the unique violation of registering `username` to [`duplicate_user`].
+pub(crate) fn user_error(username: &str) -> impl FnOnce(diesel::result::Error) -> RepoError + '_ {
+    move |err| match RepoError::from(err) {
+        RepoError::Conflict(_) => duplicate_user(username),
+        other => other,
+    }
+}
+
+/// Conflict returned when deleting the default list.
+pub(crate) fn default_list_kept() -> RepoError {
+    RepoError::Conflict("the default list cannot be deleted".to_string())
+}
+
+/// Conflict returned when deleting a list that has `count` todos without cascading.
+pub(crate) fn list_not_empty(count: i64) -> RepoError {
+    RepoError::Conflict(format!(
+        "the list still has {count} todo(s), delete with `cascade=true` to remove them too"
+    ))
+}
+
+/// Conflict returned when restoring a todo whose parent is in the trash.
+pub(crate) fn parent_trashed() -> RepoError {
+    RepoError::Conflict("the parent of the todo is in the trash, restore it first".to_string())
+}
+
+/// Validation error on a single field, for rules that need stored data.
+pub(crate) fn invalid(field: &str, code: &str, message: &str) -> RepoError {
+    RepoError::Validation(vec![FieldError::new(field, code, message)])
+}
+
+/// Validation error when a todo with subtasks would move to another list.
+pub(crate) fn subtasks_stay() -> RepoError {
+    invalid(
+        "list_id",
+        "has_subtasks",
+        "a todo with subtasks cannot move to another list",
+    )
+}
+
+/// Checks that `parent_id` may become the parent of the todo `id` (`None` for
+/// a new todo) in the list `list_id`.
+///
+/// `lookup` returns `parent_id` and `list_id` of a stored todo. Fails with
+/// `Missing("parent")` for an unknown parent and with a validation error for a
+/// parent in another list or one that would close a cycle.
+pub(crate) fn check_parent(
+    id: Option<i32>,
+    list_id: i32,
+    parent_id: i32,
+    mut lookup: impl FnMut(i32) -> RepoResult<Option<(Option<i32>, i32)>>,
+) -> RepoResult<()> {
+    let cycle = || {
+        invalid(
+            "parent_id",
+            "cycle",
+            "a todo cannot be a subtask of itself or of its own subtasks",
+        )
+    };
+    if id == Some(parent_id) {
+        return Err(cycle());
+    }
+    let Some((mut ancestor, parent_list)) = lookup(parent_id)? else {
+        return Err(RepoError::Missing("parent"));
+    };
+    if parent_list != list_id {
+        return Err(invalid(
+            "parent_id",
+            "other_list",
+            "the parent must be in the same list",
+        ));
+    }
+    while let Some(current) = ancestor {
+        if id == Some(current) {
+            return Err(cycle());
+        }
+        ancestor = lookup(current)?.and_then(|(parent, _)| parent);
+    }
+    Ok(())
+}
+
+/// Conflict returned when completing a todo while the todos `blockers` are open.
+pub(crate) fn blocked(blockers: &[i32]) -> RepoError {
+    let ids: Vec<String> = blockers.iter().map(i32::to_string).collect();
+    RepoError::Conflict(format!(
+        "the todo is blocked by the open todo(s) {}, complete them first",
+        ids.join(", ")
+    ))
+}
+
+/// Checks that the todo `blocker_id` may become a blocker of the todo
+/// `blocked_id`.
+///
+/// `blockers_of` returns the blockers of a stored todo. Fails with a validation
+/// error if `blocked_id` already blocks `blocker_id`, directly or through other
+/// todos, or if both are the same todo.
+pub(crate) fn check_dependency(
+    blocked_id: i32,
+    blocker_id: i32,
+    mut blockers_of: impl FnMut(i32) -> RepoResult<Vec<i32>>,
+) -> RepoResult<()> {
+    let mut seen = std::collections::HashSet::new();
+    let mut pending = vec![blocker_id];
+    while let Some(current) = pending.pop() {
+        if current == blocked_id {
+            return Err(invalid(
+                "blocker_id",
+                "cycle",
+                "a todo cannot be blocked by itself or by a todo it blocks",
+            ));
+        }
+        if seen.insert(current) {
+            pending.extend(blockers_of(current)?);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use chrono::{Local, NaiveDateTime};
+
+use super::error::{RepoError, RepoResult};
+use crate::models::batch::{BatchOp, BatchOutcome};
+use crate::models::history::{HistoryAction, NewHistoryEntry};
+use crate::models::todo::{NewTodo, Todo, TodoPatch};
+
+/// A write of todos, one for each method of [`TodoRepo`](super::todo_repo::TodoRepo)
+/// that changes todos, see [`TodoRepo::mutate`](super::todo_repo::TodoRepo::mutate).
+pub enum Mutation {
+    Create {
+        todo: NewTodo,
+    },
+    Update {
+        id: i32,
+        todo: Todo,
+        version: Option<i32>,
+    },
+    Patch {
+        id: i32,
+        patch: TodoPatch,
+        version: Option<i32>,
+    },
+    /// Moves the todo with its subtasks to the trash.
+    Delete {
+        id: i32,
+        version: Option<i32>,
+    },
+    Restore {
+        id: i32,
+    },
+    Purge {
+        id: i32,
+    },
+    PurgeTrash {
+        before: NaiveDateTime,
+    },
+    Batch {
+        ops: Vec<BatchOp>,
+    },
+    AddBlocker {
+        id: i32,
+        blocker_id: i32,
+    },
+    RemoveBlocker {
+        id: i32,
+        blocker_id: i32,
+    },
+    AttachTag {
+        todo_id: i32,
+        tag_id: i32,
+    },
+    DetachTag {
+        todo_id: i32,
+        tag_id: i32,
+    },
+    DeleteList {
+        id: i32,
+        cascade: bool,
+    },
+}
+
+impl From<BatchOp> for Mutation {
+    /// The single write an operation of a batch stands for.
+    fn from(op: BatchOp) -> Self {
+        let patch = op.patch();
+        match op {
+            BatchOp::Create { todo } => Mutation::Create { todo },
+            BatchOp::Update { id, version, .. } | BatchOp::Complete { id, version } => {
+                Mutation::Patch {
+                    id,
+                    patch: patch.unwrap_or_default(),
+                    version,
+                }
+            }
+            BatchOp::Delete { id, version } => Mutation::Delete { id, version },
+        }
+    }
+}
+
+/// What the method a [`Mutation`] stands for returns.
+// Nearly every outcome is a todo, boxing it would only add allocations.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum Outcome {
+    /// The created or changed todo.
+    Todo(Todo),
+    /// The number of deleted todos, or of deleted lists.
+    Count(usize),
+    /// One result per operation of a batch.
+    Batch(Vec<RepoResult<BatchOutcome>>),
+}
+
+impl Outcome {
+    /// The outcome of a single operation of a batch.
+    pub fn into_batch_outcome(self) -> RepoResult<BatchOutcome> {
+        match self {
+            Outcome::Todo(todo) => Ok(BatchOutcome::Todo(todo)),
+            Outcome::Count(count) => Ok(BatchOutcome::Deleted(count)),
+            Outcome::Batch(_) => Err(unexpected()),
+        }
+    }
+}
+
+fn unexpected() -> RepoError {
+    RepoError::Internal("a mutation returned an unexpected outcome".to_string())
+}
+
+/// A todo a mutation created, changed, trashed, restored or purged.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub action: HistoryAction,
+    /// The todo before, `None` if it was created or restored.
+    pub before: Option<Todo>,
+    /// The todo after, `None` if it was trashed or purged.
+    pub after: Option<Todo>,
+}
+
+impl Change {
+    pub fn created(todo: Todo) -> Self {
+        Change {
+            action: HistoryAction::Created,
+            before: None,
+            after: Some(todo),
+        }
+    }
+
+    pub fn updated(before: Todo, after: Todo) -> Self {
+        Change {
+            action: HistoryAction::Updated,
+            before: Some(before),
+            after: Some(after),
+        }
+    }
+
+    pub fn deleted(todo: Todo) -> Self {
+        Change {
+            action: HistoryAction::Deleted,
+            before: Some(todo),
+            after: None,
+        }
+    }
+
+    pub fn restored(todo: Todo) -> Self {
+        Change {
+            action: HistoryAction::Restored,
+            before: None,
+            after: Some(todo),
+        }
+    }
+
+    pub fn purged(todo: Todo) -> Self {
+        Change {
+            action: HistoryAction::Purged,
+            before: Some(todo),
+            after: None,
+        }
+    }
+}
+
+/// Who makes a mutation. Given one, [`TodoRepo::mutate`](super::todo_repo::TodoRepo::mutate)
+/// records the changes in the audit log together with the mutation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Actor {
+    /// The user, `None` for the server itself.
+    pub user_id: Option<i32>,
+}
+
+impl Actor {
+    /// The audit log entries for `changes`, made now.
+    pub fn history(self, changes: &[Change]) -> Vec<NewHistoryEntry> {
+        let now = Local::now().naive_local();
+        changes
+            .iter()
+            .filter_map(|change| {
+                NewHistoryEntry::new(
+                    change.action,
+                    self.user_id,
+                    now,
+                    change.before.clone(),
+                    change.after.clone(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// What [`TodoRepo::mutate`](super::todo_repo::TodoRepo::mutate) did.
+#[derive(Debug)]
+pub struct Mutated {
+    pub outcome: Outcome,
+    /// Every todo the mutation touched in the order it did, also those changed
+    /// as a side effect: parents completed along with their last subtask and
+    /// the next instance of a completed recurring todo.
+    pub changes: Vec<Change>,
+}
+
+impl Mutated {
+    pub fn new(outcome: Outcome, changes: Vec<Change>) -> Self {
+        Mutated { outcome, changes }
+    }
+
+    /// The todo a create or an update returns.
+    pub fn todo(self) -> RepoResult<Todo> {
+        match self.outcome {
+            Outcome::Todo(todo) => Ok(todo),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// The number a deletion returns.
+    pub fn count(self) -> RepoResult<usize> {
+        match self.outcome {
+            Outcome::Count(count) => Ok(count),
+            _ => Err(unexpected()),
+        }
+    }
+
+    /// The results of a batch.
+    pub fn batch(self) -> RepoResult<Vec<RepoResult<BatchOutcome>>> {
+        match self.outcome {
+            Outcome::Batch(results) => Ok(results),
+            _ => Err(unexpected()),
+        }
+    }
+}
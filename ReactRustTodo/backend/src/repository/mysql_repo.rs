@@ -1,69 +1,1208 @@
 use async_trait::async_trait;
+use chrono::{Local, NaiveDateTime};
+use diesel::dsl;
+use diesel::mysql::Mysql;
 use diesel::prelude::*;
-use diesel::r2d2::{ConnectionManager, Pool};
-use dotenvy::dotenv;
-use std::fmt::Error;
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 
-// putting self into the use statement is a shorthand for:
-// use diesel::r2d2;
-
-use crate::models::todo::{NewTodo, Todo};
+use crate::models::api_key::{ApiKey, NewApiKeyRecord, Scope};
+use crate::models::batch::{BatchOp, BatchOutcome};
+use crate::models::history::{HistoryEntry, HistoryQuery, NewHistoryEntry};
+use crate::models::list::{NewList, TodoList, DEFAULT_LIST_ID};
+use crate::models::query::{SortField, SortOrder, TagMode, TodoPage, TodoQuery};
+use crate::models::share::Share;
+use crate::models::tag::{NewTag, Tag};
+use crate::models::todo::{NewTodo, Todo, TodoPatch};
+use crate::models::tree::Progress;
+use crate::models::user::{NewUser, User, UserRecord};
+use crate::recurrence::follow_up;
+use crate::repository::error::{RepoError, RepoResult};
+use crate::repository::mutation::{Actor, Change, Mutated, Mutation, Outcome};
 use crate::repository::schema::todos::dsl::*;
-use crate::repository::todo_repo::TodoRepo;
+use crate::repository::schema::{
+    api_keys, list_shares, lists, tags, todo_dependencies, todo_history, todo_shares, todo_tags,
+    todos, users,
+};
+use crate::repository::todo_repo::{check_version, TodoRepo};
+use crate::repository::{
+    blocked, check_dependency, check_parent, default_list_kept, like_pattern, list_not_empty,
+    parent_trashed, subtasks_stay, tag_error, user_error,
+};
+use crate::validation::Validate;
 
 pub struct MysqlRepo {
     pub pool: Pool<ConnectionManager<MysqlConnection>>,
+    /// Whether completing a todo with open blockers is rejected.
+    pub block_completion: bool,
+}
+
+impl MysqlRepo {
+    fn conn(&self) -> RepoResult<PooledConnection<ConnectionManager<MysqlConnection>>> {
+        Ok(self.pool.get()?)
+    }
+}
+
+define_sql_function!(fn last_insert_id() -> Unsigned<BigInt>);
+
+/// The id `LAST_INSERT_ID()` reports for the previous insert on `conn`.
+fn inserted_id(conn: &mut MysqlConnection) -> RepoResult<i32> {
+    let id = diesel::select(last_insert_id()).get_result::<u64>(conn)?;
+    i32::try_from(id)
+        .map_err(|_| RepoError::Internal(format!("inserted id {id} does not fit into an INT")))
+}
+
+/// Columns of [`User`], everything but the password hash.
+const USER_COLUMNS: (users::user_id, users::username, users::created_at) =
+    (users::user_id, users::username, users::created_at);
+
+/// Columns of [`ApiKey`], everything but the hash of the secret.
+const API_KEY_COLUMNS: (
+    api_keys::key_id,
+    api_keys::user_id,
+    api_keys::name,
+    api_keys::prefix,
+    api_keys::scopes,
+    api_keys::expires_at,
+    api_keys::created_at,
+) = (
+    api_keys::key_id,
+    api_keys::user_id,
+    api_keys::name,
+    api_keys::prefix,
+    api_keys::scopes,
+    api_keys::expires_at,
+    api_keys::created_at,
+);
+
+fn find_user(conn: &mut MysqlConnection, id: i32) -> RepoResult<User> {
+    users::table
+        .find(id)
+        .select(USER_COLUMNS)
+        .get_result::<User>(conn)
+        .optional()?
+        .ok_or(RepoError::Missing("user"))
+}
+
+fn find_list(conn: &mut MysqlConnection, id: i32) -> RepoResult<TodoList> {
+    lists::table
+        .find(id)
+        .get_result::<TodoList>(conn)
+        .optional()?
+        .ok_or(RepoError::Missing("list"))
+}
+
+/// Inserts `todo` and reads it back, to be called inside a transaction.
+fn insert(conn: &mut MysqlConnection, todo: &NewTodo) -> RepoResult<Todo> {
+    // LAST_INSERT_ID() is tracked per connection, so concurrent inserts on
+    // other connections cannot make us read back someone else's row.
+    diesel::insert_into(todos).values(todo).execute(conn)?;
+    let id = inserted_id(conn)?;
+    Ok(find_live(id).get_result::<Todo>(conn)?)
+}
+
+/// Creates the next instance if the update of `previous` completed a recurring
+/// todo, see [`follow_up`], and adds the update and the new instance to `changes`.
+fn finish_update(
+    conn: &mut MysqlConnection,
+    previous: Todo,
+    updated: Todo,
+    changes: &mut Vec<Change>,
+) -> RepoResult<Todo> {
+    let Some(next) = follow_up(&previous, &updated, Local::now().naive_local()) else {
+        let updated = with_details(conn, updated)?;
+        changes.push(Change::updated(previous, updated.clone()));
+        return Ok(updated);
+    };
+    let next = insert(conn, &next)?;
+    let tag_ids = todo_tags::table
+        .filter(todo_tags::todo_id.eq(updated.todo_id))
+        .select(todo_tags::tag_id)
+        .load::<i32>(conn)?;
+    for id in tag_ids {
+        diesel::insert_into(todo_tags::table)
+            .values((
+                todo_tags::todo_id.eq(next.todo_id),
+                todo_tags::tag_id.eq(id),
+            ))
+            .execute(conn)?;
+    }
+    let target = todos.find(updated.todo_id);
+    diesel::update(target)
+        .set(recurrence.eq(None::<String>))
+        .execute(conn)?;
+    let updated = target.get_result::<Todo>(conn)?;
+    let updated = with_details(conn, updated)?;
+    changes.push(Change::updated(previous, updated.clone()));
+    changes.push(Change::created(with_details(conn, next)?));
+    Ok(updated)
+}
+
+/// The todo `id` unless it is in the trash, for reads instead of `todos.find(id)`.
+#[dsl::auto_type(no_type_alias)]
+fn find_live(id: i32) -> _ {
+    todos::table
+        .filter(todo_id.eq(id))
+        .filter(deleted_at.is_null())
+}
+
+/// The todo `id` if it is in the trash.
+#[dsl::auto_type(no_type_alias)]
+fn find_trashed(id: i32) -> _ {
+    todos::table
+        .filter(todo_id.eq(id))
+        .filter(deleted_at.is_not_null())
+}
+
+/// Builds the filtered (but unsorted and unpaginated) query for `query`.
+fn filtered(query: &TodoQuery) -> todos::BoxedQuery<'static, Mysql> {
+    let mut q = todos::table.filter(deleted_at.is_null()).into_boxed();
+    if let Some(owner) = query.owner_id {
+        q = q.filter(owner_id.eq(owner));
+    }
+    if let Some(user) = query.visible_to {
+        // Mirrors `share::todo_role`: own todos, todos shared with the user and
+        // all todos of lists the user owns or that are shared with them.
+        let shared_todos = todo_shares::table
+            .filter(todo_shares::user_id.eq(user))
+            .select(todo_shares::todo_id);
+        let owned_lists = lists::table
+            .filter(lists::owner_id.eq(user))
+            .select(lists::list_id);
+        let shared_lists = list_shares::table
+            .filter(list_shares::user_id.eq(user))
+            .select(list_shares::list_id);
+        q = q.filter(
+            owner_id
+                .eq(user)
+                .or(todo_id.eq_any(shared_todos))
+                .or(list_id.eq_any(owned_lists))
+                .or(list_id.eq_any(shared_lists)),
+        );
+    }
+    if let Some(list) = query.list_id {
+        q = q.filter(list_id.eq(list));
+    }
+    match query.completed {
+        Some(true) => q = q.filter(completed.eq(true)),
+        Some(false) => q = q.filter(completed.eq(false).or(completed.is_null())),
+        None => {}
+    }
+    if let Some(needle) = &query.title {
+        q = q.filter(title.like(like_pattern(needle)));
+    }
+    if let Some(needle) = &query.description {
+        q = q.filter(description.like(like_pattern(needle)));
+    }
+    if let Some(after) = query.created_after {
+        q = q.filter(created_at.ge(after));
+    }
+    if let Some(before) = query.created_before {
+        q = q.filter(created_at.lt(before));
+    }
+    if let Some(after) = query.due_after {
+        q = q.filter(due_at.ge(after));
+    }
+    if let Some(before) = query.due_before {
+        q = q.filter(due_at.lt(before));
+    }
+    // The default collation of `tags.name` ignores case.
+    let names: Vec<String> = query.tag_names().into_iter().map(String::from).collect();
+    let tagged = |names: Vec<String>| {
+        todo_tags::table
+            .inner_join(tags::table)
+            .filter(tags::name.eq_any(names))
+            .select(todo_tags::todo_id)
+    };
+    match query.tag_mode {
+        _ if names.is_empty() => {}
+        TagMode::Or => q = q.filter(todo_id.eq_any(tagged(names))),
+        TagMode::And => {
+            for name in names {
+                q = q.filter(todo_id.eq_any(tagged(vec![name])));
+            }
+        }
+    }
+    q
+}
+
+/// Builds the filtered (but unpaginated) query of the audit log for `query`.
+fn history_filtered(query: &HistoryQuery) -> todo_history::BoxedQuery<'static, Mysql> {
+    let mut q = todo_history::table.into_boxed();
+    if let Some(id) = query.todo_id {
+        q = q.filter(todo_history::todo_id.eq(id));
+    }
+    if let Some(user) = query.user_id {
+        q = q.filter(todo_history::user_id.eq(user));
+    }
+    if let Some(after) = query.changed_after {
+        q = q.filter(todo_history::changed_at.ge(after));
+    }
+    if let Some(before) = query.changed_before {
+        q = q.filter(todo_history::changed_at.lt(before));
+    }
+    if let Some(user) = query.visible_to {
+        q = q.filter(
+            todo_history::owner_id
+                .eq(user)
+                .or(todo_history::user_id.eq(user)),
+        );
+    }
+    q
+}
+
+/// Fills in the tags, blockers and progress of `items`.
+fn load_details(conn: &mut MysqlConnection, items: &mut [Todo]) -> QueryResult<()> {
+    let ids: Vec<i32> = items.iter().map(|t| t.todo_id).collect();
+    let links = todo_tags::table
+        .inner_join(tags::table)
+        .filter(todo_tags::todo_id.eq_any(&ids))
+        .order((tags::name.asc(), tags::tag_id.asc()))
+        .select((todo_tags::todo_id, tags::all_columns))
+        .load::<(i32, Tag)>(conn)?;
+    for (id, tag) in links {
+        if let Some(todo) = items.iter_mut().find(|t| t.todo_id == id) {
+            todo.tags.push(tag);
+        }
+    }
+    let dependencies = todo_dependencies::table
+        .filter(todo_dependencies::blocked_id.eq_any(&ids))
+        .order(todo_dependencies::blocker_id.asc())
+        .load::<(i32, i32)>(conn)?;
+    for (id, blocker) in dependencies {
+        if let Some(todo) = items.iter_mut().find(|t| t.todo_id == id) {
+            todo.blocked_by.push(blocker);
+        }
+    }
+    let subtasks = todos
+        .filter(parent_id.eq_any(&ids))
+        .filter(deleted_at.is_null())
+        .select((parent_id, completed))
+        .load::<(Option<i32>, Option<bool>)>(conn)?;
+    for todo in items.iter_mut() {
+        todo.progress = Progress::of(
+            subtasks
+                .iter()
+                .filter(|(parent, _)| *parent == Some(todo.todo_id))
+                .map(|(_, done)| *done),
+        );
+    }
+    Ok(())
+}
+
+fn with_details(conn: &mut MysqlConnection, mut todo: Todo) -> QueryResult<Todo> {
+    load_details(conn, std::slice::from_mut(&mut todo))?;
+    Ok(todo)
+}
+
+/// Ids of the todos below `id`, one entry per level, direct subtasks first,
+/// trashed ones included.
+fn descendant_levels(conn: &mut MysqlConnection, id: i32) -> QueryResult<Vec<Vec<i32>>> {
+    let mut levels: Vec<Vec<i32>> = Vec::new();
+    let mut parents = vec![id];
+    loop {
+        let children = todos
+            .filter(parent_id.eq_any(&parents))
+            .select(todo_id)
+            .load::<i32>(conn)?;
+        if children.is_empty() {
+            return Ok(levels);
+        }
+        parents = children.clone();
+        levels.push(children);
+    }
+}
+
+/// Checks the list and parent of the todo `id` (`None` for a new todo), see [`check_parent`].
+fn check_placement(
+    conn: &mut MysqlConnection,
+    id: Option<i32>,
+    list: i32,
+    parent: Option<i32>,
+) -> RepoResult<()> {
+    find_list(conn, list)?;
+    if let Some(id) = id {
+        let moved = find_live(id).select(list_id).first::<i32>(conn)? != list;
+        let subtasks = todos
+            .filter(parent_id.eq(id))
+            .filter(deleted_at.is_null())
+            .count()
+            .get_result::<i64>(conn)?;
+        if moved && subtasks > 0 {
+            return Err(subtasks_stay());
+        }
+    }
+    match parent {
+        Some(parent) => check_parent(id, list, parent, |x| {
+            Ok(find_live(x)
+                .select((parent_id, list_id))
+                .first::<(Option<i32>, i32)>(conn)
+                .optional()?)
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Completes the todos from `start` upwards whose subtasks are all done, like
+/// [`MemStore::roll_up`](super::mem_repo::MemStore::roll_up).
+fn roll_up(
+    conn: &mut MysqlConnection,
+    mut start: Option<i32>,
+    block_completion: bool,
+    changes: &mut Vec<Change>,
+) -> RepoResult<()> {
+    while let Some(id) = start {
+        let todo = find_live(id).get_result::<Todo>(conn)?;
+        if todo.completed != Some(true) {
+            let subtasks = todos
+                .filter(parent_id.eq(id))
+                .filter(deleted_at.is_null())
+                .select(completed)
+                .load::<Option<bool>>(conn)?;
+            let done = Progress::of(subtasks).is_some_and(|p| p.is_done());
+            let blocked = block_completion && !open_blockers(conn, id)?.is_empty();
+            if !(todo.auto_complete && done) || blocked {
+                return Ok(());
+            }
+            let previous = with_details(conn, todo.clone())?;
+            diesel::update(todos.find(id))
+                .set((completed.eq(true), version.eq(version + 1)))
+                .execute(conn)?;
+            let updated = find_live(id).get_result::<Todo>(conn)?;
+            finish_update(conn, previous, updated, changes)?;
+        }
+        start = todo.parent_id;
+    }
+    Ok(())
+}
+
+/// Blockers of the todo `id` that are not completed, ordered by id.
+fn open_blockers(conn: &mut MysqlConnection, id: i32) -> QueryResult<Vec<i32>> {
+    todo_dependencies::table
+        .inner_join(todos)
+        .filter(todo_dependencies::blocked_id.eq(id))
+        .filter(completed.eq(false).or(completed.is_null()))
+        .select(todo_dependencies::blocker_id)
+        .order(todo_dependencies::blocker_id.asc())
+        .load::<i32>(conn)
+}
+
+/// Fails if setting `done` completes `previous` while it has open blockers.
+fn check_unblocked(
+    conn: &mut MysqlConnection,
+    previous: &Todo,
+    done: Option<bool>,
+) -> RepoResult<()> {
+    if done != Some(true) || previous.completed == Some(true) {
+        return Ok(());
+    }
+    let open = open_blockers(conn, previous.todo_id)?;
+    if !open.is_empty() {
+        return Err(blocked(&open));
+    }
+    Ok(())
+}
+
+/// Fails with `Missing("blocker")` unless the todo `id` exists.
+fn find_blocker(conn: &mut MysqlConnection, id: i32) -> RepoResult<()> {
+    find_live(id)
+        .select(todo_id)
+        .first::<i32>(conn)
+        .optional()?
+        .ok_or(RepoError::Missing("blocker"))?;
+    Ok(())
+}
+
+fn find_tag(conn: &mut MysqlConnection, id: i32) -> RepoResult<Tag> {
+    tags::table
+        .find(id)
+        .get_result::<Tag>(conn)
+        .optional()?
+        .ok_or(RepoError::Missing("tag"))
+}
+
+/// The todos `ids` with their details, trashed ones included, ordered by id.
+fn load_todos(conn: &mut MysqlConnection, ids: &[i32]) -> QueryResult<Vec<Todo>> {
+    let mut items = todos
+        .filter(todo_id.eq_any(ids))
+        .order(todo_id.asc())
+        .load::<Todo>(conn)?;
+    load_details(conn, &mut items)?;
+    Ok(items)
+}
+
+/// Checks the placement of `todo` and creates it, to be called inside a transaction.
+fn create(
+    conn: &mut MysqlConnection,
+    todo: &NewTodo,
+    changes: &mut Vec<Change>,
+) -> RepoResult<Todo> {
+    todo.validate()?;
+    check_placement(conn, None, todo.list_id, todo.parent_id)?;
+    let created = insert(conn, todo)?;
+    let created = with_details(conn, created)?;
+    changes.push(Change::created(created.clone()));
+    Ok(created)
+}
+
+/// Moves the todo `id` with its subtasks to the trash, to be called inside a transaction.
+fn delete(
+    conn: &mut MysqlConnection,
+    id: i32,
+    expected: Option<i32>,
+    block_completion: bool,
+    changes: &mut Vec<Change>,
+) -> RepoResult<usize> {
+    let todo = find_live(id).get_result::<Todo>(conn)?;
+    check_version(&todo, expected)?;
+    let mut ids = descendant_levels(conn, id)?.concat();
+    ids.push(id);
+    // Taken before `trash` drops the dependencies.
+    let doomed = load_todos(conn, &ids)?;
+    let at = Local::now().naive_local();
+    let trashed = diesel::update(todos.find(id).filter(version.eq(todo.version)))
+        .set(deleted_at.eq(at))
+        .execute(conn)?;
+    if trashed == 0 {
+        return Err(RepoError::stale(id, todo.version));
+    }
+    let count = trashed + trash(conn, &ids, at)?;
+    changes.extend(
+        doomed
+            .into_iter()
+            .filter(|t| t.deleted_at.is_none())
+            .map(Change::deleted),
+    );
+    roll_up(conn, todo.parent_id, block_completion, changes)?;
+    Ok(count)
+}
+
+/// Moves the todos `ids` that are in use to the trash at `at` and drops their
+/// dependencies, to be called inside a transaction.
+fn trash(conn: &mut MysqlConnection, ids: &[i32], at: NaiveDateTime) -> QueryResult<usize> {
+    diesel::delete(
+        todo_dependencies::table.filter(
+            todo_dependencies::blocked_id
+                .eq_any(ids)
+                .or(todo_dependencies::blocker_id.eq_any(ids)),
+        ),
+    )
+    .execute(conn)?;
+    diesel::update(
+        todos
+            .filter(todo_id.eq_any(ids))
+            .filter(deleted_at.is_null()),
+    )
+    .set(deleted_at.eq(at))
+    .execute(conn)
+}
+
+/// Brings the trashed todo `id` back with the subtasks trashed along with it,
+/// to be called inside a transaction.
+fn restore(
+    conn: &mut MysqlConnection,
+    id: i32,
+    block_completion: bool,
+    changes: &mut Vec<Change>,
+) -> RepoResult<Todo> {
+    let todo = find_trashed(id).get_result::<Todo>(conn)?;
+    if let Some(parent) = todo.parent_id {
+        find_live(parent)
+            .select(todo_id)
+            .first::<i32>(conn)
+            .optional()?
+            .ok_or_else(parent_trashed)?;
+    }
+    let mut ids = descendant_levels(conn, id)?.concat();
+    ids.push(id);
+    let ids = todos
+        .filter(todo_id.eq_any(ids))
+        .filter(deleted_at.eq(todo.deleted_at))
+        .select(todo_id)
+        .load::<i32>(conn)?;
+    diesel::update(todos.filter(todo_id.eq_any(&ids)))
+        .set(deleted_at.eq(None::<NaiveDateTime>))
+        .execute(conn)?;
+    changes.extend(load_todos(conn, &ids)?.into_iter().map(Change::restored));
+    roll_up(conn, todo.parent_id, block_completion, changes)?;
+    let todo = find_live(id).get_result::<Todo>(conn)?;
+    Ok(with_details(conn, todo)?)
+}
+
+/// Deletes the todo `id` with its subtasks for good, to be called inside a transaction.
+fn purge(conn: &mut MysqlConnection, id: i32, changes: &mut Vec<Change>) -> RepoResult<usize> {
+    let levels = descendant_levels(conn, id)?;
+    let mut ids = levels.concat();
+    ids.push(id);
+    changes.extend(load_todos(conn, &ids)?.into_iter().map(Change::purged));
+    let mut count = 0;
+    // Deepest level first: MySQL stops cascading after 15 levels. The
+    // links go with the todos through `ON DELETE CASCADE`.
+    for level in levels.into_iter().rev() {
+        count += diesel::delete(todos.filter(todo_id.eq_any(level))).execute(conn)?;
+    }
+    count += diesel::delete(todos.find(id)).execute(conn)?;
+    Ok(count)
+}
+
+/// Replaces the todo `id` with `todo`, to be called inside a transaction.
+fn update_todo(
+    conn: &mut MysqlConnection,
+    id: i32,
+    mut todo: Todo,
+    expected: Option<i32>,
+    block_completion: bool,
+    changes: &mut Vec<Change>,
+) -> RepoResult<Todo> {
+    todo.validate()?;
+    todo.todo_id = id;
+    let previous = find_live(id).get_result::<Todo>(conn)?;
+    let previous = with_details(conn, previous)?;
+    check_version(&previous, expected)?;
+    if block_completion {
+        check_unblocked(conn, &previous, todo.completed)?;
+    }
+    check_placement(conn, Some(id), todo.list_id, todo.parent_id)?;
+    let count = diesel::update(todos.find(id).filter(version.eq(previous.version)))
+        .set((&todo, version.eq(version + 1)))
+        .execute(conn)?;
+    if count == 0 {
+        return Err(RepoError::stale(id, previous.version));
+    }
+    let previous_parent = previous.parent_id;
+    let updated = find_live(id).get_result::<Todo>(conn)?;
+    let updated = finish_update(conn, previous, updated, changes)?;
+    roll_up(conn, previous_parent, block_completion, changes)?;
+    roll_up(conn, updated.parent_id, block_completion, changes)?;
+    Ok(updated)
+}
+
+/// Applies `patch` to the todo `id`, to be called inside a transaction.
+fn patch_todo(
+    conn: &mut MysqlConnection,
+    id: i32,
+    patch: &TodoPatch,
+    expected: Option<i32>,
+    block_completion: bool,
+    changes: &mut Vec<Change>,
+) -> RepoResult<Todo> {
+    patch.validate()?;
+    let previous = find_live(id).get_result::<Todo>(conn)?;
+    let previous = with_details(conn, previous)?;
+    check_version(&previous, expected)?;
+    if patch.is_empty() {
+        // Nothing changes, so the version stays as it is.
+        return Ok(previous);
+    }
+    patch.validate_against(&previous)?;
+    if block_completion {
+        check_unblocked(conn, &previous, patch.completed)?;
+    }
+    if patch.list_id.is_some() || patch.parent_id.is_some() {
+        let list = patch.list_id.unwrap_or(previous.list_id);
+        let parent = patch.parent_id.unwrap_or(previous.parent_id);
+        check_placement(conn, Some(id), list, parent)?;
+    }
+    let count = diesel::update(todos.find(id).filter(version.eq(previous.version)))
+        .set((patch, version.eq(version + 1)))
+        .execute(conn)?;
+    if count == 0 {
+        return Err(RepoError::stale(id, previous.version));
+    }
+    let previous_parent = previous.parent_id;
+    let patched = find_live(id).get_result::<Todo>(conn)?;
+    let patched = finish_update(conn, previous, patched, changes)?;
+    roll_up(conn, previous_parent, block_completion, changes)?;
+    roll_up(conn, patched.parent_id, block_completion, changes)?;
+    Ok(patched)
+}
+
+/// Adds or removes the blocker `blocker` of the todo `id`, to be called inside a transaction.
+fn set_blocker(
+    conn: &mut MysqlConnection,
+    id: i32,
+    blocker: i32,
+    present: bool,
+    changes: &mut Vec<Change>,
+) -> RepoResult<Todo> {
+    let previous = find_live(id).get_result::<Todo>(conn)?;
+    let previous = with_details(conn, previous)?;
+    find_blocker(conn, blocker)?;
+    if present {
+        check_dependency(id, blocker, |x| {
+            Ok(todo_dependencies::table
+                .filter(todo_dependencies::blocked_id.eq(x))
+                .select(todo_dependencies::blocker_id)
+                .load::<i32>(conn)?)
+        })?;
+        diesel::insert_or_ignore_into(todo_dependencies::table)
+            .values((
+                todo_dependencies::blocked_id.eq(id),
+                todo_dependencies::blocker_id.eq(blocker),
+            ))
+            .execute(conn)?;
+    } else {
+        diesel::delete(todo_dependencies::table.find((id, blocker))).execute(conn)?;
+    }
+    let todo = find_live(id).get_result::<Todo>(conn)?;
+    let todo = with_details(conn, todo)?;
+    changes.push(Change::updated(previous, todo.clone()));
+    Ok(todo)
+}
+
+/// Attaches or detaches the tag `tag` of the todo `id`, to be called inside a transaction.
+fn retag(
+    conn: &mut MysqlConnection,
+    id: i32,
+    tag: i32,
+    attach: bool,
+    changes: &mut Vec<Change>,
+) -> RepoResult<Todo> {
+    let previous = find_live(id).get_result::<Todo>(conn)?;
+    let previous = with_details(conn, previous)?;
+    find_tag(conn, tag)?;
+    if attach {
+        diesel::insert_or_ignore_into(todo_tags::table)
+            .values((todo_tags::todo_id.eq(id), todo_tags::tag_id.eq(tag)))
+            .execute(conn)?;
+    } else {
+        diesel::delete(todo_tags::table.find((id, tag))).execute(conn)?;
+    }
+    let todo = find_live(id).get_result::<Todo>(conn)?;
+    let todo = with_details(conn, todo)?;
+    changes.push(Change::updated(previous, todo.clone()));
+    Ok(todo)
+}
+
+/// Deletes the list `id`, its todos too if `cascade`, to be called inside a transaction.
+fn delete_list(
+    conn: &mut MysqlConnection,
+    id: i32,
+    cascade: bool,
+    changes: &mut Vec<Change>,
+) -> RepoResult<usize> {
+    if id == DEFAULT_LIST_ID {
+        return Err(default_list_kept());
+    }
+    find_list(conn, id)?;
+    let count = todos
+        .filter(list_id.eq(id))
+        .filter(deleted_at.is_null())
+        .count()
+        .get_result::<i64>(conn)?;
+    if count > 0 && !cascade {
+        return Err(list_not_empty(count));
+    }
+    // Subtasks are in the list of their parent, so purging the top-level
+    // todos takes all of them, trashed ones included, level by level.
+    let roots = todos
+        .filter(list_id.eq(id))
+        .filter(parent_id.is_null())
+        .select(todo_id)
+        .load::<i32>(conn)?;
+    for root in roots {
+        purge(conn, root, changes)?;
+    }
+    Ok(diesel::delete(lists::table.find(id)).execute(conn)?)
+}
+
+/// Applies one operation of a batch, to be called inside its transaction.
+fn apply(
+    conn: &mut MysqlConnection,
+    op: BatchOp,
+    block_completion: bool,
+    changes: &mut Vec<Change>,
+) -> RepoResult<BatchOutcome> {
+    let patch = op.patch().unwrap_or_default();
+    match op {
+        BatchOp::Create { todo } => create(conn, &todo, changes).map(BatchOutcome::Todo),
+        BatchOp::Update {
+            id,
+            version: expected,
+            ..
+        }
+        | BatchOp::Complete {
+            id,
+            version: expected,
+        } => patch_todo(conn, id, &patch, expected, block_completion, changes)
+            .map(BatchOutcome::Todo),
+        BatchOp::Delete {
+            id,
+            version: expected,
+        } => delete(conn, id, expected, block_completion, changes).map(BatchOutcome::Deleted),
+    }
+}
+
+/// Appends `entries` to the audit log.
+fn insert_history(conn: &mut MysqlConnection, entries: &[NewHistoryEntry]) -> RepoResult<()> {
+    for entry in entries {
+        let (before, after) = entry
+            .snapshots()
+            .map_err(|err| RepoError::Internal(err.to_string()))?;
+        diesel::insert_into(todo_history::table)
+            .values((
+                todo_history::todo_id.eq(entry.todo_id),
+                todo_history::owner_id.eq(entry.owner_id),
+                todo_history::user_id.eq(entry.user_id),
+                todo_history::action.eq(entry.action.as_str()),
+                todo_history::changed_at.eq(entry.changed_at),
+                todo_history::before_todo.eq(before),
+                todo_history::after_todo.eq(after),
+            ))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Carries out `mutation`, to be called inside a transaction.
+fn write(
+    conn: &mut MysqlConnection,
+    mutation: Mutation,
+    block_completion: bool,
+    changes: &mut Vec<Change>,
+) -> RepoResult<Outcome> {
+    Ok(match mutation {
+        Mutation::Create { todo } => Outcome::Todo(create(conn, &todo, changes)?),
+        Mutation::Update {
+            id,
+            todo,
+            version: expected,
+        } => Outcome::Todo(update_todo(
+            conn,
+            id,
+            todo,
+            expected,
+            block_completion,
+            changes,
+        )?),
+        Mutation::Patch {
+            id,
+            patch,
+            version: expected,
+        } => Outcome::Todo(patch_todo(
+            conn,
+            id,
+            &patch,
+            expected,
+            block_completion,
+            changes,
+        )?),
+        Mutation::Delete {
+            id,
+            version: expected,
+        } => Outcome::Count(delete(conn, id, expected, block_completion, changes)?),
+        Mutation::Restore { id } => Outcome::Todo(restore(conn, id, block_completion, changes)?),
+        Mutation::Purge { id } => {
+            find_trashed(id).select(todo_id).first::<i32>(conn)?;
+            Outcome::Count(purge(conn, id, changes)?)
+        }
+        Mutation::PurgeTrash { before } => {
+            let ids = todos
+                .filter(deleted_at.lt(before))
+                .order(todo_id.asc())
+                .select(todo_id)
+                .load::<i32>(conn)?;
+            // Subtasks are trashed no later than their parent, so purging a
+            // todo with its subtasks only removes todos in `ids`.
+            let mut count = 0;
+            for id in ids {
+                count += purge(conn, id, changes)?;
+            }
+            Outcome::Count(count)
+        }
+        Mutation::Batch { ops } => Outcome::Batch(
+            ops.into_iter()
+                .enumerate()
+                .map(|(index, op)| {
+                    apply(conn, op, block_completion, changes)
+                        .map(Ok)
+                        .map_err(|err| err.in_batch(index))
+                })
+                .collect::<RepoResult<Vec<_>>>()?,
+        ),
+        Mutation::AddBlocker { id, blocker_id } => {
+            Outcome::Todo(set_blocker(conn, id, blocker_id, true, changes)?)
+        }
+        Mutation::RemoveBlocker { id, blocker_id } => {
+            Outcome::Todo(set_blocker(conn, id, blocker_id, false, changes)?)
+        }
+        Mutation::AttachTag {
+            todo_id: id,
+            tag_id,
+        } => Outcome::Todo(retag(conn, id, tag_id, true, changes)?),
+        Mutation::DetachTag {
+            todo_id: id,
+            tag_id,
+        } => Outcome::Todo(retag(conn, id, tag_id, false, changes)?),
+        Mutation::DeleteList { id, cascade } => {
+            Outcome::Count(delete_list(conn, id, cascade, changes)?)
+        }
+    })
 }
 
 #[async_trait]
 impl TodoRepo for MysqlRepo {
-    async fn get_todos(&self) -> Vec<Todo> {
-        todos
-            .load::<Todo>(&mut self.pool.get().unwrap())
-            .expect("Error loading all todos")
+    async fn query_todos(&self, query: TodoQuery) -> RepoResult<TodoPage> {
+        let mut conn = self.conn()?;
+        let total = filtered(&query).count().get_result::<i64>(&mut conn)?;
+
+        let mut q = filtered(&query);
+        q = match (query.sort, query.order) {
+            (SortField::TodoId, SortOrder::Asc) => q.order(todo_id.asc()),
+            (SortField::TodoId, SortOrder::Desc) => q.order(todo_id.desc()),
+            (SortField::Title, SortOrder::Asc) => q.order((title.asc(), todo_id.asc())),
+            (SortField::Title, SortOrder::Desc) => q.order((title.desc(), todo_id.desc())),
+            (SortField::CreatedAt, SortOrder::Asc) => q.order((created_at.asc(), todo_id.asc())),
+            (SortField::CreatedAt, SortOrder::Desc) => q.order((created_at.desc(), todo_id.desc())),
+            (SortField::Completed, SortOrder::Asc) => q.order((completed.asc(), todo_id.asc())),
+            (SortField::Completed, SortOrder::Desc) => q.order((completed.desc(), todo_id.desc())),
+            (SortField::DueAt, SortOrder::Asc) => q.order((due_at.asc(), todo_id.asc())),
+            (SortField::DueAt, SortOrder::Desc) => q.order((due_at.desc(), todo_id.desc())),
+        };
+        if let Some(limit) = query.limit {
+            q = q.limit(i64::from(limit));
+        }
+        if let Some(offset) = query.offset {
+            // MySQL does not accept OFFSET without LIMIT.
+            if query.limit.is_none() {
+                q = q.limit(i64::MAX);
+            }
+            q = q.offset(i64::from(offset));
+        }
+        let mut items = q.load::<Todo>(&mut conn)?;
+        load_details(&mut conn, &mut items)?;
+
+        Ok(TodoPage { items, total })
+    }
+
+    async fn mutate(&self, mutation: Mutation, actor: Option<Actor>) -> RepoResult<Mutated> {
+        self.conn()?.transaction(|conn| {
+            let mut changes = Vec::new();
+            let outcome = write(conn, mutation, self.block_completion, &mut changes)?;
+            if let Some(actor) = actor {
+                insert_history(conn, &actor.history(&changes))?;
+            }
+            Ok(Mutated::new(outcome, changes))
+        })
+    }
+
+    async fn get_todo_by_id(&self, id: i32) -> RepoResult<Todo> {
+        let mut conn = self.conn()?;
+        let todo = find_live(id).get_result::<Todo>(&mut conn)?;
+        Ok(with_details(&mut conn, todo)?)
     }
 
-    async fn create_todo(&self, todo: NewTodo) -> Result<Todo, Error> {
-        let todo = NewTodo { ..todo };
-        diesel::insert_into(todos)
-            .values(&todo)
-            .execute(&mut self.pool.get().unwrap())
-            .expect("Error creating new todo");
-        let updated_todo = todos
-            .order(todo_id.desc())
-            .first(&mut self.pool.get().unwrap())
-            .expect("Error loading todo");
+    async fn get_trash(&self, owner: Option<i32>) -> RepoResult<Vec<Todo>> {
+        self.conn()?.transaction(|conn| {
+            let mut q = todos.filter(deleted_at.is_not_null()).into_boxed();
+            if let Some(owner) = owner {
+                q = q.filter(owner_id.eq(owner));
+            }
+            let mut items = q
+                .order((deleted_at.desc(), todo_id.asc()))
+                .load::<Todo>(conn)?;
+            load_details(conn, &mut items)?;
+            Ok(items)
+        })
+    }
 
-        Ok(updated_todo)
+    fn atomic_batches(&self) -> bool {
+        true
     }
 
-    async fn get_todo_by_id(&self, id: i32) -> Option<Todo> {
-        let todo = todos
-            .find(id)
-            .get_result::<Todo>(&mut self.pool.get().unwrap())
-            .expect("Error loading todo by id");
-        Some(todo)
+    async fn get_descendants(&self, id: i32) -> RepoResult<Vec<Todo>> {
+        self.conn()?.transaction(|conn| {
+            find_live(id).select(todo_id).first::<i32>(conn)?;
+            let ids = descendant_levels(conn, id)?.concat();
+            let mut items = todos
+                .filter(todo_id.eq_any(ids))
+                .filter(deleted_at.is_null())
+                .order(todo_id.asc())
+                .load::<Todo>(conn)?;
+            load_details(conn, &mut items)?;
+            Ok(items)
+        })
     }
 
-    async fn delete_todo_by_id(&self, id: i32) -> Option<usize> {
-        let count = diesel::delete(todos.find(id))
-            .execute(&mut self.pool.get().unwrap())
-            .expect("Error deleting todo");
-        Some(count)
+    async fn get_next_todos(&self, list: Option<i32>) -> RepoResult<Vec<Todo>> {
+        self.conn()?.transaction(|conn| {
+            if let Some(list) = list {
+                find_list(conn, list)?;
+            }
+            let open = TodoQuery {
+                list_id: list,
+                completed: Some(false),
+                ..TodoQuery::default()
+            };
+            let waiting = todo_dependencies::table
+                .inner_join(todos)
+                .filter(completed.eq(false).or(completed.is_null()))
+                .select(todo_dependencies::blocked_id)
+                .load::<i32>(conn)?;
+            let mut items = filtered(&open)
+                .filter(todo_id.ne_all(waiting))
+                .order(todo_id.asc())
+                .load::<Todo>(conn)?;
+            load_details(conn, &mut items)?;
+            Ok(items)
+        })
     }
 
-    async fn update_todo_by_id(&self, id: i32, mut todo: Todo) -> Option<Todo> {
-        todo.todo_id = id;
-        let _todo = diesel::update(todos.find(id))
-            .set(&todo)
-            .execute(&mut self.pool.get().unwrap())
-            .expect("Error updating todo");
+    async fn get_tags(&self, visible_to: Option<i32>) -> RepoResult<Vec<Tag>> {
+        let mut q = tags::table.into_boxed();
+        if let Some(user) = visible_to {
+            let visible = TodoQuery {
+                visible_to: Some(user),
+                ..TodoQuery::default()
+            };
+            let attached = todo_tags::table
+                .filter(todo_tags::todo_id.eq_any(filtered(&visible).select(todo_id)))
+                .select(todo_tags::tag_id);
+            q = q.filter(tags::owner_id.eq(user).or(tags::tag_id.eq_any(attached)));
+        }
+        Ok(q.order((tags::name.asc(), tags::tag_id.asc()))
+            .load::<Tag>(&mut self.conn()?)?)
+    }
+
+    async fn create_tag(&self, tag: NewTag) -> RepoResult<Tag> {
+        tag.validate()?;
+        self.conn()?.transaction(|conn| {
+            diesel::insert_into(tags::table)
+                .values((tags::name.eq(&tag.name), tags::owner_id.eq(tag.owner_id)))
+                .execute(conn)
+                .map_err(tag_error(&tag.name))?;
+            let id = inserted_id(conn)?;
+            find_tag(conn, id)
+        })
+    }
 
-        let updated_todo = todos
-            .find(id)
-            .get_result::<Todo>(&mut self.pool.get().unwrap())
-            .expect("Error loading todo by id");
+    async fn get_tag_by_id(&self, id: i32) -> RepoResult<Tag> {
+        let mut conn = self.conn()?;
+        find_tag(&mut conn, id)
+    }
+
+    async fn update_tag_by_id(&self, id: i32, tag: NewTag) -> RepoResult<Tag> {
+        tag.validate()?;
+        self.conn()?.transaction(|conn| {
+            // MySQL counts unchanged rows as not affected, so check existence first.
+            find_tag(conn, id)?;
+            diesel::update(tags::table.find(id))
+                .set(tags::name.eq(&tag.name))
+                .execute(conn)
+                .map_err(tag_error(&tag.name))?;
+            find_tag(conn, id)
+        })
+    }
+
+    async fn delete_tag_by_id(&self, id: i32) -> RepoResult<usize> {
+        let count = diesel::delete(tags::table.find(id)).execute(&mut self.conn()?)?;
+        if count == 0 {
+            return Err(RepoError::Missing("tag"));
+        }
+        Ok(count)
+    }
+
+    async fn get_lists(&self) -> RepoResult<Vec<TodoList>> {
+        Ok(lists::table
+            .order(lists::list_id.asc())
+            .load::<TodoList>(&mut self.conn()?)?)
+    }
+
+    async fn create_list(&self, list: NewList) -> RepoResult<TodoList> {
+        list.validate()?;
+        self.conn()?.transaction(|conn| {
+            diesel::insert_into(lists::table)
+                .values((
+                    lists::name.eq(&list.name),
+                    lists::owner_id.eq(list.owner_id),
+                ))
+                .execute(conn)?;
+            let id = inserted_id(conn)?;
+            find_list(conn, id)
+        })
+    }
+
+    async fn get_list_by_id(&self, id: i32) -> RepoResult<TodoList> {
+        let mut conn = self.conn()?;
+        find_list(&mut conn, id)
+    }
+
+    async fn update_list_by_id(&self, id: i32, list: NewList) -> RepoResult<TodoList> {
+        list.validate()?;
+        self.conn()?.transaction(|conn| {
+            // MySQL counts unchanged rows as not affected, so check existence first.
+            find_list(conn, id)?;
+            diesel::update(lists::table.find(id))
+                .set(lists::name.eq(&list.name))
+                .execute(conn)?;
+            find_list(conn, id)
+        })
+    }
+
+    async fn get_todo_shares(&self, id: i32) -> RepoResult<Vec<Share>> {
+        let mut conn = self.conn()?;
+        find_live(id).select(todo_id).get_result::<i32>(&mut conn)?;
+        Ok(todo_shares::table
+            .filter(todo_shares::todo_id.eq(id))
+            .order(todo_shares::user_id.asc())
+            .select((todo_shares::user_id, todo_shares::role))
+            .load::<Share>(&mut conn)?)
+    }
+
+    async fn share_todo(&self, id: i32, share: Share) -> RepoResult<Share> {
+        self.conn()?.transaction(|conn| {
+            find_live(id).select(todo_id).get_result::<i32>(conn)?;
+            find_user(conn, share.user_id)?;
+            diesel::replace_into(todo_shares::table)
+                .values((
+                    todo_shares::todo_id.eq(id),
+                    todo_shares::user_id.eq(share.user_id),
+                    todo_shares::role.eq(share.role.as_str()),
+                ))
+                .execute(conn)?;
+            Ok(share)
+        })
+    }
+
+    async fn unshare_todo(&self, id: i32, user: i32) -> RepoResult<usize> {
+        self.conn()?.transaction(|conn| {
+            find_live(id).select(todo_id).get_result::<i32>(conn)?;
+            Ok(diesel::delete(todo_shares::table.find((id, user))).execute(conn)?)
+        })
+    }
+
+    async fn get_list_shares(&self, id: i32) -> RepoResult<Vec<Share>> {
+        let mut conn = self.conn()?;
+        find_list(&mut conn, id)?;
+        Ok(list_shares::table
+            .filter(list_shares::list_id.eq(id))
+            .order(list_shares::user_id.asc())
+            .select((list_shares::user_id, list_shares::role))
+            .load::<Share>(&mut conn)?)
+    }
+
+    async fn share_list(&self, id: i32, share: Share) -> RepoResult<Share> {
+        self.conn()?.transaction(|conn| {
+            find_list(conn, id)?;
+            find_user(conn, share.user_id)?;
+            diesel::replace_into(list_shares::table)
+                .values((
+                    list_shares::list_id.eq(id),
+                    list_shares::user_id.eq(share.user_id),
+                    list_shares::role.eq(share.role.as_str()),
+                ))
+                .execute(conn)?;
+            Ok(share)
+        })
+    }
+
+    async fn unshare_list(&self, id: i32, user: i32) -> RepoResult<usize> {
+        self.conn()?.transaction(|conn| {
+            find_list(conn, id)?;
+            Ok(diesel::delete(list_shares::table.find((id, user))).execute(conn)?)
+        })
+    }
+
+    async fn create_user(&self, user: NewUser) -> RepoResult<User> {
+        self.conn()?.transaction(|conn| {
+            diesel::insert_into(users::table)
+                .values((
+                    users::username.eq(&user.username),
+                    users::password_hash.eq(&user.password_hash),
+                ))
+                .execute(conn)
+                .map_err(user_error(&user.username))?;
+            let id = inserted_id(conn)?;
+            find_user(conn, id)
+        })
+    }
+
+    async fn get_user_by_id(&self, id: i32) -> RepoResult<User> {
+        let mut conn = self.conn()?;
+        find_user(&mut conn, id)
+    }
+
+    async fn get_user_record(&self, name: &str) -> RepoResult<UserRecord> {
+        // The default collation of `users.username` ignores case.
+        let (user, hash) = users::table
+            .filter(users::username.eq(name))
+            .select((USER_COLUMNS, users::password_hash))
+            .get_result::<(User, String)>(&mut self.conn()?)
+            .optional()?
+            .ok_or(RepoError::Missing("user"))?;
+        Ok(UserRecord {
+            user,
+            password_hash: hash,
+        })
+    }
+
+    async fn create_api_key(&self, key: NewApiKeyRecord) -> RepoResult<ApiKey> {
+        self.conn()?.transaction(|conn| {
+            find_user(conn, key.user_id)?;
+            diesel::insert_into(api_keys::table)
+                .values((
+                    api_keys::user_id.eq(key.user_id),
+                    api_keys::name.eq(&key.key.name),
+                    api_keys::prefix.eq(&key.prefix),
+                    api_keys::key_hash.eq(&key.key_hash),
+                    api_keys::scopes.eq(Scope::join(&key.key.scopes)),
+                    api_keys::expires_at.eq(key.key.expires_at),
+                ))
+                .execute(conn)?;
+            let id = inserted_id(conn)?;
+            Ok(api_keys::table
+                .find(id)
+                .select(API_KEY_COLUMNS)
+                .get_result::<ApiKey>(conn)?)
+        })
+    }
+
+    async fn get_api_keys(&self, owner: i32) -> RepoResult<Vec<ApiKey>> {
+        Ok(api_keys::table
+            .filter(api_keys::user_id.eq(owner))
+            .order(api_keys::key_id.asc())
+            .select(API_KEY_COLUMNS)
+            .load::<ApiKey>(&mut self.conn()?)?)
+    }
+
+    async fn get_api_key_by_hash(&self, hash: &str) -> RepoResult<ApiKey> {
+        api_keys::table
+            .filter(api_keys::key_hash.eq(hash))
+            .select(API_KEY_COLUMNS)
+            .get_result::<ApiKey>(&mut self.conn()?)
+            .optional()?
+            .ok_or(RepoError::Missing("api key"))
+    }
+
+    async fn delete_api_key(&self, owner: i32, id: i32) -> RepoResult<usize> {
+        let deleted = diesel::delete(
+            api_keys::table
+                .filter(api_keys::key_id.eq(id))
+                .filter(api_keys::user_id.eq(owner)),
+        )
+        .execute(&mut self.conn()?)?;
+        if deleted == 0 {
+            return Err(RepoError::NotFound);
+        }
+        Ok(deleted)
+    }
+
+    async fn record_history(&self, entries: Vec<NewHistoryEntry>) -> RepoResult<()> {
+        self.conn()?
+            .transaction(|conn| insert_history(conn, &entries))
+    }
 
-        Some(updated_todo)
+    async fn get_history(&self, query: HistoryQuery) -> RepoResult<Vec<HistoryEntry>> {
+        let mut q = history_filtered(&query).order(todo_history::history_id.asc());
+        if let Some(limit) = query.limit {
+            q = q.limit(i64::from(limit));
+        }
+        if let Some(offset) = query.offset {
+            // MySQL does not accept OFFSET without LIMIT.
+            if query.limit.is_none() {
+                q = q.limit(i64::MAX);
+            }
+            q = q.offset(i64::from(offset));
+        }
+        Ok(q.load::<HistoryEntry>(&mut self.conn()?)?)
     }
 }
@@ -0,0 +1,560 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+
+use super::error::{RepoError, RepoResult};
+use super::mutation::{Actor, Mutated, Mutation, Outcome};
+use super::todo_repo::{apply_one_by_one, changed, deleted, TodoRepo};
+use super::RepoBox;
+use crate::models::api_key::{ApiKey, NewApiKeyRecord};
+use crate::models::batch::BatchOp;
+use crate::models::history::{HistoryEntry, HistoryQuery, NewHistoryEntry};
+use crate::models::list::{NewList, TodoList, DEFAULT_LIST_ID};
+use crate::models::query::{TodoPage, TodoQuery};
+use crate::models::share::{self, Role, Share};
+use crate::models::tag::{NewTag, Tag};
+use crate::models::todo::{NewTodo, Todo, TodoPatch};
+use crate::models::user::{NewUser, User, UserRecord};
+use crate::validation::FieldError;
+
+/// The todos of one user on top of another repository.
+///
+/// New todos and lists belong to the user. Other todos are visible as far as
+/// they are shared with the user, directly or through their list, see
+/// [`share::todo_role`]; the rest look as if they did not exist. Lists without
+/// owner can be used by everyone. Tags are visible to their owner and wherever
+/// they are attached to a visible todo, and only their owner may rename or
+/// delete them. Users and API keys are passed through.
+///
+/// The audit log shows the changes of the user's own todos and the changes
+/// they made; the whole history of a todo only while they can see the todo.
+#[derive(Clone)]
+pub struct OwnedRepo {
+    inner: RepoBox,
+    owner_id: i32,
+}
+
+impl OwnedRepo {
+    pub fn new(inner: RepoBox, owner_id: i32) -> Self {
+        OwnedRepo { inner, owner_id }
+    }
+
+    pub fn owner_id(&self) -> i32 {
+        self.owner_id
+    }
+
+    /// The role of the user on `todo`, `None` if they cannot see it.
+    async fn role_on(&self, todo: &Todo) -> RepoResult<Option<Role>> {
+        if todo.owner_id == Some(self.owner_id) {
+            return Ok(Some(Role::Owner));
+        }
+        let todo_shares = self.inner.get_todo_shares(todo.todo_id).await?;
+        let list = self.inner.get_list_by_id(todo.list_id).await?;
+        let list_shares = self.inner.get_list_shares(todo.list_id).await?;
+        Ok(share::todo_role(
+            self.owner_id,
+            todo,
+            &todo_shares,
+            &list,
+            &list_shares,
+        ))
+    }
+
+    /// The role of the user on `list`; lists without owner are open to everyone
+    /// but cannot be shared.
+    async fn role_on_list(&self, list: &TodoList) -> RepoResult<Option<Role>> {
+        if list.owner_id.is_none() {
+            return Ok(Some(Role::Editor));
+        }
+        let shares = self.inner.get_list_shares(list.list_id).await?;
+        Ok(share::list_role(self.owner_id, list, &shares))
+    }
+
+    /// The todo `id` if the user has at least `role` on it. Todos they cannot
+    /// see are `RepoError::NotFound`, a lesser role is `RepoError::Forbidden`.
+    async fn todo_with_role(&self, id: i32, role: Role) -> RepoResult<Todo> {
+        let todo = self.inner.get_todo_by_id(id).await?;
+        check_role(self.role_on(&todo).await?, role, "todo", id)?;
+        Ok(todo)
+    }
+
+    /// The tag `id` if the user can see it, see [`TodoRepo::get_tags`]; other
+    /// tags are `RepoError::Missing("tag")`.
+    async fn visible_tag(&self, id: i32) -> RepoResult<Tag> {
+        self.inner
+            .get_tags(Some(self.owner_id))
+            .await?
+            .into_iter()
+            .find(|tag| tag.tag_id == id)
+            .ok_or(RepoError::Missing("tag"))
+    }
+
+    /// The tag `id` if the user created it. Visible tags of others and tags
+    /// without owner are `RepoError::Forbidden`, they may also be attached to
+    /// todos the user cannot see.
+    async fn own_tag(&self, id: i32) -> RepoResult<Tag> {
+        let tag = self.visible_tag(id).await?;
+        if tag.owner_id != Some(self.owner_id) {
+            return Err(RepoError::Forbidden(format!(
+                "the tag {id} belongs to another user"
+            )));
+        }
+        Ok(tag)
+    }
+
+    /// Like [`OwnedRepo::todo_with_role`] for the list `id`.
+    async fn list_with_role(&self, id: i32, role: Role) -> RepoResult<TodoList> {
+        let list = self.inner.get_list_by_id(id).await?;
+        check_role(self.role_on_list(&list).await?, role, "list", id)?;
+        Ok(list)
+    }
+
+    /// Like [`OwnedRepo::todo_with_role`], reporting an unknown todo as `Missing(what)`.
+    async fn check_todo(&self, id: i32, role: Role, what: &'static str) -> RepoResult<()> {
+        match self.todo_with_role(id, role).await {
+            Err(RepoError::NotFound) => Err(RepoError::Missing(what)),
+            other => other.map(|_| ()),
+        }
+    }
+
+    async fn check_parent(&self, parent_id: Option<i32>) -> RepoResult<()> {
+        match parent_id {
+            Some(parent_id) => self.check_todo(parent_id, Role::Editor, "parent").await,
+            None => Ok(()),
+        }
+    }
+
+    /// Adding todos to a list needs the editor role on it.
+    async fn check_list(&self, list_id: i32) -> RepoResult<()> {
+        match self.list_with_role(list_id, Role::Editor).await {
+            Err(RepoError::NotFound) => Err(RepoError::Missing("list")),
+            other => other.map(|_| ()),
+        }
+    }
+
+    /// Moving `todo` to the list `list_id` decides who else can see it, and the
+    /// owner of the new list would own it; only owners of the todo may do so.
+    async fn check_move(&self, todo: &Todo, list_id: i32) -> RepoResult<()> {
+        check_role(self.role_on(todo).await?, Role::Owner, "todo", todo.todo_id)?;
+        self.check_list(list_id).await
+    }
+
+    /// Checks a new todo and makes it the user's.
+    async fn prepare_create(&self, mut new: NewTodo) -> RepoResult<NewTodo> {
+        self.check_list(new.list_id).await?;
+        self.check_parent(new.parent_id).await?;
+        new.owner_id = Some(self.owner_id);
+        Ok(new)
+    }
+
+    /// Checks that the user may apply `patch` to the todo `id`.
+    async fn check_patch(&self, id: i32, patch: &TodoPatch) -> RepoResult<()> {
+        let previous = self.todo_with_role(id, Role::Editor).await?;
+        if let Some(list_id) = patch.list_id.filter(|list_id| *list_id != previous.list_id) {
+            self.check_move(&previous, list_id).await?;
+        }
+        if let Some(parent_id) = patch.parent_id {
+            self.check_parent(parent_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the user may replace the todo `id` with `todo`.
+    async fn check_update(&self, id: i32, todo: &Todo) -> RepoResult<()> {
+        let previous = self.todo_with_role(id, Role::Editor).await?;
+        if todo.list_id != previous.list_id {
+            self.check_move(&previous, todo.list_id).await?;
+        }
+        if todo.parent_id != previous.parent_id {
+            self.check_parent(todo.parent_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Checks `op` like the single method it stands for would.
+    async fn prepare_op(&self, op: BatchOp) -> RepoResult<BatchOp> {
+        match op {
+            BatchOp::Create { todo } => {
+                let todo = self.prepare_create(todo).await?;
+                return Ok(BatchOp::Create { todo });
+            }
+            BatchOp::Update { id, ref patch, .. } => self.check_patch(id, patch).await?,
+            BatchOp::Delete { id, .. } => {
+                self.todo_with_role(id, Role::Owner).await?;
+            }
+            BatchOp::Complete { id, .. } => {
+                self.todo_with_role(id, Role::Editor).await?;
+            }
+        }
+        Ok(op)
+    }
+
+    /// The user's own todos, of the list `list_id` if given.
+    async fn own_todos(&self, list_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        let query = TodoQuery {
+            list_id,
+            owner_id: Some(self.owner_id),
+            ..TodoQuery::default()
+        };
+        Ok(self.inner.query_todos(query).await?.items)
+    }
+
+    /// Fails with `RepoError::NotFound` unless the todo `id` is in the user's trash.
+    async fn check_own_trash(&self, id: i32) -> RepoResult<()> {
+        let trash = self.inner.get_trash(Some(self.owner_id)).await?;
+        if !trash.iter().any(|todo| todo.todo_id == id) {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Only empties the user's own trash.
+    async fn purge_own_trash(
+        &self,
+        before: NaiveDateTime,
+        actor: Option<Actor>,
+    ) -> RepoResult<Mutated> {
+        let mut count = 0;
+        let mut changes = Vec::new();
+        for todo in self.inner.get_trash(Some(self.owner_id)).await? {
+            if todo.deleted_at.is_some_and(|at| at < before) {
+                let purge = Mutation::Purge { id: todo.todo_id };
+                // Subtasks may have gone with a todo purged before.
+                match self.inner.mutate(purge, actor).await {
+                    Err(RepoError::NotFound) => {}
+                    purged => {
+                        let mut purged = purged?;
+                        changes.append(&mut purged.changes);
+                        count += purged.count()?;
+                    }
+                }
+            }
+        }
+        Ok(Mutated::new(Outcome::Count(count), changes))
+    }
+
+    /// The owner of a list deletes it with all todos. For lists without owner,
+    /// cascading only deletes the todos of the user; as long as other users
+    /// have todos in the list, it is kept.
+    async fn delete_list(
+        &self,
+        id: i32,
+        cascade: bool,
+        actor: Option<Actor>,
+    ) -> RepoResult<Mutated> {
+        let list = self.list_with_role(id, Role::Editor).await?;
+        if list.owner_id.is_some() {
+            self.list_with_role(id, Role::Owner).await?;
+            return self
+                .inner
+                .mutate(Mutation::DeleteList { id, cascade }, actor)
+                .await;
+        }
+        let mut changes = Vec::new();
+        if cascade && id != DEFAULT_LIST_ID {
+            let query = TodoQuery {
+                list_id: Some(id),
+                owner_id: Some(self.owner_id),
+                ..TodoQuery::default()
+            };
+            let todos = self.inner.query_todos(query).await?.items;
+            // Deleting the top-level todos deletes their subtasks as well.
+            for todo in todos.iter().filter(|todo| todo.parent_id.is_none()) {
+                let delete = Mutation::Delete {
+                    id: todo.todo_id,
+                    version: None,
+                };
+                changes.extend(self.inner.mutate(delete, actor).await?.changes);
+            }
+        }
+        let cascade = false;
+        let mut deleted = self
+            .inner
+            .mutate(Mutation::DeleteList { id, cascade }, actor)
+            .await?;
+        changes.append(&mut deleted.changes);
+        deleted.changes = changes;
+        Ok(deleted)
+    }
+
+    /// Sharing with oneself would only hide the role one already has.
+    fn check_share(&self, share: &Share) -> RepoResult<()> {
+        if share.user_id == self.owner_id {
+            return Err(vec![FieldError::new(
+                "user_id",
+                "self",
+                "a todo or list cannot be shared with yourself",
+            )]
+            .into());
+        }
+        Ok(())
+    }
+}
+
+fn check_role(granted: Option<Role>, needed: Role, what: &str, id: i32) -> RepoResult<()> {
+    match granted {
+        None => Err(RepoError::NotFound),
+        Some(granted) if granted < needed => Err(RepoError::Forbidden(format!(
+            "the {what} {id} needs the `{needed}` role"
+        ))),
+        Some(_) => Ok(()),
+    }
+}
+
+#[async_trait]
+impl TodoRepo for OwnedRepo {
+    async fn query_todos(&self, query: TodoQuery) -> RepoResult<TodoPage> {
+        let query = TodoQuery {
+            visible_to: Some(self.owner_id),
+            ..query
+        };
+        self.inner.query_todos(query).await
+    }
+
+    /// Every write is checked like the method it stands for. For a
+    /// transactional repository every operation of a batch is checked up
+    /// front and the batch only goes through if all pass.
+    async fn mutate(&self, mutation: Mutation, actor: Option<Actor>) -> RepoResult<Mutated> {
+        let mutation = match mutation {
+            Mutation::Create { todo } => Mutation::Create {
+                todo: self.prepare_create(todo).await?,
+            },
+            Mutation::Update { id, ref todo, .. } => {
+                self.check_update(id, todo).await?;
+                mutation
+            }
+            Mutation::Patch { id, ref patch, .. } => {
+                self.check_patch(id, patch).await?;
+                mutation
+            }
+            Mutation::Delete { id, .. } => {
+                self.todo_with_role(id, Role::Owner).await?;
+                mutation
+            }
+            Mutation::Restore { id } | Mutation::Purge { id } => {
+                self.check_own_trash(id).await?;
+                mutation
+            }
+            Mutation::PurgeTrash { before } => return self.purge_own_trash(before, actor).await,
+            Mutation::Batch { ops } if self.atomic_batches() => {
+                let mut prepared = Vec::with_capacity(ops.len());
+                for (index, op) in ops.into_iter().enumerate() {
+                    prepared.push(
+                        self.prepare_op(op)
+                            .await
+                            .map_err(|err| err.in_batch(index))?,
+                    );
+                }
+                Mutation::Batch { ops: prepared }
+            }
+            Mutation::Batch { ops } => return Ok(apply_one_by_one(self, ops, actor).await),
+            Mutation::AddBlocker { id, blocker_id }
+            | Mutation::RemoveBlocker { id, blocker_id } => {
+                self.todo_with_role(id, Role::Editor).await?;
+                self.check_todo(blocker_id, Role::Viewer, "blocker").await?;
+                mutation
+            }
+            Mutation::AttachTag { todo_id, tag_id } => {
+                self.todo_with_role(todo_id, Role::Editor).await?;
+                self.visible_tag(tag_id).await?;
+                mutation
+            }
+            Mutation::DetachTag { todo_id, .. } => {
+                self.todo_with_role(todo_id, Role::Editor).await?;
+                mutation
+            }
+            Mutation::DeleteList { id, cascade } => {
+                return self.delete_list(id, cascade, actor).await
+            }
+        };
+        self.inner.mutate(mutation, actor).await
+    }
+
+    async fn get_todo_by_id(&self, id: i32) -> RepoResult<Todo> {
+        self.todo_with_role(id, Role::Viewer).await
+    }
+
+    /// Trashed todos belong to the trash of their owner.
+    async fn get_trash(&self, _owner_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        self.inner.get_trash(Some(self.owner_id)).await
+    }
+
+    fn atomic_batches(&self) -> bool {
+        self.inner.atomic_batches()
+    }
+
+    /// Only the user's own todos, shared ones are left alone.
+    async fn delete_completed(&self, list_id: Option<i32>) -> RepoResult<usize> {
+        let todos = self.own_todos(list_id).await?;
+        deleted(self.apply_batch(BatchOp::delete_completed(&todos)).await?)
+    }
+
+    /// Only the user's own todos, shared ones are left alone.
+    async fn complete_all(&self, list_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        let todos = self.own_todos(list_id).await?;
+        changed(self.apply_batch(BatchOp::complete_open(&todos)).await?)
+    }
+
+    async fn get_descendants(&self, id: i32) -> RepoResult<Vec<Todo>> {
+        self.todo_with_role(id, Role::Viewer).await?;
+        self.inner.get_descendants(id).await
+    }
+
+    async fn get_next_todos(&self, list_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        let query = TodoQuery {
+            list_id,
+            ..TodoQuery::default()
+        };
+        let visible: HashSet<i32> = self
+            .query_todos(query)
+            .await?
+            .items
+            .iter()
+            .map(|todo| todo.todo_id)
+            .collect();
+        let mut todos = self.inner.get_next_todos(list_id).await?;
+        todos.retain(|todo| visible.contains(&todo.todo_id));
+        Ok(todos)
+    }
+
+    /// Only the tags the user can see.
+    async fn get_tags(&self, _visible_to: Option<i32>) -> RepoResult<Vec<Tag>> {
+        self.inner.get_tags(Some(self.owner_id)).await
+    }
+
+    async fn create_tag(&self, mut new: NewTag) -> RepoResult<Tag> {
+        new.owner_id = Some(self.owner_id);
+        self.inner.create_tag(new).await
+    }
+
+    async fn get_tag_by_id(&self, id: i32) -> RepoResult<Tag> {
+        self.visible_tag(id).await
+    }
+
+    async fn update_tag_by_id(&self, id: i32, tag: NewTag) -> RepoResult<Tag> {
+        self.own_tag(id).await?;
+        self.inner.update_tag_by_id(id, tag).await
+    }
+
+    async fn delete_tag_by_id(&self, id: i32) -> RepoResult<usize> {
+        self.own_tag(id).await?;
+        self.inner.delete_tag_by_id(id).await
+    }
+
+    async fn get_lists(&self) -> RepoResult<Vec<TodoList>> {
+        let mut visible = Vec::new();
+        for list in self.inner.get_lists().await? {
+            if self.role_on_list(&list).await?.is_some() {
+                visible.push(list);
+            }
+        }
+        Ok(visible)
+    }
+
+    async fn create_list(&self, mut new: NewList) -> RepoResult<TodoList> {
+        new.owner_id = Some(self.owner_id);
+        self.inner.create_list(new).await
+    }
+
+    async fn get_list_by_id(&self, id: i32) -> RepoResult<TodoList> {
+        self.list_with_role(id, Role::Viewer).await
+    }
+
+    async fn update_list_by_id(&self, id: i32, list: NewList) -> RepoResult<TodoList> {
+        self.list_with_role(id, Role::Editor).await?;
+        self.inner.update_list_by_id(id, list).await
+    }
+
+    async fn get_todo_shares(&self, todo_id: i32) -> RepoResult<Vec<Share>> {
+        self.todo_with_role(todo_id, Role::Viewer).await?;
+        self.inner.get_todo_shares(todo_id).await
+    }
+
+    async fn share_todo(&self, todo_id: i32, share: Share) -> RepoResult<Share> {
+        self.todo_with_role(todo_id, Role::Owner).await?;
+        self.check_share(&share)?;
+        self.inner.share_todo(todo_id, share).await
+    }
+
+    /// Users may always leave a todo shared with them.
+    async fn unshare_todo(&self, todo_id: i32, user_id: i32) -> RepoResult<usize> {
+        let role = if user_id == self.owner_id {
+            Role::Viewer
+        } else {
+            Role::Owner
+        };
+        self.todo_with_role(todo_id, role).await?;
+        self.inner.unshare_todo(todo_id, user_id).await
+    }
+
+    async fn get_list_shares(&self, list_id: i32) -> RepoResult<Vec<Share>> {
+        self.list_with_role(list_id, Role::Viewer).await?;
+        self.inner.get_list_shares(list_id).await
+    }
+
+    async fn share_list(&self, list_id: i32, share: Share) -> RepoResult<Share> {
+        self.list_with_role(list_id, Role::Owner).await?;
+        self.check_share(&share)?;
+        self.inner.share_list(list_id, share).await
+    }
+
+    /// Users may always leave a list shared with them.
+    async fn unshare_list(&self, list_id: i32, user_id: i32) -> RepoResult<usize> {
+        let role = if user_id == self.owner_id {
+            Role::Viewer
+        } else {
+            Role::Owner
+        };
+        self.list_with_role(list_id, role).await?;
+        self.inner.unshare_list(list_id, user_id).await
+    }
+
+    async fn create_user(&self, new: NewUser) -> RepoResult<User> {
+        self.inner.create_user(new).await
+    }
+
+    async fn get_user_by_id(&self, id: i32) -> RepoResult<User> {
+        self.inner.get_user_by_id(id).await
+    }
+
+    async fn get_user_record(&self, username: &str) -> RepoResult<UserRecord> {
+        self.inner.get_user_record(username).await
+    }
+
+    async fn create_api_key(&self, key: NewApiKeyRecord) -> RepoResult<ApiKey> {
+        self.inner.create_api_key(key).await
+    }
+
+    async fn get_api_keys(&self, user_id: i32) -> RepoResult<Vec<ApiKey>> {
+        self.inner.get_api_keys(user_id).await
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> RepoResult<ApiKey> {
+        self.inner.get_api_key_by_hash(key_hash).await
+    }
+
+    async fn delete_api_key(&self, user_id: i32, key_id: i32) -> RepoResult<usize> {
+        self.inner.delete_api_key(user_id, key_id).await
+    }
+
+    async fn record_history(&self, entries: Vec<NewHistoryEntry>) -> RepoResult<()> {
+        self.inner.record_history(entries).await
+    }
+
+    async fn get_history(&self, query: HistoryQuery) -> RepoResult<Vec<HistoryEntry>> {
+        let visible = match query.todo_id {
+            Some(id) => match self.todo_with_role(id, Role::Viewer).await {
+                Ok(_) => true,
+                Err(RepoError::NotFound) => false,
+                Err(err) => return Err(err),
+            },
+            None => false,
+        };
+        let query = HistoryQuery {
+            visible_to: (!visible).then_some(self.owner_id),
+            ..query
+        };
+        self.inner.get_history(query).await
+    }
+}
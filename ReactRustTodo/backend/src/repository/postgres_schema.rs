@@ -0,0 +1,140 @@
+// Schema of the PostgreSQL database, see `migrations_postgres`.
+
+diesel::table! {
+    todos (todo_id) {
+        todo_id -> Int4,
+        #[max_length = 255]
+        title -> Varchar,
+        description -> Nullable<Text>,
+        created_at -> Nullable<Timestamp>,
+        completed -> Nullable<Bool>,
+        due_at -> Nullable<Timestamp>,
+        remind_at -> Nullable<Timestamp>,
+        #[max_length = 255]
+        recurrence -> Nullable<Varchar>,
+        list_id -> Int4,
+        parent_id -> Nullable<Int4>,
+        auto_complete -> Bool,
+        owner_id -> Nullable<Integer>,
+        version -> Integer,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    lists (list_id) {
+        list_id -> Int4,
+        #[max_length = 100]
+        name -> Varchar,
+        owner_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    list_shares (list_id, user_id) {
+        list_id -> Int4,
+        user_id -> Int4,
+        #[max_length = 10]
+        role -> Varchar,
+    }
+}
+
+diesel::table! {
+    todo_shares (todo_id, user_id) {
+        todo_id -> Int4,
+        user_id -> Int4,
+        #[max_length = 10]
+        role -> Varchar,
+    }
+}
+
+diesel::table! {
+    tags (tag_id) {
+        tag_id -> Int4,
+        #[max_length = 50]
+        name -> Varchar,
+        owner_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    todo_tags (todo_id, tag_id) {
+        todo_id -> Int4,
+        tag_id -> Int4,
+    }
+}
+
+diesel::table! {
+    todo_dependencies (blocked_id, blocker_id) {
+        blocked_id -> Int4,
+        blocker_id -> Int4,
+    }
+}
+
+diesel::table! {
+    users (user_id) {
+        user_id -> Integer,
+        #[max_length = 50]
+        username -> Varchar,
+        #[max_length = 255]
+        password_hash -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    api_keys (key_id) {
+        key_id -> Int4,
+        user_id -> Int4,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 20]
+        prefix -> Varchar,
+        #[max_length = 64]
+        key_hash -> Varchar,
+        #[max_length = 50]
+        scopes -> Varchar,
+        expires_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    todo_history (history_id) {
+        history_id -> Int4,
+        todo_id -> Int4,
+        owner_id -> Nullable<Int4>,
+        user_id -> Nullable<Int4>,
+        #[max_length = 16]
+        action -> Varchar,
+        changed_at -> Timestamp,
+        before_todo -> Nullable<Text>,
+        after_todo -> Nullable<Text>,
+    }
+}
+
+diesel::joinable!(api_keys -> users (user_id));
+diesel::joinable!(list_shares -> lists (list_id));
+diesel::joinable!(list_shares -> users (user_id));
+diesel::joinable!(lists -> users (owner_id));
+diesel::joinable!(tags -> users (owner_id));
+diesel::joinable!(todo_dependencies -> todos (blocker_id));
+diesel::joinable!(todo_shares -> todos (todo_id));
+diesel::joinable!(todo_shares -> users (user_id));
+diesel::joinable!(todo_tags -> tags (tag_id));
+diesel::joinable!(todo_tags -> todos (todo_id));
+diesel::joinable!(todos -> lists (list_id));
+diesel::joinable!(todos -> users (owner_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    list_shares,
+    lists,
+    tags,
+    todo_dependencies,
+    todo_history,
+    todo_shares,
+    todo_tags,
+    todos,
+    users,
+);
@@ -8,5 +8,133 @@ diesel::table! {
         description -> Nullable<Text>,
         created_at -> Nullable<Datetime>,
         completed -> Nullable<Bool>,
+        due_at -> Nullable<Datetime>,
+        remind_at -> Nullable<Datetime>,
+        #[max_length = 255]
+        recurrence -> Nullable<Varchar>,
+        list_id -> Integer,
+        parent_id -> Nullable<Integer>,
+        auto_complete -> Bool,
+        owner_id -> Nullable<Integer>,
+        version -> Integer,
+        deleted_at -> Nullable<Datetime>,
+    }
+}
+
+diesel::table! {
+    lists (list_id) {
+        list_id -> Integer,
+        #[max_length = 100]
+        name -> Varchar,
+        owner_id -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    list_shares (list_id, user_id) {
+        list_id -> Integer,
+        user_id -> Integer,
+        #[max_length = 10]
+        role -> Varchar,
+    }
+}
+
+diesel::table! {
+    todo_shares (todo_id, user_id) {
+        todo_id -> Integer,
+        user_id -> Integer,
+        #[max_length = 10]
+        role -> Varchar,
     }
 }
+
+diesel::table! {
+    tags (tag_id) {
+        tag_id -> Integer,
+        #[max_length = 50]
+        name -> Varchar,
+        owner_id -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    todo_tags (todo_id, tag_id) {
+        todo_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
+diesel::table! {
+    todo_dependencies (blocked_id, blocker_id) {
+        blocked_id -> Integer,
+        blocker_id -> Integer,
+    }
+}
+
+diesel::table! {
+    users (user_id) {
+        user_id -> Integer,
+        #[max_length = 50]
+        username -> Varchar,
+        #[max_length = 255]
+        password_hash -> Varchar,
+        created_at -> Datetime,
+    }
+}
+
+diesel::table! {
+    api_keys (key_id) {
+        key_id -> Integer,
+        user_id -> Integer,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 20]
+        prefix -> Varchar,
+        #[max_length = 64]
+        key_hash -> Varchar,
+        #[max_length = 50]
+        scopes -> Varchar,
+        expires_at -> Nullable<Datetime>,
+        created_at -> Datetime,
+    }
+}
+
+diesel::table! {
+    todo_history (history_id) {
+        history_id -> Integer,
+        todo_id -> Integer,
+        owner_id -> Nullable<Integer>,
+        user_id -> Nullable<Integer>,
+        #[max_length = 16]
+        action -> Varchar,
+        changed_at -> Datetime,
+        before_todo -> Nullable<Text>,
+        after_todo -> Nullable<Text>,
+    }
+}
+
+diesel::joinable!(api_keys -> users (user_id));
+diesel::joinable!(list_shares -> lists (list_id));
+diesel::joinable!(list_shares -> users (user_id));
+diesel::joinable!(lists -> users (owner_id));
+diesel::joinable!(tags -> users (owner_id));
+diesel::joinable!(todo_dependencies -> todos (blocker_id));
+diesel::joinable!(todo_shares -> todos (todo_id));
+diesel::joinable!(todo_shares -> users (user_id));
+diesel::joinable!(todo_tags -> tags (tag_id));
+diesel::joinable!(todo_tags -> todos (todo_id));
+diesel::joinable!(todos -> lists (list_id));
+diesel::joinable!(todos -> users (owner_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    list_shares,
+    lists,
+    tags,
+    todo_dependencies,
+    todo_history,
+    todo_shares,
+    todo_tags,
+    todos,
+    users,
+);
@@ -0,0 +1,127 @@
+// Schema of the SQLite database, see `migrations_sqlite`.
+
+diesel::table! {
+    todos (todo_id) {
+        todo_id -> Integer,
+        title -> Text,
+        description -> Nullable<Text>,
+        created_at -> Nullable<Timestamp>,
+        completed -> Nullable<Bool>,
+        due_at -> Nullable<Timestamp>,
+        remind_at -> Nullable<Timestamp>,
+        recurrence -> Nullable<Text>,
+        list_id -> Integer,
+        parent_id -> Nullable<Integer>,
+        auto_complete -> Bool,
+        owner_id -> Nullable<Integer>,
+        version -> Integer,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    lists (list_id) {
+        list_id -> Integer,
+        name -> Text,
+        owner_id -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    list_shares (list_id, user_id) {
+        list_id -> Integer,
+        user_id -> Integer,
+        role -> Text,
+    }
+}
+
+diesel::table! {
+    todo_shares (todo_id, user_id) {
+        todo_id -> Integer,
+        user_id -> Integer,
+        role -> Text,
+    }
+}
+
+diesel::table! {
+    tags (tag_id) {
+        tag_id -> Integer,
+        name -> Text,
+        owner_id -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    todo_tags (todo_id, tag_id) {
+        todo_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
+diesel::table! {
+    todo_dependencies (blocked_id, blocker_id) {
+        blocked_id -> Integer,
+        blocker_id -> Integer,
+    }
+}
+
+diesel::table! {
+    users (user_id) {
+        user_id -> Integer,
+        username -> Text,
+        password_hash -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    api_keys (key_id) {
+        key_id -> Integer,
+        user_id -> Integer,
+        name -> Text,
+        prefix -> Text,
+        key_hash -> Text,
+        scopes -> Text,
+        expires_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    todo_history (history_id) {
+        history_id -> Integer,
+        todo_id -> Integer,
+        owner_id -> Nullable<Integer>,
+        user_id -> Nullable<Integer>,
+        action -> Text,
+        changed_at -> Timestamp,
+        before_todo -> Nullable<Text>,
+        after_todo -> Nullable<Text>,
+    }
+}
+
+diesel::joinable!(api_keys -> users (user_id));
+diesel::joinable!(list_shares -> lists (list_id));
+diesel::joinable!(list_shares -> users (user_id));
+diesel::joinable!(lists -> users (owner_id));
+diesel::joinable!(tags -> users (owner_id));
+diesel::joinable!(todo_dependencies -> todos (blocker_id));
+diesel::joinable!(todo_shares -> todos (todo_id));
+diesel::joinable!(todo_shares -> users (user_id));
+diesel::joinable!(todo_tags -> tags (tag_id));
+diesel::joinable!(todo_tags -> todos (todo_id));
+diesel::joinable!(todos -> lists (list_id));
+diesel::joinable!(todos -> users (owner_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    list_shares,
+    lists,
+    tags,
+    todo_dependencies,
+    todo_history,
+    todo_shares,
+    todo_tags,
+    todos,
+    users,
+);
@@ -1,16 +1,337 @@
-use std::fmt::Error;
-
-use crate::models::{
-    self,
-    todo::{NewTodo, Todo},
-};
+use crate::models::api_key::{ApiKey, NewApiKeyRecord};
+use crate::models::batch::{BatchOp, BatchOutcome};
+use crate::models::dependency::topological_order;
+use crate::models::history::{HistoryEntry, HistoryQuery, NewHistoryEntry};
+use crate::models::list::{NewList, TodoList};
+use crate::models::query::{TodoPage, TodoQuery};
+use crate::models::share::Share;
+use crate::models::tag::{NewTag, Tag};
+use crate::models::todo::{NewTodo, Todo, TodoPatch};
+use crate::models::tree::TodoTree;
+use crate::models::user::{NewUser, User, UserRecord};
+use crate::repository::error::{RepoError, RepoResult};
+use crate::repository::mutation::{Actor, Mutated, Mutation, Outcome};
 use async_trait::async_trait;
+use chrono::NaiveDateTime;
 
 #[async_trait]
 pub trait TodoRepo: Send + Sync + 'static {
-    async fn get_todos(&self) -> Vec<Todo>;
-    async fn create_todo(&self, new: NewTodo) -> Result<Todo, Error>;
-    async fn get_todo_by_id(&self, id: i32) -> Option<Todo>;
-    async fn delete_todo_by_id(&self, id: i32) -> Option<usize>;
-    async fn update_todo_by_id(&self, id: i32, mut todo: Todo) -> Option<Todo>;
+    /// Returns all todos ordered by id.
+    async fn get_todos(&self) -> RepoResult<Vec<Todo>> {
+        Ok(self.query_todos(TodoQuery::default()).await?.items)
+    }
+    /// Returns the page of todos selected by `query` and the total number of matches.
+    async fn query_todos(&self, query: TodoQuery) -> RepoResult<TodoPage>;
+    /// Applies `mutation` like the method it stands for does and reports every
+    /// todo it touched, see [`Mutated::changes`].
+    ///
+    /// All methods that write todos go through here, so a repository on top of
+    /// another one only needs to look at this method to see every change.
+    /// With an `actor` the changes are recorded in the audit log in the same
+    /// transaction, see [`TodoRepo::record_history`]; the methods pass none.
+    async fn mutate(&self, mutation: Mutation, actor: Option<Actor>) -> RepoResult<Mutated>;
+    /// Returns `RepoError::Missing("list")` if the list of the todo does not exist.
+    ///
+    /// A subtask must be in the list of its parent, an unknown parent is
+    /// `RepoError::Missing("parent")`.
+    async fn create_todo(&self, new: NewTodo) -> RepoResult<Todo> {
+        self.mutate(Mutation::Create { todo: new }, None)
+            .await?
+            .todo()
+    }
+    async fn get_todo_by_id(&self, id: i32) -> RepoResult<Todo>;
+    /// Moves the todo together with all its subtasks to the trash, see
+    /// [`TodoRepo::get_trash`]. Trashed todos are left out everywhere else as if
+    /// they were deleted, and they lose their blockers and the todos they block.
+    ///
+    /// Returns the number of trashed todos, or `RepoError::NotFound` if there was none.
+    async fn delete_todo_by_id(&self, id: i32) -> RepoResult<usize> {
+        self.delete_todo_if_version(id, None).await
+    }
+    /// Like [`TodoRepo::delete_todo_by_id`]; with `Some(version)` it fails with
+    /// `RepoError::PreconditionFailed` unless the todo still has that version.
+    async fn delete_todo_if_version(&self, id: i32, version: Option<i32>) -> RepoResult<usize> {
+        self.mutate(Mutation::Delete { id, version }, None)
+            .await?
+            .count()
+    }
+    /// Replaces the todo with `id`; the stored id is kept whatever `todo.todo_id` says.
+    /// Changing `list_id` moves the todo, see [`TodoRepo::create_todo`] for unknown lists.
+    ///
+    /// A todo with subtasks cannot be moved to another list, and a todo cannot
+    /// become a subtask of itself or of one of its subtasks. Once the update
+    /// completes the last open subtask, parents with `auto_complete` are
+    /// completed as well.
+    ///
+    /// Unless disabled for the repository, completing a todo with open blockers
+    /// returns `RepoError::Conflict`; such parents are not completed automatically.
+    ///
+    /// Every update, also completing a parent automatically, increases
+    /// [`Todo::version`].
+    async fn update_todo_by_id(&self, id: i32, todo: Todo) -> RepoResult<Todo> {
+        self.update_todo_if_version(id, todo, None).await
+    }
+    /// Like [`TodoRepo::update_todo_by_id`], see [`TodoRepo::delete_todo_if_version`]
+    /// for `version`.
+    async fn update_todo_if_version(
+        &self,
+        id: i32,
+        todo: Todo,
+        version: Option<i32>,
+    ) -> RepoResult<Todo> {
+        self.mutate(Mutation::Update { id, todo, version }, None)
+            .await?
+            .todo()
+    }
+    /// Merges the present fields of `patch` into the todo with `id`, see
+    /// [`TodoRepo::update_todo_by_id`].
+    async fn patch_todo_by_id(&self, id: i32, patch: TodoPatch) -> RepoResult<Todo> {
+        self.patch_todo_if_version(id, patch, None).await
+    }
+    /// Like [`TodoRepo::patch_todo_by_id`], see [`TodoRepo::delete_todo_if_version`]
+    /// for `version`.
+    async fn patch_todo_if_version(
+        &self,
+        id: i32,
+        patch: TodoPatch,
+        version: Option<i32>,
+    ) -> RepoResult<Todo> {
+        self.mutate(Mutation::Patch { id, patch, version }, None)
+            .await?
+            .todo()
+    }
+    /// Whether [`TodoRepo::apply_batch`] applies a batch in one transaction.
+    fn atomic_batches(&self) -> bool {
+        false
+    }
+    /// Applies `ops` in order, each like the method it stands for, and
+    /// returns one result per operation.
+    ///
+    /// With [`TodoRepo::atomic_batches`] either all operations succeed or
+    /// none; the first failure is returned as `RepoError::Batch` with its
+    /// index. Otherwise every operation runs on its own and a failed one does
+    /// not stop the others.
+    async fn apply_batch(&self, ops: Vec<BatchOp>) -> RepoResult<Vec<RepoResult<BatchOutcome>>> {
+        self.mutate(Mutation::Batch { ops }, None).await?.batch()
+    }
+    /// Deletes the completed todos, only those of the list `list_id` if
+    /// given, with their subtasks in one batch, see [`BatchOp::delete_completed`].
+    ///
+    /// Returns the number of deleted todos.
+    async fn delete_completed(&self, list_id: Option<i32>) -> RepoResult<usize> {
+        let query = TodoQuery {
+            list_id,
+            ..TodoQuery::default()
+        };
+        let todos = self.query_todos(query).await?.items;
+        deleted(self.apply_batch(BatchOp::delete_completed(&todos)).await?)
+    }
+    /// Completes the open todos, only those of the list `list_id` if given,
+    /// in one batch, see [`BatchOp::complete_open`].
+    ///
+    /// Returns the completed todos.
+    async fn complete_all(&self, list_id: Option<i32>) -> RepoResult<Vec<Todo>> {
+        let query = TodoQuery {
+            list_id,
+            ..TodoQuery::default()
+        };
+        let todos = self.query_todos(query).await?.items;
+        changed(self.apply_batch(BatchOp::complete_open(&todos)).await?)
+    }
+    /// Returns the trashed todos, only those of the user `owner_id` if given,
+    /// most recently trashed first.
+    async fn get_trash(&self, owner_id: Option<i32>) -> RepoResult<Vec<Todo>>;
+    /// Takes the trashed todo `id` out of the trash together with the subtasks
+    /// trashed along with it, as they were; blockers are not restored.
+    ///
+    /// Returns `RepoError::NotFound` unless the todo is in the trash, and
+    /// `RepoError::Conflict` while its parent is.
+    async fn restore_todo(&self, id: i32) -> RepoResult<Todo> {
+        self.mutate(Mutation::Restore { id }, None).await?.todo()
+    }
+    /// Deletes the trashed todo `id` with its subtasks for good.
+    ///
+    /// Returns the number of deleted todos, or `RepoError::NotFound` unless the
+    /// todo is in the trash.
+    async fn purge_todo(&self, id: i32) -> RepoResult<usize> {
+        self.mutate(Mutation::Purge { id }, None).await?.count()
+    }
+    /// Deletes the todos trashed before `before` for good, returns their number.
+    async fn purge_trash(&self, before: NaiveDateTime) -> RepoResult<usize> {
+        self.mutate(Mutation::PurgeTrash { before }, None)
+            .await?
+            .count()
+    }
+    /// Returns all todos below the todo with `id` ordered by id.
+    async fn get_descendants(&self, id: i32) -> RepoResult<Vec<Todo>>;
+    /// Returns the todo with `id` and its subtasks as a tree.
+    async fn get_subtree(&self, id: i32) -> RepoResult<TodoTree> {
+        let root = self.get_todo_by_id(id).await?;
+        let descendants = self.get_descendants(id).await?;
+        Ok(TodoTree::build(root, descendants))
+    }
+
+    /// Lets the todo `id` wait for the todo `blocker_id`, adding it twice is not an error.
+    ///
+    /// Returns `RepoError::Missing("blocker")` for an unknown blocker and a
+    /// validation error if the dependency would close a cycle.
+    async fn add_blocker(&self, id: i32, blocker_id: i32) -> RepoResult<Todo> {
+        self.mutate(Mutation::AddBlocker { id, blocker_id }, None)
+            .await?
+            .todo()
+    }
+    /// Removes the dependency, also if there was none.
+    async fn remove_blocker(&self, id: i32, blocker_id: i32) -> RepoResult<Todo> {
+        self.mutate(Mutation::RemoveBlocker { id, blocker_id }, None)
+            .await?
+            .todo()
+    }
+    /// Returns the open todos whose blockers are all completed ordered by id,
+    /// only those of the list `list_id` if given.
+    async fn get_next_todos(&self, list_id: Option<i32>) -> RepoResult<Vec<Todo>>;
+    /// Returns all todos of the list `list_id`, each after its blockers, see
+    /// [`topological_order`].
+    async fn get_ordered_todos(&self, list_id: i32) -> RepoResult<Vec<Todo>> {
+        self.get_list_by_id(list_id).await?;
+        let query = TodoQuery {
+            list_id: Some(list_id),
+            ..TodoQuery::default()
+        };
+        Ok(topological_order(self.query_todos(query).await?.items))
+    }
+
+    /// Returns all tags ordered by name; if `visible_to` is given, only the
+    /// tags of that user and those attached to todos they can see, see
+    /// [`TodoQuery::visible_to`].
+    async fn get_tags(&self, visible_to: Option<i32>) -> RepoResult<Vec<Tag>>;
+    /// Returns `RepoError::Conflict` if the owner already has a tag with the
+    /// same name (ignoring case). Tags without owner never conflict.
+    async fn create_tag(&self, new: NewTag) -> RepoResult<Tag>;
+    async fn get_tag_by_id(&self, id: i32) -> RepoResult<Tag>;
+    /// Renames the tag with `id`, see [`TodoRepo::create_tag`] for conflicts.
+    async fn update_tag_by_id(&self, id: i32, tag: NewTag) -> RepoResult<Tag>;
+    /// Deletes the tag and detaches it from all todos.
+    async fn delete_tag_by_id(&self, id: i32) -> RepoResult<usize>;
+    /// Attaches the tag to the todo, attaching it twice is not an error.
+    async fn attach_tag(&self, todo_id: i32, tag_id: i32) -> RepoResult<Todo> {
+        self.mutate(Mutation::AttachTag { todo_id, tag_id }, None)
+            .await?
+            .todo()
+    }
+    /// Detaches the tag from the todo, also if it was not attached.
+    async fn detach_tag(&self, todo_id: i32, tag_id: i32) -> RepoResult<Todo> {
+        self.mutate(Mutation::DetachTag { todo_id, tag_id }, None)
+            .await?
+            .todo()
+    }
+
+    /// Returns all lists ordered by id, the default list first.
+    async fn get_lists(&self) -> RepoResult<Vec<TodoList>>;
+    async fn create_list(&self, new: NewList) -> RepoResult<TodoList>;
+    async fn get_list_by_id(&self, id: i32) -> RepoResult<TodoList>;
+    /// Renames the list with `id`.
+    async fn update_list_by_id(&self, id: i32, list: NewList) -> RepoResult<TodoList>;
+    /// Deletes the list, and with `cascade` its todos as well.
+    ///
+    /// Returns `RepoError::Conflict` for the default list and, without
+    /// `cascade`, for a list that still has todos.
+    async fn delete_list_by_id(&self, id: i32, cascade: bool) -> RepoResult<usize> {
+        self.mutate(Mutation::DeleteList { id, cascade }, None)
+            .await?
+            .count()
+    }
+
+    /// Returns the users the todo is shared with ordered by user id.
+    async fn get_todo_shares(&self, todo_id: i32) -> RepoResult<Vec<Share>>;
+    /// Shares the todo with `share.user_id`, replacing an earlier role.
+    ///
+    /// Returns `RepoError::Missing("user")` for an unknown user.
+    async fn share_todo(&self, todo_id: i32, share: Share) -> RepoResult<Share>;
+    /// Stops sharing the todo with the user, also if it was not shared.
+    async fn unshare_todo(&self, todo_id: i32, user_id: i32) -> RepoResult<usize>;
+    /// Like [`TodoRepo::get_todo_shares`] for the list `list_id`.
+    async fn get_list_shares(&self, list_id: i32) -> RepoResult<Vec<Share>>;
+    /// Like [`TodoRepo::share_todo`] for the list `list_id`.
+    async fn share_list(&self, list_id: i32, share: Share) -> RepoResult<Share>;
+    /// Like [`TodoRepo::unshare_todo`] for the list `list_id`.
+    async fn unshare_list(&self, list_id: i32, user_id: i32) -> RepoResult<usize>;
+
+    /// Returns `RepoError::Conflict` if the username is taken (ignoring case).
+    async fn create_user(&self, new: NewUser) -> RepoResult<User>;
+    /// Returns `RepoError::Missing("user")` for an unknown id.
+    async fn get_user_by_id(&self, id: i32) -> RepoResult<User>;
+    /// Looks the user up by name (ignoring case) to check its password.
+    async fn get_user_record(&self, username: &str) -> RepoResult<UserRecord>;
+
+    /// Stores a new API key of `new.user_id`.
+    async fn create_api_key(&self, new: NewApiKeyRecord) -> RepoResult<ApiKey>;
+    /// Returns the keys of the user `user_id` ordered by id, expired ones included.
+    async fn get_api_keys(&self, user_id: i32) -> RepoResult<Vec<ApiKey>>;
+    /// Looks a key up by the hash of its secret, `RepoError::Missing("api key")`
+    /// if there is none.
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> RepoResult<ApiKey>;
+    /// Revokes the key `key_id` of the user `user_id`.
+    ///
+    /// Returns `RepoError::NotFound` for unknown keys and keys of other users.
+    async fn delete_api_key(&self, user_id: i32, key_id: i32) -> RepoResult<usize>;
+
+    /// Appends `entries` to the audit log. Entries are never changed and stay
+    /// when their todo is deleted for good.
+    async fn record_history(&self, entries: Vec<NewHistoryEntry>) -> RepoResult<()>;
+    /// Returns the entries of the audit log selected by `query` in the order
+    /// they were recorded.
+    async fn get_history(&self, query: HistoryQuery) -> RepoResult<Vec<HistoryEntry>>;
+}
+
+/// Fails with `RepoError::PreconditionFailed` if `expected` is given and
+/// differs from the version of the stored `todo`.
+pub fn check_version(todo: &Todo, expected: Option<i32>) -> RepoResult<()> {
+    match expected {
+        Some(expected) if expected != todo.version => Err(RepoError::stale(todo.todo_id, expected)),
+        _ => Ok(()),
+    }
+}
+
+/// [`Mutation::Batch`] without a transaction: every operation on its own
+/// through [`TodoRepo::mutate`] of `repo`, made by `actor`.
+pub async fn apply_one_by_one<R: TodoRepo + ?Sized>(
+    repo: &R,
+    ops: Vec<BatchOp>,
+    actor: Option<Actor>,
+) -> Mutated {
+    let mut results = Vec::with_capacity(ops.len());
+    let mut changes = Vec::new();
+    for op in ops {
+        let result = match repo.mutate(op.into(), actor).await {
+            Ok(mutated) => {
+                changes.extend(mutated.changes);
+                mutated.outcome.into_batch_outcome()
+            }
+            Err(err) => Err(err),
+        };
+        results.push(result);
+    }
+    Mutated::new(Outcome::Batch(results), changes)
+}
+
+/// The number of todos a batch of deletions deleted, or its first error.
+pub fn deleted(results: Vec<RepoResult<BatchOutcome>>) -> RepoResult<usize> {
+    let mut count = 0;
+    for result in results {
+        if let BatchOutcome::Deleted(deleted) = result? {
+            count += deleted;
+        }
+    }
+    Ok(count)
+}
+
+/// The todos a batch created or changed, or its first error.
+pub fn changed(results: Vec<RepoResult<BatchOutcome>>) -> RepoResult<Vec<Todo>> {
+    let mut todos = Vec::with_capacity(results.len());
+    for result in results {
+        if let BatchOutcome::Todo(todo) = result? {
+            todos.push(todo);
+        }
+    }
+    Ok(todos)
 }
@@ -0,0 +1,333 @@
+use chrono::{Duration, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::api_key::NewApiKey;
+use crate::models::list::NewList;
+use crate::models::tag::NewTag;
+use crate::models::todo::{NewTodo, Todo, TodoPatch};
+use crate::models::user::Credentials;
+use crate::recurrence::Recurrence;
+
+/// Maximum title length in characters, mirrors `VARCHAR(255)` in the migrations.
+pub const TITLE_MAX_LEN: usize = 255;
+
+/// Maximum length of a recurrence rule, the column is a `VARCHAR(255)` as well.
+pub const RECURRENCE_MAX_LEN: usize = 255;
+
+/// Maximum tag name length in characters, mirrors `VARCHAR(50)` in the migrations.
+pub const TAG_NAME_MAX_LEN: usize = 50;
+
+/// Maximum list name length in characters, mirrors `VARCHAR(100)` in the migrations.
+pub const LIST_NAME_MAX_LEN: usize = 100;
+
+/// Maximum username length in characters, mirrors `VARCHAR(50)` in the migrations.
+pub const USERNAME_MAX_LEN: usize = 50;
+
+/// Minimum password length in characters.
+pub const PASSWORD_MIN_LEN: usize = 8;
+
+/// Maximum password length in characters, hashing longer input is wasted work.
+pub const PASSWORD_MAX_LEN: usize = 128;
+
+/// Maximum API key name length in characters, mirrors `VARCHAR(100)` in the migrations.
+pub const API_KEY_NAME_MAX_LEN: usize = 100;
+
+/// How far `created_at` may lie ahead of the server clock before it counts as
+/// "in the future". Absorbs small clock differences between client and server.
+pub const CLOCK_SKEW_TOLERANCE_SECS: i64 = 5 * 60;
+
+/// A single rule violation on one field of a payload.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, code: &str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.to_string(),
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Implemented by every payload that is checked before it reaches a repository.
+pub trait Validate {
+    /// Returns all violations at once instead of stopping at the first one.
+    fn validate(&self) -> Result<(), Vec<FieldError>>;
+}
+
+fn check_title(title: &str, errors: &mut Vec<FieldError>) {
+    if title.trim().is_empty() {
+        errors.push(FieldError::new("title", "blank", "title must not be blank"));
+    } else if title.chars().count() > TITLE_MAX_LEN {
+        errors.push(FieldError::new(
+            "title",
+            "too_long",
+            format!("title must be at most {TITLE_MAX_LEN} characters"),
+        ));
+    }
+}
+
+fn check_description(description: Option<&str>, errors: &mut Vec<FieldError>) {
+    // An empty string is what the frontend sends for "no description", so only
+    // descriptions made of nothing but whitespace are rejected.
+    if let Some(description) = description {
+        if !description.is_empty() && description.trim().is_empty() {
+            errors.push(FieldError::new(
+                "description",
+                "blank",
+                "description must not consist of whitespace only",
+            ));
+        }
+    }
+}
+
+fn check_created_at(created_at: Option<NaiveDateTime>, errors: &mut Vec<FieldError>) {
+    if let Some(created_at) = created_at {
+        if created_at > Local::now().naive_local() + Duration::seconds(CLOCK_SKEW_TOLERANCE_SECS) {
+            errors.push(FieldError::new(
+                "created_at",
+                "in_future",
+                "created_at must not be in the future",
+            ));
+        }
+    }
+}
+
+fn check_remind_at(
+    remind_at: Option<NaiveDateTime>,
+    due_at: Option<NaiveDateTime>,
+    errors: &mut Vec<FieldError>,
+) {
+    if let (Some(remind_at), Some(due_at)) = (remind_at, due_at) {
+        if remind_at > due_at {
+            errors.push(FieldError::new(
+                "remind_at",
+                "after_due_at",
+                "remind_at must not be later than due_at",
+            ));
+        }
+    }
+}
+
+fn check_recurrence(recurrence: Option<&str>, errors: &mut Vec<FieldError>) {
+    if let Some(rule) = recurrence {
+        if rule.chars().count() > RECURRENCE_MAX_LEN {
+            errors.push(FieldError::new(
+                "recurrence",
+                "too_long",
+                format!("recurrence must be at most {RECURRENCE_MAX_LEN} characters"),
+            ));
+        } else if let Err(err) = rule.parse::<Recurrence>() {
+            errors.push(FieldError::new("recurrence", "invalid", err.to_string()));
+        }
+    }
+}
+
+fn check_name(name: &str, max_len: usize, errors: &mut Vec<FieldError>) {
+    if name.trim().is_empty() {
+        errors.push(FieldError::new("name", "blank", "name must not be blank"));
+    } else if name.chars().count() > max_len {
+        errors.push(FieldError::new(
+            "name",
+            "too_long",
+            format!("name must be at most {max_len} characters"),
+        ));
+    }
+}
+
+fn check_tag_name(name: &str, errors: &mut Vec<FieldError>) {
+    check_name(name, TAG_NAME_MAX_LEN, errors);
+    if errors.is_empty() && name.contains(',') {
+        // `?tag=a,b` separates names by commas.
+        errors.push(FieldError::new(
+            "name",
+            "invalid",
+            "name must not contain commas",
+        ));
+    }
+}
+
+fn check_username(username: &str, errors: &mut Vec<FieldError>) {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+    if username.is_empty() {
+        errors.push(FieldError::new(
+            "username",
+            "blank",
+            "username must not be blank",
+        ));
+    } else if username.chars().count() > USERNAME_MAX_LEN {
+        errors.push(FieldError::new(
+            "username",
+            "too_long",
+            format!("username must be at most {USERNAME_MAX_LEN} characters"),
+        ));
+    } else if !username.chars().all(allowed) {
+        errors.push(FieldError::new(
+            "username",
+            "invalid",
+            "username may only contain letters, digits, `_`, `-` and `.`",
+        ));
+    }
+}
+
+fn check_password(password: &str, errors: &mut Vec<FieldError>) {
+    let len = password.chars().count();
+    if len < PASSWORD_MIN_LEN {
+        errors.push(FieldError::new(
+            "password",
+            "too_short",
+            format!("password must be at least {PASSWORD_MIN_LEN} characters"),
+        ));
+    } else if len > PASSWORD_MAX_LEN {
+        errors.push(FieldError::new(
+            "password",
+            "too_long",
+            format!("password must be at most {PASSWORD_MAX_LEN} characters"),
+        ));
+    }
+}
+
+fn check_api_key(key: &NewApiKey, errors: &mut Vec<FieldError>) {
+    if key.scopes.is_empty() {
+        errors.push(FieldError::new(
+            "scopes",
+            "empty",
+            "a key needs at least one scope",
+        ));
+    }
+    if key
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Local::now().naive_local())
+    {
+        errors.push(FieldError::new(
+            "expires_at",
+            "in_past",
+            "expires_at must be in the future",
+        ));
+    }
+}
+
+fn into_result(errors: Vec<FieldError>) -> Result<(), Vec<FieldError>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// The rules shared by new and complete todos.
+fn validate_todo(
+    title: &str,
+    description: Option<&str>,
+    created_at: Option<NaiveDateTime>,
+    due_at: Option<NaiveDateTime>,
+    remind_at: Option<NaiveDateTime>,
+    recurrence: Option<&str>,
+) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    check_title(title, &mut errors);
+    check_description(description, &mut errors);
+    check_created_at(created_at, &mut errors);
+    check_remind_at(remind_at, due_at, &mut errors);
+    check_recurrence(recurrence, &mut errors);
+    into_result(errors)
+}
+
+impl Validate for NewTodo {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        validate_todo(
+            &self.title,
+            self.description.as_deref(),
+            self.created_at,
+            self.due_at,
+            self.remind_at,
+            self.recurrence.as_deref(),
+        )
+    }
+}
+
+impl Validate for Todo {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        validate_todo(
+            &self.title,
+            self.description.as_deref(),
+            self.created_at,
+            self.due_at,
+            self.remind_at,
+            self.recurrence.as_deref(),
+        )
+    }
+}
+
+impl Validate for TodoPatch {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        if let Some(title) = &self.title {
+            check_title(title, &mut errors);
+        }
+        if let Some(description) = &self.description {
+            check_description(description.as_deref(), &mut errors);
+        }
+        // With only one of them patched, repositories check it against the
+        // stored todo, see `TodoPatch::validate_against`.
+        if let (Some(remind_at), Some(due_at)) = (self.remind_at, self.due_at) {
+            check_remind_at(remind_at, due_at, &mut errors);
+        }
+        if let Some(recurrence) = &self.recurrence {
+            check_recurrence(recurrence.as_deref(), &mut errors);
+        }
+        into_result(errors)
+    }
+}
+
+impl TodoPatch {
+    /// Checks the rules spanning several fields on `stored` with the patch
+    /// applied, so patching only `remind_at` or `due_at` cannot break them.
+    pub fn validate_against(&self, stored: &Todo) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let remind_at = self.remind_at.unwrap_or(stored.remind_at);
+        let due_at = self.due_at.unwrap_or(stored.due_at);
+        check_remind_at(remind_at, due_at, &mut errors);
+        into_result(errors)
+    }
+}
+
+impl Validate for NewTag {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        check_tag_name(&self.name, &mut errors);
+        into_result(errors)
+    }
+}
+
+impl Validate for NewList {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        check_name(&self.name, LIST_NAME_MAX_LEN, &mut errors);
+        into_result(errors)
+    }
+}
+
+impl Validate for Credentials {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        check_username(&self.username, &mut errors);
+        check_password(&self.password, &mut errors);
+        into_result(errors)
+    }
+}
+
+impl Validate for NewApiKey {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        check_name(&self.name, API_KEY_NAME_MAX_LEN, &mut errors);
+        check_api_key(self, &mut errors);
+        into_result(errors)
+    }
+}
@@ -1,5 +1,5 @@
 #!/bin/bash
 
 cargo llvm-cov \
-      --ignore-filename-regex '(.cargo/|tests/|examples/|target/|build.rs|main.rs|todo.rs|mysql_repo.rs|schema.rs)' \
+      --ignore-filename-regex '(.cargo/|tests/|examples/|target/|build.rs|main.rs|todo.rs|mysql_repo.rs|postgres_repo.rs|schema.rs)' \
       --html
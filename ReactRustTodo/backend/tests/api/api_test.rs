@@ -1,21 +1,47 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use actix_web::{
-    dev::{Response, Service},
-    http::{self, StatusCode},
-    test, web, App, Error,
+    http::{header, StatusCode},
+    test, web, App,
 };
+use chrono::{Duration, Local};
 use serde_json::json;
 use TodoRustBackend::{
-    api,
-    models::todo::Todo,
-    repository::{self, mem_repo::MemRepo, RepoBox},
+    api::{
+        self,
+        api::{BulkResponse, TOTAL_COUNT_HEADER},
+        auth::LoginResponse,
+        error::{ErrorResponse, Problem, PROBLEM_CONTENT_TYPE},
+        version::{DEPRECATION_HEADER, SUNSET_HEADER},
+    },
+    auth::{hash_api_key, AuthKeys},
+    models::{
+        api_key::{ApiKey, IssuedApiKey, NewApiKey, NewApiKeyRecord, Scope},
+        history::{HistoryAction, HistoryEntry},
+        list::{TodoList, DEFAULT_LIST_ID},
+        share::{Role, Share},
+        todo::Todo,
+        user::{NewUser, User},
+    },
+    repository::{mem_repo::MemRepo, RepoBox},
 };
 
 fn test_mem_repo() -> RepoBox {
-    Arc::new(MemRepo {
-        inner: Arc::new(Mutex::new(Vec::new())),
-    })
+    Arc::new(MemRepo::new())
+}
+
+fn test_keys() -> AuthKeys {
+    AuthKeys::new(b"test secret")
+}
+
+/// `Authorization` header of the user `user_id`; the token is all the API checks.
+fn auth_as(user_id: i32) -> (header::HeaderName, String) {
+    let token = test_keys().issue(user_id).unwrap();
+    (header::AUTHORIZATION, format!("Bearer {token}"))
+}
+
+fn auth() -> (header::HeaderName, String) {
+    auth_as(1)
 }
 
 // Health endpoint tests
@@ -47,11 +73,15 @@ async fn get_all_todos_empty() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
             .configure(api::api::config),
     )
     .await;
 
-    let req = test::TestRequest::get().uri("/api/todos").to_request();
+    let req = test::TestRequest::get()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .to_request();
 
     let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
@@ -65,6 +95,7 @@ async fn get_all_todos_with_data() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
             .configure(api::api::config),
     )
     .await;
@@ -72,19 +103,24 @@ async fn get_all_todos_with_data() {
     // Create first todo
     let req1 = test::TestRequest::post()
         .uri("/api/todos")
-        .set_json(&json!({ "title": "First todo", "description": "First description" }))
+        .insert_header(auth())
+        .set_json(json!({ "title": "First todo", "description": "First description" }))
         .to_request();
     test::call_service(&app, req1).await;
 
     // Create second todo
     let req2 = test::TestRequest::post()
         .uri("/api/todos")
-        .set_json(&json!({ "title": "Second todo", "description": "Second description" }))
+        .insert_header(auth())
+        .set_json(json!({ "title": "Second todo", "description": "Second description" }))
         .to_request();
     test::call_service(&app, req2).await;
 
     // Get all todos
-    let req = test::TestRequest::get().uri("/api/todos").to_request();
+    let req = test::TestRequest::get()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .to_request();
     let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
 
@@ -100,6 +136,7 @@ async fn update_todo_by_id_success() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
             .configure(api::api::config),
     )
     .await;
@@ -107,7 +144,8 @@ async fn update_todo_by_id_success() {
     // Create a todo
     let create_req = test::TestRequest::post()
         .uri("/api/todos")
-        .set_json(&json!({ "title": "Original title", "description": "Original description" }))
+        .insert_header(auth())
+        .set_json(json!({ "title": "Original title", "description": "Original description" }))
         .to_request();
     let create_resp = test::call_service(&app, create_req).await;
     let created_todo: Todo = test::read_body_json(create_resp).await;
@@ -115,7 +153,8 @@ async fn update_todo_by_id_success() {
     // Update the todo
     let update_req = test::TestRequest::put()
         .uri(&format!("/api/todos/{}", created_todo.todo_id))
-        .set_json(&json!({
+        .insert_header(auth())
+        .set_json(json!({
             "todo_id": created_todo.todo_id,
             "title": "Updated title",
             "description": "Updated description",
@@ -141,13 +180,15 @@ async fn update_todo_by_id_not_found() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
             .configure(api::api::config),
     )
     .await;
 
     let req = test::TestRequest::put()
         .uri("/api/todos/999")
-        .set_json(&json!({
+        .insert_header(auth())
+        .set_json(json!({
             "todo_id": 999,
             "title": "Updated title",
             "description": "Updated description"
@@ -163,6 +204,7 @@ async fn delete_todo_by_id_success() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
             .configure(api::api::config),
     )
     .await;
@@ -170,7 +212,8 @@ async fn delete_todo_by_id_success() {
     // Create a todo
     let create_req = test::TestRequest::post()
         .uri("/api/todos")
-        .set_json(&json!({ "title": "To be deleted", "description": "Will be removed" }))
+        .insert_header(auth())
+        .set_json(json!({ "title": "To be deleted", "description": "Will be removed" }))
         .to_request();
     let create_resp = test::call_service(&app, create_req).await;
     let created_todo: Todo = test::read_body_json(create_resp).await;
@@ -178,6 +221,7 @@ async fn delete_todo_by_id_success() {
     // Delete the todo
     let delete_req = test::TestRequest::delete()
         .uri(&format!("/api/todos/{}", created_todo.todo_id))
+        .insert_header(auth())
         .to_request();
     let delete_resp = test::call_service(&app, delete_req).await;
     assert!(delete_resp.status().is_success());
@@ -185,6 +229,7 @@ async fn delete_todo_by_id_success() {
     // Verify it's deleted by trying to get it
     let get_req = test::TestRequest::get()
         .uri(&format!("/api/todos/{}", created_todo.todo_id))
+        .insert_header(auth())
         .to_request();
     let get_resp = test::call_service(&app, get_req).await;
     assert_eq!(get_resp.status(), StatusCode::NOT_FOUND);
@@ -196,6 +241,7 @@ async fn full_crud_workflow() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
             .configure(api::api::config),
     )
     .await;
@@ -203,7 +249,8 @@ async fn full_crud_workflow() {
     // 1. Create a todo
     let create_req = test::TestRequest::post()
         .uri("/api/todos")
-        .set_json(&json!({ "title": "Buy groceries", "description": "Milk, eggs, bread" }))
+        .insert_header(auth())
+        .set_json(json!({ "title": "Buy groceries", "description": "Milk, eggs, bread" }))
         .to_request();
     let create_resp = test::call_service(&app, create_req).await;
     assert!(create_resp.status().is_success());
@@ -212,6 +259,7 @@ async fn full_crud_workflow() {
     // 2. Read the todo
     let get_req = test::TestRequest::get()
         .uri(&format!("/api/todos/{}", created_todo.todo_id))
+        .insert_header(auth())
         .to_request();
     let get_resp = test::call_service(&app, get_req).await;
     assert!(get_resp.status().is_success());
@@ -221,7 +269,8 @@ async fn full_crud_workflow() {
     // 3. Update the todo
     let update_req = test::TestRequest::put()
         .uri(&format!("/api/todos/{}", created_todo.todo_id))
-        .set_json(&json!({
+        .insert_header(auth())
+        .set_json(json!({
             "todo_id": created_todo.todo_id,
             "title": "Buy groceries",
             "description": "Milk, eggs, bread, cheese",
@@ -237,6 +286,7 @@ async fn full_crud_workflow() {
     // 4. Delete the todo
     let delete_req = test::TestRequest::delete()
         .uri(&format!("/api/todos/{}", created_todo.todo_id))
+        .insert_header(auth())
         .to_request();
     let delete_resp = test::call_service(&app, delete_req).await;
     assert!(delete_resp.status().is_success());
@@ -244,6 +294,7 @@ async fn full_crud_workflow() {
     // 5. Verify deletion
     let verify_req = test::TestRequest::get()
         .uri(&format!("/api/todos/{}", created_todo.todo_id))
+        .insert_header(auth())
         .to_request();
     let verify_resp = test::call_service(&app, verify_req).await;
     assert_eq!(verify_resp.status(), StatusCode::NOT_FOUND);
@@ -254,12 +305,14 @@ async fn delete_todo_by_id_not_found() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
             .configure(api::api::config),
     )
     .await;
 
     let req = test::TestRequest::delete()
         .uri("/api/todos/999")
+        .insert_header(auth())
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::NOT_FOUND);
@@ -271,6 +324,7 @@ async fn get_todo_by_id_success() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
             .configure(api::api::config),
     )
     .await;
@@ -278,7 +332,8 @@ async fn get_todo_by_id_success() {
     // Create a todo
     let create_req = test::TestRequest::post()
         .uri("/api/todos")
-        .set_json(&json!({ "title": "Test todo", "description": "Test description" }))
+        .insert_header(auth())
+        .set_json(json!({ "title": "Test todo", "description": "Test description" }))
         .to_request();
     let create_resp = test::call_service(&app, create_req).await;
     let created_todo: Todo = test::read_body_json(create_resp).await;
@@ -286,6 +341,7 @@ async fn get_todo_by_id_success() {
     // Get the todo by id
     let req = test::TestRequest::get()
         .uri(&format!("/api/todos/{}", created_todo.todo_id))
+        .insert_header(auth())
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
@@ -301,11 +357,15 @@ async fn get_todo_by_id_not_found() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
             .configure(api::api::config),
     )
     .await;
 
-    let req = test::TestRequest::get().uri("/api/todos/999").to_request();
+    let req = test::TestRequest::get()
+        .uri("/api/todos/999")
+        .insert_header(auth())
+        .to_request();
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 }
@@ -315,13 +375,15 @@ async fn create_todo_without_description() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
             .configure(api::api::config),
     )
     .await;
 
     let req = test::TestRequest::post()
         .uri("/api/todos")
-        .set_json(&json!({ "title": "Buy groceries" }))
+        .insert_header(auth())
+        .set_json(json!({ "title": "Buy groceries" }))
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
@@ -335,13 +397,15 @@ async fn create_todo_success() {
     let app = test::init_service(
         App::new()
             .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
             .configure(api::api::config),
     )
     .await;
 
     let req = test::TestRequest::post()
         .uri("/api/todos")
-        .set_json(&json!({ "title": "New todo", "description": "New description" }))
+        .insert_header(auth())
+        .set_json(json!({ "title": "New todo", "description": "New description" }))
         .to_request();
     let resp = test::call_service(&app, req).await;
     assert!(resp.status().is_success());
@@ -352,3 +416,2218 @@ async fn create_todo_success() {
         Some("New description".to_string())
     );
 }
+
+// Error body tests
+#[actix_web::test]
+async fn not_found_returns_structured_error() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos/999")
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.code, "not_found");
+    assert_eq!(err.message, "todo not found");
+}
+
+#[actix_web::test]
+async fn create_todo_malformed_json_returns_bad_request() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .insert_header(("content-type", "application/json"))
+        .set_payload("{ \"title\": ")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.code, "bad_request");
+}
+
+#[actix_web::test]
+async fn non_numeric_id_returns_bad_request() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos/abc")
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.code, "bad_request");
+}
+
+// Filtering, sorting and pagination tests
+#[actix_web::test]
+async fn get_todos_with_query_parameters() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    for (title, completed) in [("Alpha", false), ("beta", true), ("Gamma", true)] {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header(auth())
+            .set_json(json!({ "title": title, "completed": completed }))
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?completed=true&sort=title&order=desc&limit=1")
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get(TOTAL_COUNT_HEADER).unwrap(), "2");
+
+    let todos: Vec<Todo> = test::read_body_json(resp).await;
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].title, "Gamma");
+}
+
+#[actix_web::test]
+async fn get_todos_without_parameters_reports_total() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get(TOTAL_COUNT_HEADER).unwrap(), "0");
+}
+
+#[actix_web::test]
+async fn get_todos_with_invalid_parameters_returns_bad_request() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    for uri in [
+        "/api/todos?limit=-1",
+        "/api/todos?sort=priority",
+        "/api/todos?completed=maybe",
+    ] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(auth())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{uri}");
+    }
+}
+
+// Patch todo tests
+#[actix_web::test]
+async fn patch_todo_by_id_success() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let create_req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({ "title": "Patch me", "description": "Keep me" }))
+        .to_request();
+    let create_resp = test::call_service(&app, create_req).await;
+    let created_todo: Todo = test::read_body_json(create_resp).await;
+
+    let patch_req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}", created_todo.todo_id))
+        .insert_header(auth())
+        .set_json(json!({ "completed": true, "todo_id": 99 }))
+        .to_request();
+    let patch_resp = test::call_service(&app, patch_req).await;
+    assert!(patch_resp.status().is_success());
+
+    let patched: Todo = test::read_body_json(patch_resp).await;
+    assert_eq!(patched.todo_id, created_todo.todo_id);
+    assert_eq!(patched.title, "Patch me");
+    assert_eq!(patched.description, Some("Keep me".to_string()));
+    assert_eq!(patched.completed, Some(true));
+}
+
+#[actix_web::test]
+async fn patch_todo_by_id_clears_description() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let create_req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({ "title": "Patch me", "description": "Remove me" }))
+        .to_request();
+    let create_resp = test::call_service(&app, create_req).await;
+    let created_todo: Todo = test::read_body_json(create_resp).await;
+
+    let patch_req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}", created_todo.todo_id))
+        .insert_header(auth())
+        .set_json(json!({ "description": null }))
+        .to_request();
+    let patched: Todo = test::call_and_read_body_json(&app, patch_req).await;
+    assert_eq!(patched.description, None);
+    assert_eq!(patched.title, "Patch me");
+}
+
+#[actix_web::test]
+async fn patch_todo_by_id_not_found() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::patch()
+        .uri("/api/todos/999")
+        .insert_header(auth())
+        .set_json(json!({ "completed": true }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+// Validation tests
+#[actix_web::test]
+async fn create_todo_with_invalid_payload_returns_unprocessable_entity() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({
+            "title": "   ",
+            "description": "  ",
+            "created_at": "2999-01-01T00:00:00"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.code, "validation_failed");
+    let fields: Vec<&str> = err.details.iter().map(|d| d.field.as_str()).collect();
+    assert_eq!(fields, vec!["title", "description", "created_at"]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .to_request();
+    let todos: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    assert!(todos.is_empty());
+}
+
+#[actix_web::test]
+async fn update_and_patch_with_too_long_title_return_unprocessable_entity() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let create_req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({ "title": "Valid" }))
+        .to_request();
+    let created_todo: Todo = test::call_and_read_body_json(&app, create_req).await;
+    let long_title = "x".repeat(256);
+
+    let put_req = test::TestRequest::put()
+        .uri(&format!("/api/todos/{}", created_todo.todo_id))
+        .insert_header(auth())
+        .set_json(json!({ "todo_id": created_todo.todo_id, "title": long_title }))
+        .to_request();
+    let resp = test::call_service(&app, put_req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let patch_req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}", created_todo.todo_id))
+        .insert_header(auth())
+        .set_json(json!({ "title": long_title }))
+        .to_request();
+    let resp = test::call_service(&app, patch_req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.details[0].field, "title");
+    assert_eq!(err.details[0].code, "too_long");
+}
+
+// Due date endpoints
+#[actix_web::test]
+async fn overdue_and_due_todos() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let now = Local::now().naive_local();
+    let seeds = [
+        ("Long overdue", Some(now - Duration::days(3)), false),
+        ("Overdue", Some(now - Duration::hours(1)), false),
+        ("Done but overdue", Some(now - Duration::days(1)), true),
+        ("Tomorrow", Some(now + Duration::days(1)), false),
+        ("Next month", Some(now + Duration::days(30)), false),
+        ("No due date", None, false),
+    ];
+    for (title, due_at, completed) in seeds {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header(auth())
+            .set_json(json!({ "title": title, "due_at": due_at, "completed": completed }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos/overdue")
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(TOTAL_COUNT_HEADER).unwrap(), "2");
+    let todos: Vec<Todo> = test::read_body_json(resp).await;
+    let titles: Vec<&str> = todos.iter().map(|t| t.title.as_str()).collect();
+    assert_eq!(titles, vec!["Long overdue", "Overdue"]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos/due")
+        .insert_header(auth())
+        .to_request();
+    let todos: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    let titles: Vec<&str> = todos.iter().map(|t| t.title.as_str()).collect();
+    assert_eq!(titles, vec!["Tomorrow"]);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos/due?days=31")
+        .insert_header(auth())
+        .to_request();
+    let todos: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    let titles: Vec<&str> = todos.iter().map(|t| t.title.as_str()).collect();
+    assert_eq!(titles, vec!["Tomorrow", "Next month"]);
+}
+
+#[actix_web::test]
+async fn due_todos_rejects_invalid_days() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    for uri in ["/api/todos/due?days=-1", "/api/todos/due?days=100000"] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(auth())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{uri}");
+        let err: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(err.code, "bad_request");
+    }
+}
+
+#[actix_web::test]
+async fn reminder_after_due_date_is_rejected() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({
+            "title": "Pay rent",
+            "due_at": "2030-01-01T00:00:00",
+            "remind_at": "2030-01-02T00:00:00"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.details[0].field, "remind_at");
+    assert_eq!(err.details[0].code, "after_due_at");
+}
+
+#[actix_web::test]
+async fn completing_recurring_todo_schedules_next_one() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({
+            "title": "Water plants",
+            "due_at": "2030-01-10T09:00:00",
+            "recurrence": "FREQ=DAILY;INTERVAL=2"
+        }))
+        .to_request();
+    let created: Todo = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}", created.todo_id))
+        .insert_header(auth())
+        .set_json(json!({ "completed": true }))
+        .to_request();
+    let completed: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(completed.recurrence, None);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?completed=false")
+        .insert_header(auth())
+        .to_request();
+    let open: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].due_at.unwrap().to_string(), "2030-01-12 09:00:00");
+    assert_eq!(open[0].recurrence.as_deref(), Some("FREQ=DAILY;INTERVAL=2"));
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({ "title": "Broken", "recurrence": "FREQ=YEARLY" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.details[0].field, "recurrence");
+    assert_eq!(err.details[0].code, "invalid");
+}
+
+#[actix_web::test]
+async fn tag_endpoints_and_tag_filter() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let mut tag_ids = Vec::new();
+    for name in ["backend", "urgent"] {
+        let req = test::TestRequest::post()
+            .uri("/api/tags")
+            .insert_header(auth())
+            .set_json(json!({ "name": name }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let tag: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(tag["name"], name);
+        tag_ids.push(tag["tag_id"].as_i64().unwrap());
+    }
+    let req = test::TestRequest::post()
+        .uri("/api/tags")
+        .insert_header(auth())
+        .set_json(json!({ "name": "Backend" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    for title in ["api", "hotfix"] {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header(auth())
+            .set_json(json!({ "title": title }))
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+    for (todo_id, tag_id) in [(1, tag_ids[0]), (2, tag_ids[0]), (2, tag_ids[1])] {
+        let req = test::TestRequest::put()
+            .uri(&format!("/api/todos/{todo_id}/tags/{tag_id}"))
+            .insert_header(auth())
+            .to_request();
+        let todo: Todo = test::call_and_read_body_json(&app, req).await;
+        assert!(todo.tags.iter().any(|tag| i64::from(tag.tag_id) == tag_id));
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?tag=backend,urgent&tag_mode=and")
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(TOTAL_COUNT_HEADER).unwrap(), "1");
+    let todos: Vec<Todo> = test::read_body_json(resp).await;
+    assert_eq!(todos[0].title, "hotfix");
+    assert_eq!(todos[0].tags.len(), 2);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/todos/2/tags/{}", tag_ids[1]))
+        .insert_header(auth())
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(todo.tags.len(), 1);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/tags/{}", tag_ids[1]))
+        .insert_header(auth())
+        .set_json(json!({ "name": "asap" }))
+        .to_request();
+    let tag: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(tag["name"], "asap");
+
+    let req = test::TestRequest::get()
+        .uri("/api/tags")
+        .insert_header(auth())
+        .to_request();
+    let tags: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    let names: Vec<&str> = tags.iter().map(|t| t["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["asap", "backend"]);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/tags/{}", tag_ids[0]))
+        .insert_header(auth())
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/tags/{}", tag_ids[0]))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.code, "not_found");
+    assert_eq!(err.message, "tag not found");
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos/1")
+        .insert_header(auth())
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    assert!(todo.tags.is_empty());
+
+    let req = test::TestRequest::post()
+        .uri("/api/tags")
+        .insert_header(auth())
+        .set_json(json!({ "name": "a,b" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[actix_web::test]
+async fn list_endpoints_and_nested_todo_routes() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/lists")
+        .insert_header(auth())
+        .to_request();
+    let lists: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(lists.len(), 1);
+    assert_eq!(lists[0]["name"], "Inbox");
+
+    let req = test::TestRequest::post()
+        .uri("/api/lists")
+        .insert_header(auth())
+        .set_json(json!({ "name": "Work" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let work: serde_json::Value = test::read_body_json(resp).await;
+    let work_id = work["list_id"].as_i64().unwrap();
+    let req = test::TestRequest::post()
+        .uri("/api/lists")
+        .insert_header(auth())
+        .set_json(json!({ "name": " " }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // The path decides the list, whatever the body says.
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/lists/{work_id}/todos"))
+        .insert_header(auth())
+        .set_json(json!({ "title": "Report", "list_id": 1 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let report: Todo = test::read_body_json(resp).await;
+    assert_eq!(i64::from(report.list_id), work_id);
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({ "title": "Milk" }))
+        .to_request();
+    let milk: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(milk.list_id, 1);
+
+    // The flat collection only shows the default list.
+    let req = test::TestRequest::get()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .to_request();
+    let todos: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].title, "Milk");
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/lists/{work_id}/todos"))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(TOTAL_COUNT_HEADER).unwrap(), "1");
+    let todos: Vec<Todo> = test::read_body_json(resp).await;
+    assert_eq!(todos[0].title, "Report");
+    // Flat routes by id reach todos of every list.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", report.todo_id))
+        .insert_header(auth())
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    // Nested routes only see todos of their list.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/lists/{work_id}/todos/{}", milk.todo_id))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/lists/{work_id}/todos/{}", milk.todo_id))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::get()
+        .uri("/api/lists/99/todos")
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.message, "list not found");
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/lists/{work_id}/todos/{}", report.todo_id))
+        .insert_header(auth())
+        .set_json(json!({
+            "todo_id": report.todo_id,
+            "title": "Quarterly report",
+            "description": null,
+            "created_at": null,
+            "completed": false
+        }))
+        .to_request();
+    let updated: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(updated.title, "Quarterly report");
+    assert_eq!(i64::from(updated.list_id), work_id);
+
+    // Moving a todo into another list.
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/lists/1/todos/{}", milk.todo_id))
+        .insert_header(auth())
+        .set_json(json!({ "list_id": work_id }))
+        .to_request();
+    let moved: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(i64::from(moved.list_id), work_id);
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}", milk.todo_id))
+        .insert_header(auth())
+        .set_json(json!({ "list_id": 99 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/lists/{work_id}"))
+        .insert_header(auth())
+        .set_json(json!({ "name": "Office" }))
+        .to_request();
+    let renamed: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(renamed["name"], "Office");
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/lists/{work_id}"))
+        .insert_header(auth())
+        .to_request();
+    let list: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(list["name"], "Office");
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/lists/{work_id}/todos/{}", milk.todo_id))
+        .insert_header(auth())
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+}
+
+#[actix_web::test]
+async fn delete_list_cascades_only_on_request() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/api/lists")
+        .insert_header(auth())
+        .set_json(json!({ "name": "Work" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let work: serde_json::Value = test::read_body_json(resp).await;
+    let work_id = work["list_id"].as_i64().unwrap();
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/lists/{work_id}/todos"))
+        .insert_header(auth())
+        .set_json(json!({ "title": "Report" }))
+        .to_request();
+    let report: Todo = test::call_and_read_body_json(&app, req).await;
+
+    for uri in [
+        format!("/api/lists/{work_id}"),
+        format!("/api/lists/{work_id}?cascade=false"),
+        "/api/lists/1?cascade=true".to_string(),
+    ] {
+        let req = test::TestRequest::delete()
+            .uri(&uri)
+            .insert_header(auth())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT, "{uri}");
+        let err: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(err.code, "conflict");
+    }
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/lists/{work_id}?cascade=yes"))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/lists/{work_id}?cascade=true"))
+        .insert_header(auth())
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", report.todo_id))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/lists/{work_id}"))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn subtree_endpoint_and_subtask_rules() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({ "title": "Trip", "auto_complete": true }))
+        .to_request();
+    let trip: Todo = test::call_and_read_body_json(&app, req).await;
+    let mut subtasks = Vec::new();
+    for title in ["Book flight", "Pack"] {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header(auth())
+            .set_json(json!({ "title": title, "parent_id": trip.todo_id }))
+            .to_request();
+        let subtask: Todo = test::call_and_read_body_json(&app, req).await;
+        subtasks.push(subtask.todo_id);
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}/subtree", trip.todo_id))
+        .insert_header(auth())
+        .to_request();
+    let tree: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(tree["title"], "Trip");
+    assert_eq!(tree["progress"], json!({ "completed": 0, "total": 2 }));
+    assert_eq!(tree["children"][1]["title"], "Pack");
+    assert_eq!(tree["children"][1]["children"], json!([]));
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}", trip.todo_id))
+        .insert_header(auth())
+        .set_json(json!({ "parent_id": subtasks[0] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.details[0].code, "cycle");
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({ "title": "Orphan", "parent_id": 99 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    for id in &subtasks {
+        let req = test::TestRequest::patch()
+            .uri(&format!("/api/todos/{id}"))
+            .insert_header(auth())
+            .set_json(json!({ "completed": true }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", trip.todo_id))
+        .insert_header(auth())
+        .to_request();
+    let trip_now: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(trip_now.completed, Some(true));
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/todos/{}", trip.todo_id))
+        .insert_header(auth())
+        .to_request();
+    let deleted: usize = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(deleted, 3);
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}/subtree", trip.todo_id))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn blocker_endpoints_next_and_ordered_todos() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+    let mut ids = Vec::new();
+    for title in ["Ship", "Build", "Design"] {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header(auth())
+            .set_json(json!({ "title": title }))
+            .to_request();
+        let todo: Todo = test::call_and_read_body_json(&app, req).await;
+        ids.push(todo.todo_id);
+    }
+    let (ship, build, design) = (ids[0], ids[1], ids[2]);
+    for (id, blocker) in [(ship, build), (build, design)] {
+        let req = test::TestRequest::put()
+            .uri(&format!("/api/todos/{id}/blockers/{blocker}"))
+            .insert_header(auth())
+            .to_request();
+        let todo: Todo = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(todo.blocked_by, vec![blocker]);
+    }
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/todos/{design}/blockers/{ship}"))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.details[0].code, "cycle");
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/todos/{ship}/blockers/99"))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos/next")
+        .insert_header(auth())
+        .to_request();
+    let next: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        next.iter().map(|t| t.todo_id).collect::<Vec<_>>(),
+        vec![design]
+    );
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/ordered?list_id={DEFAULT_LIST_ID}"))
+        .insert_header(auth())
+        .to_request();
+    let ordered: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    let order: Vec<i32> = ordered.iter().map(|t| t.todo_id).collect();
+    assert_eq!(order, vec![design, build, ship]);
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{ship}"))
+        .insert_header(auth())
+        .set_json(json!({ "completed": true }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.code, "conflict");
+    assert!(err.message.contains(&build.to_string()));
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/todos/{ship}/blockers/{build}"))
+        .insert_header(auth())
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    assert!(todo.blocked_by.is_empty());
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{ship}"))
+        .insert_header(auth())
+        .set_json(json!({ "completed": true }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+}
+
+#[actix_web::test]
+async fn todo_routes_require_a_valid_token() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let expired = test_keys()
+        .with_ttl(Duration::seconds(-10))
+        .issue(1)
+        .unwrap();
+    let forged = AuthKeys::new(b"other secret").issue(1).unwrap();
+    let headers = [
+        None,
+        Some("Basic YWxpY2U6c2VjcmV0".to_string()),
+        Some(format!("Bearer {expired}")),
+        Some(format!("Bearer {forged}")),
+    ];
+    for value in headers {
+        for uri in ["/api/todos", "/api/todos/1", "/api/lists", "/api/auth/me"] {
+            let mut req = test::TestRequest::get().uri(uri);
+            if let Some(value) = &value {
+                req = req.insert_header((header::AUTHORIZATION, value.as_str()));
+            }
+            let resp = test::call_service(&app, req.to_request()).await;
+            assert_eq!(
+                resp.status(),
+                StatusCode::UNAUTHORIZED,
+                "{uri} with {value:?}"
+            );
+            assert_eq!(
+                resp.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+                "Bearer"
+            );
+            let err: ErrorResponse = test::read_body_json(resp).await;
+            assert_eq!(err.code, "unauthorized");
+        }
+    }
+
+    // Writes are rejected before anything is stored.
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .set_json(json!({ "title": "Anonymous" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    let req = test::TestRequest::get()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .to_request();
+    let todos: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    assert!(todos.is_empty());
+
+    let req = test::TestRequest::get().uri("/api/health").to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+}
+
+#[actix_web::test]
+async fn register_login_and_me() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+    let credentials = json!({ "username": "alice", "password": "correct horse" });
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(&credentials)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let v: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(v["username"], "alice");
+    assert!(v.get("password_hash").is_none());
+    let alice: User = serde_json::from_value(v).unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({ "username": "Alice", "password": "another one" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::CONFLICT
+    );
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({ "username": "bob", "password": "short" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.details[0].field, "password");
+
+    for (username, password) in [("alice", "wrong password"), ("bob", "correct horse")] {
+        let req = test::TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({ "username": username, "password": password }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let err: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(err.message, "unauthorized: invalid username or password");
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(json!({ "username": "ALICE", "password": "correct horse" }))
+        .to_request();
+    let login: LoginResponse = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(login.token_type, "Bearer");
+    assert_eq!(login.expires_in, 24 * 60 * 60);
+    assert_eq!(login.user, alice);
+
+    let req = test::TestRequest::get()
+        .uri("/api/auth/me")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", login.token)))
+        .to_request();
+    let me: User = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(me, alice);
+}
+
+#[actix_web::test]
+async fn users_only_see_their_own_todos() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth_as(1))
+        .set_json(json!({ "title": "Alice's" }))
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(todo.owner_id, Some(1));
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth_as(2))
+        .set_json(json!({ "title": "Bob's", "owner_id": 1 }))
+        .to_request();
+    let theirs: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(theirs.owner_id, Some(2));
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos")
+        .insert_header(auth_as(2))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get(TOTAL_COUNT_HEADER).unwrap(), "1");
+    let todos: Vec<Todo> = test::read_body_json(resp).await;
+    assert_eq!(todos[0].todo_id, theirs.todo_id);
+
+    let uri = format!("/api/todos/{}", todo.todo_id);
+    let requests = [
+        test::TestRequest::get().uri(&uri),
+        test::TestRequest::patch()
+            .uri(&uri)
+            .set_json(json!({ "completed": true })),
+        test::TestRequest::delete().uri(&uri),
+        test::TestRequest::get().uri(&format!("{uri}/subtree")),
+        test::TestRequest::get().uri(&format!(
+            "/api/lists/{DEFAULT_LIST_ID}/todos/{}",
+            todo.todo_id
+        )),
+    ];
+    for req in requests {
+        let resp = test::call_service(&app, req.insert_header(auth_as(2)).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    // Nor can their todos be used as parent or blocker.
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth_as(2))
+        .set_json(json!({ "title": "Sub", "parent_id": todo.todo_id }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/api/todos/{}/blockers/{}",
+            theirs.todo_id, todo.todo_id
+        ))
+        .insert_header(auth_as(2))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.message, "blocker not found");
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(auth_as(1))
+        .to_request();
+    let unchanged: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_ne!(unchanged.completed, Some(true));
+}
+
+#[actix_web::test]
+async fn tags_can_only_be_changed_by_their_owner() {
+    let repo = test_mem_repo();
+    for username in ["alice", "bob"] {
+        let new_user = NewUser {
+            username: username.to_string(),
+            password_hash: "hash".to_string(),
+        };
+        repo.create_user(new_user).await.unwrap();
+    }
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(repo))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+    let tag_names = |tags: Vec<serde_json::Value>| -> Vec<String> {
+        tags.iter()
+            .map(|tag| tag["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let req = test::TestRequest::post()
+        .uri("/api/tags")
+        .insert_header(auth_as(1))
+        .set_json(json!({ "name": "private", "owner_id": 2 }))
+        .to_request();
+    let tag: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(tag["owner_id"], 1);
+    let uri = format!("/api/tags/{}", tag["tag_id"]);
+
+    // Bob neither sees the tag nor learns its name is taken.
+    let req = test::TestRequest::get()
+        .uri("/api/tags")
+        .insert_header(auth_as(2))
+        .to_request();
+    let tags: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert!(tags.is_empty());
+    for req in [
+        test::TestRequest::get().uri(&uri),
+        test::TestRequest::delete().uri(&uri),
+    ] {
+        let resp = test::call_service(&app, req.insert_header(auth_as(2)).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+    let req = test::TestRequest::post()
+        .uri("/api/tags")
+        .insert_header(auth_as(2))
+        .set_json(json!({ "name": "Private" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    // Once it is on a todo shared with him, he sees it but cannot change it.
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth_as(1))
+        .set_json(json!({ "title": "Alice's" }))
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/api/todos/{}/tags/{}",
+            todo.todo_id, tag["tag_id"]
+        ))
+        .insert_header(auth_as(1))
+        .to_request();
+    test::call_service(&app, req).await;
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/todos/{}/shares/2", todo.todo_id))
+        .insert_header(auth_as(1))
+        .set_json(json!({ "role": "viewer" }))
+        .to_request();
+    test::call_service(&app, req).await;
+    let req = test::TestRequest::get()
+        .uri("/api/tags")
+        .insert_header(auth_as(2))
+        .to_request();
+    let tags: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(tag_names(tags), vec!["private", "Private"]);
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(auth_as(2))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.code, "forbidden");
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(auth_as(2))
+        .set_json(json!({ "name": "mine" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    // He can use it on his own todos, though.
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth_as(2))
+        .set_json(json!({ "title": "Bob's" }))
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::put()
+        .uri(&format!(
+            "/api/todos/{}/tags/{}",
+            todo.todo_id, tag["tag_id"]
+        ))
+        .insert_header(auth_as(2))
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(todo.tags[0].name, "private");
+
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(auth_as(1))
+        .set_json(json!({ "name": "shared" }))
+        .to_request();
+    let renamed: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(renamed["owner_id"], 1);
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(auth_as(1))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+}
+
+#[actix_web::test]
+async fn api_keys_act_with_their_scopes() {
+    let repo = test_mem_repo();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(repo.clone()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(json!({ "username": "ci", "password": "correct horse" }))
+        .to_request();
+    let user: User = test::call_and_read_body_json(&app, req).await;
+    let bearer = |secret: &str| (header::AUTHORIZATION, format!("Bearer {secret}"));
+
+    let mut keys = Vec::new();
+    for scope in ["read", "write"] {
+        let req = test::TestRequest::post()
+            .uri("/api/keys")
+            .insert_header(auth_as(user.user_id))
+            .set_json(json!({ "name": format!("{scope} bot"), "scopes": [scope] }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let issued: IssuedApiKey = test::read_body_json(resp).await;
+        assert!(issued.secret.starts_with("tdk_"));
+        assert!(issued.secret.starts_with(&issued.key.prefix));
+        keys.push(issued);
+    }
+    let (read, write) = (&keys[0], &keys[1]);
+
+    // Listing never shows secrets or hashes.
+    let req = test::TestRequest::get()
+        .uri("/api/keys")
+        .insert_header(auth_as(user.user_id))
+        .to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    let listed: Vec<ApiKey> = serde_json::from_slice(&body).unwrap();
+    assert_eq!(listed, vec![read.key.clone(), write.key.clone()]);
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(!body.contains(&read.secret) && !body.contains("key_hash"));
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos")
+        .insert_header(bearer(&read.secret))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    for req in [
+        test::TestRequest::post()
+            .uri("/api/todos")
+            .set_json(json!({ "title": "Deploy" })),
+        test::TestRequest::get().uri("/api/keys"),
+    ] {
+        let resp =
+            test::call_service(&app, req.insert_header(bearer(&read.secret)).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let err: ErrorResponse = test::read_body_json(resp).await;
+        assert_eq!(err.code, "forbidden");
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(bearer(&write.secret))
+        .set_json(json!({ "title": "Deploy" }))
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(todo.owner_id, Some(user.user_id));
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", todo.todo_id))
+        .insert_header(bearer(&write.secret))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // Revoked, unknown and expired keys are rejected.
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/keys/{}", read.key.key_id))
+        .insert_header(auth_as(user.user_id))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    repo.create_api_key(NewApiKeyRecord {
+        user_id: user.user_id,
+        key: NewApiKey {
+            name: "old".to_string(),
+            scopes: vec![Scope::Read],
+            expires_at: Some(Local::now().naive_local() - Duration::minutes(1)),
+        },
+        prefix: "tdk_expired".to_string(),
+        key_hash: hash_api_key("tdk_expired"),
+    })
+    .await
+    .unwrap();
+    for secret in [read.secret.as_str(), "tdk_unknown", "tdk_expired"] {
+        let req = test::TestRequest::get()
+            .uri("/api/todos")
+            .insert_header(bearer(secret))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{secret}");
+    }
+}
+
+#[actix_web::test]
+async fn api_keys_are_validated() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+    let req = test::TestRequest::post()
+        .uri("/api/keys")
+        .insert_header(auth())
+        .set_json(json!({ "name": "bot", "scopes": [] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let req = test::TestRequest::post()
+        .uri("/api/keys")
+        .insert_header(auth())
+        .set_json(json!({ "name": "bot", "scopes": ["root"] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn shared_todos_and_lists_follow_roles() {
+    let repo = test_mem_repo();
+    for username in ["alice", "bob"] {
+        let new_user = NewUser {
+            username: username.to_string(),
+            password_hash: "hash".to_string(),
+        };
+        repo.create_user(new_user).await.unwrap();
+    }
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(repo))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth_as(1))
+        .set_json(json!({ "title": "Alice's" }))
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    let uri = format!("/api/todos/{}", todo.todo_id);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("{uri}/shares/2"))
+        .insert_header(auth_as(1))
+        .set_json(json!({ "role": "viewer" }))
+        .to_request();
+    let share: Share = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(share.role, Role::Viewer);
+
+    // Bob sees the todo marked as shared, Alice sees it as her own.
+    for (user_id, shared) in [(1, false), (2, true)] {
+        let req = test::TestRequest::get()
+            .uri("/api/todos")
+            .insert_header(auth_as(user_id))
+            .to_request();
+        let todos: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(todos[0]["todo_id"], todo.todo_id);
+        assert_eq!(todos[0]["shared"], shared);
+    }
+
+    // A viewer can neither change nor reshare it.
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(auth_as(2))
+        .set_json(json!({ "completed": true }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.code, "forbidden");
+    let req = test::TestRequest::put()
+        .uri(&format!("{uri}/shares/2"))
+        .insert_header(auth_as(2))
+        .set_json(json!({ "role": "owner" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let req = test::TestRequest::put()
+        .uri(&format!("{uri}/shares/2"))
+        .insert_header(auth_as(1))
+        .set_json(json!({ "role": "editor" }))
+        .to_request();
+    test::call_service(&app, req).await;
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(auth_as(2))
+        .set_json(json!({ "completed": true }))
+        .to_request();
+    let patched: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(patched.completed, Some(true));
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(auth_as(2))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{uri}/shares"))
+        .insert_header(auth_as(1))
+        .to_request();
+    let shares: Vec<Share> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        shares,
+        vec![Share {
+            user_id: 2,
+            role: Role::Editor
+        }]
+    );
+
+    // Unknown users and sharing with oneself are rejected.
+    let req = test::TestRequest::put()
+        .uri(&format!("{uri}/shares/3"))
+        .insert_header(auth_as(1))
+        .set_json(json!({ "role": "viewer" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    let req = test::TestRequest::put()
+        .uri(&format!("{uri}/shares/1"))
+        .insert_header(auth_as(1))
+        .set_json(json!({ "role": "viewer" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+
+    // Lists belong to their creator and are shared with all their todos.
+    let req = test::TestRequest::post()
+        .uri("/api/lists")
+        .insert_header(auth_as(1))
+        .set_json(json!({ "name": "Project" }))
+        .to_request();
+    let list: TodoList = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(list.owner_id, Some(1));
+    let list_uri = format!("/api/lists/{}", list.list_id);
+    let req = test::TestRequest::get()
+        .uri(&list_uri)
+        .insert_header(auth_as(2))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    let req = test::TestRequest::put()
+        .uri(&format!("{list_uri}/shares/2"))
+        .insert_header(auth_as(1))
+        .set_json(json!({ "role": "viewer" }))
+        .to_request();
+    test::call_service(&app, req).await;
+    let req = test::TestRequest::get()
+        .uri(&list_uri)
+        .insert_header(auth_as(2))
+        .to_request();
+    let seen: TodoList = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(seen, list);
+
+    // Bob may leave the list again.
+    let req = test::TestRequest::delete()
+        .uri(&format!("{list_uri}/shares/2"))
+        .insert_header(auth_as(2))
+        .to_request();
+    let removed: usize = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(removed, 1);
+}
+
+#[actix_web::test]
+async fn editors_cannot_move_shared_todos_into_their_lists() {
+    let repo = test_mem_repo();
+    for username in ["alice", "bob"] {
+        let new_user = NewUser {
+            username: username.to_string(),
+            password_hash: "hash".to_string(),
+        };
+        repo.create_user(new_user).await.unwrap();
+    }
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(repo))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth_as(1))
+        .set_json(json!({ "title": "Alice's" }))
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    let uri = format!("/api/todos/{}", todo.todo_id);
+    let req = test::TestRequest::put()
+        .uri(&format!("{uri}/shares/2"))
+        .insert_header(auth_as(1))
+        .set_json(json!({ "role": "editor" }))
+        .to_request();
+    test::call_service(&app, req).await;
+    let req = test::TestRequest::post()
+        .uri("/api/lists")
+        .insert_header(auth_as(2))
+        .set_json(json!({ "name": "Bob's" }))
+        .to_request();
+    let list: TodoList = test::call_and_read_body_json(&app, req).await;
+
+    // As owner of the list, Bob would own the todo afterwards.
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(auth_as(2))
+        .set_json(json!({ "list_id": list.list_id }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let mut moved = todo.clone();
+    moved.list_id = list.list_id;
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(auth_as(2))
+        .set_json(&moved)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::post()
+        .uri("/api/todos/bulk")
+        .insert_header(auth_as(2))
+        .set_json(json!({ "operations": [
+            { "op": "update", "id": todo.todo_id, "patch": { "list_id": list.list_id } },
+        ] }))
+        .to_request();
+    let body: BulkResponse = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body.results[0].status, 403);
+
+    // Editing without moving still works, and the todo stays where it was.
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(auth_as(2))
+        .set_json(json!({ "title": "Edited by Bob" }))
+        .to_request();
+    let patched: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(patched.list_id, todo.list_id);
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(auth_as(2))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::FORBIDDEN
+    );
+}
+
+#[actix_web::test]
+async fn conditional_requests_use_etags() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({ "title": "Shared draft" }))
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(todo.version, 1);
+    let uri = format!("/api/todos/{}", todo.todo_id);
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    let etag = resp.headers().get(header::ETAG).unwrap().clone();
+
+    // Unchanged: the cached copy is still good.
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(auth())
+        .insert_header((header::IF_NONE_MATCH, etag.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+    // The first writer wins and gets the new tag back.
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(auth())
+        .insert_header((header::IF_MATCH, etag.clone()))
+        .set_json(json!({ "title": "Final" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let new_etag = resp.headers().get(header::ETAG).unwrap().clone();
+    assert_ne!(new_etag, etag);
+    let patched: Todo = test::read_body_json(resp).await;
+    assert_eq!(patched.version, 2);
+
+    // The second one still holds the old tag.
+    let mut stale = patched.clone();
+    stale.title = "Lost update".to_string();
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(auth())
+        .insert_header((header::IF_MATCH, etag.clone()))
+        .set_json(&stale)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.code, "precondition_failed");
+    let req = test::TestRequest::delete()
+        .uri(&format!(
+            "/api/lists/{}/todos/{}",
+            todo.list_id, todo.todo_id
+        ))
+        .insert_header(auth())
+        .insert_header((header::IF_MATCH, etag.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(auth())
+        .insert_header((header::IF_NONE_MATCH, etag))
+        .to_request();
+    let current: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(current.title, "Final");
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(auth())
+        .insert_header((header::IF_MATCH, new_etag))
+        .to_request();
+    let deleted: usize = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(deleted, 1);
+}
+
+// API version tests
+#[actix_web::test]
+async fn versions_share_the_repository() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/v1/todos")
+        .insert_header(auth())
+        .set_json(json!({ "title": "Written by v1" }))
+        .to_request();
+    let created: Todo = test::call_and_read_body_json(&app, req).await;
+
+    for prefix in ["/api", "/api/v1", "/api/v2"] {
+        let req = test::TestRequest::get()
+            .uri(&format!("{prefix}/todos/{}", created.todo_id))
+            .insert_header(auth())
+            .to_request();
+        let todo: Todo = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(todo.title, "Written by v1", "{prefix}");
+        assert_eq!(todo.version, created.version);
+    }
+}
+
+#[actix_web::test]
+async fn deprecated_versions_send_sunset_headers() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    for (uri, successor) in [
+        (
+            "/api/todos/1",
+            "</api/v2/todos/1>; rel=\"successor-version\"",
+        ),
+        (
+            "/api/v1/todos/1",
+            "</api/v2/todos/1>; rel=\"successor-version\"",
+        ),
+        (
+            "/api/v1/health",
+            "</api/v2/health>; rel=\"successor-version\"",
+        ),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(auth())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        // Errors are marked too.
+        let headers = resp.headers();
+        assert_eq!(
+            headers.get(DEPRECATION_HEADER).unwrap(),
+            "@1792281600",
+            "{uri}"
+        );
+        assert_eq!(
+            headers.get(SUNSET_HEADER).unwrap(),
+            "Fri, 30 Apr 2027 00:00:00 GMT"
+        );
+        assert_eq!(headers.get(header::LINK).unwrap(), successor);
+    }
+
+    let req = test::TestRequest::get().uri("/api/v2/health").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert!(resp.headers().get(DEPRECATION_HEADER).is_none());
+    assert!(resp.headers().get(SUNSET_HEADER).is_none());
+    assert!(resp.headers().get(header::LINK).is_none());
+}
+
+#[actix_web::test]
+async fn v2_errors_are_problem_details() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let requests = [
+        (
+            test::TestRequest::get()
+                .uri("/api/v2/todos/99")
+                .insert_header(auth()),
+            StatusCode::NOT_FOUND,
+            "not_found",
+        ),
+        (
+            test::TestRequest::get()
+                .uri("/api/v2/todos/abc")
+                .insert_header(auth()),
+            StatusCode::BAD_REQUEST,
+            "bad_request",
+        ),
+        (
+            test::TestRequest::post()
+                .uri("/api/v2/todos")
+                .insert_header(auth())
+                .set_json(json!({ "title": "" })),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "validation_failed",
+        ),
+        (
+            test::TestRequest::get().uri("/api/v2/todos"),
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+        ),
+        (
+            test::TestRequest::get().uri("/api/v2/not-existing"),
+            StatusCode::NOT_FOUND,
+            "not_found",
+        ),
+    ];
+    for (req, status, code) in requests {
+        let resp = test::call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status(), status, "{code}");
+        let content_type = resp.headers().get(header::CONTENT_TYPE).unwrap();
+        assert_eq!(content_type, PROBLEM_CONTENT_TYPE);
+        if status == StatusCode::UNAUTHORIZED {
+            assert_eq!(
+                resp.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+                "Bearer"
+            );
+        }
+
+        let problem: Problem = test::read_body_json(resp).await;
+        assert_eq!(problem.problem_type, "about:blank");
+        assert_eq!(problem.status, status.as_u16());
+        assert_eq!(problem.title, status.canonical_reason().unwrap());
+        assert_eq!(problem.code, code);
+        assert_eq!(!problem.errors.is_empty(), code == "validation_failed");
+    }
+
+    // v1 keeps its error shape.
+    let req = test::TestRequest::get()
+        .uri("/api/v1/todos/99")
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/json"
+    );
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.code, "not_found");
+}
+
+#[actix_web::test]
+async fn bulk_operations_report_each_result() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth_as(2))
+        .set_json(json!({ "title": "Not mine" }))
+        .to_request();
+    let theirs: Todo = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos/bulk")
+        .insert_header(auth())
+        .set_json(json!({ "operations": [
+            { "op": "create", "todo": { "title": "First" } },
+            { "op": "create", "todo": { "title": "" } },
+            { "op": "complete", "id": theirs.todo_id },
+            { "op": "update", "id": 99, "patch": { "title": "Second" }, "version": 1 },
+            { "op": "delete", "id": 99 },
+        ] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    let body: BulkResponse = test::read_body_json(resp).await;
+    assert!(!body.atomic);
+    let statuses: Vec<u16> = body.results.iter().map(|item| item.status).collect();
+    assert_eq!(statuses, vec![200, 422, 404, 404, 404]);
+    assert_eq!(body.results[0].todo.as_ref().unwrap().title, "First");
+    let error = body.results[1].error.as_ref().unwrap();
+    assert_eq!(error.code, "validation_failed");
+    assert_eq!(error.details[0].field, "title");
+
+    let id = body.results[0].todo.as_ref().unwrap().todo_id;
+    let req = test::TestRequest::post()
+        .uri("/api/todos/bulk")
+        .insert_header(auth())
+        .set_json(json!({ "operations": [
+            { "op": "update", "id": id, "patch": { "title": "Renamed" }, "version": 1 },
+            { "op": "complete", "id": id, "version": 2 },
+            { "op": "delete", "id": id },
+        ] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: BulkResponse = test::read_body_json(resp).await;
+    let done = body.results[1].todo.as_ref().unwrap();
+    assert_eq!(
+        (done.title.as_str(), done.completed),
+        ("Renamed", Some(true))
+    );
+    assert_eq!(body.results[2].deleted, Some(1));
+
+    // Unknown operations and oversized batches are refused as a whole.
+    let req = test::TestRequest::post()
+        .uri("/api/todos/bulk")
+        .insert_header(auth())
+        .set_json(json!({ "operations": [{ "op": "archive", "id": id }] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let operations = vec![json!({ "op": "delete", "id": 1 }); 1001];
+    let req = test::TestRequest::post()
+        .uri("/api/todos/bulk")
+        .insert_header(auth())
+        .set_json(json!({ "operations": operations }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let err: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(err.details[0].field, "operations");
+    assert_eq!(err.details[0].code, "length");
+}
+
+#[actix_web::test]
+async fn complete_all_and_delete_completed() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let mut ids = Vec::new();
+    for (user, title) in [(1, "Ship"), (1, "Build"), (2, "Theirs")] {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header(auth_as(user))
+            .set_json(json!({ "title": title }))
+            .to_request();
+        let todo: Todo = test::call_and_read_body_json(&app, req).await;
+        ids.push(todo.todo_id);
+    }
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/todos/{}/blockers/{}", ids[0], ids[1]))
+        .insert_header(auth())
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/api/todos/complete-all?list_id={DEFAULT_LIST_ID}"
+        ))
+        .insert_header(auth())
+        .to_request();
+    let done: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    let done_ids: Vec<i32> = done.iter().map(|todo| todo.todo_id).collect();
+    assert_eq!(done_ids, vec![ids[1], ids[0]]);
+
+    let req = test::TestRequest::delete()
+        .uri("/api/todos/completed")
+        .insert_header(auth())
+        .to_request();
+    let deleted: usize = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(deleted, 2);
+
+    // The other user's todo was neither completed nor deleted.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", ids[2]))
+        .insert_header(auth_as(2))
+        .to_request();
+    let theirs: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_ne!(theirs.completed, Some(true));
+}
+
+#[actix_web::test]
+async fn deleted_todos_go_to_the_trash() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let mut ids = Vec::new();
+    for (user, title) in [(1, "Mine"), (2, "Theirs")] {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header(auth_as(user))
+            .set_json(json!({ "title": title }))
+            .to_request();
+        let todo: Todo = test::call_and_read_body_json(&app, req).await;
+        let req = test::TestRequest::delete()
+            .uri(&format!("/api/todos/{}", todo.todo_id))
+            .insert_header(auth_as(user))
+            .to_request();
+        let deleted: usize = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(deleted, 1);
+        ids.push(todo.todo_id);
+    }
+
+    // Each user only sees their own trash.
+    let req = test::TestRequest::get()
+        .uri("/api/trash")
+        .insert_header(auth())
+        .to_request();
+    let trash: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].todo_id, ids[0]);
+    assert!(trash[0].deleted_at.is_some());
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/todos/{}/restore", ids[0]))
+        .insert_header(auth_as(2))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/todos/{}/restore", ids[0]))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().contains_key(header::ETAG));
+    let restored: Todo = test::read_body_json(resp).await;
+    assert_eq!(restored.deleted_at, None);
+    let req = test::TestRequest::get()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .to_request();
+    let todos: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(todos.len(), 1);
+
+    // Deleting from the trash is final.
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/trash/{}", ids[0]))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/todos/{}", ids[0]))
+        .insert_header(auth())
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/trash/{}", ids[0]))
+        .insert_header(auth())
+        .to_request();
+    let purged: usize = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(purged, 1);
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/todos/{}/restore", ids[0]))
+        .insert_header(auth())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::delete()
+        .uri("/api/trash")
+        .insert_header(auth_as(2))
+        .to_request();
+    let purged: usize = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(purged, 1);
+    let req = test::TestRequest::get()
+        .uri("/api/trash")
+        .insert_header(auth_as(2))
+        .to_request();
+    let trash: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    assert!(trash.is_empty());
+}
+
+#[actix_web::test]
+async fn changes_are_in_the_history() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_mem_repo()))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth())
+        .set_json(json!({ "title": "Audited" }))
+        .to_request();
+    let todo: Todo = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}", todo.todo_id))
+        .insert_header(auth())
+        .set_json(json!({ "title": "Renamed" }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    for uri in ["/api/todos", "/api/trash"] {
+        let req = test::TestRequest::delete()
+            .uri(&format!("{uri}/{}", todo.todo_id))
+            .insert_header(auth())
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+
+    // The history outlives the purged todo.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}/history", todo.todo_id))
+        .insert_header(auth())
+        .to_request();
+    let history: Vec<HistoryEntry> = test::call_and_read_body_json(&app, req).await;
+    let actions: Vec<HistoryAction> = history.iter().map(|entry| entry.action).collect();
+    assert_eq!(
+        actions,
+        vec![
+            HistoryAction::Created,
+            HistoryAction::Updated,
+            HistoryAction::Deleted,
+            HistoryAction::Purged
+        ]
+    );
+    assert_eq!(history[1].before.as_ref().unwrap().title, "Audited");
+    assert_eq!(history[1].after.as_ref().unwrap().title, "Renamed");
+    assert!(history.iter().all(|entry| entry.user_id == Some(1)));
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}/history", todo.todo_id))
+        .insert_header(auth_as(2))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/api/audit?changed_after={}&limit=10",
+            history[2].changed_at.format("%Y-%m-%dT%H:%M:%S%.f")
+        ))
+        .insert_header(auth())
+        .to_request();
+    let audit: Vec<HistoryEntry> = test::call_and_read_body_json(&app, req).await;
+    let ids: Vec<i32> = audit.iter().map(|entry| entry.history_id).collect();
+    assert_eq!(ids, vec![history[2].history_id, history[3].history_id]);
+    let req = test::TestRequest::get()
+        .uri("/api/audit")
+        .insert_header(auth_as(2))
+        .to_request();
+    let audit: Vec<HistoryEntry> = test::call_and_read_body_json(&app, req).await;
+    assert!(audit.is_empty());
+}
@@ -0,0 +1,58 @@
+use chrono::Duration;
+use TodoRustBackend::auth::{
+    generate_api_key, hash_api_key, hash_password, verify_password, AuthKeys, API_KEY_PREFIX,
+    DUMMY_PASSWORD_HASH,
+};
+
+#[test]
+fn test_password_hashes_are_salted_and_verifiable() {
+    let hash = hash_password("correct horse").unwrap();
+    assert!(hash.starts_with("$argon2id$"));
+    assert_ne!(hash, hash_password("correct horse").unwrap());
+    assert!(verify_password("correct horse", &hash));
+    assert!(!verify_password("battery staple", &hash));
+    assert!(!verify_password("correct horse", "not a hash"));
+}
+
+#[test]
+fn test_dummy_hash_costs_as_much_as_a_real_one() {
+    // Everything up to the salt names the algorithm and its parameters.
+    let params = |hash: &str| hash.rsplitn(3, '$').nth(2).unwrap().to_string();
+    let hash = hash_password("correct horse").unwrap();
+    assert_eq!(params(DUMMY_PASSWORD_HASH), params(&hash));
+    assert!(argon2::PasswordHash::new(DUMMY_PASSWORD_HASH).is_ok());
+    assert!(!verify_password("correct horse", DUMMY_PASSWORD_HASH));
+}
+
+#[test]
+fn test_tokens_carry_the_user_id() {
+    let keys = AuthKeys::new(b"secret");
+    let token = keys.issue(42).unwrap();
+    assert_eq!(keys.verify(&token), Some(42));
+
+    assert_eq!(AuthKeys::new(b"other secret").verify(&token), None);
+    assert_eq!(keys.verify("not.a.token"), None);
+    let mut tampered = token.clone();
+    tampered.pop();
+    assert_eq!(keys.verify(&tampered), None);
+}
+
+#[test]
+fn test_expired_tokens_are_rejected() {
+    let keys = AuthKeys::new(b"secret").with_ttl(Duration::seconds(-10));
+    let token = keys.issue(1).unwrap();
+    assert_eq!(keys.verify(&token), None);
+}
+
+#[test]
+fn test_api_keys_are_random_and_hashed_stably() {
+    let key = generate_api_key();
+    assert!(key.starts_with(API_KEY_PREFIX));
+    assert_eq!(key.len(), API_KEY_PREFIX.len() + 64);
+    assert_ne!(key, generate_api_key());
+
+    let hash = hash_api_key(&key);
+    assert_eq!(hash.len(), 64);
+    assert_eq!(hash, hash_api_key(&key));
+    assert_ne!(hash, hash_api_key(&generate_api_key()));
+}
@@ -0,0 +1,115 @@
+use std::{collections::HashSet, sync::Arc, thread};
+
+use actix_web::{http::header, rt::System, test, web, App};
+use serde_json::json;
+use TodoRustBackend::{
+    api,
+    auth::AuthKeys,
+    models::todo::Todo,
+    repository::{mem_repo::MemRepo, RepoBox},
+};
+
+const THREADS: usize = 8;
+const REQUESTS_PER_THREAD: usize = 25;
+
+fn test_keys() -> AuthKeys {
+    AuthKeys::new(b"test secret")
+}
+
+/// `Authorization` header of the user with id 1.
+fn auth() -> (header::HeaderName, String) {
+    let token = test_keys().issue(1).unwrap();
+    (header::AUTHORIZATION, format!("Bearer {token}"))
+}
+
+/// Fires `POST /api/todos` from several threads at once, each thread with its
+/// own app instance on top of the shared repository, and checks that every
+/// caller got back its own todo under a unique id.
+async fn hammer_create(repo: RepoBox) {
+    let handles: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let repo = repo.clone();
+            thread::spawn(move || {
+                System::new().block_on(async move {
+                    let app = test::init_service(
+                        App::new()
+                            .app_data(web::Data::new(repo))
+                            .app_data(web::Data::new(test_keys()))
+                            .configure(api::api::config),
+                    )
+                    .await;
+                    let mut created = Vec::new();
+                    for i in 0..REQUESTS_PER_THREAD {
+                        let title = format!("thread {t} todo {i}");
+                        let req = test::TestRequest::post()
+                            .uri("/api/todos")
+                            .insert_header(auth())
+                            .set_json(json!({ "title": title }))
+                            .to_request();
+                        let resp = test::call_service(&app, req).await;
+                        assert!(resp.status().is_success());
+                        let todo: Todo = test::read_body_json(resp).await;
+                        assert_eq!(todo.title, title, "got another caller's todo");
+                        created.push(todo.todo_id);
+                    }
+                    created
+                })
+            })
+        })
+        .collect();
+
+    let ids: Vec<i32> = handles
+        .into_iter()
+        .flat_map(|h| h.join().unwrap())
+        .collect();
+    let unique: HashSet<i32> = ids.iter().copied().collect();
+    assert_eq!(ids.len(), THREADS * REQUESTS_PER_THREAD);
+    assert_eq!(unique.len(), ids.len(), "duplicate ids were handed out");
+
+    let todos = repo.get_todos().await.unwrap();
+    assert_eq!(todos.len(), THREADS * REQUESTS_PER_THREAD);
+}
+
+#[actix_web::test]
+async fn test_parallel_creates_mem_repo() {
+    hammer_create(Arc::new(MemRepo::new())).await;
+}
+
+#[cfg(feature = "sqlite")]
+#[actix_web::test]
+async fn test_parallel_creates_sqlite_repo() {
+    use TodoRustBackend::repository::sqlite_repo::SqliteRepo;
+
+    let dir = tempfile::tempdir().unwrap();
+    let url = format!("sqlite://{}", dir.path().join("todos.db").display());
+    hammer_create(Arc::new(SqliteRepo::new(&url).unwrap())).await;
+}
+
+#[actix_web::test]
+async fn test_ids_are_not_reused_after_deleting_the_last_todo() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(MemRepo::new()) as RepoBox))
+            .app_data(web::Data::new(test_keys()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let mut last_id = 0;
+    for _ in 0..3 {
+        let req = test::TestRequest::post()
+            .uri("/api/todos")
+            .insert_header(auth())
+            .set_json(json!({ "title": "short lived" }))
+            .to_request();
+        let todo: Todo = test::call_and_read_body_json(&app, req).await;
+        assert!(todo.todo_id > last_id);
+        last_id = todo.todo_id;
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/api/todos/{}", todo.todo_id))
+            .insert_header(auth())
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+}
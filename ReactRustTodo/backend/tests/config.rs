@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use clap::{CommandFactory, Parser};
+use TodoRustBackend::{
+    auth::AuthKeys,
+    config::{Backend, Cli, Command, Config, ConfigError, PartialConfig},
+    models::todo::NewTodo,
+    repository::error::RepoError,
+};
+
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    move |key| vars.get(key).cloned()
+}
+
+fn cli(args: &[&str]) -> Cli {
+    Cli::try_parse_from(std::iter::once("todo-backend").chain(args.iter().copied())).unwrap()
+}
+
+fn new_todo(title: &str) -> NewTodo {
+    serde_json::from_value(serde_json::json!({ "title": title })).unwrap()
+}
+
+fn write_config(dir: &tempfile::TempDir, content: &str) -> String {
+    let path = dir.path().join("todo.toml");
+    std::fs::write(&path, content).unwrap();
+    path.display().to_string()
+}
+
+#[test]
+fn test_defaults_without_any_layer() {
+    let config = Config::load(&cli(&[]), env(&[])).unwrap();
+    assert_eq!(config, Config::default());
+    assert_eq!(config.backend, Backend::Database);
+    assert_eq!(config.host, "127.0.0.1");
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.cors_origins, vec!["http://localhost:5173"]);
+    assert_eq!(config.static_dir, PathBuf::from("./static"));
+}
+
+#[test]
+fn test_config_file_overrides_defaults() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_config(
+        &dir,
+        r#"
+backend = "file"
+data_dir = "/var/lib/todo"
+port = 9000
+cors_origins = ["https://todo.example.com"]
+"#,
+    );
+
+    let config = Config::load(&cli(&["--config", &path]), env(&[])).unwrap();
+    assert_eq!(config.backend, Backend::File);
+    assert_eq!(config.data_dir, PathBuf::from("/var/lib/todo"));
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.cors_origins, vec!["https://todo.example.com"]);
+    // Untouched settings keep their defaults.
+    assert_eq!(config.host, "127.0.0.1");
+}
+
+#[test]
+fn test_env_overrides_config_file_and_flags_override_env() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_config(&dir, "port = 9000\nhost = \"0.0.0.0\"\npool_size = 2\n");
+    let vars = [
+        ("TODO_CONFIG", path.as_str()),
+        ("TODO_PORT", "9100"),
+        ("TODO_POOL_SIZE", "4"),
+        ("TODO_CORS_ORIGINS", "http://a.test, http://b.test"),
+    ];
+
+    let config = Config::load(&cli(&["serve", "--port", "9200"]), env(&vars)).unwrap();
+    assert_eq!(config.host, "0.0.0.0");
+    assert_eq!(config.pool_size, 4);
+    assert_eq!(config.cors_origins, vec!["http://a.test", "http://b.test"]);
+    assert_eq!(config.port, 9200);
+}
+
+#[test]
+fn test_flags_and_subcommands() {
+    let parsed = cli(&[
+        "serve",
+        "-b",
+        "memory",
+        "--cors-origin",
+        "http://a.test,http://b.test",
+        "--cors-origin",
+        "http://c.test",
+        "--log-level",
+        "debug",
+    ]);
+    assert_eq!(parsed.command, Some(Command::Serve));
+    assert_eq!(parsed.overrides.backend, Some(Backend::Memory));
+    assert_eq!(
+        parsed.overrides.cors_origins,
+        Some(vec![
+            "http://a.test".to_string(),
+            "http://b.test".to_string(),
+            "http://c.test".to_string()
+        ])
+    );
+    assert_eq!(parsed.overrides.log_level, Some("debug".to_string()));
+
+    assert_eq!(cli(&[]).command, None);
+    assert_eq!(cli(&["config"]).command, Some(Command::Config));
+    // The old dash-stripping parser accepted this, clap does not.
+    assert!(Cli::try_parse_from(["todo-backend", "-m-"]).is_err());
+}
+
+#[test]
+fn test_help_lists_subcommands_and_settings() {
+    let help = Cli::command().render_long_help().to_string();
+    for expected in [
+        "serve",
+        "config",
+        "--backend",
+        "--database-url",
+        "--host",
+        "--port",
+        "--cors-origin",
+        "--static-dir",
+        "--pool-size",
+        "--log-level",
+        "--block-completion",
+        "--jwt-secret",
+        "--token-ttl-hours",
+        "--trash-retention-days",
+        "TODO_PORT",
+    ] {
+        assert!(help.contains(expected), "`{expected}` missing in help");
+    }
+}
+
+#[test]
+fn test_invalid_settings_are_reported() {
+    let err = Config::load(&cli(&[]), env(&[("TODO_PORT", "eighty")])).unwrap_err();
+    assert!(matches!(err, ConfigError::Env { ref var, .. } if var == "TODO_PORT"));
+
+    let err = PartialConfig::from_env(env(&[("TODO_BACKEND", "redis")])).unwrap_err();
+    assert!(matches!(err, ConfigError::Env { ref var, .. } if var == "TODO_BACKEND"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_config(&dir, "prot = 8080\n");
+    let err = Config::load(&cli(&["-c", &path]), env(&[])).unwrap_err();
+    assert!(matches!(err, ConfigError::File { .. }));
+    assert!(err.to_string().contains("prot"));
+
+    let missing = dir.path().join("missing.toml");
+    let err = PartialConfig::from_file(&missing).unwrap_err();
+    assert!(matches!(err, ConfigError::File { .. }));
+}
+
+#[test]
+fn test_printed_config_is_a_valid_config_file() {
+    let config = Config {
+        backend: Backend::Memory,
+        database_url: Some("sqlite://todos.db".to_string()),
+        ..Default::default()
+    };
+
+    let layer: PartialConfig = toml::from_str(&config.to_toml()).unwrap();
+    let mut reloaded = Config::default();
+    reloaded.merge(layer);
+    assert_eq!(reloaded, config);
+}
+
+#[actix_web::test]
+async fn test_open_repository_per_backend() {
+    let mut config = Config::default();
+    assert!(matches!(
+        config.open_repository(),
+        Err(RepoError::Unavailable(_))
+    ));
+
+    config.backend = Backend::Memory;
+    let repo = config.open_repository().unwrap();
+    assert!(repo.get_todos().await.unwrap().is_empty());
+
+    let dir = tempfile::tempdir().unwrap();
+    config.backend = Backend::File;
+    config.data_dir = dir.path().to_path_buf();
+    config.open_repository().unwrap();
+    assert!(dir.path().join("wal.log").exists());
+}
+
+#[actix_web::test]
+async fn test_block_completion_setting() {
+    assert!(Config::default().block_completion);
+    let parsed = cli(&["--block-completion", "false"]);
+    assert_eq!(parsed.overrides.block_completion, Some(false));
+    let vars = [("TODO_BLOCK_COMPLETION", "false")];
+    let config = Config::load(&cli(&[]), env(&vars)).unwrap();
+    assert!(!config.block_completion);
+    let err = PartialConfig::from_env(env(&[("TODO_BLOCK_COMPLETION", "maybe")])).unwrap_err();
+    assert!(matches!(err, ConfigError::Env { ref var, .. } if var == "TODO_BLOCK_COMPLETION"));
+
+    // The setting reaches the repository.
+    let config = Config {
+        backend: Backend::Memory,
+        ..config
+    };
+    let repo = config.open_repository().unwrap();
+    let blocker = repo.create_todo(new_todo("Blocker")).await.unwrap();
+    let todo = repo.create_todo(new_todo("Blocked")).await.unwrap();
+    repo.add_blocker(todo.todo_id, blocker.todo_id)
+        .await
+        .unwrap();
+    let mut todo = repo.get_todo_by_id(todo.todo_id).await.unwrap();
+    todo.completed = Some(true);
+    let done = repo.update_todo_by_id(todo.todo_id, todo).await.unwrap();
+    assert_eq!(done.completed, Some(true));
+}
+
+#[test]
+fn test_auth_settings() {
+    let config = Config::default();
+    assert_eq!(config.jwt_secret, None);
+    assert_eq!(config.token_ttl_hours, 24);
+    // Without secret every start signs with another random one.
+    let token = config.auth_keys().issue(1).unwrap();
+    assert_eq!(config.auth_keys().verify(&token), None);
+
+    let vars = [("TODO_JWT_SECRET", "s3cret")];
+    let config = Config::load(&cli(&["--token-ttl-hours", "2"]), env(&vars)).unwrap();
+    assert_eq!(config.jwt_secret.as_deref(), Some("s3cret"));
+    let keys = config.auth_keys();
+    assert_eq!(keys.ttl().num_hours(), 2);
+    let token = AuthKeys::new(b"s3cret").issue(7).unwrap();
+    assert_eq!(keys.verify(&token), Some(7));
+
+    // The secret is not printed.
+    assert!(!config.to_toml().contains("s3cret"));
+}
+
+#[test]
+fn test_trash_retention_setting() {
+    let config = Config::default();
+    assert_eq!(config.trash_retention_days, 30);
+    assert_eq!(config.trash_retention().unwrap().num_days(), 30);
+
+    let vars = [("TODO_TRASH_RETENTION_DAYS", "7")];
+    let config = Config::load(&cli(&[]), env(&vars)).unwrap();
+    assert_eq!(config.trash_retention().unwrap().num_days(), 7);
+
+    // 0 keeps trashed todos until the trash is emptied.
+    let config = Config::load(&cli(&["--trash-retention-days", "0"]), env(&vars)).unwrap();
+    assert_eq!(config.trash_retention(), None);
+}
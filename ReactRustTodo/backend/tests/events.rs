@@ -0,0 +1,354 @@
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    http::header,
+    test, web, App,
+};
+use TodoRustBackend::{
+    api::{self, events::LAST_EVENT_ID_HEADER},
+    auth::AuthKeys,
+    events::{Delivery, EventBus, Subscription},
+    models::{
+        event::{EventKind, TodoEvent},
+        list::DEFAULT_LIST_ID,
+        share::{Role, Share},
+        todo::{NewTodo, TodoPatch},
+        user::NewUser,
+    },
+    repository::{event_repo::EventRepo, mem_repo::MemRepo, todo_repo::TodoRepo, RepoBox},
+};
+
+fn create_new_todo(title: &str, owner_id: i32) -> NewTodo {
+    NewTodo {
+        title: title.to_string(),
+        description: None,
+        created_at: None,
+        completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: Some(owner_id),
+    }
+}
+
+fn event_repo(events: &EventBus) -> EventRepo {
+    EventRepo::new(Arc::new(MemRepo::new()), events.clone())
+}
+
+/// The next event of `subscription`, panicking on a reset.
+async fn next_event(subscription: &mut Subscription) -> Arc<TodoEvent> {
+    match subscription.next().await {
+        Some(Delivery::Event(event)) => event,
+        other => panic!("expected an event, got {other:?}"),
+    }
+}
+
+/// Ids and kinds of the events already published after `last_event_id`.
+async fn published(events: &EventBus, last_event_id: u64) -> Vec<(i32, EventKind)> {
+    let mut subscription = events.subscribe(Some(last_event_id));
+    let mut seen = Vec::new();
+    for _ in last_event_id..events.last_event_id() {
+        let event = next_event(&mut subscription).await;
+        seen.push((event.todo_id, event.kind));
+    }
+    seen
+}
+
+#[actix_web::test]
+async fn test_mutations_publish_events() {
+    let events = EventBus::default();
+    let repo = event_repo(&events);
+    let todo = repo.create_todo(create_new_todo("Watch", 1)).await.unwrap();
+    let sub = NewTodo {
+        parent_id: Some(todo.todo_id),
+        ..create_new_todo("Sub", 1)
+    };
+    let sub = repo.create_todo(sub).await.unwrap();
+    let patch = TodoPatch {
+        title: Some("Watched".to_string()),
+        ..Default::default()
+    };
+    repo.patch_todo_by_id(todo.todo_id, patch).await.unwrap();
+    repo.get_todos().await.unwrap();
+    repo.delete_todo_by_id(todo.todo_id).await.unwrap();
+
+    assert_eq!(
+        published(&events, 0).await,
+        vec![
+            (todo.todo_id, EventKind::Created),
+            (sub.todo_id, EventKind::Created),
+            (todo.todo_id, EventKind::Updated),
+            (todo.todo_id, EventKind::Deleted),
+            (sub.todo_id, EventKind::Deleted),
+        ]
+    );
+    let mut subscription = events.subscribe(Some(2));
+    let updated = next_event(&mut subscription).await;
+    assert_eq!(updated.event_id, 3);
+    assert_eq!(updated.todo.as_ref().unwrap().title, "Watched");
+    assert_eq!(updated.audience, vec![1]);
+    next_event(&mut subscription).await;
+    assert!(next_event(&mut subscription).await.todo.is_none());
+}
+
+#[actix_web::test]
+async fn test_restoring_publishes_created_events() {
+    let events = EventBus::default();
+    let repo = event_repo(&events);
+    let todo = repo.create_todo(create_new_todo("Back", 1)).await.unwrap();
+    let sub = NewTodo {
+        parent_id: Some(todo.todo_id),
+        ..create_new_todo("Sub", 1)
+    };
+    let sub = repo.create_todo(sub).await.unwrap();
+    repo.delete_todo_by_id(todo.todo_id).await.unwrap();
+    let last_event_id = events.last_event_id();
+    repo.restore_todo(todo.todo_id).await.unwrap();
+
+    assert_eq!(
+        published(&events, last_event_id).await,
+        vec![
+            (todo.todo_id, EventKind::Created),
+            (sub.todo_id, EventKind::Created),
+        ]
+    );
+    // Purging trashed todos does not report them again.
+    repo.delete_todo_by_id(todo.todo_id).await.unwrap();
+    let last_event_id = events.last_event_id();
+    repo.purge_todo(todo.todo_id).await.unwrap();
+    assert_eq!(events.last_event_id(), last_event_id);
+}
+
+#[actix_web::test]
+async fn test_completing_a_recurring_todo_publishes_its_next_instance() {
+    let events = EventBus::default();
+    let repo = event_repo(&events);
+    let new = NewTodo {
+        due_at: Some(chrono::Local::now().naive_local()),
+        recurrence: Some("FREQ=DAILY".to_string()),
+        ..create_new_todo("Water plants", 1)
+    };
+    let todo = repo.create_todo(new).await.unwrap();
+    let last_event_id = events.last_event_id();
+    let patch = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    repo.patch_todo_by_id(todo.todo_id, patch).await.unwrap();
+
+    let next = repo
+        .get_todos()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|t| t.todo_id != todo.todo_id)
+        .unwrap();
+    assert_eq!(
+        published(&events, last_event_id).await,
+        vec![
+            (todo.todo_id, EventKind::Updated),
+            (next.todo_id, EventKind::Created),
+        ]
+    );
+}
+
+#[actix_web::test]
+async fn test_completing_the_last_subtask_publishes_its_parent() {
+    let events = EventBus::default();
+    let repo = event_repo(&events);
+    let parent = NewTodo {
+        auto_complete: true,
+        ..create_new_todo("Move", 1)
+    };
+    let parent = repo.create_todo(parent).await.unwrap();
+    let sub = NewTodo {
+        parent_id: Some(parent.todo_id),
+        ..create_new_todo("Pack", 1)
+    };
+    let sub = repo.create_todo(sub).await.unwrap();
+    let last_event_id = events.last_event_id();
+    let patch = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    repo.patch_todo_by_id(sub.todo_id, patch).await.unwrap();
+
+    assert_eq!(
+        published(&events, last_event_id).await,
+        vec![
+            (sub.todo_id, EventKind::Updated),
+            (parent.todo_id, EventKind::Updated),
+        ]
+    );
+    let mut subscription = events.subscribe(Some(last_event_id + 1));
+    let completed = next_event(&mut subscription).await;
+    assert_eq!(completed.todo.as_ref().unwrap().completed, Some(true));
+}
+
+#[actix_web::test]
+async fn test_failed_mutations_publish_nothing() {
+    let events = EventBus::default();
+    let repo = event_repo(&events);
+    let todo = repo.create_todo(create_new_todo("Only", 1)).await.unwrap();
+    let patch = TodoPatch {
+        title: Some(" ".to_string()),
+        ..Default::default()
+    };
+    assert!(repo.patch_todo_by_id(todo.todo_id, patch).await.is_err());
+    assert!(repo.delete_todo_by_id(todo.todo_id + 1).await.is_err());
+    assert_eq!(events.last_event_id(), 1);
+}
+
+#[actix_web::test]
+async fn test_events_reach_the_users_a_todo_is_shared_with() {
+    let events = EventBus::default();
+    let repo = event_repo(&events);
+    for username in ["alice", "bob"] {
+        let new_user = NewUser {
+            username: username.to_string(),
+            password_hash: "hash".to_string(),
+        };
+        repo.create_user(new_user).await.unwrap();
+    }
+    let todo = repo.create_todo(create_new_todo("Ours", 1)).await.unwrap();
+    let share = Share {
+        user_id: 2,
+        role: Role::Viewer,
+    };
+    repo.share_todo(todo.todo_id, share).await.unwrap();
+
+    let mut subscription = events.subscribe(Some(0));
+    assert_eq!(next_event(&mut subscription).await.audience, vec![1]);
+    let shared = next_event(&mut subscription).await;
+    assert_eq!(shared.kind, EventKind::Updated);
+    assert_eq!(shared.audience, vec![1, 2]);
+}
+
+#[actix_web::test]
+async fn test_resuming_replays_missed_events_or_resets() {
+    let events = EventBus::with_capacity(3);
+    let repo = event_repo(&events);
+    for title in ["a", "b", "c", "d", "e"] {
+        repo.create_todo(create_new_todo(title, 1)).await.unwrap();
+    }
+
+    let mut subscription = events.subscribe(Some(3));
+    assert_eq!(next_event(&mut subscription).await.event_id, 4);
+    assert_eq!(next_event(&mut subscription).await.event_id, 5);
+    // Live events follow the replayed ones.
+    repo.create_todo(create_new_todo("f", 1)).await.unwrap();
+    assert_eq!(next_event(&mut subscription).await.event_id, 6);
+
+    // Event 2 is no longer kept, and ids from before a restart are unknown.
+    for last_event_id in [1, 99] {
+        let mut subscription = events.subscribe(Some(last_event_id));
+        assert!(matches!(subscription.next().await, Some(Delivery::Reset)));
+    }
+    let mut subscription = events.subscribe(Some(3));
+    assert_eq!(next_event(&mut subscription).await.event_id, 4);
+}
+
+#[actix_web::test]
+async fn test_slow_subscribers_are_reset() {
+    let events = EventBus::with_capacity(2);
+    let repo = event_repo(&events);
+    let mut subscription = events.subscribe(None);
+    for title in ["a", "b", "c"] {
+        repo.create_todo(create_new_todo(title, 1)).await.unwrap();
+    }
+    assert!(matches!(subscription.next().await, Some(Delivery::Reset)));
+    assert_eq!(next_event(&mut subscription).await.event_id, 2);
+}
+
+/// The next chunk of a streamed body as text.
+async fn next_chunk(body: &mut BoxBody) -> String {
+    let chunk = poll_fn(|cx| Pin::new(&mut *body).poll_next(cx))
+        .await
+        .unwrap()
+        .unwrap();
+    String::from_utf8(chunk.to_vec()).unwrap()
+}
+
+#[actix_web::test]
+async fn test_event_stream_endpoint() {
+    let events = EventBus::default();
+    let repo: RepoBox = Arc::new(event_repo(&events));
+    let keys = AuthKeys::new(b"test secret");
+    let token = keys.issue(1).unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(repo.clone()))
+            .app_data(web::Data::new(keys))
+            .app_data(web::Data::new(events.clone()))
+            .configure(api::api::config),
+    )
+    .await;
+    let theirs = repo.create_todo(create_new_todo("Bob's", 2)).await.unwrap();
+    let mine = repo.create_todo(create_new_todo("Mine", 1)).await.unwrap();
+
+    let req = test::TestRequest::get().uri("/api/events").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    // `EventSource` cannot set headers and sends the token in the query.
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/events?access_token={token}"))
+        .insert_header((LAST_EVENT_ID_HEADER, "0"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        resp.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/event-stream"
+    );
+    let mut body = resp.into_body().boxed();
+    // Bob's todo is skipped.
+    let chunk = next_chunk(&mut body).await;
+    assert!(
+        chunk.starts_with("id: 2\nevent: created\ndata: {"),
+        "{chunk}"
+    );
+    assert!(chunk.contains(&format!("\"todo_id\":{}", mine.todo_id)));
+    assert!(chunk.ends_with("\n\n"));
+
+    repo.delete_todo_by_id(theirs.todo_id).await.unwrap();
+    repo.delete_todo_by_id(mine.todo_id).await.unwrap();
+    let chunk = next_chunk(&mut body).await;
+    assert!(chunk.starts_with("id: 4\nevent: deleted\n"), "{chunk}");
+}
+
+#[actix_web::test]
+async fn test_event_stream_ends_when_the_token_expires() {
+    let events = EventBus::default();
+    let repo: RepoBox = Arc::new(event_repo(&events));
+    let keys = AuthKeys::new(b"test secret").with_ttl(chrono::Duration::seconds(2));
+    let token = keys.issue(1).unwrap();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(repo.clone()))
+            .app_data(web::Data::new(keys))
+            .app_data(web::Data::new(events.clone()))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/events")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {token}")))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 200);
+    let mut body = resp.into_body().boxed();
+    assert_eq!(next_chunk(&mut body).await, ": keep-alive\n\n");
+    // Events after the expiry no longer reach the stream.
+    repo.create_todo(create_new_todo("Late", 1)).await.unwrap();
+    assert!(poll_fn(|cx| Pin::new(&mut body).poll_next(cx))
+        .await
+        .is_none());
+}
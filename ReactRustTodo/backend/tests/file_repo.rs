@@ -0,0 +1,573 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use TodoRustBackend::{
+    models::{
+        api_key::{NewApiKey, NewApiKeyRecord, Scope},
+        history::{HistoryAction, HistoryQuery, NewHistoryEntry},
+        list::{NewList, DEFAULT_LIST_ID, DEFAULT_LIST_NAME},
+        share::{Role, Share},
+        tag::NewTag,
+        todo::{NewTodo, TodoPatch},
+        user::NewUser,
+    },
+    repository::{
+        self, audit_repo::AuditRepo, error::RepoError, file_repo::FileRepo, todo_repo::TodoRepo,
+    },
+};
+
+fn create_new_todo(title: &str) -> NewTodo {
+    NewTodo {
+        title: title.to_string(),
+        description: None,
+        created_at: None,
+        completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+    }
+}
+
+fn wal_lines(dir: &Path) -> usize {
+    fs::read_to_string(dir.join("wal.log"))
+        .unwrap()
+        .lines()
+        .count()
+}
+
+#[actix_web::test]
+async fn test_file_repo_survives_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    {
+        let repo = FileRepo::new(dir.path()).unwrap();
+        repo.create_todo(create_new_todo("First")).await.unwrap();
+        let second = repo.create_todo(create_new_todo("Second")).await.unwrap();
+        let patch = TodoPatch {
+            completed: Some(true),
+            ..Default::default()
+        };
+        repo.patch_todo_by_id(second.todo_id, patch).await.unwrap();
+        let third = repo.create_todo(create_new_todo("Third")).await.unwrap();
+        repo.delete_todo_by_id(third.todo_id).await.unwrap();
+    }
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let todos = repo.get_todos().await.unwrap();
+    assert_eq!(todos.len(), 2);
+    assert_eq!(todos[1].title, "Second");
+    assert_eq!(todos[1].completed, Some(true));
+    assert_eq!(todos[1].version, 2);
+
+    // The id of the deleted newest todo is not handed out again.
+    let fourth = repo.create_todo(create_new_todo("Fourth")).await.unwrap();
+    assert_eq!(fourth.todo_id, 4);
+}
+
+#[actix_web::test]
+async fn test_file_repo_compacts_log_into_snapshot() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = FileRepo::with_compact_every(dir.path(), 3).unwrap();
+    repo.create_todo(create_new_todo("One")).await.unwrap();
+    repo.create_todo(create_new_todo("Two")).await.unwrap();
+    assert_eq!(wal_lines(dir.path()), 2);
+    assert!(!dir.path().join("snapshot.json").exists());
+
+    repo.create_todo(create_new_todo("Three")).await.unwrap();
+    assert_eq!(wal_lines(dir.path()), 0);
+    assert!(dir.path().join("snapshot.json").exists());
+
+    repo.create_todo(create_new_todo("Four")).await.unwrap();
+    drop(repo);
+    let repo = FileRepo::new(dir.path()).unwrap();
+    assert_eq!(repo.get_todos().await.unwrap().len(), 4);
+}
+
+#[actix_web::test]
+async fn test_file_repo_keeps_mutations_when_compaction_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = FileRepo::with_compact_every(dir.path(), 1).unwrap();
+    // The snapshot is written aside first, which a directory in its way prevents.
+    fs::create_dir(dir.path().join("snapshot.json.tmp")).unwrap();
+    let todo = repo.create_todo(create_new_todo("Logged")).await.unwrap();
+    assert_eq!(wal_lines(dir.path()), 1);
+    assert_eq!(
+        repo.get_todo_by_id(todo.todo_id).await.unwrap().title,
+        "Logged"
+    );
+
+    fs::remove_dir(dir.path().join("snapshot.json.tmp")).unwrap();
+    repo.create_todo(create_new_todo("Compacted"))
+        .await
+        .unwrap();
+    assert_eq!(wal_lines(dir.path()), 0);
+    drop(repo);
+    let repo = FileRepo::new(dir.path()).unwrap();
+    assert_eq!(repo.get_todos().await.unwrap().len(), 2);
+}
+
+#[actix_web::test]
+async fn test_file_repo_ignores_torn_last_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    {
+        let repo = FileRepo::new(dir.path()).unwrap();
+        repo.create_todo(create_new_todo("Kept")).await.unwrap();
+        // Simulate a crash while appending the next entry.
+        let mut wal = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join("wal.log"))
+            .unwrap();
+        wal.write_all(br#"{"op":"put","todo":{"todo_id":2,"ti"#)
+            .unwrap();
+    }
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let todos = repo.get_todos().await.unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].title, "Kept");
+    let next = repo.create_todo(create_new_todo("Next")).await.unwrap();
+    assert_eq!(next.todo_id, 2);
+}
+
+#[actix_web::test]
+async fn test_file_repo_replays_log_already_in_snapshot() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let first = repo.create_todo(create_new_todo("First")).await.unwrap();
+    repo.create_todo(create_new_todo("Second")).await.unwrap();
+    repo.delete_todo_by_id(first.todo_id).await.unwrap();
+    let log = fs::read(dir.path().join("wal.log")).unwrap();
+
+    // A crash after writing the snapshot but before truncating the log leaves
+    // both behind; replaying the entries again must not change the result.
+    repo.compact().unwrap();
+    drop(repo);
+    fs::write(dir.path().join("wal.log"), log).unwrap();
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let todos = repo.get_todos().await.unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].title, "Second");
+}
+
+#[actix_web::test]
+async fn test_file_repo_keeps_trash_across_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let restored = repo.create_todo(create_new_todo("Restored")).await.unwrap();
+    let trashed = repo.create_todo(create_new_todo("Trashed")).await.unwrap();
+    let purged = repo.create_todo(create_new_todo("Purged")).await.unwrap();
+    for todo in [&restored, &trashed, &purged] {
+        repo.delete_todo_by_id(todo.todo_id).await.unwrap();
+    }
+    repo.restore_todo(restored.todo_id).await.unwrap();
+    repo.purge_todo(purged.todo_id).await.unwrap();
+
+    // Replaying the log on top of its own snapshot changes nothing.
+    let log = fs::read(dir.path().join("wal.log")).unwrap();
+    repo.compact().unwrap();
+    drop(repo);
+    fs::write(dir.path().join("wal.log"), log).unwrap();
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let todos = repo.get_todos().await.unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].todo_id, restored.todo_id);
+    let trash = repo.get_trash(None).await.unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].todo_id, trashed.todo_id);
+    assert!(trash[0].deleted_at.is_some());
+}
+
+#[actix_web::test]
+async fn test_file_repo_keeps_history_across_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Arc::new(FileRepo::new(dir.path()).unwrap());
+    let audited = AuditRepo::new(repo.clone(), Some(1));
+    let todo = audited
+        .create_todo(create_new_todo("Audited"))
+        .await
+        .unwrap();
+    audited.delete_todo_by_id(todo.todo_id).await.unwrap();
+    audited.purge_todo(todo.todo_id).await.unwrap();
+
+    // Replayed entries already in the snapshot are not recorded twice.
+    let log = fs::read(dir.path().join("wal.log")).unwrap();
+    repo.compact().unwrap();
+    drop(audited);
+    drop(repo);
+    fs::write(dir.path().join("wal.log"), log).unwrap();
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let history = repo.get_history(HistoryQuery::default()).await.unwrap();
+    let actions: Vec<HistoryAction> = history.iter().map(|entry| entry.action).collect();
+    assert_eq!(
+        actions,
+        vec![
+            HistoryAction::Created,
+            HistoryAction::Deleted,
+            HistoryAction::Purged
+        ]
+    );
+    assert_eq!(history[0].after.as_ref().unwrap().title, "Audited");
+    assert_eq!(history[2].user_id, Some(1));
+
+    // New entries continue after the replayed ones.
+    let after = history[0].after.clone();
+    let entry = NewHistoryEntry::new(
+        HistoryAction::Updated,
+        None,
+        history[0].changed_at,
+        after.clone(),
+        after,
+    )
+    .unwrap();
+    repo.record_history(vec![entry]).await.unwrap();
+    let history = repo.get_history(HistoryQuery::default()).await.unwrap();
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[3].history_id, 4);
+}
+
+#[actix_web::test]
+async fn test_file_repo_logs_mutation_with_its_history_at_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Arc::new(FileRepo::new(dir.path()).unwrap());
+    let parent = NewTodo {
+        auto_complete: true,
+        ..create_new_todo("Move")
+    };
+    let parent = repo.create_todo(parent).await.unwrap();
+    let sub = NewTodo {
+        parent_id: Some(parent.todo_id),
+        ..create_new_todo("Pack")
+    };
+    let sub = repo.create_todo(sub).await.unwrap();
+    let lines = wal_lines(dir.path());
+
+    // Completing the last subtask completes the parent; both changes and
+    // their history are one log entry.
+    let audited = AuditRepo::new(repo.clone(), Some(1));
+    let patch = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    audited.patch_todo_by_id(sub.todo_id, patch).await.unwrap();
+    assert_eq!(wal_lines(dir.path()), lines + 1);
+    drop(audited);
+    drop(repo);
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    assert_eq!(
+        repo.get_todo_by_id(parent.todo_id).await.unwrap().completed,
+        Some(true)
+    );
+    let history = repo.get_history(HistoryQuery::default()).await.unwrap();
+    let changed: Vec<(HistoryAction, i32)> = history
+        .iter()
+        .map(|entry| (entry.action, entry.todo_id))
+        .collect();
+    assert_eq!(
+        changed,
+        vec![
+            (HistoryAction::Updated, sub.todo_id),
+            (HistoryAction::Updated, parent.todo_id)
+        ]
+    );
+    assert!(history.iter().all(|entry| entry.user_id == Some(1)));
+}
+
+#[actix_web::test]
+async fn test_file_repo_rejects_corrupt_log() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("wal.log"),
+        "not json\n{\"op\":\"delete\",\"todo_id\":1}\n",
+    )
+    .unwrap();
+
+    assert!(matches!(
+        FileRepo::new(dir.path()),
+        Err(RepoError::Internal(_))
+    ));
+}
+
+#[actix_web::test]
+async fn test_connect_file_url() {
+    let dir = tempfile::tempdir().unwrap();
+    let url = format!("file://{}", dir.path().display());
+
+    let repo = repository::connect(&url).unwrap();
+    repo.create_todo(create_new_todo("Via URL")).await.unwrap();
+    drop(repo);
+
+    let repo = repository::connect(&url).unwrap();
+    assert_eq!(repo.get_todos().await.unwrap()[0].title, "Via URL");
+}
+
+#[actix_web::test]
+async fn test_file_repo_keeps_tags_across_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let tag = |name: &str| NewTag {
+        name: name.to_string(),
+        owner_id: None,
+    };
+    {
+        let repo = FileRepo::new(dir.path()).unwrap();
+        let todo = repo.create_todo(create_new_todo("Tagged")).await.unwrap();
+        let ops = repo.create_tag(tag("ops")).await.unwrap();
+        let old = repo.create_tag(tag("old")).await.unwrap();
+        repo.attach_tag(todo.todo_id, ops.tag_id).await.unwrap();
+        repo.attach_tag(todo.todo_id, old.tag_id).await.unwrap();
+        repo.delete_tag_by_id(old.tag_id).await.unwrap();
+    }
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let todo = repo.get_todo_by_id(1).await.unwrap();
+    let names: Vec<&str> = todo.tags.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, vec!["ops"]);
+    // The id of the deleted tag is not handed out again.
+    assert_eq!(repo.create_tag(tag("new")).await.unwrap().tag_id, 3);
+}
+
+#[actix_web::test]
+async fn test_file_repo_reads_snapshot_without_tags() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("snapshot.json"),
+        r#"{"todos":[{"todo_id":1,"title":"Old","description":null,"created_at":null,"completed":false}],"last_todo_id":1}"#,
+    )
+    .unwrap();
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let todo = repo.get_todo_by_id(1).await.unwrap();
+    assert_eq!(todo.title, "Old");
+    assert!(todo.tags.is_empty());
+    assert!(repo.get_tags(None).await.unwrap().is_empty());
+    // Todos from before lists existed belong to the default list.
+    assert_eq!(todo.list_id, DEFAULT_LIST_ID);
+    let lists = repo.get_lists().await.unwrap();
+    assert_eq!(lists.len(), 1);
+    assert_eq!(lists[0].name, DEFAULT_LIST_NAME);
+    // Nor are they subtasks.
+    assert_eq!(todo.parent_id, None);
+    assert!(!todo.auto_complete);
+    assert_eq!(todo.version, 1);
+}
+
+#[actix_web::test]
+async fn test_file_repo_keeps_lists_across_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let list = |name: &str| NewList {
+        name: name.to_string(),
+        owner_id: None,
+    };
+    let (work, old) = {
+        let repo = FileRepo::new(dir.path()).unwrap();
+        let work = repo.create_list(list("Work")).await.unwrap();
+        let old = repo.create_list(list("Old")).await.unwrap();
+        for (title, list_id) in [("Report", work.list_id), ("Archived", old.list_id)] {
+            let mut todo = create_new_todo(title);
+            todo.list_id = list_id;
+            repo.create_todo(todo).await.unwrap();
+        }
+        repo.delete_list_by_id(old.list_id, true).await.unwrap();
+        (work, old)
+    };
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let names: Vec<String> = repo
+        .get_lists()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|l| l.name)
+        .collect();
+    assert_eq!(names, vec![DEFAULT_LIST_NAME, "Work"]);
+    let todos = repo.get_todos().await.unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].list_id, work.list_id);
+    // The id of the deleted list is not handed out again.
+    let new = repo.create_list(list("New")).await.unwrap();
+    assert_eq!(new.list_id, old.list_id + 1);
+}
+
+#[actix_web::test]
+async fn test_file_repo_keeps_subtasks_across_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let subtask = |title: &str, parent_id: i32| NewTodo {
+        parent_id: Some(parent_id),
+        ..create_new_todo(title)
+    };
+    let (root, kept) = {
+        let repo = FileRepo::new(dir.path()).unwrap();
+        let root = repo.create_todo(create_new_todo("Root")).await.unwrap();
+        let gone = repo
+            .create_todo(subtask("Gone", root.todo_id))
+            .await
+            .unwrap();
+        repo.create_todo(subtask("Gone too", gone.todo_id))
+            .await
+            .unwrap();
+        let kept = repo
+            .create_todo(subtask("Kept", root.todo_id))
+            .await
+            .unwrap();
+        assert_eq!(repo.delete_todo_by_id(gone.todo_id).await.unwrap(), 2);
+        (root, kept)
+    };
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let tree = repo.get_subtree(root.todo_id).await.unwrap();
+    assert_eq!(tree.children.len(), 1);
+    assert_eq!(tree.children[0].todo.todo_id, kept.todo_id);
+    assert_eq!(tree.todo.progress.unwrap().total, 1);
+    assert_eq!(repo.get_todos().await.unwrap().len(), 2);
+}
+
+#[actix_web::test]
+async fn test_file_repo_keeps_blockers_across_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let (build, ship, docs) = {
+        let repo = FileRepo::new(dir.path()).unwrap();
+        let build = repo.create_todo(create_new_todo("Build")).await.unwrap();
+        let ship = repo.create_todo(create_new_todo("Ship")).await.unwrap();
+        let docs = repo.create_todo(create_new_todo("Docs")).await.unwrap();
+        repo.add_blocker(ship.todo_id, build.todo_id).await.unwrap();
+        repo.add_blocker(ship.todo_id, docs.todo_id).await.unwrap();
+        repo.remove_blocker(ship.todo_id, docs.todo_id)
+            .await
+            .unwrap();
+        (build, ship, docs)
+    };
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let ship_now = repo.get_todo_by_id(ship.todo_id).await.unwrap();
+    assert_eq!(ship_now.blocked_by, vec![build.todo_id]);
+    let next = repo.get_next_todos(None).await.unwrap();
+    let ids: Vec<i32> = next.iter().map(|t| t.todo_id).collect();
+    assert_eq!(ids, vec![build.todo_id, docs.todo_id]);
+    // Still rejected after the restart.
+    let complete = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    let result = repo.patch_todo_by_id(ship.todo_id, complete).await;
+    assert!(matches!(result, Err(RepoError::Conflict(_))));
+}
+
+#[actix_web::test]
+async fn test_file_repo_keeps_users_across_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let alice = {
+        let repo = FileRepo::new(dir.path()).unwrap();
+        let new_user = NewUser {
+            username: "alice".to_string(),
+            password_hash: "hash".to_string(),
+        };
+        let alice = repo.create_user(new_user).await.unwrap();
+        let mut new_todo = create_new_todo("Mine");
+        new_todo.owner_id = Some(alice.user_id);
+        repo.create_todo(new_todo).await.unwrap();
+        alice
+    };
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    let record = repo.get_user_record("Alice").await.unwrap();
+    assert_eq!(record.user, alice);
+    assert_eq!(record.password_hash, "hash");
+    let todos = repo.get_todos().await.unwrap();
+    assert_eq!(todos[0].owner_id, Some(alice.user_id));
+    let new_user = NewUser {
+        username: "bob".to_string(),
+        password_hash: "hash".to_string(),
+    };
+    let bob = repo.create_user(new_user).await.unwrap();
+    assert!(bob.user_id > alice.user_id);
+}
+
+#[actix_web::test]
+async fn test_file_repo_keeps_api_keys_across_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let (kept, revoked) = {
+        let repo = FileRepo::new(dir.path()).unwrap();
+        let new_user = NewUser {
+            username: "ci".to_string(),
+            password_hash: "hash".to_string(),
+        };
+        let user = repo.create_user(new_user).await.unwrap();
+        let new_key = |hash: &str| NewApiKeyRecord {
+            user_id: user.user_id,
+            key: NewApiKey {
+                name: "bot".to_string(),
+                scopes: vec![Scope::Read, Scope::Write],
+                expires_at: None,
+            },
+            prefix: "tdk_0000".to_string(),
+            key_hash: hash.to_string(),
+        };
+        let kept = repo.create_api_key(new_key("kept")).await.unwrap();
+        let revoked = repo.create_api_key(new_key("revoked")).await.unwrap();
+        repo.delete_api_key(user.user_id, revoked.key_id)
+            .await
+            .unwrap();
+        (kept, revoked)
+    };
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    assert_eq!(repo.get_api_key_by_hash("kept").await.unwrap(), kept);
+    assert_eq!(
+        repo.get_api_key_by_hash("revoked").await.unwrap_err(),
+        RepoError::Missing("api key")
+    );
+    assert_eq!(
+        repo.get_api_keys(kept.user_id).await.unwrap(),
+        vec![kept.clone()]
+    );
+    assert!(revoked.key_id > kept.key_id);
+}
+
+#[actix_web::test]
+async fn test_file_repo_keeps_shares_across_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let new_user = |username: &str| NewUser {
+        username: username.to_string(),
+        password_hash: "hash".to_string(),
+    };
+    let editor = |user_id: i32| Share {
+        user_id,
+        role: Role::Editor,
+    };
+    let (todo, list, bob, carol) = {
+        let repo = FileRepo::new(dir.path()).unwrap();
+        let alice = repo.create_user(new_user("alice")).await.unwrap().user_id;
+        let bob = repo.create_user(new_user("bob")).await.unwrap().user_id;
+        let carol = repo.create_user(new_user("carol")).await.unwrap().user_id;
+        let new_list = NewList {
+            name: "Project".to_string(),
+            owner_id: Some(alice),
+        };
+        let list = repo.create_list(new_list).await.unwrap();
+        let todo = repo.create_todo(create_new_todo("Shared")).await.unwrap();
+        repo.share_todo(todo.todo_id, editor(bob)).await.unwrap();
+        repo.share_todo(todo.todo_id, editor(carol)).await.unwrap();
+        repo.unshare_todo(todo.todo_id, carol).await.unwrap();
+        repo.share_list(list.list_id, editor(carol)).await.unwrap();
+        (todo, list, bob, carol)
+    };
+
+    let repo = FileRepo::new(dir.path()).unwrap();
+    assert_eq!(
+        repo.get_todo_shares(todo.todo_id).await.unwrap(),
+        vec![editor(bob)]
+    );
+    assert_eq!(
+        repo.get_list_shares(list.list_id).await.unwrap(),
+        vec![editor(carol)]
+    );
+    assert_eq!(repo.get_list_by_id(list.list_id).await.unwrap(), list);
+}
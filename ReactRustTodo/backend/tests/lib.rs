@@ -1,3 +1,14 @@
 pub mod api;
+pub mod auth;
+pub mod concurrency;
+pub mod config;
+pub mod events;
+pub mod file_repo;
 pub mod mem_repo;
 pub mod models;
+pub mod openapi;
+pub mod postgres_repo;
+pub mod recurrence;
+pub mod repo_error;
+pub mod sqlite_repo;
+pub mod validation;
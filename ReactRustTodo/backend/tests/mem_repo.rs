@@ -1,354 +1,2514 @@
-use std::sync::{Arc, Mutex};
+use chrono::{NaiveDate, NaiveDateTime};
 use TodoRustBackend::{
-    models::todo::{NewTodo, Todo},
-    repository::{mem_repo::MemRepo, todo_repo::TodoRepo},
+    models::{
+        api_key::{NewApiKey, NewApiKeyRecord, Scope},
+        batch::{BatchOp, BatchOutcome},
+        history::{HistoryAction, HistoryEntry, HistoryQuery, NewHistoryEntry},
+        list::{NewList, DEFAULT_LIST_ID, DEFAULT_LIST_NAME},
+        query::{SortField, SortOrder, TagMode, TodoQuery},
+        share::{Role, Share},
+        tag::{NewTag, Tag},
+        todo::{NewTodo, Todo, TodoPatch},
+        user::NewUser,
+    },
+    repository::{
+        audit_repo::AuditRepo,
+        error::RepoError,
+        file_repo::FileRepo,
+        mem_repo::MemRepo,
+        mutation::{Actor, Mutated, Mutation},
+        owned_repo::OwnedRepo,
+        todo_repo::TodoRepo,
+        RepoBox,
+    },
 };
 
-fn create_test_repo() -> MemRepo {
-    MemRepo {
-        inner: Arc::new(Mutex::new(Vec::new())),
+/// Expands the behaviour tests once per repository implementation, each module
+/// providing its own `TestRepo` and `create_test_repo()`.
+macro_rules! behaviour_tests {
+    ($($test:item)*) => {
+        mod mem {
+            use super::*;
+
+            type TestRepo = MemRepo;
+
+            fn create_test_repo() -> TestRepo {
+                MemRepo::new()
+            }
+
+            $($test)*
+        }
+
+        mod file {
+            use super::*;
+
+            /// A `FileRepo` in a temporary directory that is removed on drop.
+            struct TestRepo {
+                repo: FileRepo,
+                _dir: tempfile::TempDir,
+            }
+
+            impl std::ops::Deref for TestRepo {
+                type Target = FileRepo;
+
+                fn deref(&self) -> &FileRepo {
+                    &self.repo
+                }
+            }
+
+            fn create_test_repo() -> TestRepo {
+                let dir = tempfile::tempdir().unwrap();
+                // Compact often so the snapshot path is exercised as well.
+                let repo = FileRepo::with_compact_every(dir.path(), 3).unwrap();
+                TestRepo { repo, _dir: dir }
+            }
+
+            $($test)*
+        }
+    };
+}
+
+fn new_tag(name: &str) -> NewTag {
+    NewTag {
+        name: name.to_string(),
+        owner_id: None,
     }
 }
 
+fn new_list(name: &str) -> NewList {
+    NewList {
+        name: name.to_string(),
+        owner_id: None,
+    }
+}
+
+fn tag_names(todo: &Todo) -> Vec<&str> {
+    todo.tags.iter().map(|tag| tag.name.as_str()).collect()
+}
+
 fn create_new_todo(title: &str, description: Option<&str>) -> NewTodo {
     NewTodo {
         title: title.to_string(),
         description: description.map(|s| s.to_string()),
         created_at: None,
         completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
     }
 }
 
-#[actix_web::test]
-async fn test_create_multiple_todos() {
-    let repo = create_test_repo();
-
-    let todo1 = create_new_todo("First Todo", Some("First Description"));
-    let result1 = repo.create_todo(todo1).await;
-    assert!(result1.is_ok());
-    assert_eq!(result1.unwrap().todo_id, 1);
-
-    let todo2 = create_new_todo("Second Todo", Some("Second Description"));
-    let result2 = repo.create_todo(todo2).await;
-    assert!(result2.is_ok());
-    assert_eq!(result2.unwrap().todo_id, 2);
-
-    let todo3 = create_new_todo("Third Todo", None);
-    let result3 = repo.create_todo(todo3).await;
-    assert!(result3.is_ok());
-    assert_eq!(result3.unwrap().todo_id, 3);
+fn create_subtask(title: &str, parent_id: i32) -> NewTodo {
+    NewTodo {
+        parent_id: Some(parent_id),
+        ..create_new_todo(title, None)
+    }
 }
 
-#[actix_web::test]
-async fn test_get_todos_empty() {
-    let repo = create_test_repo();
-    let todos = repo.get_todos().await;
-    assert_eq!(todos.len(), 0);
+fn new_user(username: &str) -> NewUser {
+    NewUser {
+        username: username.to_string(),
+        password_hash: format!("hash of {username}"),
+    }
 }
 
-#[actix_web::test]
-async fn test_get_todos_with_data() {
-    let repo = create_test_repo();
+fn new_api_key(user_id: i32, hash: &str, scopes: Vec<Scope>) -> NewApiKeyRecord {
+    NewApiKeyRecord {
+        user_id,
+        key: NewApiKey {
+            name: format!("key {hash}"),
+            scopes,
+            expires_at: None,
+        },
+        prefix: "tdk_0000".to_string(),
+        key_hash: hash.to_string(),
+    }
+}
 
-    // Create multiple todos
-    repo.create_todo(create_new_todo("Todo 1", Some("Description 1")))
-        .await
-        .unwrap();
-    repo.create_todo(create_new_todo("Todo 2", Some("Description 2")))
-        .await
-        .unwrap();
-    repo.create_todo(create_new_todo("Todo 3", None))
-        .await
-        .unwrap();
+fn on_first_of_may(hour: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, 5, 1)
+        .unwrap()
+        .and_hms_opt(hour, 0, 0)
+        .unwrap()
+}
 
-    let todos = repo.get_todos().await;
-    assert_eq!(todos.len(), 3);
-    assert_eq!(todos[0].title, "Todo 1");
-    assert_eq!(todos[1].title, "Todo 2");
-    assert_eq!(todos[2].title, "Todo 3");
+/// A change of a todo of user 1 without snapshots.
+fn history_entry(
+    todo_id: i32,
+    user_id: Option<i32>,
+    action: HistoryAction,
+    hour: u32,
+) -> NewHistoryEntry {
+    NewHistoryEntry {
+        todo_id,
+        owner_id: Some(1),
+        user_id,
+        action,
+        changed_at: on_first_of_may(hour),
+        before: None,
+        after: None,
+    }
 }
 
-#[actix_web::test]
-async fn test_get_todo_by_id_success() {
-    let repo = create_test_repo();
+fn history_ids(entries: &[HistoryEntry]) -> Vec<i32> {
+    entries.iter().map(|entry| entry.history_id).collect()
+}
 
-    let created = repo
-        .create_todo(create_new_todo("Test Todo", Some("Test Description")))
-        .await
-        .unwrap();
+/// The code of the single field error in `result`.
+fn error_code<T: std::fmt::Debug>(result: Result<T, RepoError>) -> String {
+    match result {
+        Err(RepoError::Validation(errors)) => errors[0].code.clone(),
+        other => panic!("expected a validation error, got {other:?}"),
+    }
+}
 
-    let found = repo.get_todo_by_id(created.todo_id).await;
-    assert!(found.is_some());
+behaviour_tests! {
+    #[actix_web::test]
+    async fn test_create_multiple_todos() {
+        let repo = create_test_repo();
 
-    let todo = found.unwrap();
-    assert_eq!(todo.todo_id, created.todo_id);
-    assert_eq!(todo.title, "Test Todo");
-    assert_eq!(todo.description, Some("Test Description".to_string()));
-}
+        let todo1 = create_new_todo("First Todo", Some("First Description"));
+        let result1 = repo.create_todo(todo1).await;
+        assert!(result1.is_ok());
+        assert_eq!(result1.unwrap().todo_id, 1);
 
-#[actix_web::test]
-async fn test_get_todo_by_id_not_found() {
-    let repo = create_test_repo();
-    let found = repo.get_todo_by_id(999).await;
-    assert!(found.is_none());
-}
+        let todo2 = create_new_todo("Second Todo", Some("Second Description"));
+        let result2 = repo.create_todo(todo2).await;
+        assert!(result2.is_ok());
+        assert_eq!(result2.unwrap().todo_id, 2);
 
-#[actix_web::test]
-async fn test_get_todo_by_id_multiple_todos() {
-    let repo = create_test_repo();
+        let todo3 = create_new_todo("Third Todo", None);
+        let result3 = repo.create_todo(todo3).await;
+        assert!(result3.is_ok());
+        assert_eq!(result3.unwrap().todo_id, 3);
+    }
 
-    // Create multiple todos
-    let todo1 = repo
-        .create_todo(create_new_todo("First", Some("First Description")))
-        .await
-        .unwrap();
-    let todo2 = repo
-        .create_todo(create_new_todo("Second", Some("Second Description")))
-        .await
-        .unwrap();
-    let todo3 = repo
-        .create_todo(create_new_todo("Third", Some("Third Description")))
-        .await
-        .unwrap();
+    #[actix_web::test]
+    async fn test_get_todos_empty() {
+        let repo = create_test_repo();
+        let todos = repo.get_todos().await.unwrap();
+        assert_eq!(todos.len(), 0);
+    }
 
-    // Verify we can get each one individually
-    let found1 = repo.get_todo_by_id(todo1.todo_id).await;
-    assert!(found1.is_some());
-    assert_eq!(found1.unwrap().title, "First");
+    #[actix_web::test]
+    async fn test_get_todos_with_data() {
+        let repo = create_test_repo();
 
-    let found2 = repo.get_todo_by_id(todo2.todo_id).await;
-    assert!(found2.is_some());
-    assert_eq!(found2.unwrap().title, "Second");
+        // Create multiple todos
+        repo.create_todo(create_new_todo("Todo 1", Some("Description 1")))
+            .await
+            .unwrap();
+        repo.create_todo(create_new_todo("Todo 2", Some("Description 2")))
+            .await
+            .unwrap();
+        repo.create_todo(create_new_todo("Todo 3", None))
+            .await
+            .unwrap();
 
-    let found3 = repo.get_todo_by_id(todo3.todo_id).await;
-    assert!(found3.is_some());
-    assert_eq!(found3.unwrap().title, "Third");
-}
+        let todos = repo.get_todos().await.unwrap();
+        assert_eq!(todos.len(), 3);
+        assert_eq!(todos[0].title, "Todo 1");
+        assert_eq!(todos[1].title, "Todo 2");
+        assert_eq!(todos[2].title, "Todo 3");
+    }
 
-#[actix_web::test]
-async fn test_update_todo_by_id_success() {
-    let repo = create_test_repo();
-
-    let created = repo
-        .create_todo(create_new_todo(
-            "Original Title",
-            Some("Original Description"),
-        ))
-        .await
-        .unwrap();
+    #[actix_web::test]
+    async fn test_get_todo_by_id_success() {
+        let repo = create_test_repo();
 
-    let mut updated_todo = created.clone();
-    updated_todo.title = "Updated Title".to_string();
-    updated_todo.description = Some("Updated Description".to_string());
-    updated_todo.completed = Some(true);
+        let created = repo
+            .create_todo(create_new_todo("Test Todo", Some("Test Description")))
+            .await
+            .unwrap();
 
-    let result = repo.update_todo_by_id(created.todo_id, updated_todo).await;
-    assert!(result.is_some());
+        let found = repo.get_todo_by_id(created.todo_id).await;
+        assert!(found.is_ok());
 
-    let updated = result.unwrap();
-    assert_eq!(updated.todo_id, created.todo_id);
-    assert_eq!(updated.title, "Updated Title");
-    assert_eq!(updated.description, Some("Updated Description".to_string()));
-    assert_eq!(updated.completed, Some(true));
+        let todo = found.unwrap();
+        assert_eq!(todo.todo_id, created.todo_id);
+        assert_eq!(todo.title, "Test Todo");
+        assert_eq!(todo.description, Some("Test Description".to_string()));
+    }
 
-    // Verify the change persisted
-    let fetched = repo.get_todo_by_id(created.todo_id).await;
-    assert!(fetched.is_some());
-    assert_eq!(fetched.unwrap().title, "Updated Title");
-}
+    #[actix_web::test]
+    async fn test_get_todo_by_id_not_found() {
+        let repo = create_test_repo();
+        let found = repo.get_todo_by_id(999).await;
+        assert_eq!(found.unwrap_err(), RepoError::NotFound);
+    }
 
-#[actix_web::test]
-async fn test_update_todo_by_id_not_found() {
-    let repo = create_test_repo();
+    #[actix_web::test]
+    async fn test_get_todo_by_id_multiple_todos() {
+        let repo = create_test_repo();
 
-    let fake_todo = Todo {
-        todo_id: 999,
-        title: "Fake Todo".to_string(),
-        description: Some("Doesn't exist".to_string()),
-        created_at: None,
-        completed: Some(false),
-    };
+        // Create multiple todos
+        let todo1 = repo
+            .create_todo(create_new_todo("First", Some("First Description")))
+            .await
+            .unwrap();
+        let todo2 = repo
+            .create_todo(create_new_todo("Second", Some("Second Description")))
+            .await
+            .unwrap();
+        let todo3 = repo
+            .create_todo(create_new_todo("Third", Some("Third Description")))
+            .await
+            .unwrap();
 
-    let result = repo.update_todo_by_id(999, fake_todo).await;
-    assert!(result.is_none());
-}
+        // Verify we can get each one individually
+        let found1 = repo.get_todo_by_id(todo1.todo_id).await;
+        assert!(found1.is_ok());
+        assert_eq!(found1.unwrap().title, "First");
 
-#[actix_web::test]
-async fn test_update_todo_partial_fields() {
-    let repo = create_test_repo();
+        let found2 = repo.get_todo_by_id(todo2.todo_id).await;
+        assert!(found2.is_ok());
+        assert_eq!(found2.unwrap().title, "Second");
+
+        let found3 = repo.get_todo_by_id(todo3.todo_id).await;
+        assert!(found3.is_ok());
+        assert_eq!(found3.unwrap().title, "Third");
+    }
+
+    #[actix_web::test]
+    async fn test_update_todo_by_id_success() {
+        let repo = create_test_repo();
+
+        let created = repo
+            .create_todo(create_new_todo(
+                "Original Title",
+                Some("Original Description"),
+            ))
+            .await
+            .unwrap();
+
+        let mut updated_todo = created.clone();
+        updated_todo.title = "Updated Title".to_string();
+        updated_todo.description = Some("Updated Description".to_string());
+        updated_todo.completed = Some(true);
+
+        let result = repo.update_todo_by_id(created.todo_id, updated_todo).await;
+        assert!(result.is_ok());
+
+        let updated = result.unwrap();
+        assert_eq!(updated.todo_id, created.todo_id);
+        assert_eq!(updated.title, "Updated Title");
+        assert_eq!(updated.description, Some("Updated Description".to_string()));
+        assert_eq!(updated.completed, Some(true));
+
+        // Verify the change persisted
+        let fetched = repo.get_todo_by_id(created.todo_id).await;
+        assert!(fetched.is_ok());
+        assert_eq!(fetched.unwrap().title, "Updated Title");
+    }
+
+    #[actix_web::test]
+    async fn test_update_todo_by_id_not_found() {
+        let repo = create_test_repo();
+
+        let fake_todo = Todo {
+            todo_id: 999,
+            title: "Fake Todo".to_string(),
+            description: Some("Doesn't exist".to_string()),
+            created_at: None,
+            completed: Some(false),
+            due_at: None,
+            remind_at: None,
+            recurrence: None,
+            list_id: DEFAULT_LIST_ID,
+            parent_id: None,
+            auto_complete: false,
+            owner_id: None,
+            version: 1,
+            deleted_at: None,
+            tags: Vec::new(),
+            blocked_by: Vec::new(),
+            progress: None,
+        };
+
+        let result = repo.update_todo_by_id(999, fake_todo).await;
+        assert_eq!(result.unwrap_err(), RepoError::NotFound);
+    }
+
+    #[actix_web::test]
+    async fn test_update_todo_partial_fields() {
+        let repo = create_test_repo();
+
+        let created = repo
+            .create_todo(create_new_todo("Original", Some("Description")))
+            .await
+            .unwrap();
+
+        // Update only the completed status
+        let mut updated_todo = created.clone();
+        updated_todo.completed = Some(true);
+
+        let result = repo.update_todo_by_id(created.todo_id, updated_todo).await;
+        assert!(result.is_ok());
+
+        let updated = result.unwrap();
+        assert_eq!(updated.title, "Original");
+        assert_eq!(updated.description, Some("Description".to_string()));
+        assert_eq!(updated.completed, Some(true));
+    }
+
+    #[actix_web::test]
+    async fn test_delete_todo_by_id_success() {
+        let repo = create_test_repo();
+
+        let created = repo
+            .create_todo(create_new_todo("To Delete", Some("Will be removed")))
+            .await
+            .unwrap();
+
+        let deleted = repo.delete_todo_by_id(created.todo_id).await;
+        assert!(deleted.is_ok());
+
+        // Verify it's actually deleted
+        let not_found = repo.get_todo_by_id(created.todo_id).await;
+        assert_eq!(not_found.unwrap_err(), RepoError::NotFound);
+    }
+
+    #[actix_web::test]
+    async fn test_delete_todo_by_id_not_found() {
+        let repo = create_test_repo();
+        let result = repo.delete_todo_by_id(999).await;
+        assert_eq!(result.unwrap_err(), RepoError::NotFound);
+    }
+
+    #[actix_web::test]
+    async fn test_delete_todo_from_multiple() {
+        let repo = create_test_repo();
+
+        // Create multiple todos
+        let todo1 = repo
+            .create_todo(create_new_todo("First", Some("First Description")))
+            .await
+            .unwrap();
+        let todo2 = repo
+            .create_todo(create_new_todo("Second", Some("Second Description")))
+            .await
+            .unwrap();
+        let todo3 = repo
+            .create_todo(create_new_todo("Third", Some("Third Description")))
+            .await
+            .unwrap();
+
+        // Delete the middle one
+        let deleted = repo.delete_todo_by_id(todo2.todo_id).await;
+        assert!(deleted.is_ok());
+
+        // Verify the list now has 2 items
+        let todos = repo.get_todos().await.unwrap();
+        assert_eq!(todos.len(), 2);
+
+        // Verify the correct todos remain
+        assert_eq!(todos[0].todo_id, todo1.todo_id);
+        assert_eq!(todos[1].todo_id, todo3.todo_id);
+
+        // Verify we can't get the deleted todo
+        let not_found = repo.get_todo_by_id(todo2.todo_id).await;
+        assert_eq!(not_found.unwrap_err(), RepoError::NotFound);
+    }
+
+    #[actix_web::test]
+    async fn test_repository_consistency() {
+        let repo = create_test_repo();
+
+        // Create
+        let created = repo
+            .create_todo(create_new_todo(
+                "Consistency Test",
+                Some("Testing consistency"),
+            ))
+            .await
+            .unwrap();
+
+        // Read
+        let fetched = repo.get_todo_by_id(created.todo_id).await.unwrap();
+        assert_eq!(created.todo_id, fetched.todo_id);
+        assert_eq!(created.title, fetched.title);
+
+        // Update
+        let mut updated_todo = fetched.clone();
+        updated_todo.completed = Some(true);
+        let updated = repo
+            .update_todo_by_id(created.todo_id, updated_todo)
+            .await
+            .unwrap();
+        assert_eq!(updated.completed, Some(true));
+
+        // Verify update persisted
+        let refetched = repo.get_todo_by_id(created.todo_id).await.unwrap();
+        assert_eq!(refetched.completed, Some(true));
+
+        // Delete
+        let deleted = repo.delete_todo_by_id(created.todo_id).await;
+        assert!(deleted.is_ok());
+
+        // Verify deletion
+        let not_found = repo.get_todo_by_id(created.todo_id).await;
+        assert_eq!(not_found.unwrap_err(), RepoError::NotFound);
+
+        // Verify empty list
+        let todos = repo.get_todos().await.unwrap();
+        assert_eq!(todos.len(), 0);
+    }
+
+    #[actix_web::test]
+    async fn test_todo_id_not_reused_after_delete() {
+        let repo = create_test_repo();
+
+        // Create and delete a todo
+        let todo1 = repo
+            .create_todo(create_new_todo("First", None))
+            .await
+            .unwrap();
+        assert_eq!(todo1.todo_id, 1);
+
+        repo.delete_todo_by_id(todo1.todo_id).await.unwrap();
+
+        // Ids come from a monotonic counter, so a deleted id is never handed out again
+        let todo2 = repo
+            .create_todo(create_new_todo("Second", None))
+            .await
+            .unwrap();
+        assert_eq!(todo2.todo_id, 2);
+        assert_eq!(repo.get_todo_by_id(1).await.unwrap_err(), RepoError::NotFound);
+    }
+
+    #[actix_web::test]
+    async fn test_create_todo() {
+        let repo = create_test_repo();
+
+        let new_todo = create_new_todo("Ronny's Test Todo", Some("Testing creation"));
+        let result = repo.create_todo(new_todo).await;
+        assert!(result.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_create_todo_without_description() {
+        let repo = create_test_repo();
+
+        let new_todo = create_new_todo("No Description Todo", None);
+        let result = repo.create_todo(new_todo).await;
+        assert!(result.is_ok());
+    }
+
+    // New Todos should increment their ID automatically (in memory db behaviour)
+    #[actix_web::test]
+    async fn test_todo_id_increments() {
+        let repo = create_test_repo();
+        let todo1 = repo
+            .create_todo(create_new_todo("First Todo", None))
+            .await
+            .unwrap();
+        assert_eq!(todo1.todo_id, 1);
+        let todo2 = repo
+            .create_todo(create_new_todo("Second Todo", None))
+            .await
+            .unwrap();
+        assert_eq!(todo2.todo_id, 2);
+    }
+
+    fn at(day: u32) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(2024, 1, day).and_then(|d| d.and_hms_opt(12, 0, 0))
+    }
+
+    async fn seed_query_repo() -> TestRepo {
+        let repo = create_test_repo();
+        let seeds = [
+            ("Buy milk", Some("from the store"), 3, false),
+            ("write report", None, 1, true),
+            ("Call Bob", Some("about the STORE opening"), 2, false),
+            ("buy stamps", Some("post office"), 4, true),
+        ];
+        for (title, description, day, completed) in seeds {
+            let mut todo = create_new_todo(title, description);
+            todo.created_at = at(day);
+            todo.completed = Some(completed);
+            repo.create_todo(todo).await.unwrap();
+        }
+        repo
+    }
+
+    #[actix_web::test]
+    async fn test_query_default_returns_everything_by_id() {
+        let repo = seed_query_repo().await;
+
+        let page = repo.query_todos(TodoQuery::default()).await.unwrap();
+        assert_eq!(page.total, 4);
+        let ids: Vec<i32> = page.items.iter().map(|t| t.todo_id).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+    }
+
+    #[actix_web::test]
+    async fn test_query_filter_completed() {
+        let repo = seed_query_repo().await;
+
+        let query = TodoQuery {
+            completed: Some(true),
+            ..Default::default()
+        };
+        let page = repo.query_todos(query).await.unwrap();
+        assert_eq!(page.total, 2);
+        assert!(page.items.iter().all(|t| t.completed == Some(true)));
+    }
+
+    #[actix_web::test]
+    async fn test_query_filter_title_and_description_ignore_case() {
+        let repo = seed_query_repo().await;
+
+        let query = TodoQuery {
+            title: Some("BUY".to_string()),
+            ..Default::default()
+        };
+        let page = repo.query_todos(query).await.unwrap();
+        assert_eq!(page.total, 2);
+
+        let query = TodoQuery {
+            description: Some("store".to_string()),
+            ..Default::default()
+        };
+        let page = repo.query_todos(query).await.unwrap();
+        let titles: Vec<&str> = page.items.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["Buy milk", "Call Bob"]);
+    }
+
+    #[actix_web::test]
+    async fn test_query_filter_created_range() {
+        let repo = seed_query_repo().await;
+
+        let query = TodoQuery {
+            created_after: at(2),
+            created_before: at(4),
+            ..Default::default()
+        };
+        let page = repo.query_todos(query).await.unwrap();
+        let titles: Vec<&str> = page.items.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["Buy milk", "Call Bob"]);
+    }
+
+    #[actix_web::test]
+    async fn test_query_sort_by_created_at_desc() {
+        let repo = seed_query_repo().await;
+
+        let query = TodoQuery {
+            sort: SortField::CreatedAt,
+            order: SortOrder::Desc,
+            ..Default::default()
+        };
+        let page = repo.query_todos(query).await.unwrap();
+        let titles: Vec<&str> = page.items.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["buy stamps", "Buy milk", "Call Bob", "write report"]);
+    }
+
+    #[actix_web::test]
+    async fn test_query_sort_by_title_ignores_case() {
+        let repo = seed_query_repo().await;
+
+        let query = TodoQuery {
+            sort: SortField::Title,
+            ..Default::default()
+        };
+        let page = repo.query_todos(query).await.unwrap();
+        let titles: Vec<&str> = page.items.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["Buy milk", "buy stamps", "Call Bob", "write report"]);
+    }
+
+    #[actix_web::test]
+    async fn test_query_pagination_keeps_total() {
+        let repo = seed_query_repo().await;
+
+        let query = TodoQuery {
+            limit: Some(2),
+            offset: Some(1),
+            ..Default::default()
+        };
+        let page = repo.query_todos(query).await.unwrap();
+        assert_eq!(page.total, 4);
+        let ids: Vec<i32> = page.items.iter().map(|t| t.todo_id).collect();
+        assert_eq!(ids, vec![2, 3]);
+
+        let query = TodoQuery {
+            offset: Some(10),
+            ..Default::default()
+        };
+        let page = repo.query_todos(query).await.unwrap();
+        assert_eq!(page.total, 4);
+        assert!(page.items.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_patch_todo_merges_fields() {
+        let repo = create_test_repo();
+        let mut new_todo = create_new_todo("Original", Some("Description"));
+        new_todo.created_at = at(5);
+        let created = repo.create_todo(new_todo).await.unwrap();
+
+        let patch = TodoPatch {
+            completed: Some(true),
+            ..Default::default()
+        };
+        let patched = repo.patch_todo_by_id(created.todo_id, patch).await.unwrap();
+        assert_eq!(patched.todo_id, created.todo_id);
+        assert_eq!(patched.title, "Original");
+        assert_eq!(patched.description, Some("Description".to_string()));
+        assert_eq!(patched.created_at, at(5));
+        assert_eq!(patched.completed, Some(true));
+
+        let fetched = repo.get_todo_by_id(created.todo_id).await.unwrap();
+        assert_eq!(fetched.completed, Some(true));
+    }
+
+    #[actix_web::test]
+    async fn test_patch_todo_not_found() {
+        let repo = create_test_repo();
+        let result = repo.patch_todo_by_id(999, TodoPatch::default()).await;
+        assert_eq!(result.unwrap_err(), RepoError::NotFound);
+    }
+
+    #[actix_web::test]
+    async fn test_update_todo_keeps_stored_id() {
+        let repo = create_test_repo();
+        let created = repo
+            .create_todo(create_new_todo("Original", None))
+            .await
+            .unwrap();
+
+        let mut updated_todo = created.clone();
+        updated_todo.todo_id = 42;
+        let updated = repo
+            .update_todo_by_id(created.todo_id, updated_todo)
+            .await
+            .unwrap();
+        assert_eq!(updated.todo_id, created.todo_id);
+        assert_eq!(repo.get_todo_by_id(42).await.unwrap_err(), RepoError::NotFound);
+    }
+
+    #[actix_web::test]
+    async fn test_repo_rejects_invalid_todos() {
+        let repo = create_test_repo();
+
+        let result = repo.create_todo(create_new_todo("   ", None)).await;
+        assert!(matches!(result, Err(RepoError::Validation(_))));
+        assert_eq!(repo.get_todos().await.unwrap().len(), 0);
+
+        let created = repo
+            .create_todo(create_new_todo("Valid", None))
+            .await
+            .unwrap();
+
+        let mut invalid = created.clone();
+        invalid.title = "x".repeat(256);
+        let result = repo.update_todo_by_id(created.todo_id, invalid).await;
+        assert!(matches!(result, Err(RepoError::Validation(_))));
+
+        let patch = TodoPatch {
+            description: Some(Some(" ".to_string())),
+            ..Default::default()
+        };
+        let result = repo.patch_todo_by_id(created.todo_id, patch).await;
+        assert!(matches!(result, Err(RepoError::Validation(_))));
+
+        let stored = repo.get_todo_by_id(created.todo_id).await.unwrap();
+        assert_eq!(stored.title, "Valid");
+        assert_eq!(stored.description, None);
+    }
+
+    #[actix_web::test]
+    async fn test_query_due_range_and_sort() {
+        let repo = create_test_repo();
+        for (title, due_day) in [
+            ("c", Some(5)),
+            ("a", Some(2)),
+            ("none", None),
+            ("b", Some(3)),
+        ] {
+            let mut todo = create_new_todo(title, None);
+            todo.due_at = due_day.and_then(at);
+            repo.create_todo(todo).await.unwrap();
+        }
+
+        let page = repo
+            .query_todos(TodoQuery {
+                due_after: at(2),
+                due_before: at(5),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let titles: Vec<&str> = page.items.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["a", "b"]);
+
+        let page = repo
+            .query_todos(TodoQuery {
+                sort: SortField::DueAt,
+                order: SortOrder::Desc,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let titles: Vec<&str> = page.items.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["c", "b", "a", "none"]);
+    }
+
+    #[actix_web::test]
+    async fn test_patch_sets_and_clears_due_dates() {
+        let repo = create_test_repo();
+        let created = repo
+            .create_todo(create_new_todo("Due", None))
+            .await
+            .unwrap();
+
+        let patch = TodoPatch {
+            due_at: Some(at(10)),
+            remind_at: Some(at(9)),
+            ..Default::default()
+        };
+        let patched = repo.patch_todo_by_id(created.todo_id, patch).await.unwrap();
+        assert_eq!(patched.due_at, at(10));
+        assert_eq!(patched.remind_at, at(9));
+
+        let patch = TodoPatch {
+            remind_at: Some(None),
+            ..Default::default()
+        };
+        let patched = repo.patch_todo_by_id(created.todo_id, patch).await.unwrap();
+        assert_eq!(patched.due_at, at(10));
+        assert_eq!(patched.remind_at, None);
+        assert_eq!(repo.get_todo_by_id(created.todo_id).await.unwrap().remind_at, None);
+    }
+
+    #[actix_web::test]
+    async fn test_patch_checks_reminder_against_stored_due_date() {
+        let repo = create_test_repo();
+        let mut new_todo = create_new_todo("Due", None);
+        new_todo.due_at = at(10);
+        new_todo.remind_at = at(9);
+        let created = repo.create_todo(new_todo).await.unwrap();
+
+        let patch = TodoPatch {
+            remind_at: Some(at(11)),
+            ..Default::default()
+        };
+        let result = repo.patch_todo_by_id(created.todo_id, patch).await;
+        assert_eq!(error_code(result), "after_due_at");
+        let patch = TodoPatch {
+            due_at: Some(at(8)),
+            ..Default::default()
+        };
+        let result = repo.patch_todo_by_id(created.todo_id, patch).await;
+        assert_eq!(error_code(result), "after_due_at");
+
+        let stored = repo.get_todo_by_id(created.todo_id).await.unwrap();
+        assert_eq!((stored.due_at, stored.remind_at), (at(10), at(9)));
+        let patch = TodoPatch {
+            due_at: Some(None),
+            ..Default::default()
+        };
+        repo.patch_todo_by_id(created.todo_id, patch).await.unwrap();
+    }
+
+    #[actix_web::test]
+    async fn test_completing_recurring_todo_creates_next_instance() {
+        let repo = create_test_repo();
+        let due = NaiveDate::from_ymd_opt(2030, 3, 4).and_then(|d| d.and_hms_opt(9, 0, 0));
+        let mut new_todo = create_new_todo("Standup", Some("daily"));
+        new_todo.due_at = due;
+        new_todo.recurrence = Some("freq=daily".to_string());
+        let created = repo.create_todo(new_todo).await.unwrap();
+
+        // Edits that do not complete the todo leave the rule alone.
+        let mut renamed = created.clone();
+        renamed.title = "Team standup".to_string();
+        repo.update_todo_by_id(created.todo_id, renamed.clone()).await.unwrap();
+        assert_eq!(repo.get_todos().await.unwrap().len(), 1);
+
+        let mut completed = renamed;
+        completed.completed = Some(true);
+        let updated = repo
+            .update_todo_by_id(created.todo_id, completed.clone())
+            .await
+            .unwrap();
+        assert_eq!(updated.completed, Some(true));
+        assert_eq!(updated.recurrence, None);
+
+        let todos = repo.get_todos().await.unwrap();
+        assert_eq!(todos.len(), 2);
+        let next = &todos[1];
+        assert!(next.todo_id > created.todo_id);
+        assert_eq!(next.title, "Team standup");
+        assert_eq!(next.completed, Some(false));
+        assert_eq!(next.due_at, NaiveDate::from_ymd_opt(2030, 3, 5).and_then(|d| d.and_hms_opt(9, 0, 0)));
+        assert_eq!(next.recurrence, Some("FREQ=DAILY".to_string()));
+
+        // Saving the completed todo again does not create another instance.
+        repo.update_todo_by_id(created.todo_id, completed).await.unwrap();
+        assert_eq!(repo.get_todos().await.unwrap().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_patch_completing_recurring_todo_creates_next_instance() {
+        let repo = create_test_repo();
+        let due = NaiveDate::from_ymd_opt(2030, 1, 31).and_then(|d| d.and_hms_opt(8, 0, 0));
+        let mut new_todo = create_new_todo("Pay rent", None);
+        new_todo.due_at = due;
+        new_todo.remind_at = NaiveDate::from_ymd_opt(2030, 1, 30).and_then(|d| d.and_hms_opt(8, 0, 0));
+        new_todo.recurrence = Some("FREQ=MONTHLY;BYMONTHDAY=31".to_string());
+        let created = repo.create_todo(new_todo).await.unwrap();
+
+        let patch = TodoPatch {
+            completed: Some(true),
+            ..Default::default()
+        };
+        let patched = repo.patch_todo_by_id(created.todo_id, patch).await.unwrap();
+        assert_eq!(patched.recurrence, None);
+
+        let todos = repo.get_todos().await.unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[1].due_at, NaiveDate::from_ymd_opt(2030, 2, 28).and_then(|d| d.and_hms_opt(8, 0, 0)));
+        assert_eq!(todos[1].remind_at, NaiveDate::from_ymd_opt(2030, 2, 27).and_then(|d| d.and_hms_opt(8, 0, 0)));
+        assert_eq!(todos[1].recurrence, Some("FREQ=MONTHLY;BYMONTHDAY=31".to_string()));
+    }
+
+    #[actix_web::test]
+    async fn test_mutations_report_side_effects() {
+        use HistoryAction::*;
+
+        let repo = create_test_repo();
+        let complete = |id: i32| Mutation::Patch {
+            id,
+            patch: TodoPatch {
+                completed: Some(true),
+                ..Default::default()
+            },
+            version: None,
+        };
+        let reported = |mutated: &Mutated| -> Vec<(HistoryAction, i32)> {
+            mutated
+                .changes
+                .iter()
+                .map(|change| {
+                    let todo = change.after.as_ref().or(change.before.as_ref()).unwrap();
+                    (change.action, todo.todo_id)
+                })
+                .collect()
+        };
+
+        // The next instance of a recurring todo, with the tags of the completed one.
+        let mut new_todo = create_new_todo("Pay rent", None);
+        new_todo.due_at = NaiveDate::from_ymd_opt(2030, 1, 31).and_then(|d| d.and_hms_opt(8, 0, 0));
+        new_todo.recurrence = Some("FREQ=DAILY".to_string());
+        let rent = repo.create_todo(new_todo).await.unwrap();
+        let tag = repo.create_tag(new_tag("bills")).await.unwrap();
+        repo.attach_tag(rent.todo_id, tag.tag_id).await.unwrap();
+        let mutated = repo.mutate(complete(rent.todo_id), None).await.unwrap();
+        let next = mutated.changes[1].after.clone().unwrap();
+        assert_eq!(reported(&mutated), vec![(Updated, rent.todo_id), (Created, next.todo_id)]);
+        assert_eq!(mutated.changes[0].before.as_ref().unwrap().completed, Some(false));
+        assert_eq!(next.recurrence, Some("FREQ=DAILY".to_string()));
+        assert_eq!(tag_names(&next), vec!["bills"]);
+        assert_eq!(repo.get_todo_by_id(next.todo_id).await.unwrap().title, "Pay rent");
+
+        // A parent completed along with its last subtask.
+        let mut parent = create_new_todo("Move", None);
+        parent.auto_complete = true;
+        let parent = repo.create_todo(parent).await.unwrap();
+        let sub = repo.create_todo(create_subtask("Pack", parent.todo_id)).await.unwrap();
+        let actor = Actor { user_id: Some(7) };
+        let mutated = repo.mutate(complete(sub.todo_id), Some(actor)).await.unwrap();
+        assert_eq!(reported(&mutated), vec![(Updated, sub.todo_id), (Updated, parent.todo_id)]);
+        assert_eq!(mutated.changes[1].after.as_ref().unwrap().completed, Some(true));
+        // Given an actor, the side effects are recorded along with the change.
+        let history = repo.get_history(HistoryQuery::default()).await.unwrap();
+        let recorded: Vec<(HistoryAction, i32)> =
+            history.iter().map(|entry| (entry.action, entry.todo_id)).collect();
+        assert_eq!(recorded, vec![(Updated, sub.todo_id), (Updated, parent.todo_id)]);
+        assert!(history.iter().all(|entry| entry.user_id == Some(7)));
+
+        // Subtasks go to the trash and come back with their parent.
+        let delete = Mutation::Delete {
+            id: parent.todo_id,
+            version: None,
+        };
+        let mutated = repo.mutate(delete, None).await.unwrap();
+        let mut trashed = reported(&mutated);
+        trashed.sort_unstable_by_key(|(_, id)| *id);
+        assert_eq!(trashed, vec![(Deleted, parent.todo_id), (Deleted, sub.todo_id)]);
+        let mutated = repo.mutate(Mutation::Restore { id: parent.todo_id }, None).await.unwrap();
+        let mut restored = reported(&mutated);
+        restored.sort_unstable_by_key(|(_, id)| *id);
+        assert_eq!(restored, vec![(Restored, parent.todo_id), (Restored, sub.todo_id)]);
+    }
+
+    #[actix_web::test]
+    async fn test_tag_crud() {
+        let repo = create_test_repo();
+        let owned = |name: &str| NewTag {
+            owner_id: Some(1),
+            ..new_tag(name)
+        };
+        let ops = repo.create_tag(owned("ops")).await.unwrap();
+        let backend = repo.create_tag(owned("Backend")).await.unwrap();
+        assert_ne!(ops.tag_id, backend.tag_id);
 
-    let created = repo
-        .create_todo(create_new_todo("Original", Some("Description")))
+        let result = repo.create_tag(owned("OPS")).await;
+        assert!(matches!(result, Err(RepoError::Conflict(_))));
+        let result = repo.create_tag(owned(" ")).await;
+        assert!(matches!(result, Err(RepoError::Validation(_))));
+
+        let names: Vec<String> = repo
+            .get_tags(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect();
+        assert_eq!(names, vec!["Backend", "ops"]);
+
+        let renamed = repo
+            .update_tag_by_id(ops.tag_id, new_tag("Ops"))
+            .await
+            .unwrap();
+        assert_eq!(renamed.tag_id, ops.tag_id);
+        assert_eq!(repo.get_tag_by_id(ops.tag_id).await.unwrap().name, "Ops");
+        let result = repo.update_tag_by_id(ops.tag_id, owned("backend")).await;
+        assert!(matches!(result, Err(RepoError::Conflict(_))));
+
+        assert_eq!(repo.delete_tag_by_id(ops.tag_id).await.unwrap(), 1);
+        assert_eq!(
+            repo.get_tag_by_id(ops.tag_id).await.unwrap_err(),
+            RepoError::Missing("tag")
+        );
+        assert_eq!(
+            repo.delete_tag_by_id(ops.tag_id).await.unwrap_err(),
+            RepoError::Missing("tag")
+        );
+        assert_eq!(
+            repo.update_tag_by_id(ops.tag_id, new_tag("ops")).await.unwrap_err(),
+            RepoError::Missing("tag")
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_tag_names_are_unique_per_owner() {
+        let repo = create_test_repo();
+        let owned = |name: &str, owner_id: i32| NewTag {
+            owner_id: Some(owner_id),
+            ..new_tag(name)
+        };
+        let alice = repo.create_tag(owned("ops", 1)).await.unwrap();
+        let bob = repo.create_tag(owned("Ops", 2)).await.unwrap();
+        assert_ne!(alice.tag_id, bob.tag_id);
+        let result = repo.update_tag_by_id(bob.tag_id, new_tag("backend")).await;
+        assert_eq!(result.unwrap().owner_id, Some(2));
+        let result = repo.update_tag_by_id(alice.tag_id, new_tag("Backend")).await;
+        assert_eq!(result.unwrap().name, "Backend");
+        // Tags without owner never clash, like `NULL`s in a unique index.
+        repo.create_tag(new_tag("legacy")).await.unwrap();
+        repo.create_tag(new_tag("legacy")).await.unwrap();
+        assert_eq!(repo.get_tags(None).await.unwrap().len(), 4);
+    }
+
+    #[actix_web::test]
+    async fn test_tags_are_visible_to_owners_and_on_visible_todos() {
+        let repo = create_test_repo();
+        for username in ["alice", "bob", "carol"] {
+            repo.create_user(new_user(username)).await.unwrap();
+        }
+        let owned = |name: &str, owner_id: i32| NewTag {
+            owner_id: Some(owner_id),
+            ..new_tag(name)
+        };
+        let mine = repo.create_tag(owned("mine", 1)).await.unwrap();
+        let theirs = repo.create_tag(owned("theirs", 2)).await.unwrap();
+        repo.create_tag(owned("hidden", 2)).await.unwrap();
+        let mut new_todo = create_new_todo("Alice's", None);
+        new_todo.owner_id = Some(1);
+        let todo = repo.create_todo(new_todo).await.unwrap();
+        repo.attach_tag(todo.todo_id, theirs.tag_id).await.unwrap();
+
+        let names = |tags: Vec<Tag>| -> Vec<String> {
+            tags.into_iter().map(|tag| tag.name).collect()
+        };
+        let visible = repo.get_tags(Some(1)).await.unwrap();
+        assert_eq!(names(visible), vec!["mine", "theirs"]);
+        let visible = repo.get_tags(Some(2)).await.unwrap();
+        assert_eq!(names(visible), vec!["hidden", "theirs"]);
+        assert!(repo.get_tags(Some(3)).await.unwrap().is_empty());
+
+        // Sharing the todo shows its tags, but not the other tags of its owner.
+        let share = Share {
+            user_id: 3,
+            role: Role::Viewer,
+        };
+        repo.share_todo(todo.todo_id, share).await.unwrap();
+        let visible = repo.get_tags(Some(3)).await.unwrap();
+        assert_eq!(names(visible), vec!["theirs"]);
+        repo.attach_tag(todo.todo_id, mine.tag_id).await.unwrap();
+        assert_eq!(repo.get_tags(Some(3)).await.unwrap().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_attach_and_detach_tags() {
+        let repo = create_test_repo();
+        let todo = repo
+            .create_todo(create_new_todo("Deploy", None))
+            .await
+            .unwrap();
+        assert!(todo.tags.is_empty());
+        let urgent = repo.create_tag(new_tag("urgent")).await.unwrap();
+        let ops = repo.create_tag(new_tag("ops")).await.unwrap();
+
+        repo.attach_tag(todo.todo_id, urgent.tag_id).await.unwrap();
+        let tagged = repo.attach_tag(todo.todo_id, ops.tag_id).await.unwrap();
+        assert_eq!(tag_names(&tagged), vec!["ops", "urgent"]);
+        // Attaching twice changes nothing.
+        let tagged = repo.attach_tag(todo.todo_id, ops.tag_id).await.unwrap();
+        assert_eq!(tagged.tags.len(), 2);
+        let stored = repo.get_todo_by_id(todo.todo_id).await.unwrap();
+        assert_eq!(tag_names(&stored), vec!["ops", "urgent"]);
+        assert_eq!(tag_names(&repo.get_todos().await.unwrap()[0]), vec!["ops", "urgent"]);
+
+        let untagged = repo.detach_tag(todo.todo_id, urgent.tag_id).await.unwrap();
+        assert_eq!(tag_names(&untagged), vec!["ops"]);
+        let untagged = repo.detach_tag(todo.todo_id, urgent.tag_id).await.unwrap();
+        assert_eq!(tag_names(&untagged), vec!["ops"]);
+
+        assert_eq!(
+            repo.attach_tag(todo.todo_id, 99).await.unwrap_err(),
+            RepoError::Missing("tag")
+        );
+        assert_eq!(
+            repo.attach_tag(99, ops.tag_id).await.unwrap_err(),
+            RepoError::NotFound
+        );
+
+        // Renaming shows up on the todo, deleting detaches.
+        repo.update_tag_by_id(ops.tag_id, new_tag("operations"))
+            .await
+            .unwrap();
+        let stored = repo.get_todo_by_id(todo.todo_id).await.unwrap();
+        assert_eq!(tag_names(&stored), vec!["operations"]);
+        repo.delete_tag_by_id(ops.tag_id).await.unwrap();
+        assert!(repo.get_todo_by_id(todo.todo_id).await.unwrap().tags.is_empty());
+
+        // Deleting a tagged todo leaves the tag alone.
+        repo.attach_tag(todo.todo_id, urgent.tag_id).await.unwrap();
+        repo.delete_todo_by_id(todo.todo_id).await.unwrap();
+        assert_eq!(repo.get_tags(None).await.unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_update_keeps_tags() {
+        let repo = create_test_repo();
+        let mut new_todo = create_new_todo("Rotate keys", None);
+        new_todo.due_at = NaiveDate::from_ymd_opt(2030, 6, 1).and_then(|d| d.and_hms_opt(9, 0, 0));
+        new_todo.recurrence = Some("FREQ=MONTHLY;BYMONTHDAY=1".to_string());
+        let todo = repo.create_todo(new_todo).await.unwrap();
+        let ops = repo.create_tag(new_tag("ops")).await.unwrap();
+        let mut todo = repo.attach_tag(todo.todo_id, ops.tag_id).await.unwrap();
+
+        // Tags in the body of an update are ignored.
+        todo.tags.clear();
+        todo.title = "Rotate all keys".to_string();
+        let updated = repo.update_todo_by_id(todo.todo_id, todo).await.unwrap();
+        assert_eq!(tag_names(&updated), vec!["ops"]);
+
+        // The next instance of a recurring todo keeps the tags.
+        let patch = TodoPatch {
+            completed: Some(true),
+            ..Default::default()
+        };
+        let completed = repo.patch_todo_by_id(updated.todo_id, patch).await.unwrap();
+        assert_eq!(tag_names(&completed), vec!["ops"]);
+        let todos = repo.get_todos().await.unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(tag_names(&todos[1]), vec!["ops"]);
+    }
+
+    #[actix_web::test]
+    async fn test_query_filter_by_tags() {
+        let repo = create_test_repo();
+        let backend = repo.create_tag(new_tag("backend")).await.unwrap();
+        let urgent = repo.create_tag(new_tag("urgent")).await.unwrap();
+        for (title, tags) in [
+            ("api", vec![backend.tag_id]),
+            ("hotfix", vec![backend.tag_id, urgent.tag_id]),
+            ("call", vec![urgent.tag_id]),
+            ("plain", vec![]),
+        ] {
+            let todo = repo.create_todo(create_new_todo(title, None)).await.unwrap();
+            for tag_id in tags {
+                repo.attach_tag(todo.todo_id, tag_id).await.unwrap();
+            }
+        }
+
+        let titles = |query: TodoQuery| {
+            let repo = &repo;
+            async move {
+                repo.query_todos(query)
+                    .await
+                    .unwrap()
+                    .items
+                    .into_iter()
+                    .map(|t| t.title)
+                    .collect::<Vec<_>>()
+            }
+        };
+        let query = TodoQuery {
+            tag: Some("backend".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(titles(query).await, vec!["api", "hotfix"]);
+
+        let query = TodoQuery {
+            tag: Some("Backend, URGENT".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(titles(query).await, vec!["api", "hotfix", "call"]);
+
+        let query = TodoQuery {
+            tag: Some("backend,urgent".to_string()),
+            tag_mode: TagMode::And,
+            ..Default::default()
+        };
+        let page = repo.query_todos(query.clone()).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(titles(query).await, vec!["hotfix"]);
+
+        let query = TodoQuery {
+            tag: Some("unknown".to_string()),
+            ..Default::default()
+        };
+        assert!(titles(query).await.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_list_crud() {
+        let repo = create_test_repo();
+        let lists = repo.get_lists().await.unwrap();
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].list_id, DEFAULT_LIST_ID);
+        assert_eq!(lists[0].name, DEFAULT_LIST_NAME);
+
+        let work = repo.create_list(new_list("Work")).await.unwrap();
+        assert_ne!(work.list_id, DEFAULT_LIST_ID);
+        let result = repo.create_list(new_list("")).await;
+        assert!(matches!(result, Err(RepoError::Validation(_))));
+
+        let renamed = repo
+            .update_list_by_id(work.list_id, new_list("Office"))
+            .await
+            .unwrap();
+        assert_eq!(renamed.list_id, work.list_id);
+        assert_eq!(repo.get_list_by_id(work.list_id).await.unwrap().name, "Office");
+        let names: Vec<String> = repo
+            .get_lists()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|list| list.name)
+            .collect();
+        assert_eq!(names, vec![DEFAULT_LIST_NAME, "Office"]);
+
+        assert_eq!(repo.delete_list_by_id(work.list_id, false).await.unwrap(), 1);
+        assert_eq!(
+            repo.get_list_by_id(work.list_id).await.unwrap_err(),
+            RepoError::Missing("list")
+        );
+        assert_eq!(
+            repo.delete_list_by_id(work.list_id, true).await.unwrap_err(),
+            RepoError::Missing("list")
+        );
+        assert_eq!(
+            repo.update_list_by_id(work.list_id, new_list("Work")).await.unwrap_err(),
+            RepoError::Missing("list")
+        );
+        // Ids are not reused.
+        let home = repo.create_list(new_list("Home")).await.unwrap();
+        assert!(home.list_id > work.list_id);
+    }
+
+    #[actix_web::test]
+    async fn test_todos_belong_to_lists() {
+        let repo = create_test_repo();
+        let work = repo.create_list(new_list("Work")).await.unwrap();
+        let inbox_todo = repo
+            .create_todo(create_new_todo("Buy milk", None))
+            .await
+            .unwrap();
+        assert_eq!(inbox_todo.list_id, DEFAULT_LIST_ID);
+        let mut new_todo = create_new_todo("Write report", None);
+        new_todo.list_id = work.list_id;
+        let work_todo = repo.create_todo(new_todo).await.unwrap();
+        assert_eq!(work_todo.list_id, work.list_id);
+
+        let in_list = |list_id| TodoQuery {
+            list_id: Some(list_id),
+            ..Default::default()
+        };
+        let page = repo.query_todos(in_list(work.list_id)).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].title, "Write report");
+        assert_eq!(repo.query_todos(in_list(DEFAULT_LIST_ID)).await.unwrap().total, 1);
+        assert_eq!(repo.get_todos().await.unwrap().len(), 2);
+
+        // Moving a todo to another list.
+        let patch = TodoPatch {
+            list_id: Some(work.list_id),
+            ..Default::default()
+        };
+        let moved = repo.patch_todo_by_id(inbox_todo.todo_id, patch).await.unwrap();
+        assert_eq!(moved.list_id, work.list_id);
+        assert_eq!(repo.query_todos(in_list(work.list_id)).await.unwrap().total, 2);
+
+        // Unknown lists are rejected everywhere.
+        let mut new_todo = create_new_todo("Lost", None);
+        new_todo.list_id = 99;
+        assert_eq!(
+            repo.create_todo(new_todo).await.unwrap_err(),
+            RepoError::Missing("list")
+        );
+        let patch = TodoPatch {
+            list_id: Some(99),
+            ..Default::default()
+        };
+        assert_eq!(
+            repo.patch_todo_by_id(moved.todo_id, patch).await.unwrap_err(),
+            RepoError::Missing("list")
+        );
+        let mut todo = moved.clone();
+        todo.list_id = 99;
+        assert_eq!(
+            repo.update_todo_by_id(todo.todo_id, todo).await.unwrap_err(),
+            RepoError::Missing("list")
+        );
+        assert_eq!(repo.get_todo_by_id(moved.todo_id).await.unwrap().list_id, work.list_id);
+    }
+
+    #[actix_web::test]
+    async fn test_next_instance_stays_in_list() {
+        let repo = create_test_repo();
+        let work = repo.create_list(new_list("Work")).await.unwrap();
+        let mut new_todo = create_new_todo("Standup", None);
+        new_todo.list_id = work.list_id;
+        new_todo.recurrence = Some("FREQ=DAILY".to_string());
+        let todo = repo.create_todo(new_todo).await.unwrap();
+
+        let patch = TodoPatch {
+            completed: Some(true),
+            ..Default::default()
+        };
+        repo.patch_todo_by_id(todo.todo_id, patch).await.unwrap();
+        let todos = repo.get_todos().await.unwrap();
+        assert_eq!(todos.len(), 2);
+        assert!(todos.iter().all(|t| t.list_id == work.list_id));
+    }
+
+    #[actix_web::test]
+    async fn test_delete_list_refuses_or_cascades() {
+        let repo = create_test_repo();
+        let work = repo.create_list(new_list("Work")).await.unwrap();
+        let ops = repo.create_tag(new_tag("ops")).await.unwrap();
+        let mut new_todo = create_new_todo("Deploy", None);
+        new_todo.list_id = work.list_id;
+        let todo = repo.create_todo(new_todo).await.unwrap();
+        repo.attach_tag(todo.todo_id, ops.tag_id).await.unwrap();
+        let kept = repo
+            .create_todo(create_new_todo("Buy milk", None))
+            .await
+            .unwrap();
+
+        let result = repo.delete_list_by_id(work.list_id, false).await;
+        assert!(matches!(result, Err(RepoError::Conflict(_))));
+        assert_eq!(repo.get_todos().await.unwrap().len(), 2);
+
+        assert_eq!(repo.delete_list_by_id(work.list_id, true).await.unwrap(), 1);
+        assert_eq!(
+            repo.get_todo_by_id(todo.todo_id).await.unwrap_err(),
+            RepoError::NotFound
+        );
+        let todos = repo.get_todos().await.unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].todo_id, kept.todo_id);
+        // The tags of deleted todos stay.
+        assert_eq!(repo.get_tags(None).await.unwrap().len(), 1);
+
+        // The default list cannot go, not even when empty.
+        repo.delete_todo_by_id(kept.todo_id).await.unwrap();
+        for cascade in [false, true] {
+            let result = repo.delete_list_by_id(DEFAULT_LIST_ID, cascade).await;
+            assert!(matches!(result, Err(RepoError::Conflict(_))));
+        }
+        assert_eq!(repo.get_lists().await.unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_subtasks_and_subtree() {
+        let repo = create_test_repo();
+        let root = repo.create_todo(create_new_todo("Move", None)).await.unwrap();
+        assert_eq!(root.progress, None);
+        let pack = repo
+            .create_todo(create_subtask("Pack", root.todo_id))
+            .await
+            .unwrap();
+        let books = repo
+            .create_todo(create_subtask("Books", pack.todo_id))
+            .await
+            .unwrap();
+        let mut new_todo = create_subtask("Cancel internet", root.todo_id);
+        new_todo.completed = Some(true);
+        let cancel = repo.create_todo(new_todo).await.unwrap();
+        assert_eq!(cancel.parent_id, Some(root.todo_id));
+
+        let root = repo.get_todo_by_id(root.todo_id).await.unwrap();
+        let progress = root.progress.unwrap();
+        assert_eq!((progress.completed, progress.total), (1, 2));
+        assert!(!progress.is_done());
+
+        let ids: Vec<i32> = repo
+            .get_descendants(root.todo_id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|t| t.todo_id)
+            .collect();
+        assert_eq!(ids, vec![pack.todo_id, books.todo_id, cancel.todo_id]);
+
+        let tree = repo.get_subtree(root.todo_id).await.unwrap();
+        assert_eq!(tree.todo.title, "Move");
+        let children: Vec<&str> = tree.children.iter().map(|c| c.todo.title.as_str()).collect();
+        assert_eq!(children, vec!["Pack", "Cancel internet"]);
+        assert_eq!(tree.children[0].children[0].todo.todo_id, books.todo_id);
+        assert!(tree.children[1].children.is_empty());
+
+        let leaf = repo.get_subtree(books.todo_id).await.unwrap();
+        assert!(leaf.children.is_empty());
+        assert_eq!(repo.get_subtree(99).await.unwrap_err(), RepoError::NotFound);
+        assert_eq!(repo.get_descendants(99).await.unwrap_err(), RepoError::NotFound);
+    }
+
+    #[actix_web::test]
+    async fn test_subtask_placement_rules() {
+        let repo = create_test_repo();
+        let work = repo.create_list(new_list("Work")).await.unwrap();
+        let root = repo.create_todo(create_new_todo("Root", None)).await.unwrap();
+        let child = repo
+            .create_todo(create_subtask("Child", root.todo_id))
+            .await
+            .unwrap();
+        let grandchild = repo
+            .create_todo(create_subtask("Grandchild", child.todo_id))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repo.create_todo(create_subtask("Orphan", 99)).await.unwrap_err(),
+            RepoError::Missing("parent")
+        );
+        let mut new_todo = create_subtask("Elsewhere", root.todo_id);
+        new_todo.list_id = work.list_id;
+        assert_eq!(error_code(repo.create_todo(new_todo).await), "other_list");
+
+        // A todo cannot end up below itself.
+        let reparent = |parent_id| TodoPatch {
+            parent_id: Some(Some(parent_id)),
+            ..Default::default()
+        };
+        for parent_id in [root.todo_id, child.todo_id, grandchild.todo_id] {
+            let result = repo.patch_todo_by_id(root.todo_id, reparent(parent_id)).await;
+            assert_eq!(error_code(result), "cycle");
+        }
+        let mut todo = child.clone();
+        todo.parent_id = Some(grandchild.todo_id);
+        assert_eq!(error_code(repo.update_todo_by_id(child.todo_id, todo).await), "cycle");
+
+        // Todos with subtasks stay in their list, subtasks may move once detached.
+        let move_to = |list_id| TodoPatch {
+            list_id: Some(list_id),
+            ..Default::default()
+        };
+        let result = repo.patch_todo_by_id(child.todo_id, move_to(work.list_id)).await;
+        assert_eq!(error_code(result), "has_subtasks");
+        let result = repo.patch_todo_by_id(grandchild.todo_id, move_to(work.list_id)).await;
+        assert_eq!(error_code(result), "other_list");
+        let patch = TodoPatch {
+            parent_id: Some(None),
+            list_id: Some(work.list_id),
+            ..Default::default()
+        };
+        let moved = repo.patch_todo_by_id(grandchild.todo_id, patch).await.unwrap();
+        assert_eq!((moved.parent_id, moved.list_id), (None, work.list_id));
+
+        // Re-parenting within the list is fine.
+        let moved = repo
+            .patch_todo_by_id(child.todo_id, reparent(root.todo_id))
+            .await
+            .unwrap();
+        assert_eq!(moved.parent_id, Some(root.todo_id));
+        assert_eq!(repo.get_todo_by_id(root.todo_id).await.unwrap().parent_id, None);
+    }
+
+    #[actix_web::test]
+    async fn test_completion_rolls_up() {
+        let repo = create_test_repo();
+        let mut new_todo = create_new_todo("Release", None);
+        new_todo.auto_complete = true;
+        let release = repo.create_todo(new_todo).await.unwrap();
+        let mut new_todo = create_subtask("Test", release.todo_id);
+        new_todo.auto_complete = true;
+        let test = repo.create_todo(new_todo).await.unwrap();
+        let unit = repo
+            .create_todo(create_subtask("Unit tests", test.todo_id))
+            .await
+            .unwrap();
+        let docs = repo
+            .create_todo(create_subtask("Docs", release.todo_id))
+            .await
+            .unwrap();
+        let complete = TodoPatch {
+            completed: Some(true),
+            ..Default::default()
+        };
+
+        // Completing the only subtask of `Test` completes it, `Release` still
+        // waits for `Docs`.
+        let done = repo.patch_todo_by_id(unit.todo_id, complete.clone()).await.unwrap();
+        assert_eq!(done.completed, Some(true));
+        assert_eq!(repo.get_todo_by_id(test.todo_id).await.unwrap().completed, Some(true));
+        let release_now = repo.get_todo_by_id(release.todo_id).await.unwrap();
+        assert_eq!(release_now.completed, Some(false));
+        let progress = release_now.progress.unwrap();
+        assert_eq!((progress.completed, progress.total), (1, 2));
+
+        let mut todo = repo.get_todo_by_id(docs.todo_id).await.unwrap();
+        todo.completed = Some(true);
+        repo.update_todo_by_id(docs.todo_id, todo).await.unwrap();
+        let release_now = repo.get_todo_by_id(release.todo_id).await.unwrap();
+        assert_eq!(release_now.completed, Some(true));
+        assert!(release_now.progress.unwrap().is_done());
+
+        // Reopening the parent sticks, only a change below it rolls up again.
+        let reopen = TodoPatch {
+            completed: Some(false),
+            ..Default::default()
+        };
+        let reopened = repo.patch_todo_by_id(release.todo_id, reopen).await.unwrap();
+        assert_eq!(reopened.completed, Some(false));
+
+        // Without `auto_complete` the parent only reports its progress.
+        let plain = repo.create_todo(create_new_todo("Plain", None)).await.unwrap();
+        let step = repo
+            .create_todo(create_subtask("Step", plain.todo_id))
+            .await
+            .unwrap();
+        repo.patch_todo_by_id(step.todo_id, complete).await.unwrap();
+        let plain = repo.get_todo_by_id(plain.todo_id).await.unwrap();
+        assert_eq!(plain.completed, Some(false));
+        assert!(plain.progress.unwrap().is_done());
+    }
+
+    #[actix_web::test]
+    async fn test_delete_removes_subtasks() {
+        let repo = create_test_repo();
+        let ops = repo.create_tag(new_tag("ops")).await.unwrap();
+        let mut new_todo = create_new_todo("Project", None);
+        new_todo.auto_complete = true;
+        let project = repo.create_todo(new_todo).await.unwrap();
+        let phase = repo
+            .create_todo(create_subtask("Phase", project.todo_id))
+            .await
+            .unwrap();
+        let task = repo
+            .create_todo(create_subtask("Task", phase.todo_id))
+            .await
+            .unwrap();
+        repo.attach_tag(task.todo_id, ops.tag_id).await.unwrap();
+        let mut new_todo = create_subtask("Kickoff", project.todo_id);
+        new_todo.completed = Some(true);
+        repo.create_todo(new_todo).await.unwrap();
+        let other = repo.create_todo(create_new_todo("Other", None)).await.unwrap();
+
+        // Removing the open phase leaves only completed subtasks behind.
+        assert_eq!(repo.delete_todo_by_id(phase.todo_id).await.unwrap(), 2);
+        assert_eq!(
+            repo.get_todo_by_id(task.todo_id).await.unwrap_err(),
+            RepoError::NotFound
+        );
+        let project_now = repo.get_todo_by_id(project.todo_id).await.unwrap();
+        assert_eq!(project_now.completed, Some(true));
+        let progress = project_now.progress.unwrap();
+        assert_eq!((progress.completed, progress.total), (1, 1));
+
+        assert_eq!(repo.delete_todo_by_id(project.todo_id).await.unwrap(), 2);
+        let todos = repo.get_todos().await.unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].todo_id, other.todo_id);
+        assert_eq!(
+            repo.delete_todo_by_id(project.todo_id).await.unwrap_err(),
+            RepoError::NotFound
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_blockers() {
+        let repo = create_test_repo();
+        let design = repo.create_todo(create_new_todo("Design", None)).await.unwrap();
+        let build = repo.create_todo(create_new_todo("Build", None)).await.unwrap();
+        let ship = repo.create_todo(create_new_todo("Ship", None)).await.unwrap();
+
+        let todo = repo.add_blocker(ship.todo_id, build.todo_id).await.unwrap();
+        assert_eq!(todo.blocked_by, vec![build.todo_id]);
+        // Adding a blocker twice is not an error.
+        repo.add_blocker(ship.todo_id, build.todo_id).await.unwrap();
+        let todo = repo.add_blocker(ship.todo_id, design.todo_id).await.unwrap();
+        assert_eq!(todo.blocked_by, vec![design.todo_id, build.todo_id]);
+        repo.add_blocker(build.todo_id, design.todo_id).await.unwrap();
+        let todo = repo.get_todo_by_id(ship.todo_id).await.unwrap();
+        assert_eq!(todo.blocked_by, vec![design.todo_id, build.todo_id]);
+
+        assert_eq!(
+            repo.add_blocker(ship.todo_id, 999).await.unwrap_err(),
+            RepoError::Missing("blocker")
+        );
+        assert_eq!(
+            repo.add_blocker(999, ship.todo_id).await.unwrap_err(),
+            RepoError::NotFound
+        );
+
+        // Neither a todo itself nor a todo it blocks, directly or not, may block it.
+        for (blocked, blocker) in [(&design, &design), (&build, &ship), (&design, &ship)] {
+            let result = repo.add_blocker(blocked.todo_id, blocker.todo_id).await;
+            assert_eq!(error_code(result), "cycle");
+        }
+
+        let todo = repo.remove_blocker(ship.todo_id, build.todo_id).await.unwrap();
+        assert_eq!(todo.blocked_by, vec![design.todo_id]);
+        // Removing it again is fine, the cycle through `build` is gone.
+        repo.remove_blocker(ship.todo_id, build.todo_id).await.unwrap();
+        repo.add_blocker(build.todo_id, ship.todo_id).await.unwrap();
+        assert_eq!(
+            repo.remove_blocker(ship.todo_id, 999).await.unwrap_err(),
+            RepoError::Missing("blocker")
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_next_and_ordered_todos() {
+        let repo = create_test_repo();
+        let work = repo.create_list(new_list("Work")).await.unwrap();
+        let ship = repo.create_todo(create_new_todo("Ship", None)).await.unwrap();
+        let build = repo.create_todo(create_new_todo("Build", None)).await.unwrap();
+        let design = repo.create_todo(create_new_todo("Design", None)).await.unwrap();
+        let mut new_todo = create_new_todo("Done", None);
+        new_todo.completed = Some(true);
+        let done = repo.create_todo(new_todo).await.unwrap();
+        let mut new_todo = create_new_todo("Elsewhere", None);
+        new_todo.list_id = work.list_id;
+        let elsewhere = repo.create_todo(new_todo).await.unwrap();
+        repo.add_blocker(ship.todo_id, build.todo_id).await.unwrap();
+        repo.add_blocker(build.todo_id, design.todo_id).await.unwrap();
+        repo.add_blocker(design.todo_id, done.todo_id).await.unwrap();
+        // A blocker in another list counts as well.
+        repo.add_blocker(elsewhere.todo_id, ship.todo_id).await.unwrap();
+
+        let ids = |todos: Vec<Todo>| todos.iter().map(|t| t.todo_id).collect::<Vec<_>>();
+        let next = repo.get_next_todos(None).await.unwrap();
+        assert_eq!(ids(next), vec![design.todo_id]);
+        let next = repo.get_next_todos(Some(work.list_id)).await.unwrap();
+        assert!(next.is_empty());
+        assert_eq!(
+            repo.get_next_todos(Some(999)).await.unwrap_err(),
+            RepoError::Missing("list")
+        );
+
+        let ordered = repo.get_ordered_todos(DEFAULT_LIST_ID).await.unwrap();
+        assert_eq!(
+            ids(ordered),
+            vec![done.todo_id, design.todo_id, build.todo_id, ship.todo_id]
+        );
+        assert_eq!(
+            repo.get_ordered_todos(999).await.unwrap_err(),
+            RepoError::Missing("list")
+        );
+
+        let complete = TodoPatch {
+            completed: Some(true),
+            ..Default::default()
+        };
+        repo.patch_todo_by_id(design.todo_id, complete).await.unwrap();
+        let next = repo.get_next_todos(None).await.unwrap();
+        assert_eq!(ids(next), vec![build.todo_id]);
+    }
+
+    #[actix_web::test]
+    async fn test_completion_waits_for_blockers() {
+        let repo = create_test_repo();
+        let build = repo.create_todo(create_new_todo("Build", None)).await.unwrap();
+        let mut new_todo = create_new_todo("Ship", None);
+        new_todo.auto_complete = true;
+        let ship = repo.create_todo(new_todo).await.unwrap();
+        let step = repo
+            .create_todo(create_subtask("Step", ship.todo_id))
+            .await
+            .unwrap();
+        repo.add_blocker(ship.todo_id, build.todo_id).await.unwrap();
+        let complete = TodoPatch {
+            completed: Some(true),
+            ..Default::default()
+        };
+
+        let err = repo
+            .patch_todo_by_id(ship.todo_id, complete.clone())
+            .await
+            .unwrap_err();
+        let blocker = build.todo_id.to_string();
+        assert!(matches!(&err, RepoError::Conflict(msg) if msg.contains(&blocker)));
+        let mut todo = repo.get_todo_by_id(ship.todo_id).await.unwrap();
+        todo.completed = Some(true);
+        let result = repo.update_todo_by_id(ship.todo_id, todo.clone()).await;
+        assert!(matches!(result, Err(RepoError::Conflict(_))));
+        // Other changes of a blocked todo are fine.
+        todo.completed = Some(false);
+        todo.title = "Ship it".to_string();
+        repo.update_todo_by_id(ship.todo_id, todo).await.unwrap();
+
+        // The roll-up leaves the blocked parent open.
+        repo.patch_todo_by_id(step.todo_id, complete.clone()).await.unwrap();
+        let ship_now = repo.get_todo_by_id(ship.todo_id).await.unwrap();
+        assert_eq!(ship_now.completed, Some(false));
+        assert!(ship_now.progress.unwrap().is_done());
+
+        repo.patch_todo_by_id(build.todo_id, complete.clone()).await.unwrap();
+        let done = repo.patch_todo_by_id(ship.todo_id, complete).await.unwrap();
+        assert_eq!(done.completed, Some(true));
+    }
+
+    #[actix_web::test]
+    async fn test_delete_removes_dependencies() {
+        let repo = create_test_repo();
+        let build = repo.create_todo(create_new_todo("Build", None)).await.unwrap();
+        let part = repo
+            .create_todo(create_subtask("Part", build.todo_id))
+            .await
+            .unwrap();
+        let ship = repo.create_todo(create_new_todo("Ship", None)).await.unwrap();
+        let docs = repo.create_todo(create_new_todo("Docs", None)).await.unwrap();
+        repo.add_blocker(ship.todo_id, part.todo_id).await.unwrap();
+        repo.add_blocker(build.todo_id, docs.todo_id).await.unwrap();
+
+        repo.delete_todo_by_id(build.todo_id).await.unwrap();
+        let ship_now = repo.get_todo_by_id(ship.todo_id).await.unwrap();
+        assert!(ship_now.blocked_by.is_empty());
+        let next = repo.get_next_todos(None).await.unwrap();
+        assert_eq!(next.len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_users() {
+        let repo = create_test_repo();
+        let alice = repo.create_user(new_user("Alice")).await.unwrap();
+        let bob = repo.create_user(new_user("bob")).await.unwrap();
+        assert_ne!(alice.user_id, bob.user_id);
+        assert_eq!(repo.get_user_by_id(alice.user_id).await.unwrap(), alice);
+
+        let result = repo.create_user(new_user("ALICE")).await;
+        assert!(matches!(result, Err(RepoError::Conflict(_))));
+
+        let record = repo.get_user_record("alice").await.unwrap();
+        assert_eq!(record.user, alice);
+        assert_eq!(record.password_hash, "hash of Alice");
+        let result = repo.get_user_record("carol").await;
+        assert_eq!(result.unwrap_err(), RepoError::Missing("user"));
+        let result = repo.get_user_by_id(bob.user_id + 1).await;
+        assert_eq!(result.unwrap_err(), RepoError::Missing("user"));
+    }
+
+    #[actix_web::test]
+    async fn test_api_keys() {
+        let repo = create_test_repo();
+        let alice = repo.create_user(new_user("alice")).await.unwrap();
+        let bob = repo.create_user(new_user("bob")).await.unwrap();
+        let read = new_api_key(alice.user_id, "aaa", vec![Scope::Read]);
+        let read = repo.create_api_key(read).await.unwrap();
+        assert_eq!(read.user_id, alice.user_id);
+        assert_eq!(read.scopes, vec![Scope::Read]);
+        let both = new_api_key(alice.user_id, "bbb", vec![Scope::Read, Scope::Write]);
+        let both = repo.create_api_key(both).await.unwrap();
+        repo.create_api_key(new_api_key(bob.user_id, "ccc", vec![Scope::Admin]))
+            .await
+            .unwrap();
+
+        assert_eq!(repo.get_api_keys(alice.user_id).await.unwrap(), vec![read.clone(), both]);
+        assert_eq!(repo.get_api_key_by_hash("aaa").await.unwrap(), read);
+        let result = repo.get_api_key_by_hash("zzz").await;
+        assert_eq!(result.unwrap_err(), RepoError::Missing("api key"));
+
+        // Only the owner can revoke a key.
+        let result = repo.delete_api_key(bob.user_id, read.key_id).await;
+        assert_eq!(result.unwrap_err(), RepoError::NotFound);
+        assert_eq!(repo.delete_api_key(alice.user_id, read.key_id).await.unwrap(), 1);
+        let result = repo.get_api_key_by_hash("aaa").await;
+        assert_eq!(result.unwrap_err(), RepoError::Missing("api key"));
+
+        let unknown = new_api_key(bob.user_id + 1, "ddd", vec![Scope::Read]);
+        let result = repo.create_api_key(unknown).await;
+        assert_eq!(result.unwrap_err(), RepoError::Missing("user"));
+    }
+
+    #[actix_web::test]
+    async fn test_owner_is_kept_and_filtered() {
+        let repo = create_test_repo();
+        let alice = repo.create_user(new_user("alice")).await.unwrap();
+        let mut new_todo = create_new_todo("Mine", None);
+        new_todo.owner_id = Some(alice.user_id);
+        let mine = repo.create_todo(new_todo).await.unwrap();
+        assert_eq!(mine.owner_id, Some(alice.user_id));
+        repo.create_todo(create_new_todo("Nobody's", None)).await.unwrap();
+
+        // Updates cannot hand a todo to someone else.
+        let mut todo = mine.clone();
+        todo.owner_id = None;
+        todo.title = "Still mine".to_string();
+        let updated = repo.update_todo_by_id(mine.todo_id, todo).await.unwrap();
+        assert_eq!(updated.owner_id, Some(alice.user_id));
+
+        let query = TodoQuery {
+            owner_id: Some(alice.user_id),
+            ..TodoQuery::default()
+        };
+        let page = repo.query_todos(query).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].title, "Still mine");
+    }
+
+    #[actix_web::test]
+    async fn test_shares_and_visibility() {
+        let repo = create_test_repo();
+        let alice = repo.create_user(new_user("alice")).await.unwrap().user_id;
+        let bob = repo.create_user(new_user("bob")).await.unwrap().user_id;
+        let owned_by = |title: &str, owner: i32, list_id: i32| NewTodo {
+            owner_id: Some(owner),
+            list_id,
+            ..create_new_todo(title, None)
+        };
+        let project = NewList {
+            owner_id: Some(alice),
+            ..new_list("Project")
+        };
+        let project = repo.create_list(project).await.unwrap();
+        assert_eq!(project.owner_id, Some(alice));
+        let single = repo.create_todo(owned_by("Single", alice, DEFAULT_LIST_ID)).await.unwrap();
+        let planned = repo.create_todo(owned_by("Planned", alice, project.list_id)).await.unwrap();
+        repo.create_todo(owned_by("Hidden", alice, DEFAULT_LIST_ID)).await.unwrap();
+
+        let visible = |user: i32| TodoQuery {
+            visible_to: Some(user),
+            ..TodoQuery::default()
+        };
+        assert_eq!(repo.query_todos(visible(bob)).await.unwrap().total, 0);
+        assert_eq!(repo.query_todos(visible(alice)).await.unwrap().total, 3);
+
+        let viewer = Share { user_id: bob, role: Role::Viewer };
+        assert_eq!(repo.share_todo(single.todo_id, viewer).await.unwrap(), viewer);
+        // Sharing again replaces the role.
+        let editor = Share { user_id: bob, role: Role::Editor };
+        repo.share_todo(single.todo_id, editor).await.unwrap();
+        assert_eq!(repo.get_todo_shares(single.todo_id).await.unwrap(), vec![editor]);
+        repo.share_list(project.list_id, viewer).await.unwrap();
+        assert_eq!(repo.get_list_shares(project.list_id).await.unwrap(), vec![viewer]);
+
+        let ids: Vec<i32> = repo
+            .query_todos(visible(bob))
+            .await
+            .unwrap()
+            .items
+            .iter()
+            .map(|todo| todo.todo_id)
+            .collect();
+        assert_eq!(ids, vec![single.todo_id, planned.todo_id]);
+
+        let unknown_user = Share { user_id: bob + 1, role: Role::Viewer };
+        let result = repo.share_todo(single.todo_id, unknown_user).await;
+        assert_eq!(result.unwrap_err(), RepoError::Missing("user"));
+        let result = repo.share_todo(planned.todo_id + 10, viewer).await;
+        assert_eq!(result.unwrap_err(), RepoError::NotFound);
+        let result = repo.share_list(project.list_id + 10, viewer).await;
+        assert_eq!(result.unwrap_err(), RepoError::Missing("list"));
+
+        assert_eq!(repo.unshare_todo(single.todo_id, bob).await.unwrap(), 1);
+        assert_eq!(repo.unshare_todo(single.todo_id, bob).await.unwrap(), 0);
+        assert_eq!(repo.unshare_list(project.list_id, bob).await.unwrap(), 1);
+        assert_eq!(repo.query_todos(visible(bob)).await.unwrap().total, 0);
+
+        // Shares go away with their todo.
+        repo.share_todo(single.todo_id, viewer).await.unwrap();
+        repo.delete_todo_by_id(single.todo_id).await.unwrap();
+        let result = repo.get_todo_shares(single.todo_id).await;
+        assert_eq!(result.unwrap_err(), RepoError::NotFound);
+    }
+
+    #[actix_web::test]
+    async fn test_versions_guard_writes() {
+        let repo = create_test_repo();
+        let mut new_todo = create_new_todo("Parent", None);
+        new_todo.auto_complete = true;
+        let parent = repo.create_todo(new_todo).await.unwrap();
+        let child = repo.create_todo(create_subtask("Child", parent.todo_id)).await.unwrap();
+        assert_eq!((parent.version, child.version), (1, 1));
+
+        let rename = |name: &str| TodoPatch {
+            title: Some(name.to_string()),
+            ..Default::default()
+        };
+        let renamed = repo.patch_todo_if_version(child.todo_id, rename("Kid"), Some(1)).await;
+        assert_eq!(renamed.unwrap().version, 2);
+        let stale = repo.patch_todo_if_version(child.todo_id, rename("Late"), Some(1)).await;
+        assert_eq!(stale.unwrap_err(), RepoError::stale(child.todo_id, 1));
+        assert_eq!(repo.get_todo_by_id(child.todo_id).await.unwrap().title, "Kid");
+
+        // Without an expected version writes always go through.
+        let mut todo = repo.get_todo_by_id(child.todo_id).await.unwrap();
+        todo.completed = Some(true);
+        let done = repo.update_todo_by_id(child.todo_id, todo).await.unwrap();
+        assert_eq!(done.version, 3);
+        // Completing the parent automatically counts as a change as well.
+        let parent_now = repo.get_todo_by_id(parent.todo_id).await.unwrap();
+        assert_eq!((parent_now.completed, parent_now.version), (Some(true), 2));
+
+        let todo = repo.get_todo_by_id(child.todo_id).await.unwrap();
+        let result = repo.update_todo_if_version(child.todo_id, todo, Some(2)).await;
+        assert_eq!(result.unwrap_err(), RepoError::stale(child.todo_id, 2));
+        let result = repo.delete_todo_if_version(parent.todo_id, Some(1)).await;
+        assert_eq!(result.unwrap_err(), RepoError::stale(parent.todo_id, 1));
+        assert_eq!(repo.delete_todo_if_version(parent.todo_id, Some(2)).await.unwrap(), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_batches_apply_one_by_one() {
+        let repo = create_test_repo();
+        assert!(!repo.atomic_batches());
+        let todo = repo.create_todo(create_new_todo("Existing", None)).await.unwrap();
+        let ops = vec![
+            BatchOp::Create { todo: create_new_todo("New", None) },
+            BatchOp::Complete { id: 999, version: None },
+            BatchOp::Update {
+                id: todo.todo_id,
+                patch: TodoPatch { title: Some("Renamed".to_string()), ..Default::default() },
+                version: Some(1),
+            },
+            BatchOp::Delete { id: todo.todo_id, version: Some(1) },
+        ];
+
+        // A failed operation does not stop the ones after it.
+        let results = repo.apply_batch(ops).await.unwrap();
+        assert_eq!(results.len(), 4);
+        assert!(matches!(&results[0], Ok(BatchOutcome::Todo(t)) if t.title == "New"));
+        assert_eq!(results[1].as_ref().unwrap_err(), &RepoError::NotFound);
+        assert!(matches!(&results[2], Ok(BatchOutcome::Todo(t)) if t.version == 2));
+        assert_eq!(results[3].as_ref().unwrap_err(), &RepoError::stale(todo.todo_id, 1));
+        assert_eq!(repo.get_todo_by_id(todo.todo_id).await.unwrap().title, "Renamed");
+    }
+
+    #[actix_web::test]
+    async fn test_delete_completed_and_complete_all() {
+        let repo = create_test_repo();
+        let list = repo.create_list(new_list("Other")).await.unwrap();
+        let ship = repo.create_todo(create_new_todo("Ship", None)).await.unwrap();
+        let build = repo.create_todo(create_new_todo("Build", None)).await.unwrap();
+        repo.add_blocker(ship.todo_id, build.todo_id).await.unwrap();
+        let elsewhere = NewTodo { list_id: list.list_id, ..create_new_todo("Elsewhere", None) };
+        let elsewhere = repo.create_todo(elsewhere).await.unwrap();
+
+        // Blockers are completed first, so the blocked todo is not refused.
+        let done = repo.complete_all(Some(DEFAULT_LIST_ID)).await.unwrap();
+        let ids: Vec<i32> = done.iter().map(|t| t.todo_id).collect();
+        assert_eq!(ids, vec![build.todo_id, ship.todo_id]);
+        assert!(done.iter().all(|t| t.completed == Some(true)));
+        let other = repo.get_todo_by_id(elsewhere.todo_id).await.unwrap();
+        assert_eq!(other.completed, Some(false));
+        assert!(repo.complete_all(Some(DEFAULT_LIST_ID)).await.unwrap().is_empty());
+
+        // A completed subtask goes with its completed parent and is counted once.
+        let step = repo.create_todo(create_subtask("Step", ship.todo_id)).await.unwrap();
+        let complete = TodoPatch { completed: Some(true), ..Default::default() };
+        repo.patch_todo_by_id(step.todo_id, complete).await.unwrap();
+        let open = repo.create_todo(create_new_todo("Open", None)).await.unwrap();
+        assert_eq!(repo.delete_completed(None).await.unwrap(), 3);
+        let left: Vec<i32> = repo.get_todos().await.unwrap().iter().map(|t| t.todo_id).collect();
+        assert_eq!(left, vec![elsewhere.todo_id, open.todo_id]);
+        assert_eq!(repo.delete_completed(None).await.unwrap(), 0);
+    }
+
+    #[actix_web::test]
+    async fn test_deleted_todos_can_be_restored() {
+        let repo = create_test_repo();
+        let ops = repo.create_tag(new_tag("ops")).await.unwrap();
+        let project = repo.create_todo(create_new_todo("Project", None)).await.unwrap();
+        let phase = repo.create_todo(create_subtask("Phase", project.todo_id)).await.unwrap();
+        let task = repo.create_todo(create_subtask("Task", phase.todo_id)).await.unwrap();
+        let review = repo.create_todo(create_new_todo("Review", None)).await.unwrap();
+        repo.attach_tag(task.todo_id, ops.tag_id).await.unwrap();
+        repo.add_blocker(review.todo_id, task.todo_id).await.unwrap();
+
+        // Trashed todos are gone for everything but the trash.
+        assert_eq!(repo.delete_todo_by_id(phase.todo_id).await.unwrap(), 2);
+        assert_eq!(repo.get_todo_by_id(task.todo_id).await.unwrap_err(), RepoError::NotFound);
+        assert_eq!(repo.get_todos().await.unwrap().len(), 2);
+        assert!(repo.get_descendants(project.todo_id).await.unwrap().is_empty());
+        assert!(repo.get_todo_by_id(review.todo_id).await.unwrap().blocked_by.is_empty());
+        let result = repo.create_todo(create_subtask("Late", phase.todo_id)).await;
+        assert_eq!(result.unwrap_err(), RepoError::Missing("parent"));
+        let trash = repo.get_trash(None).await.unwrap();
+        let ids: Vec<i32> = trash.iter().map(|t| t.todo_id).collect();
+        assert_eq!(ids, vec![phase.todo_id, task.todo_id]);
+        assert!(trash[0].deleted_at.is_some());
+        assert_eq!(trash[0].deleted_at, trash[1].deleted_at);
+        assert_eq!(tag_names(&trash[1]), vec!["ops"]);
+
+        // A subtask comes back with its parent only.
+        let result = repo.restore_todo(task.todo_id).await;
+        assert!(matches!(result, Err(RepoError::Conflict(_))));
+        let restored = repo.restore_todo(phase.todo_id).await.unwrap();
+        assert_eq!(restored.deleted_at, None);
+        assert_eq!(restored.version, phase.version);
+        assert_eq!(restored.progress.unwrap().total, 1);
+        let task_now = repo.get_todo_by_id(task.todo_id).await.unwrap();
+        assert_eq!(tag_names(&task_now), vec!["ops"]);
+        assert!(repo.get_trash(None).await.unwrap().is_empty());
+        let result = repo.restore_todo(phase.todo_id).await;
+        assert_eq!(result.unwrap_err(), RepoError::NotFound);
+        // Blockers are not restored.
+        assert!(repo.get_todo_by_id(review.todo_id).await.unwrap().blocked_by.is_empty());
+
+        // A subtask deleted before its parent stays in the trash.
+        repo.delete_todo_by_id(task.todo_id).await.unwrap();
+        repo.delete_todo_by_id(phase.todo_id).await.unwrap();
+        repo.restore_todo(phase.todo_id).await.unwrap();
+        let trash = repo.get_trash(None).await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].todo_id, task.todo_id);
+    }
+
+    #[actix_web::test]
+    async fn test_trash_is_purged() {
+        let repo = create_test_repo();
+        let first = repo.create_todo(create_new_todo("First", None)).await.unwrap();
+        let second = repo.create_todo(create_new_todo("Second", None)).await.unwrap();
+        let step = repo.create_todo(create_subtask("Step", second.todo_id)).await.unwrap();
+        let kept = repo.create_todo(create_new_todo("Kept", None)).await.unwrap();
+        repo.delete_todo_by_id(first.todo_id).await.unwrap();
+        repo.delete_todo_by_id(second.todo_id).await.unwrap();
+
+        // Most recently deleted first.
+        let trash = repo.get_trash(None).await.unwrap();
+        let ids: Vec<i32> = trash.iter().map(|t| t.todo_id).collect();
+        assert_eq!(ids, vec![second.todo_id, step.todo_id, first.todo_id]);
+
+        // Only trashed todos can be purged.
+        assert_eq!(repo.purge_todo(kept.todo_id).await.unwrap_err(), RepoError::NotFound);
+        assert_eq!(repo.purge_todo(first.todo_id).await.unwrap(), 1);
+        assert_eq!(repo.restore_todo(first.todo_id).await.unwrap_err(), RepoError::NotFound);
+
+        let long_ago = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        assert_eq!(repo.purge_trash(long_ago).await.unwrap(), 0);
+        let later = trash[0].deleted_at.unwrap() + chrono::Duration::days(1);
+        assert_eq!(repo.purge_trash(later).await.unwrap(), 2);
+        assert!(repo.get_trash(None).await.unwrap().is_empty());
+        assert_eq!(repo.get_todos().await.unwrap().len(), 1);
+        let next = repo.create_todo(create_new_todo("Next", None)).await.unwrap();
+        assert_eq!(next.todo_id, kept.todo_id + 1);
+
+        // A list holding only trashed todos counts as empty and takes them along.
+        let list = repo.create_list(new_list("Old")).await.unwrap();
+        let old = NewTodo { list_id: list.list_id, ..create_new_todo("Old", None) };
+        let old = repo.create_todo(old).await.unwrap();
+        repo.delete_todo_by_id(old.todo_id).await.unwrap();
+        repo.delete_list_by_id(list.list_id, false).await.unwrap();
+        assert!(repo.get_trash(None).await.unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_history_is_appended_and_filtered() {
+        let repo = create_test_repo();
+        let todo = repo.create_todo(create_new_todo("Audited", None)).await.unwrap();
+        let mut created = history_entry(todo.todo_id, Some(1), HistoryAction::Created, 8);
+        created.after = Some(todo.clone());
+        repo.record_history(vec![created]).await.unwrap();
+        let mut purged = history_entry(99, None, HistoryAction::Purged, 10);
+        purged.owner_id = None;
+        repo.record_history(vec![
+            history_entry(todo.todo_id, Some(2), HistoryAction::Updated, 9),
+            purged,
+        ])
         .await
         .unwrap();
+        repo.record_history(Vec::new()).await.unwrap();
 
-    // Update only the completed status
-    let mut updated_todo = created.clone();
-    updated_todo.completed = Some(true);
+        let all = repo.get_history(HistoryQuery::default()).await.unwrap();
+        assert_eq!(history_ids(&all), vec![1, 2, 3]);
+        assert_eq!(all[0].after.as_ref().unwrap().title, "Audited");
+        assert!(all[0].before.is_none());
+        assert_eq!(all[2].action, HistoryAction::Purged);
 
-    let result = repo.update_todo_by_id(created.todo_id, updated_todo).await;
-    assert!(result.is_some());
+        let query = HistoryQuery { todo_id: Some(todo.todo_id), ..HistoryQuery::default() };
+        assert_eq!(history_ids(&repo.get_history(query).await.unwrap()), vec![1, 2]);
+        let query = HistoryQuery { user_id: Some(2), ..HistoryQuery::default() };
+        assert_eq!(history_ids(&repo.get_history(query).await.unwrap()), vec![2]);
+        // `changed_after` is inclusive, `changed_before` exclusive.
+        let query = HistoryQuery {
+            changed_after: Some(on_first_of_may(9)),
+            changed_before: Some(on_first_of_may(10)),
+            ..HistoryQuery::default()
+        };
+        assert_eq!(history_ids(&repo.get_history(query).await.unwrap()), vec![2]);
+        // Visible are the changes of one's todos and the ones one made.
+        let query = HistoryQuery { visible_to: Some(1), ..HistoryQuery::default() };
+        assert_eq!(history_ids(&repo.get_history(query).await.unwrap()), vec![1, 2]);
+        let query = HistoryQuery { visible_to: Some(3), ..HistoryQuery::default() };
+        assert!(repo.get_history(query).await.unwrap().is_empty());
+        let query = HistoryQuery { limit: Some(1), offset: Some(1), ..HistoryQuery::default() };
+        assert_eq!(history_ids(&repo.get_history(query).await.unwrap()), vec![2]);
+        let query = HistoryQuery { offset: Some(2), ..HistoryQuery::default() };
+        assert_eq!(history_ids(&repo.get_history(query).await.unwrap()), vec![3]);
 
-    let updated = result.unwrap();
-    assert_eq!(updated.title, "Original");
-    assert_eq!(updated.description, Some("Description".to_string()));
-    assert_eq!(updated.completed, Some(true));
+        // The history outlives the todo.
+        repo.delete_todo_by_id(todo.todo_id).await.unwrap();
+        repo.purge_todo(todo.todo_id).await.unwrap();
+        assert_eq!(repo.get_history(HistoryQuery::default()).await.unwrap().len(), 3);
+    }
 }
 
 #[actix_web::test]
-async fn test_delete_todo_by_id_success() {
-    let repo = create_test_repo();
-
-    let created = repo
-        .create_todo(create_new_todo("To Delete", Some("Will be removed")))
+async fn test_completion_of_blocked_todos_can_be_allowed() {
+    let repo = MemRepo::new().with_block_completion(false);
+    let build = repo
+        .create_todo(create_new_todo("Build", None))
         .await
         .unwrap();
+    let mut new_todo = create_new_todo("Ship", None);
+    new_todo.auto_complete = true;
+    let ship = repo.create_todo(new_todo).await.unwrap();
+    let step = repo
+        .create_todo(create_subtask("Step", ship.todo_id))
+        .await
+        .unwrap();
+    repo.add_blocker(ship.todo_id, build.todo_id).await.unwrap();
+    let complete = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
 
-    let deleted = repo.delete_todo_by_id(created.todo_id).await;
-    assert!(deleted.is_some());
-
-    // Verify it's actually deleted
-    let not_found = repo.get_todo_by_id(created.todo_id).await;
-    assert!(not_found.is_none());
+    // The roll-up completes the parent despite its open blocker.
+    repo.patch_todo_by_id(step.todo_id, complete).await.unwrap();
+    let ship_now = repo.get_todo_by_id(ship.todo_id).await.unwrap();
+    assert_eq!(ship_now.completed, Some(true));
+    assert_eq!(ship_now.blocked_by, vec![build.todo_id]);
 }
 
 #[actix_web::test]
-async fn test_delete_todo_by_id_not_found() {
-    let repo = create_test_repo();
-    let result = repo.delete_todo_by_id(999).await;
-    assert!(result.is_none());
+async fn test_owned_repo_hides_other_users_todos() {
+    let repo: RepoBox = std::sync::Arc::new(MemRepo::new());
+    let alice = OwnedRepo::new(repo.clone(), 1);
+    let bob = OwnedRepo::new(repo.clone(), 2);
+    let mine = alice
+        .create_todo(create_new_todo("Mine", None))
+        .await
+        .unwrap();
+    assert_eq!(mine.owner_id, Some(1));
+    let theirs = bob
+        .create_todo(create_new_todo("Theirs", None))
+        .await
+        .unwrap();
+
+    assert_eq!(alice.get_todos().await.unwrap().len(), 1);
+    assert_eq!(repo.get_todos().await.unwrap().len(), 2);
+    let result = alice.get_todo_by_id(theirs.todo_id).await;
+    assert_eq!(result.unwrap_err(), RepoError::NotFound);
+    let patch = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    let result = alice.patch_todo_by_id(theirs.todo_id, patch).await;
+    assert_eq!(result.unwrap_err(), RepoError::NotFound);
+    let result = alice.delete_todo_by_id(theirs.todo_id).await;
+    assert_eq!(result.unwrap_err(), RepoError::NotFound);
+
+    // Other users' todos cannot be used as parent or blocker either.
+    let result = alice
+        .create_todo(create_subtask("Sneaky", theirs.todo_id))
+        .await;
+    assert_eq!(result.unwrap_err(), RepoError::Missing("parent"));
+    let result = alice.add_blocker(mine.todo_id, theirs.todo_id).await;
+    assert_eq!(result.unwrap_err(), RepoError::Missing("blocker"));
+    let next = alice.get_next_todos(None).await.unwrap();
+    assert_eq!(next.len(), 1);
+
+    // In a list without owner, cascading only takes the own todos, the list
+    // stays while Bob has some.
+    let list = repo.create_list(new_list("Shared")).await.unwrap();
+    let in_list = |title: &str| NewTodo {
+        list_id: list.list_id,
+        ..create_new_todo(title, None)
+    };
+    alice.create_todo(in_list("Mine too")).await.unwrap();
+    let kept = bob.create_todo(in_list("Theirs too")).await.unwrap();
+    let result = alice.delete_list_by_id(list.list_id, true).await;
+    assert!(matches!(result, Err(RepoError::Conflict(_))));
+    let left: Vec<i32> = repo
+        .get_todos()
+        .await
+        .unwrap()
+        .iter()
+        .map(|t| t.todo_id)
+        .collect();
+    assert_eq!(left, vec![mine.todo_id, theirs.todo_id, kept.todo_id]);
+    bob.delete_list_by_id(list.list_id, true).await.unwrap();
 }
 
 #[actix_web::test]
-async fn test_delete_todo_from_multiple() {
-    let repo = create_test_repo();
+async fn test_owned_repo_enforces_share_roles() {
+    let repo: RepoBox = std::sync::Arc::new(MemRepo::new());
+    let alice_id = repo.create_user(new_user("alice")).await.unwrap().user_id;
+    let bob_id = repo.create_user(new_user("bob")).await.unwrap().user_id;
+    let alice = OwnedRepo::new(repo.clone(), alice_id);
+    let bob = OwnedRepo::new(repo.clone(), bob_id);
+    let share = |role: Role| Share {
+        user_id: bob_id,
+        role,
+    };
+    let complete = || TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
 
-    // Create multiple todos
-    let todo1 = repo
-        .create_todo(create_new_todo("First", Some("First Description")))
+    let todo = alice
+        .create_todo(create_new_todo("Read me", None))
+        .await
+        .unwrap();
+    alice
+        .share_todo(todo.todo_id, share(Role::Viewer))
         .await
         .unwrap();
-    let todo2 = repo
-        .create_todo(create_new_todo("Second", Some("Second Description")))
+    assert_eq!(
+        bob.get_todo_by_id(todo.todo_id).await.unwrap().title,
+        "Read me"
+    );
+    let result = bob.patch_todo_by_id(todo.todo_id, complete()).await;
+    assert!(matches!(result, Err(RepoError::Forbidden(_))));
+    let result = bob.share_todo(todo.todo_id, share(Role::Owner)).await;
+    assert!(matches!(result, Err(RepoError::Forbidden(_))));
+
+    alice
+        .share_todo(todo.todo_id, share(Role::Editor))
         .await
         .unwrap();
-    let todo3 = repo
-        .create_todo(create_new_todo("Third", Some("Third Description")))
+    let patched = bob
+        .patch_todo_by_id(todo.todo_id, complete())
         .await
         .unwrap();
+    assert_eq!(patched.completed, Some(true));
+    assert_eq!(patched.owner_id, Some(alice_id));
+    let result = bob.delete_todo_by_id(todo.todo_id).await;
+    assert!(matches!(result, Err(RepoError::Forbidden(_))));
 
-    // Delete the middle one
-    let deleted = repo.delete_todo_by_id(todo2.todo_id).await;
-    assert!(deleted.is_some());
-
-    // Verify the list now has 2 items
-    let todos = repo.get_todos().await;
-    assert_eq!(todos.len(), 2);
+    // Sharing with oneself is refused, leaving a shared todo is allowed.
+    let result = alice.share_todo(
+        todo.todo_id,
+        Share {
+            user_id: alice_id,
+            role: Role::Viewer,
+        },
+    );
+    assert!(matches!(result.await, Err(RepoError::Validation(_))));
+    assert_eq!(bob.unshare_todo(todo.todo_id, bob_id).await.unwrap(), 1);
+    let result = bob.get_todo_by_id(todo.todo_id).await;
+    assert_eq!(result.unwrap_err(), RepoError::NotFound);
 
-    // Verify the correct todos remain
-    assert_eq!(todos[0].todo_id, todo1.todo_id);
-    assert_eq!(todos[1].todo_id, todo3.todo_id);
-
-    // Verify we can't get the deleted todo
-    let not_found = repo.get_todo_by_id(todo2.todo_id).await;
-    assert!(not_found.is_none());
+    // A list owned by Alice and shared for editing lets Bob add todos, which
+    // stay Bob's, and makes Alice the owner of them.
+    let list = alice.create_list(new_list("Project")).await.unwrap();
+    assert_eq!(list.owner_id, Some(alice_id));
+    assert!(!bob.get_lists().await.unwrap().contains(&list));
+    let in_list = || NewTodo {
+        list_id: list.list_id,
+        ..create_new_todo("Bob's", None)
+    };
+    let result = bob.create_todo(in_list()).await;
+    assert_eq!(result.unwrap_err(), RepoError::Missing("list"));
+    alice
+        .share_list(list.list_id, share(Role::Editor))
+        .await
+        .unwrap();
+    assert!(bob.get_lists().await.unwrap().contains(&list));
+    let bobs = bob.create_todo(in_list()).await.unwrap();
+    assert_eq!(bobs.owner_id, Some(bob_id));
+    let result = bob.delete_list_by_id(list.list_id, true).await;
+    assert!(matches!(result, Err(RepoError::Forbidden(_))));
+    assert_eq!(alice.delete_todo_by_id(bobs.todo_id).await.unwrap(), 1);
 }
 
 #[actix_web::test]
-async fn test_repository_consistency() {
-    let repo = create_test_repo();
-
-    // Create
-    let created = repo
-        .create_todo(create_new_todo(
-            "Consistency Test",
-            Some("Testing consistency"),
-        ))
+async fn test_owned_repo_bulk_changes_only_own_todos() {
+    let repo: RepoBox = std::sync::Arc::new(MemRepo::new());
+    let alice_id = repo.create_user(new_user("alice")).await.unwrap().user_id;
+    let bob_id = repo.create_user(new_user("bob")).await.unwrap().user_id;
+    let alice = OwnedRepo::new(repo.clone(), alice_id);
+    let bob = OwnedRepo::new(repo.clone(), bob_id);
+    let mine = bob
+        .create_todo(create_new_todo("Mine", None))
         .await
         .unwrap();
-
-    // Read
-    let fetched = repo.get_todo_by_id(created.todo_id).await.unwrap();
-    assert_eq!(created.todo_id, fetched.todo_id);
-    assert_eq!(created.title, fetched.title);
-
-    // Update
-    let mut updated_todo = fetched.clone();
-    updated_todo.completed = Some(true);
-    let updated = repo
-        .update_todo_by_id(created.todo_id, updated_todo)
+    let shared = alice
+        .create_todo(create_new_todo("Shared", None))
         .await
         .unwrap();
-    assert_eq!(updated.completed, Some(true));
-
-    // Verify update persisted
-    let refetched = repo.get_todo_by_id(created.todo_id).await.unwrap();
-    assert_eq!(refetched.completed, Some(true));
+    let share = Share {
+        user_id: bob_id,
+        role: Role::Editor,
+    };
+    alice.share_todo(shared.todo_id, share).await.unwrap();
 
-    // Delete
-    let deleted = repo.delete_todo_by_id(created.todo_id).await;
-    assert!(deleted.is_some());
+    let done = bob.complete_all(None).await.unwrap();
+    assert_eq!(done.len(), 1);
+    assert_eq!(done[0].todo_id, mine.todo_id);
+    let shared_now = alice.get_todo_by_id(shared.todo_id).await.unwrap();
+    assert_eq!(shared_now.completed, Some(false));
 
-    // Verify deletion
-    let not_found = repo.get_todo_by_id(created.todo_id).await;
-    assert!(not_found.is_none());
+    alice.complete_all(None).await.unwrap();
+    assert_eq!(bob.delete_completed(None).await.unwrap(), 1);
+    assert_eq!(repo.get_todos().await.unwrap().len(), 1);
 
-    // Verify empty list
-    let todos = repo.get_todos().await;
-    assert_eq!(todos.len(), 0);
+    // Batch operations are checked like the single calls.
+    let ops = vec![
+        BatchOp::Complete {
+            id: shared.todo_id,
+            version: None,
+        },
+        BatchOp::Delete {
+            id: shared.todo_id,
+            version: None,
+        },
+    ];
+    let results = bob.apply_batch(ops).await.unwrap();
+    assert!(results[0].is_ok());
+    assert!(matches!(results[1], Err(RepoError::Forbidden(_))));
 }
 
 #[actix_web::test]
-async fn test_todo_id_resets_after_empty() {
-    let repo = create_test_repo();
-
-    // Create and delete a todo
-    let todo1 = repo
-        .create_todo(create_new_todo("First", None))
+async fn test_owned_repo_keeps_trash_per_owner() {
+    let repo: RepoBox = std::sync::Arc::new(MemRepo::new());
+    let alice_id = repo.create_user(new_user("alice")).await.unwrap().user_id;
+    let bob_id = repo.create_user(new_user("bob")).await.unwrap().user_id;
+    let alice = OwnedRepo::new(repo.clone(), alice_id);
+    let bob = OwnedRepo::new(repo.clone(), bob_id);
+    let todo = alice
+        .create_todo(create_new_todo("Mine", None))
         .await
         .unwrap();
-    assert_eq!(todo1.todo_id, 1);
-
-    repo.delete_todo_by_id(todo1.todo_id).await;
-
-    // After all todos are deleted, ID resets to 1 (in-memory repo behavior)
-    let todo2 = repo
-        .create_todo(create_new_todo("Second", None))
+    let bobs = bob
+        .create_todo(create_new_todo("Bob's", None))
         .await
         .unwrap();
-    assert_eq!(todo2.todo_id, 1);
-}
+    alice.delete_todo_by_id(todo.todo_id).await.unwrap();
+    bob.delete_todo_by_id(bobs.todo_id).await.unwrap();
 
-#[actix_web::test]
-async fn test_create_todo() {
-    let repo = create_test_repo();
+    // Nobody else sees or touches the trashed todos of a user.
+    let trash = alice.get_trash(None).await.unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].todo_id, todo.todo_id);
+    assert_eq!(
+        bob.restore_todo(todo.todo_id).await.unwrap_err(),
+        RepoError::NotFound
+    );
+    assert_eq!(
+        bob.purge_todo(todo.todo_id).await.unwrap_err(),
+        RepoError::NotFound
+    );
+    let later = trash[0].deleted_at.unwrap() + chrono::Duration::days(1);
+    assert_eq!(bob.purge_trash(later).await.unwrap(), 1);
+    assert_eq!(repo.get_trash(None).await.unwrap().len(), 1);
 
-    let new_todo = create_new_todo("Ronny's Test Todo", Some("Testing creation"));
-    let result = repo.create_todo(new_todo).await;
-    assert!(result.is_ok());
+    let restored = alice.restore_todo(todo.todo_id).await.unwrap();
+    assert_eq!(restored.owner_id, Some(alice_id));
+    assert_eq!(bob.get_todos().await.unwrap().len(), 0);
+}
+
+/// The actions recorded for the todo `todo_id`, oldest first.
+async fn actions_of(repo: &RepoBox, todo_id: i32) -> Vec<HistoryAction> {
+    let query = HistoryQuery {
+        todo_id: Some(todo_id),
+        ..HistoryQuery::default()
+    };
+    let history = repo.get_history(query).await.unwrap();
+    history.iter().map(|entry| entry.action).collect()
 }
 
 #[actix_web::test]
-async fn test_create_todo_without_description() {
-    let repo = create_test_repo();
+async fn test_audit_repo_records_changes() {
+    use HistoryAction::*;
+
+    let repo: RepoBox = std::sync::Arc::new(MemRepo::new());
+    let audited = AuditRepo::new(repo.clone(), Some(7));
+    let project = audited
+        .create_todo(create_new_todo("Project", None))
+        .await
+        .unwrap();
+    let step = audited
+        .create_todo(create_subtask("Step", project.todo_id))
+        .await
+        .unwrap();
+    let rename = |title: &str| TodoPatch {
+        title: Some(title.to_string()),
+        ..Default::default()
+    };
+    audited
+        .patch_todo_by_id(project.todo_id, rename("Big project"))
+        .await
+        .unwrap();
+    // Failed changes are not recorded.
+    let result = audited
+        .patch_todo_if_version(project.todo_id, rename("Stale"), Some(1))
+        .await;
+    assert!(matches!(result, Err(RepoError::PreconditionFailed(_))));
+    audited.delete_todo_by_id(project.todo_id).await.unwrap();
+    audited.restore_todo(project.todo_id).await.unwrap();
+    audited.delete_todo_by_id(project.todo_id).await.unwrap();
+    audited.purge_todo(project.todo_id).await.unwrap();
 
-    let new_todo = create_new_todo("No Description Todo", None);
-    let result = repo.create_todo(new_todo).await;
-    assert!(result.is_ok());
+    let query = HistoryQuery {
+        todo_id: Some(project.todo_id),
+        ..HistoryQuery::default()
+    };
+    let history = repo.get_history(query).await.unwrap();
+    assert_eq!(
+        actions_of(&repo, project.todo_id).await,
+        vec![Created, Updated, Deleted, Restored, Deleted, Purged]
+    );
+    assert!(history.iter().all(|entry| entry.user_id == Some(7)));
+    assert_eq!(history[1].before.as_ref().unwrap().title, "Project");
+    assert_eq!(history[1].after.as_ref().unwrap().title, "Big project");
+    assert!(history[2].after.is_none());
+    assert!(history[3].before.is_none());
+    assert!(history[5].before.as_ref().unwrap().deleted_at.is_some());
+    // Subtasks go and come back with their parent.
+    assert_eq!(
+        actions_of(&repo, step.todo_id).await,
+        vec![Created, Deleted, Restored, Deleted, Purged]
+    );
+
+    // Tags and batches are changes as well; the server purges as nobody.
+    let tag = audited.create_tag(new_tag("ops")).await.unwrap();
+    let todo = audited
+        .create_todo(create_new_todo("Tagged", None))
+        .await
+        .unwrap();
+    audited.attach_tag(todo.todo_id, tag.tag_id).await.unwrap();
+    audited.complete_all(None).await.unwrap();
+    audited.delete_todo_by_id(todo.todo_id).await.unwrap();
+    let server = AuditRepo::new(repo.clone(), None);
+    let far_future = NaiveDate::from_ymd_opt(2999, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    assert_eq!(server.purge_trash(far_future).await.unwrap(), 1);
+    assert_eq!(
+        actions_of(&repo, todo.todo_id).await,
+        vec![Created, Updated, Updated, Deleted, Purged]
+    );
+    let query = HistoryQuery {
+        todo_id: Some(todo.todo_id),
+        ..HistoryQuery::default()
+    };
+    let history = repo.get_history(query).await.unwrap();
+    assert_eq!(tag_names(history[1].after.as_ref().unwrap()), vec!["ops"]);
+    assert_eq!(history[2].after.as_ref().unwrap().completed, Some(true));
+    assert_eq!(history[4].user_id, None);
 }
 
-// New Todos should increment their ID automatically (in memory db behaviour)
 #[actix_web::test]
-async fn test_todo_id_increments() {
-    let repo = create_test_repo(); 
-    let todo1 = repo
-        .create_todo(create_new_todo("First Todo", None))
+async fn test_owned_repo_shows_own_history() {
+    let repo: RepoBox = std::sync::Arc::new(MemRepo::new());
+    let alice_id = repo.create_user(new_user("alice")).await.unwrap().user_id;
+    let bob_id = repo.create_user(new_user("bob")).await.unwrap().user_id;
+    let as_user = |user_id: i32| {
+        let audited = AuditRepo::new(repo.clone(), Some(user_id));
+        OwnedRepo::new(std::sync::Arc::new(audited), user_id)
+    };
+    let alice = as_user(alice_id);
+    let bob = as_user(bob_id);
+    let shared = alice
+        .create_todo(create_new_todo("Shared", None))
         .await
         .unwrap();
-    assert_eq!(todo1.todo_id, 1);
-    let todo2 = repo
-        .create_todo(create_new_todo("Second Todo", None))
+    let secret = alice
+        .create_todo(create_new_todo("Secret", None))
         .await
         .unwrap();
-    assert_eq!(todo2.todo_id, 2);
+    let editor = Share {
+        user_id: bob_id,
+        role: Role::Editor,
+    };
+    alice.share_todo(shared.todo_id, editor).await.unwrap();
+    let patch = TodoPatch {
+        title: Some("Edited by Bob".to_string()),
+        ..Default::default()
+    };
+    bob.patch_todo_by_id(shared.todo_id, patch).await.unwrap();
+    let of_todo = |todo_id: i32| HistoryQuery {
+        todo_id: Some(todo_id),
+        ..HistoryQuery::default()
+    };
+
+    // The whole history of a todo one can see, but nothing of the others.
+    assert_eq!(
+        bob.get_history(of_todo(shared.todo_id))
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+    assert!(bob
+        .get_history(of_todo(secret.todo_id))
+        .await
+        .unwrap()
+        .is_empty());
+    let own = bob.get_history(HistoryQuery::default()).await.unwrap();
+    assert_eq!(own.len(), 1);
+    assert_eq!(own[0].user_id, Some(bob_id));
+    assert_eq!(
+        alice
+            .get_history(HistoryQuery::default())
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+
+    // Without the share only one's own changes are left.
+    alice.unshare_todo(shared.todo_id, bob_id).await.unwrap();
+    assert_eq!(
+        bob.get_history(of_todo(shared.todo_id))
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // The owner keeps the history of a purged todo.
+    alice.delete_todo_by_id(shared.todo_id).await.unwrap();
+    alice.purge_todo(shared.todo_id).await.unwrap();
+    assert_eq!(
+        alice
+            .get_history(of_todo(shared.todo_id))
+            .await
+            .unwrap()
+            .len(),
+        4
+    );
 }
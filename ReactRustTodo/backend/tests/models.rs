@@ -1,6 +1,12 @@
-use chrono::NaiveDateTime;
-use serde_json;
-use TodoRustBackend::models::todo::{NewTodo, Todo};
+use actix_web::web::Query;
+use TodoRustBackend::models::{
+    dependency::topological_order,
+    list::DEFAULT_LIST_ID,
+    query::{TagMode, TodoQuery},
+    tag::Tag,
+    todo::{NewTodo, Todo, TodoPatch},
+    tree::{Progress, TodoTree},
+};
 
 #[test]
 fn test_create_todo_struct() {
@@ -10,6 +16,18 @@ fn test_create_todo_struct() {
         description: Some("Test Description".to_string()),
         created_at: None,
         completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+        version: 1,
+        deleted_at: None,
+        tags: Vec::new(),
+        blocked_by: Vec::new(),
+        progress: None,
     };
 
     assert_eq!(todo.todo_id, 1);
@@ -27,6 +45,18 @@ fn test_todo_clone() {
         description: Some("Original Description".to_string()),
         created_at: None,
         completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+        version: 1,
+        deleted_at: None,
+        tags: Vec::new(),
+        blocked_by: Vec::new(),
+        progress: None,
     };
 
     let cloned = todo.clone();
@@ -44,6 +74,18 @@ fn test_todo_serialization() {
         description: Some("Test serialization".to_string()),
         created_at: None,
         completed: Some(true),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+        version: 1,
+        deleted_at: None,
+        tags: Vec::new(),
+        blocked_by: Vec::new(),
+        progress: None,
     };
 
     let json = serde_json::to_string(&todo).unwrap();
@@ -78,6 +120,18 @@ fn test_todo_roundtrip_serialization() {
         description: Some("Testing roundtrip".to_string()),
         created_at: None,
         completed: Some(true),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+        version: 1,
+        deleted_at: None,
+        tags: Vec::new(),
+        blocked_by: Vec::new(),
+        progress: None,
     };
 
     let json = serde_json::to_string(&original).unwrap();
@@ -96,6 +150,13 @@ fn test_create_new_todo_struct() {
         description: Some("New Description".to_string()),
         created_at: None,
         completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
     };
 
     assert_eq!(new_todo.title, "New Todo");
@@ -110,6 +171,13 @@ fn test_create_new_todo_minimal() {
         description: None,
         created_at: None,
         completed: None,
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
     };
 
     assert_eq!(new_todo.title, "Minimal New Todo");
@@ -168,6 +236,18 @@ fn test_todo_with_long_text() {
         description: Some(long_description.clone()),
         created_at: None,
         completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+        version: 1,
+        deleted_at: None,
+        tags: Vec::new(),
+        blocked_by: Vec::new(),
+        progress: None,
     };
 
     assert_eq!(todo.title.len(), 1000);
@@ -188,6 +268,18 @@ fn test_todo_with_newlines_and_quotes() {
         description: Some("Description with \"quotes\" and 'apostrophes'".to_string()),
         created_at: None,
         completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+        version: 1,
+        deleted_at: None,
+        tags: Vec::new(),
+        blocked_by: Vec::new(),
+        progress: None,
     };
 
     let json = serde_json::to_string(&todo).unwrap();
@@ -205,6 +297,18 @@ fn test_todo_debug_trait() {
         description: Some("Testing Debug".to_string()),
         created_at: None,
         completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+        version: 1,
+        deleted_at: None,
+        tags: Vec::new(),
+        blocked_by: Vec::new(),
+        progress: None,
     };
 
     let debug_str = format!("{:?}", todo);
@@ -228,6 +332,30 @@ fn test_todo_deserialization_minimal() {
     assert_eq!(todo.description, None);
     assert_eq!(todo.created_at, None);
     assert_eq!(todo.completed, None);
+    // Payloads of clients that predate due dates are still accepted.
+    assert_eq!(todo.due_at, None);
+    assert_eq!(todo.remind_at, None);
+}
+
+#[test]
+fn test_todo_with_due_dates_roundtrip() {
+    let json = r#"{
+        "todo_id": 1,
+        "title": "Pay rent",
+        "description": null,
+        "created_at": null,
+        "completed": false,
+        "due_at": "2030-01-31T18:00:00",
+        "remind_at": "2030-01-30T09:00:00"
+    }"#;
+
+    let todo: Todo = serde_json::from_str(json).unwrap();
+    assert_eq!(todo.due_at.unwrap().to_string(), "2030-01-31 18:00:00");
+    assert_eq!(todo.remind_at.unwrap().to_string(), "2030-01-30 09:00:00");
+
+    let value = serde_json::to_value(&todo).unwrap();
+    assert_eq!(value["due_at"], "2030-01-31T18:00:00");
+    assert_eq!(value["remind_at"], "2030-01-30T09:00:00");
 }
 
 #[test]
@@ -299,3 +427,248 @@ fn test_todo_with_special_characters() {
     assert_eq!(todo.created_at, None);
     assert_eq!(todo.completed, None);
 }
+
+#[test]
+fn test_todo_patch_absent_fields_are_none() {
+    let patch: TodoPatch = serde_json::from_str(r#"{ "completed": true }"#).unwrap();
+    assert_eq!(patch.title, None);
+    assert_eq!(patch.description, None);
+    assert_eq!(patch.completed, Some(true));
+    assert!(!patch.is_empty());
+}
+
+#[test]
+fn test_todo_patch_explicit_null_clears_description() {
+    let patch: TodoPatch = serde_json::from_str(r#"{ "description": null }"#).unwrap();
+    assert_eq!(patch.description, Some(None));
+
+    let mut todo = Todo {
+        todo_id: 1,
+        title: "Title".to_string(),
+        description: Some("Old".to_string()),
+        created_at: None,
+        completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+        version: 1,
+        deleted_at: None,
+        tags: Vec::new(),
+        blocked_by: Vec::new(),
+        progress: None,
+    };
+    patch.apply_to(&mut todo);
+    assert_eq!(todo.description, None);
+    assert_eq!(todo.title, "Title");
+    assert_eq!(todo.completed, Some(false));
+}
+
+#[test]
+fn test_todo_patch_clears_due_dates() {
+    let patch: TodoPatch =
+        serde_json::from_str(r#"{ "due_at": null, "remind_at": "2030-01-30T09:00:00" }"#).unwrap();
+    assert_eq!(patch.due_at, Some(None));
+    assert!(patch.remind_at.unwrap().is_some());
+    assert!(!patch.is_empty());
+
+    let mut todo: Todo = serde_json::from_str(
+        r#"{ "todo_id": 1, "title": "Title", "description": null, "created_at": null,
+             "completed": null, "due_at": "2030-01-31T18:00:00" }"#,
+    )
+    .unwrap();
+    patch.apply_to(&mut todo);
+    assert_eq!(todo.due_at, None);
+    assert!(todo.remind_at.is_some());
+}
+
+#[test]
+fn test_todo_patch_recurrence() {
+    let patch: TodoPatch = serde_json::from_str(r#"{ "recurrence": null }"#).unwrap();
+    assert_eq!(patch.recurrence, Some(None));
+    assert!(!patch.is_empty());
+
+    let mut todo: Todo = serde_json::from_str(
+        r#"{ "todo_id": 1, "title": "Title", "description": null, "created_at": null,
+             "completed": null, "recurrence": "FREQ=DAILY" }"#,
+    )
+    .unwrap();
+    assert_eq!(todo.recurrence, Some("FREQ=DAILY".to_string()));
+    patch.apply_to(&mut todo);
+    assert_eq!(todo.recurrence, None);
+    assert_eq!(serde_json::to_value(&todo).unwrap()["recurrence"], serde_json::Value::Null);
+}
+
+#[test]
+fn test_todo_tags_default_to_empty() {
+    let todo: Todo = serde_json::from_str(
+        r#"{ "todo_id": 1, "title": "Title", "description": null, "created_at": null,
+             "completed": null }"#,
+    )
+    .unwrap();
+    assert!(todo.tags.is_empty());
+
+    let todo: Todo = serde_json::from_str(
+        r#"{ "todo_id": 1, "title": "Title", "description": null, "created_at": null,
+             "completed": null, "tags": [{ "tag_id": 3, "name": "ops" }] }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        todo.tags,
+        vec![Tag {
+            tag_id: 3,
+            name: "ops".to_string(),
+            owner_id: None,
+        }]
+    );
+    assert_eq!(serde_json::to_value(&todo).unwrap()["tags"][0]["name"], "ops");
+}
+
+#[test]
+fn test_todo_query_tag_names() {
+    let query = Query::<TodoQuery>::from_query("tag=ops,%20backend,,&tag_mode=and")
+        .unwrap()
+        .into_inner();
+    assert_eq!(query.tag_names(), vec!["ops", "backend"]);
+    assert_eq!(query.tag_mode, TagMode::And);
+
+    let query = Query::<TodoQuery>::from_query("").unwrap().into_inner();
+    assert!(query.tag_names().is_empty());
+    assert_eq!(query.tag_mode, TagMode::Or);
+}
+
+#[test]
+fn test_list_id_defaults_to_default_list() {
+    let todo: Todo = serde_json::from_str(
+        r#"{ "todo_id": 1, "title": "Title", "description": null, "created_at": null,
+             "completed": null }"#,
+    )
+    .unwrap();
+    assert_eq!(todo.list_id, DEFAULT_LIST_ID);
+    let new_todo: NewTodo = serde_json::from_str(r#"{ "title": "Title" }"#).unwrap();
+    assert_eq!(new_todo.list_id, DEFAULT_LIST_ID);
+    let new_todo: NewTodo = serde_json::from_str(r#"{ "title": "Title", "list_id": 4 }"#).unwrap();
+    assert_eq!(new_todo.with_id(7).list_id, 4);
+
+    let patch: TodoPatch = serde_json::from_str(r#"{ "list_id": 4 }"#).unwrap();
+    assert_eq!(patch.list_id, Some(4));
+    assert!(!patch.is_empty());
+    let mut todo = todo;
+    patch.apply_to(&mut todo);
+    assert_eq!(todo.list_id, 4);
+}
+
+#[test]
+fn test_subtask_fields() {
+    let todo: Todo = serde_json::from_str(
+        r#"{ "todo_id": 1, "title": "Title", "description": null, "created_at": null,
+             "completed": null }"#,
+    )
+    .unwrap();
+    assert_eq!(todo.parent_id, None);
+    assert!(!todo.auto_complete);
+    // Todos without subtasks have no progress in their JSON.
+    let json = serde_json::to_value(&todo).unwrap();
+    assert!(json.get("progress").is_none());
+
+    let new_todo: NewTodo =
+        serde_json::from_str(r#"{ "title": "Title", "parent_id": 3, "auto_complete": true }"#)
+            .unwrap();
+    let todo = new_todo.with_id(7);
+    assert_eq!((todo.parent_id, todo.auto_complete), (Some(3), true));
+
+    // `null` detaches a subtask, an absent `parent_id` keeps it.
+    let patch: TodoPatch = serde_json::from_str(r#"{ "parent_id": null }"#).unwrap();
+    assert_eq!(patch.parent_id, Some(None));
+    assert!(!patch.is_empty());
+    let mut detached = todo.clone();
+    patch.apply_to(&mut detached);
+    assert_eq!(detached.parent_id, None);
+    let patch: TodoPatch = serde_json::from_str(r#"{ "auto_complete": false }"#).unwrap();
+    assert_eq!(patch.parent_id, None);
+    let mut kept = todo;
+    patch.apply_to(&mut kept);
+    assert_eq!((kept.parent_id, kept.auto_complete), (Some(3), false));
+}
+
+#[test]
+fn test_progress_of_subtasks() {
+    assert_eq!(Progress::of(Vec::new()), None);
+    let progress = Progress::of(vec![Some(true), None, Some(false)]).unwrap();
+    assert_eq!(
+        progress,
+        Progress {
+            completed: 1,
+            total: 3
+        }
+    );
+    assert!(!progress.is_done());
+    let done = Progress::of(vec![Some(true), Some(true)]).unwrap();
+    assert!(done.is_done());
+}
+
+#[test]
+fn test_todo_tree_build() {
+    let todo = |todo_id: i32, parent_id: Option<i32>| {
+        let new_todo: NewTodo = serde_json::from_str(r#"{ "title": "Title" }"#).unwrap();
+        Todo {
+            parent_id,
+            ..new_todo.with_id(todo_id)
+        }
+    };
+    let descendants = vec![
+        todo(4, Some(2)),
+        todo(3, Some(1)),
+        todo(2, Some(1)),
+        todo(5, Some(9)),
+    ];
+    let tree = TodoTree::build(todo(1, None), descendants);
+    let ids: Vec<i32> = tree.children.iter().map(|c| c.todo.todo_id).collect();
+    assert_eq!(ids, vec![2, 3]);
+    assert_eq!(tree.children[0].children[0].todo.todo_id, 4);
+    assert!(tree.children[1].children.is_empty());
+
+    // The todo fields sit next to `children` in the JSON.
+    let json = serde_json::to_value(&tree).unwrap();
+    assert_eq!(json["todo_id"], 1);
+    assert_eq!(json["children"][0]["children"][0]["todo_id"], 4);
+}
+
+#[test]
+fn test_todo_patch_empty() {
+    let patch: TodoPatch = serde_json::from_str("{}").unwrap();
+    assert!(patch.is_empty());
+}
+
+#[test]
+fn test_topological_order() {
+    let todo = |todo_id: i32, blocked_by: Vec<i32>| {
+        let new_todo: NewTodo = serde_json::from_str(r#"{ "title": "Title" }"#).unwrap();
+        Todo {
+            blocked_by,
+            ..new_todo.with_id(todo_id)
+        }
+    };
+    // 9 is not part of the list, so it does not hold back 3.
+    let todos = vec![
+        todo(1, vec![4]),
+        todo(2, vec![]),
+        todo(3, vec![9]),
+        todo(4, vec![2, 3]),
+        todo(5, vec![]),
+    ];
+    let ids: Vec<i32> = topological_order(todos).iter().map(|t| t.todo_id).collect();
+    assert_eq!(ids, vec![2, 3, 4, 1, 5]);
+
+    // Todos on a cycle come last, by id.
+    let todos = vec![todo(1, vec![2]), todo(2, vec![1]), todo(3, vec![])];
+    let ids: Vec<i32> = topological_order(todos).iter().map(|t| t.todo_id).collect();
+    assert_eq!(ids, vec![3, 1, 2]);
+
+    let json = r#"{ "todo_id": 1, "title": "Title", "completed": false }"#;
+    let todo: Todo = serde_json::from_str(json).unwrap();
+    assert!(todo.blocked_by.is_empty());
+}
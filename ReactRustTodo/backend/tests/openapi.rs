@@ -0,0 +1,152 @@
+use std::fs;
+use std::path::Path;
+
+use actix_web::{http::header, test, App};
+use serde_json::Value;
+use TodoRustBackend::api;
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+/// Prefixes `api::config` mounts the routes at.
+const MOUNTS: [&str; 3] = ["/api", "/api/v1", "/api/v2"];
+
+/// The handlers every version registers, as `(module, fn)`, read from the
+/// source of `routes` so a new `.service(...)` cannot slip past the spec.
+fn registered_handlers(src: &str) -> Vec<(String, String)> {
+    let start = src.find("fn routes(").expect("routes not found");
+    src[start..]
+        .split(".service(")
+        .skip(1)
+        .filter_map(|rest| {
+            let name = rest.split(')').next()?.trim();
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == ':')
+            {
+                return None;
+            }
+            Some(match name.split_once("::") {
+                Some((module, handler)) => (module.to_string(), handler.to_string()),
+                None => ("api".to_string(), name.to_string()),
+            })
+        })
+        .collect()
+}
+
+/// The `(method, path)` of the route attribute on `handler` in `src`.
+fn route_of(src: &str, handler: &str) -> (String, String) {
+    let at = src
+        .find(&format!("async fn {handler}("))
+        .unwrap_or_else(|| panic!("handler {handler} not found"));
+    let before = &src[..at];
+    METHODS
+        .iter()
+        .filter_map(|method| {
+            let attr = format!("#[{method}(\"");
+            let pos = before.rfind(&attr)?;
+            let path = before[pos + attr.len()..].split('"').next()?.to_string();
+            Some((pos, method.to_string(), path))
+        })
+        .max_by_key(|(pos, _, _)| *pos)
+        .map(|(_, method, path)| (method, path))
+        .unwrap_or_else(|| panic!("no route attribute on {handler}"))
+}
+
+async fn spec(prefix: &str) -> Value {
+    let app = test::init_service(App::new().configure(api::api::config)).await;
+    let req = test::TestRequest::get()
+        .uri(&format!("{prefix}/openapi.json"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    test::read_body_json(resp).await
+}
+
+#[actix_web::test]
+async fn every_route_is_documented() {
+    let api_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/api");
+    let config_src = fs::read_to_string(api_dir.join("api.rs")).unwrap();
+
+    let handlers = registered_handlers(&config_src);
+    assert!(
+        handlers.len() > 40,
+        "found only {} handlers",
+        handlers.len()
+    );
+    for prefix in MOUNTS {
+        let spec = spec(prefix).await;
+        for (module, handler) in &handlers {
+            let src = fs::read_to_string(api_dir.join(format!("{module}.rs"))).unwrap();
+            let (method, path) = route_of(&src, handler);
+            let path = format!("{prefix}{path}");
+            assert!(
+                spec["paths"][&path][&method].is_object(),
+                "{module}::{handler} ({} {path}) is missing from the OpenAPI document",
+                method.to_uppercase()
+            );
+        }
+    }
+}
+
+#[actix_web::test]
+async fn spec_describes_auth_and_schemas() {
+    let spec = spec("/api").await;
+
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+    assert_eq!(
+        spec["components"]["securitySchemes"]["bearer"]["scheme"],
+        "bearer"
+    );
+    assert!(spec["components"]["schemas"]["Todo"].is_object());
+    assert!(spec["components"]["schemas"]["ErrorResponse"].is_object());
+    // Open routes opt out of the global bearer requirement.
+    assert_eq!(
+        spec["paths"]["/api/auth/login"]["post"]["security"],
+        serde_json::json!([{}])
+    );
+    assert!(spec["paths"]["/api/todos"]["get"]["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|param| param["name"] == "completed" && param["in"] == "query"));
+}
+
+#[actix_web::test]
+async fn versions_document_deprecation_and_errors() {
+    let v1 = spec("/api/v1").await;
+    let v2 = spec("/api/v2").await;
+
+    let get_todo = &v1["paths"]["/api/v1/todos/{id}"]["get"];
+    assert_eq!(get_todo["deprecated"], true);
+    assert_eq!(
+        get_todo["responses"]["404"]["content"]["application/json"]["schema"]["$ref"],
+        "#/components/schemas/ErrorResponse"
+    );
+
+    let get_todo = &v2["paths"]["/api/v2/todos/{id}"]["get"];
+    assert!(get_todo["deprecated"].is_null());
+    assert_eq!(
+        get_todo["responses"]["404"]["content"]["application/problem+json"]["schema"]["$ref"],
+        "#/components/schemas/Problem"
+    );
+    // Success bodies stay JSON.
+    assert!(get_todo["responses"]["200"]["content"]["application/json"].is_object());
+    assert!(v2["components"]["schemas"]["Problem"].is_object());
+    assert!(v2["components"]["schemas"]["ErrorResponse"].is_null());
+}
+
+#[actix_web::test]
+async fn swagger_ui_is_served() {
+    let app = test::init_service(App::new().configure(api::api::config)).await;
+    for prefix in MOUNTS {
+        let req = test::TestRequest::get()
+            .uri(&format!("{prefix}/docs/"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert!(resp.status().is_success(), "{prefix}/docs/");
+        let content_type = resp.headers().get(header::CONTENT_TYPE).unwrap();
+        assert!(content_type.to_str().unwrap().starts_with("text/html"));
+    }
+}
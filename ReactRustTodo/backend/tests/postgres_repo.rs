@@ -0,0 +1,246 @@
+#![cfg(feature = "postgres")]
+
+//! Runs against the database in `POSTGRES_TEST_URL` and is skipped when the
+//! variable is not set. Todos, users, API keys, shares, the audit log and the
+//! lists of users are deleted before the test.
+
+use std::sync::Arc;
+
+use diesel::{sql_query, RunQueryDsl};
+use TodoRustBackend::{
+    models::{
+        api_key::{NewApiKey, NewApiKeyRecord, Scope},
+        batch::BatchOp,
+        history::{HistoryAction, HistoryQuery},
+        list::DEFAULT_LIST_ID,
+        query::{SortField, TodoQuery},
+        share::{Role, Share},
+        todo::{NewTodo, TodoPatch},
+        user::NewUser,
+    },
+    repository::{
+        audit_repo::AuditRepo, error::RepoError, postgres_repo::PostgresRepo, todo_repo::TodoRepo,
+        RepoBox,
+    },
+};
+
+fn create_new_todo(title: &str, description: Option<&str>) -> NewTodo {
+    NewTodo {
+        title: title.to_string(),
+        description: description.map(|s| s.to_string()),
+        created_at: None,
+        completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+    }
+}
+
+#[actix_web::test]
+async fn test_postgres_repo() {
+    let Ok(url) = std::env::var("POSTGRES_TEST_URL") else {
+        eprintln!("POSTGRES_TEST_URL not set, skipping");
+        return;
+    };
+    let repo = PostgresRepo::new(&url).unwrap();
+    let mut conn = repo.pool.get().unwrap();
+    for statement in [
+        "TRUNCATE todos, todo_shares, list_shares, api_keys, todo_history RESTART IDENTITY",
+        "DELETE FROM lists WHERE owner_id IS NOT NULL",
+        "DELETE FROM users",
+    ] {
+        sql_query(statement).execute(&mut conn).unwrap();
+    }
+    drop(conn);
+
+    let first = repo
+        .create_todo(create_new_todo("buy Milk", Some("Store")))
+        .await
+        .unwrap();
+    let second = repo
+        .create_todo(create_new_todo("Call Bob", None))
+        .await
+        .unwrap();
+    assert_eq!(first.todo_id, 1);
+    assert_eq!(second.todo_id, 2);
+    assert!(first.created_at.is_some());
+
+    let query = TodoQuery {
+        title: Some("MILK".to_string()),
+        ..Default::default()
+    };
+    let page = repo.query_todos(query).await.unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.items[0].todo_id, first.todo_id);
+
+    let query = TodoQuery {
+        sort: SortField::Title,
+        ..Default::default()
+    };
+    let page = repo.query_todos(query).await.unwrap();
+    assert_eq!(page.items[0].title, "buy Milk");
+
+    let patch = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    let patched = repo.patch_todo_by_id(second.todo_id, patch).await.unwrap();
+    assert_eq!(patched.completed, Some(true));
+    assert_eq!(patched.title, "Call Bob");
+
+    assert_eq!(repo.delete_todo_by_id(first.todo_id).await.unwrap(), 1);
+    assert_eq!(
+        repo.get_todo_by_id(first.todo_id).await.unwrap_err(),
+        RepoError::NotFound
+    );
+
+    // Usernames are unique ignoring case, like in the other backends.
+    let new_user = |username: &str| NewUser {
+        username: username.to_string(),
+        password_hash: "hash".to_string(),
+    };
+    let alice = repo.create_user(new_user("Alice")).await.unwrap();
+    let result = repo.create_user(new_user("alice")).await;
+    assert!(matches!(result, Err(RepoError::Conflict(_))));
+    assert_eq!(repo.get_user_record("ALICE").await.unwrap().user, alice);
+
+    // Scopes round-trip through their comma separated column.
+    let new_key = NewApiKeyRecord {
+        user_id: alice.user_id,
+        key: NewApiKey {
+            name: "bot".to_string(),
+            scopes: vec![Scope::Read, Scope::Write],
+            expires_at: None,
+        },
+        prefix: "tdk_0000".to_string(),
+        key_hash: "hash".to_string(),
+    };
+    let key = repo.create_api_key(new_key).await.unwrap();
+    assert_eq!(repo.get_api_key_by_hash("hash").await.unwrap(), key);
+    assert_eq!(key.scopes, vec![Scope::Read, Scope::Write]);
+    assert_eq!(
+        repo.delete_api_key(alice.user_id, key.key_id)
+            .await
+            .unwrap(),
+        1
+    );
+
+    // Sharing twice replaces the role, the query sees todos shared with Bob.
+    let bob = repo.create_user(new_user("Bob")).await.unwrap();
+    let mut new_todo = create_new_todo("Shared", None);
+    new_todo.owner_id = Some(alice.user_id);
+    let shared = repo.create_todo(new_todo).await.unwrap();
+    for role in [Role::Viewer, Role::Editor] {
+        let share = Share {
+            user_id: bob.user_id,
+            role,
+        };
+        repo.share_todo(shared.todo_id, share).await.unwrap();
+    }
+    let shares = repo.get_todo_shares(shared.todo_id).await.unwrap();
+    assert_eq!(shares[0].role, Role::Editor);
+    let query = TodoQuery {
+        visible_to: Some(bob.user_id),
+        ..Default::default()
+    };
+    let page = repo.query_todos(query).await.unwrap();
+    assert_eq!(page.items[0].todo_id, shared.todo_id);
+
+    // Writes with an outdated version are refused.
+    let rename = TodoPatch {
+        title: Some("Renamed".to_string()),
+        ..Default::default()
+    };
+    let renamed = repo
+        .patch_todo_if_version(shared.todo_id, rename.clone(), Some(1))
+        .await
+        .unwrap();
+    assert_eq!(renamed.version, 2);
+    assert_eq!(
+        repo.patch_todo_if_version(shared.todo_id, rename, Some(1))
+            .await
+            .unwrap_err(),
+        RepoError::stale(shared.todo_id, 1)
+    );
+
+    // A failing operation rolls the whole batch back.
+    let ops = vec![
+        BatchOp::Create {
+            todo: create_new_todo("Rolled back", None),
+        },
+        BatchOp::Delete {
+            id: 999,
+            version: None,
+        },
+    ];
+    assert_eq!(
+        repo.apply_batch(ops).await.unwrap_err(),
+        RepoError::NotFound.in_batch(1)
+    );
+    let query = TodoQuery {
+        title: Some("Rolled back".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(repo.query_todos(query).await.unwrap().total, 0);
+
+    // Deleted todos wait in the trash until they are restored or purged.
+    let parent = repo
+        .create_todo(create_new_todo("Trashed", None))
+        .await
+        .unwrap();
+    let mut new_todo = create_new_todo("Trashed child", None);
+    new_todo.parent_id = Some(parent.todo_id);
+    let child = repo.create_todo(new_todo).await.unwrap();
+    assert_eq!(repo.delete_todo_by_id(parent.todo_id).await.unwrap(), 2);
+    assert_eq!(
+        repo.get_todo_by_id(child.todo_id).await.unwrap_err(),
+        RepoError::NotFound
+    );
+    assert!(matches!(
+        repo.restore_todo(child.todo_id).await,
+        Err(RepoError::Conflict(_))
+    ));
+    let restored = repo.restore_todo(parent.todo_id).await.unwrap();
+    assert_eq!(restored.progress.unwrap().total, 1);
+    repo.delete_todo_by_id(parent.todo_id).await.unwrap();
+    assert_eq!(repo.get_trash(None).await.unwrap().len(), 2);
+    assert_eq!(repo.purge_todo(parent.todo_id).await.unwrap(), 2);
+    assert!(repo.get_trash(None).await.unwrap().is_empty());
+
+    // Every change is recorded in the audit log and outlives the todo.
+    let repo: RepoBox = Arc::new(repo);
+    let audited = AuditRepo::new(repo.clone(), None);
+    let todo = audited
+        .create_todo(create_new_todo("Audited", None))
+        .await
+        .unwrap();
+    audited.delete_todo_by_id(todo.todo_id).await.unwrap();
+    audited.purge_todo(todo.todo_id).await.unwrap();
+    let query = HistoryQuery {
+        todo_id: Some(todo.todo_id),
+        ..Default::default()
+    };
+    let history = repo.get_history(query).await.unwrap();
+    let actions: Vec<HistoryAction> = history.iter().map(|entry| entry.action).collect();
+    assert_eq!(
+        actions,
+        vec![
+            HistoryAction::Created,
+            HistoryAction::Deleted,
+            HistoryAction::Purged
+        ]
+    );
+    assert_eq!(history[2].before.as_ref().unwrap().title, "Audited");
+    let query = HistoryQuery {
+        todo_id: Some(todo.todo_id),
+        limit: Some(1),
+        offset: Some(1),
+        ..Default::default()
+    };
+    let page = repo.get_history(query).await.unwrap();
+    assert_eq!(page[0].history_id, history[1].history_id);
+}
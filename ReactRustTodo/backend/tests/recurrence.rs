@@ -0,0 +1,203 @@
+use chrono::{NaiveDate, NaiveDateTime, Weekday};
+use TodoRustBackend::{
+    models::{list::DEFAULT_LIST_ID, todo::Todo},
+    recurrence::{follow_up, Recurrence},
+};
+
+fn at(year: i32, month: u32, day: u32, hour: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|d| d.and_hms_opt(hour, 0, 0))
+        .unwrap()
+}
+
+fn rule(rule: &str) -> Recurrence {
+    rule.parse().unwrap()
+}
+
+fn recurring_todo(recurrence: &str, due_at: Option<NaiveDateTime>) -> Todo {
+    Todo {
+        todo_id: 1,
+        title: "Water plants".to_string(),
+        description: Some("balcony".to_string()),
+        created_at: None,
+        completed: Some(false),
+        due_at,
+        remind_at: None,
+        recurrence: Some(recurrence.to_string()),
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+        version: 1,
+        deleted_at: None,
+        tags: Vec::new(),
+        blocked_by: Vec::new(),
+        progress: None,
+    }
+}
+
+#[test]
+fn test_parse_rules() {
+    assert_eq!(rule("FREQ=DAILY"), Recurrence::Daily { interval: 1 });
+    assert_eq!(
+        rule("freq=daily; interval=3"),
+        Recurrence::Daily { interval: 3 }
+    );
+    assert_eq!(
+        rule("FREQ=WEEKLY;BYDAY=FR,MO,FR"),
+        Recurrence::Weekly {
+            weekdays: vec![Weekday::Mon, Weekday::Fri]
+        }
+    );
+    assert_eq!(
+        rule("FREQ=MONTHLY;BYMONTHDAY=31"),
+        Recurrence::Monthly { day: 31 }
+    );
+    assert_eq!(
+        rule("FREQ=AFTER_COMPLETION;INTERVAL=10"),
+        Recurrence::AfterCompletion { days: 10 }
+    );
+}
+
+#[test]
+fn test_display_is_canonical_and_parses_back() {
+    for (input, canonical) in [
+        ("freq=daily;interval=1", "FREQ=DAILY"),
+        ("FREQ=DAILY;INTERVAL=2", "FREQ=DAILY;INTERVAL=2"),
+        ("FREQ=WEEKLY;BYDAY=su,we", "FREQ=WEEKLY;BYDAY=WE,SU"),
+        ("BYMONTHDAY=15;FREQ=MONTHLY", "FREQ=MONTHLY;BYMONTHDAY=15"),
+        (
+            "FREQ=AFTER_COMPLETION;INTERVAL=5",
+            "FREQ=AFTER_COMPLETION;INTERVAL=5",
+        ),
+    ] {
+        let parsed = rule(input);
+        assert_eq!(parsed.to_string(), canonical);
+        assert_eq!(rule(canonical), parsed);
+    }
+}
+
+#[test]
+fn test_invalid_rules_are_rejected() {
+    for input in [
+        "",
+        "DAILY",
+        "FREQ=HOURLY",
+        "FREQ=DAILY;FREQ=DAILY",
+        "FREQ=DAILY;INTERVAL=0",
+        "FREQ=DAILY;INTERVAL=abc",
+        "FREQ=DAILY;BYDAY=MO",
+        "FREQ=WEEKLY",
+        "FREQ=WEEKLY;BYDAY=XX",
+        "FREQ=WEEKLY;BYDAY=MO;INTERVAL=2",
+        "FREQ=MONTHLY",
+        "FREQ=MONTHLY;BYMONTHDAY=32",
+        "FREQ=AFTER_COMPLETION",
+        "FREQ=DAILY;COUNT=3",
+    ] {
+        assert!(
+            input.parse::<Recurrence>().is_err(),
+            "`{input}` was accepted"
+        );
+    }
+}
+
+#[test]
+fn test_next_due_daily() {
+    let due = at(2030, 1, 10, 9);
+    // Completed on time: the next day, same time.
+    assert_eq!(
+        rule("FREQ=DAILY").next_due(Some(due), at(2030, 1, 10, 8)),
+        at(2030, 1, 11, 9)
+    );
+    // Completed late: occurrences that already passed are skipped.
+    assert_eq!(
+        rule("FREQ=DAILY;INTERVAL=2").next_due(Some(due), at(2030, 1, 15, 12)),
+        at(2030, 1, 16, 9)
+    );
+    // Without due date the rule counts from the completion.
+    assert_eq!(
+        rule("FREQ=DAILY").next_due(None, at(2030, 1, 15, 12)),
+        at(2030, 1, 16, 12)
+    );
+}
+
+#[test]
+fn test_next_due_weekly() {
+    let mondays_and_thursdays = rule("FREQ=WEEKLY;BYDAY=MO,TH");
+    // 2030-01-07 is a Monday.
+    let monday = at(2030, 1, 7, 18);
+    assert_eq!(
+        mondays_and_thursdays.next_due(Some(monday), monday),
+        at(2030, 1, 10, 18)
+    );
+    let thursday = at(2030, 1, 10, 18);
+    assert_eq!(
+        mondays_and_thursdays.next_due(Some(thursday), thursday),
+        at(2030, 1, 14, 18)
+    );
+    // A single weekday comes back a week later.
+    let sundays = rule("FREQ=WEEKLY;BYDAY=SU");
+    let sunday = at(2030, 1, 13, 10);
+    assert_eq!(sundays.next_due(Some(sunday), sunday), at(2030, 1, 20, 10));
+}
+
+#[test]
+fn test_next_due_monthly_clamps_to_last_day() {
+    let rule = rule("FREQ=MONTHLY;BYMONTHDAY=31");
+    let due = at(2030, 1, 31, 8);
+    assert_eq!(rule.next_due(Some(due), due), at(2030, 2, 28, 8));
+    let due = at(2030, 2, 28, 8);
+    assert_eq!(rule.next_due(Some(due), due), at(2030, 3, 31, 8));
+    let due = at(2030, 12, 31, 8);
+    assert_eq!(rule.next_due(Some(due), due), at(2031, 1, 31, 8));
+}
+
+#[test]
+fn test_next_due_after_completion() {
+    let rule = rule("FREQ=AFTER_COMPLETION;INTERVAL=3");
+    // Counted from the completion, keeping the time of the due date.
+    assert_eq!(
+        rule.next_due(Some(at(2030, 1, 1, 9)), at(2030, 1, 20, 17)),
+        at(2030, 1, 23, 9)
+    );
+}
+
+#[test]
+fn test_follow_up_only_when_completed() {
+    let now = at(2030, 1, 10, 12);
+    let previous = recurring_todo("FREQ=DAILY", Some(at(2030, 1, 10, 9)));
+
+    // Editing without completing does not create anything.
+    let mut updated = previous.clone();
+    updated.title = "Water all plants".to_string();
+    assert!(follow_up(&previous, &updated, now).is_none());
+
+    updated.completed = Some(true);
+    let next = follow_up(&previous, &updated, now).unwrap();
+    assert_eq!(next.title, "Water all plants");
+    assert_eq!(next.description, Some("balcony".to_string()));
+    assert_eq!(next.completed, Some(false));
+    assert_eq!(next.due_at, Some(at(2030, 1, 11, 9)));
+    assert_eq!(next.recurrence, Some("FREQ=DAILY".to_string()));
+
+    // Saving an already completed todo again does not repeat the follow-up.
+    assert!(follow_up(&updated, &updated, now).is_none());
+
+    // Neither do todos without (valid) rule.
+    let mut plain = updated.clone();
+    plain.recurrence = None;
+    assert!(follow_up(&previous, &plain, now).is_none());
+}
+
+#[test]
+fn test_follow_up_keeps_reminder_offset() {
+    let mut previous = recurring_todo("FREQ=WEEKLY;BYDAY=MO", Some(at(2030, 1, 7, 18)));
+    previous.remind_at = Some(at(2030, 1, 7, 16));
+    let mut updated = previous.clone();
+    updated.completed = Some(true);
+
+    let next = follow_up(&previous, &updated, at(2030, 1, 7, 17)).unwrap();
+    assert_eq!(next.due_at, Some(at(2030, 1, 14, 18)));
+    assert_eq!(next.remind_at, Some(at(2030, 1, 14, 16)));
+}
@@ -0,0 +1,156 @@
+use actix_web::{
+    body::to_bytes,
+    http::{header, StatusCode},
+    ResponseError,
+};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use TodoRustBackend::{
+    api::error::ErrorResponse, repository::error::RepoError, validation::FieldError,
+};
+
+fn db_error(kind: DatabaseErrorKind) -> DieselError {
+    DieselError::DatabaseError(kind, Box::new("db message".to_string()))
+}
+
+#[test]
+fn test_diesel_not_found_maps_to_not_found() {
+    assert_eq!(RepoError::from(DieselError::NotFound), RepoError::NotFound);
+}
+
+#[test]
+fn test_diesel_unique_violation_maps_to_conflict() {
+    let err = RepoError::from(db_error(DatabaseErrorKind::UniqueViolation));
+    assert_eq!(err, RepoError::Conflict("db message".to_string()));
+}
+
+#[test]
+fn test_diesel_not_null_violation_maps_to_validation() {
+    let err = RepoError::from(db_error(DatabaseErrorKind::NotNullViolation));
+    assert_eq!(
+        err,
+        RepoError::Validation(vec![FieldError::new("", "constraint", "db message")])
+    );
+}
+
+#[test]
+fn test_diesel_closed_connection_maps_to_unavailable() {
+    let err = RepoError::from(db_error(DatabaseErrorKind::ClosedConnection));
+    assert_eq!(err, RepoError::Unavailable("db message".to_string()));
+}
+
+#[test]
+fn test_other_diesel_errors_map_to_internal() {
+    let err = RepoError::from(DieselError::RollbackTransaction);
+    assert!(matches!(err, RepoError::Internal(_)));
+}
+
+#[test]
+fn test_status_codes() {
+    assert_eq!(RepoError::NotFound.status_code(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        RepoError::Conflict(String::new()).status_code(),
+        StatusCode::CONFLICT
+    );
+    assert_eq!(
+        RepoError::Validation(Vec::new()).status_code(),
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+    assert_eq!(
+        RepoError::Unauthorized(String::new()).status_code(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        RepoError::Forbidden(String::new()).status_code(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        RepoError::PreconditionFailed(String::new()).status_code(),
+        StatusCode::PRECONDITION_FAILED
+    );
+    assert_eq!(
+        RepoError::Unavailable(String::new()).status_code(),
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+    assert_eq!(
+        RepoError::Internal(String::new()).status_code(),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+}
+
+#[actix_web::test]
+async fn test_internal_error_message_is_not_leaked() {
+    let resp = RepoError::Internal("secret connection string".to_string()).error_response();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let body = to_bytes(resp.into_body()).await.unwrap();
+    let err: ErrorResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(err.code, "internal");
+    assert!(!err.message.contains("secret"));
+}
+
+#[actix_web::test]
+async fn test_unavailable_error_message_is_not_leaked() {
+    let resp =
+        RepoError::Unavailable("timed out connecting to db:3306".to_string()).error_response();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let body = to_bytes(resp.into_body()).await.unwrap();
+    let err: ErrorResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(err.code, "unavailable");
+    assert_eq!(err.message, "storage unavailable");
+}
+
+#[actix_web::test]
+async fn test_missing_resource_names_the_resource() {
+    let resp = RepoError::Missing("tag").error_response();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let body = to_bytes(resp.into_body()).await.unwrap();
+    let err: ErrorResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(err.code, "not_found");
+    assert_eq!(err.message, "tag not found");
+}
+
+#[actix_web::test]
+async fn test_unauthorized_asks_for_a_bearer_token() {
+    let resp =
+        RepoError::Unauthorized("the token is invalid or expired".to_string()).error_response();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        resp.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+        "Bearer"
+    );
+
+    let body = to_bytes(resp.into_body()).await.unwrap();
+    let err: ErrorResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(err.code, "unauthorized");
+    assert!(err.message.contains("expired"));
+}
+
+#[actix_web::test]
+async fn test_stale_version_names_the_todo() {
+    let resp = RepoError::stale(7, 3).error_response();
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+    let body = to_bytes(resp.into_body()).await.unwrap();
+    let err: ErrorResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(err.code, "precondition_failed");
+    assert_eq!(
+        err.message,
+        "precondition failed: todo 7 was changed since version 3"
+    );
+}
+
+#[actix_web::test]
+async fn test_batch_error_keeps_status_and_names_the_operation() {
+    let resp = RepoError::stale(7, 3).in_batch(2).error_response();
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+
+    let body = to_bytes(resp.into_body()).await.unwrap();
+    let err: ErrorResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(err.code, "precondition_failed");
+    assert_eq!(
+        err.message,
+        "operation 2: precondition failed: todo 7 was changed since version 3"
+    );
+}
@@ -0,0 +1,1397 @@
+#![cfg(feature = "sqlite")]
+
+use std::sync::Arc;
+
+use actix_web::{
+    http::{header, StatusCode},
+    test, web, App,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde_json::json;
+use TodoRustBackend::{
+    api::{self, auth::LoginResponse},
+    auth::AuthKeys,
+    models::{
+        api_key::{NewApiKey, NewApiKeyRecord, Scope},
+        batch::{BatchOp, BatchOutcome},
+        history::{HistoryAction, HistoryQuery},
+        list::{NewList, DEFAULT_LIST_ID, DEFAULT_LIST_NAME},
+        query::{SortField, SortOrder, TagMode, TodoQuery},
+        share::{Role, Share},
+        tag::NewTag,
+        todo::{NewTodo, Todo, TodoPatch},
+        user::NewUser,
+    },
+    repository::{
+        self,
+        audit_repo::AuditRepo,
+        error::RepoError,
+        mutation::{Actor, Mutated, Mutation},
+        sqlite_repo::SqliteRepo,
+        todo_repo::TodoRepo,
+        RepoBox,
+    },
+};
+
+fn create_test_repo() -> SqliteRepo {
+    SqliteRepo::new(":memory:").unwrap()
+}
+
+fn create_new_todo(title: &str, description: Option<&str>) -> NewTodo {
+    NewTodo {
+        title: title.to_string(),
+        description: description.map(|s| s.to_string()),
+        created_at: None,
+        completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+    }
+}
+
+fn at(day: u32) -> Option<NaiveDateTime> {
+    NaiveDate::from_ymd_opt(2024, 1, day).and_then(|d| d.and_hms_opt(12, 0, 0))
+}
+
+#[actix_web::test]
+async fn test_sqlite_crud() {
+    let repo = create_test_repo();
+
+    let created = repo
+        .create_todo(create_new_todo("First", Some("Description")))
+        .await
+        .unwrap();
+    assert_eq!(created.todo_id, 1);
+    assert_eq!(created.title, "First");
+    assert!(created.created_at.is_some(), "created_at defaults to now");
+
+    let fetched = repo.get_todo_by_id(created.todo_id).await.unwrap();
+    assert_eq!(fetched.description, Some("Description".to_string()));
+
+    let mut updated_todo = fetched.clone();
+    updated_todo.title = "Updated".to_string();
+    updated_todo.completed = Some(true);
+    let updated = repo
+        .update_todo_by_id(created.todo_id, updated_todo)
+        .await
+        .unwrap();
+    assert_eq!(updated.title, "Updated");
+    assert_eq!(updated.completed, Some(true));
+
+    assert_eq!(repo.delete_todo_by_id(created.todo_id).await.unwrap(), 1);
+    assert_eq!(
+        repo.get_todo_by_id(created.todo_id).await.unwrap_err(),
+        RepoError::NotFound
+    );
+    assert!(repo.get_todos().await.unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn test_sqlite_not_found() {
+    let repo = create_test_repo();
+    let todo = Todo {
+        todo_id: 999,
+        title: "Missing".to_string(),
+        description: None,
+        created_at: None,
+        completed: None,
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+        version: 1,
+        deleted_at: None,
+        tags: Vec::new(),
+        blocked_by: Vec::new(),
+        progress: None,
+    };
+
+    assert_eq!(
+        repo.get_todo_by_id(999).await.unwrap_err(),
+        RepoError::NotFound
+    );
+    assert_eq!(
+        repo.delete_todo_by_id(999).await.unwrap_err(),
+        RepoError::NotFound
+    );
+    assert_eq!(
+        repo.update_todo_by_id(999, todo).await.unwrap_err(),
+        RepoError::NotFound
+    );
+    assert_eq!(
+        repo.patch_todo_by_id(999, TodoPatch::default())
+            .await
+            .unwrap_err(),
+        RepoError::NotFound
+    );
+}
+
+#[actix_web::test]
+async fn test_sqlite_patch_keeps_other_fields() {
+    let repo = create_test_repo();
+    let mut new_todo = create_new_todo("Original", Some("Description"));
+    new_todo.created_at = at(5);
+    let created = repo.create_todo(new_todo).await.unwrap();
+
+    let patch = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    let patched = repo.patch_todo_by_id(created.todo_id, patch).await.unwrap();
+    assert_eq!(patched.title, "Original");
+    assert_eq!(patched.description, Some("Description".to_string()));
+    assert_eq!(patched.created_at, at(5));
+    assert_eq!(patched.completed, Some(true));
+
+    let patch = TodoPatch {
+        description: Some(None),
+        ..Default::default()
+    };
+    let patched = repo.patch_todo_by_id(created.todo_id, patch).await.unwrap();
+    assert_eq!(patched.description, None);
+}
+
+#[actix_web::test]
+async fn test_sqlite_rejects_invalid_todos() {
+    let repo = create_test_repo();
+    let result = repo.create_todo(create_new_todo(" ", None)).await;
+    assert!(matches!(result, Err(RepoError::Validation(_))));
+    assert!(repo.get_todos().await.unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn test_sqlite_query() {
+    let repo = create_test_repo();
+    let seeds = [
+        ("Buy milk", Some("from the store"), 3, false),
+        ("write report", None, 1, true),
+        ("Call Bob", Some("about the STORE opening"), 2, false),
+        ("buy stamps", Some("100% post office"), 4, true),
+    ];
+    for (title, description, day, completed) in seeds {
+        let mut todo = create_new_todo(title, description);
+        todo.created_at = at(day);
+        todo.completed = Some(completed);
+        repo.create_todo(todo).await.unwrap();
+    }
+
+    let query = TodoQuery {
+        completed: Some(false),
+        description: Some("store".to_string()),
+        ..Default::default()
+    };
+    let page = repo.query_todos(query).await.unwrap();
+    assert_eq!(page.total, 2);
+
+    // `%` is matched literally, not as a wildcard.
+    let query = TodoQuery {
+        description: Some("0%".to_string()),
+        ..Default::default()
+    };
+    let page = repo.query_todos(query).await.unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.items[0].title, "buy stamps");
+
+    let query = TodoQuery {
+        sort: SortField::Title,
+        ..Default::default()
+    };
+    let page = repo.query_todos(query).await.unwrap();
+    let titles: Vec<&str> = page.items.iter().map(|t| t.title.as_str()).collect();
+    assert_eq!(
+        titles,
+        vec!["Buy milk", "buy stamps", "Call Bob", "write report"]
+    );
+
+    let query = TodoQuery {
+        created_after: at(2),
+        sort: SortField::CreatedAt,
+        order: SortOrder::Desc,
+        limit: Some(2),
+        offset: Some(1),
+        ..Default::default()
+    };
+    let page = repo.query_todos(query).await.unwrap();
+    assert_eq!(page.total, 3);
+    let titles: Vec<&str> = page.items.iter().map(|t| t.title.as_str()).collect();
+    assert_eq!(titles, vec!["Buy milk", "Call Bob"]);
+
+    let query = TodoQuery {
+        offset: Some(3),
+        ..Default::default()
+    };
+    let page = repo.query_todos(query).await.unwrap();
+    assert_eq!(page.items.len(), 1);
+}
+
+#[actix_web::test]
+async fn test_sqlite_due_dates() {
+    let repo = create_test_repo();
+    for (title, due_day) in [
+        ("c", Some(5)),
+        ("a", Some(2)),
+        ("none", None),
+        ("b", Some(3)),
+    ] {
+        let mut todo = create_new_todo(title, None);
+        todo.due_at = due_day.and_then(at);
+        todo.remind_at = due_day.and_then(|day| at(day - 1));
+        repo.create_todo(todo).await.unwrap();
+    }
+
+    let page = repo
+        .query_todos(TodoQuery {
+            due_after: at(3),
+            sort: SortField::DueAt,
+            order: SortOrder::Desc,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let titles: Vec<&str> = page.items.iter().map(|t| t.title.as_str()).collect();
+    assert_eq!(titles, vec!["c", "b"]);
+    assert_eq!(page.items[0].remind_at, at(4));
+
+    let patch = TodoPatch {
+        due_at: Some(None),
+        ..Default::default()
+    };
+    let patched = repo.patch_todo_by_id(1, patch).await.unwrap();
+    assert_eq!(patched.due_at, None);
+    assert_eq!(patched.remind_at, at(4));
+
+    // A single patched field is checked against the stored other one.
+    let patch = TodoPatch {
+        remind_at: Some(at(4)),
+        ..Default::default()
+    };
+    let result = repo.patch_todo_by_id(4, patch).await;
+    assert!(matches!(result, Err(RepoError::Validation(_))));
+    let patch = TodoPatch {
+        due_at: Some(at(1)),
+        ..Default::default()
+    };
+    let result = repo.patch_todo_by_id(4, patch).await;
+    assert!(matches!(result, Err(RepoError::Validation(_))));
+}
+
+#[actix_web::test]
+async fn test_sqlite_recurring_todo() {
+    let repo = create_test_repo();
+    let mut todo = create_new_todo("Gym", None);
+    todo.due_at = NaiveDate::from_ymd_opt(2030, 1, 7).and_then(|d| d.and_hms_opt(18, 0, 0));
+    todo.recurrence = Some("FREQ=WEEKLY;BYDAY=MO,TH".to_string());
+    let created = repo.create_todo(todo).await.unwrap();
+
+    let patch = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    let patched = repo.patch_todo_by_id(created.todo_id, patch).await.unwrap();
+    assert_eq!(patched.recurrence, None);
+
+    let todos = repo.get_todos().await.unwrap();
+    assert_eq!(todos.len(), 2);
+    assert_eq!(
+        todos[1].due_at,
+        NaiveDate::from_ymd_opt(2030, 1, 10).and_then(|d| d.and_hms_opt(18, 0, 0))
+    );
+    assert_eq!(
+        todos[1].recurrence,
+        Some("FREQ=WEEKLY;BYDAY=MO,TH".to_string())
+    );
+    assert_eq!(todos[1].completed, Some(false));
+}
+
+#[actix_web::test]
+async fn test_sqlite_mutations_report_side_effects() {
+    use HistoryAction::*;
+
+    let repo = create_test_repo();
+    let reported = |mutated: &Mutated| -> Vec<(HistoryAction, i32)> {
+        mutated
+            .changes
+            .iter()
+            .map(|change| {
+                let todo = change.after.as_ref().or(change.before.as_ref()).unwrap();
+                (change.action, todo.todo_id)
+            })
+            .collect()
+    };
+
+    // Completing a recurring todo in a batch reports its next instance.
+    let mut todo = create_new_todo("Gym", None);
+    todo.due_at = NaiveDate::from_ymd_opt(2030, 1, 7).and_then(|d| d.and_hms_opt(18, 0, 0));
+    todo.recurrence = Some("FREQ=WEEKLY;BYDAY=MO,TH".to_string());
+    let gym = repo.create_todo(todo).await.unwrap();
+    let tag = NewTag {
+        name: "health".to_string(),
+        owner_id: None,
+    };
+    let tag = repo.create_tag(tag).await.unwrap();
+    repo.attach_tag(gym.todo_id, tag.tag_id).await.unwrap();
+    let complete = BatchOp::Complete {
+        id: gym.todo_id,
+        version: None,
+    };
+    let mutated = repo
+        .mutate(
+            Mutation::Batch {
+                ops: vec![complete],
+            },
+            None,
+        )
+        .await
+        .unwrap();
+    let next = mutated.changes[1].after.clone().unwrap();
+    assert_eq!(
+        reported(&mutated),
+        vec![(Updated, gym.todo_id), (Created, next.todo_id)]
+    );
+    assert_eq!(next.recurrence, Some("FREQ=WEEKLY;BYDAY=MO,TH".to_string()));
+    assert_eq!(next.tags.len(), 1);
+
+    // A parent completed along with its last subtask.
+    let mut parent = create_new_todo("Move", None);
+    parent.auto_complete = true;
+    let parent = repo.create_todo(parent).await.unwrap();
+    let mut sub = create_new_todo("Pack", None);
+    sub.parent_id = Some(parent.todo_id);
+    let sub = repo.create_todo(sub).await.unwrap();
+    let patch = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    let complete = Mutation::Patch {
+        id: sub.todo_id,
+        patch,
+        version: None,
+    };
+    let actor = Actor { user_id: Some(7) };
+    let mutated = repo.mutate(complete, Some(actor)).await.unwrap();
+    assert_eq!(
+        reported(&mutated),
+        vec![(Updated, sub.todo_id), (Updated, parent.todo_id)]
+    );
+    let before = mutated.changes[1].before.as_ref().unwrap();
+    assert_eq!(before.completed, Some(false));
+    assert_eq!(
+        mutated.changes[1].after.as_ref().unwrap().completed,
+        Some(true)
+    );
+    // Given an actor, the side effects are recorded in the same transaction.
+    let history = repo.get_history(HistoryQuery::default()).await.unwrap();
+    let recorded: Vec<(HistoryAction, i32)> = history
+        .iter()
+        .map(|entry| (entry.action, entry.todo_id))
+        .collect();
+    assert_eq!(
+        recorded,
+        vec![(Updated, sub.todo_id), (Updated, parent.todo_id)]
+    );
+    assert!(history.iter().all(|entry| entry.user_id == Some(7)));
+
+    // Trashing, restoring and purging report the whole subtree.
+    let delete = Mutation::Delete {
+        id: parent.todo_id,
+        version: None,
+    };
+    let mutated = repo.mutate(delete, None).await.unwrap();
+    assert_eq!(
+        reported(&mutated),
+        vec![(Deleted, parent.todo_id), (Deleted, sub.todo_id)]
+    );
+    let restore = Mutation::Restore { id: parent.todo_id };
+    let mutated = repo.mutate(restore, None).await.unwrap();
+    assert_eq!(
+        reported(&mutated),
+        vec![(Restored, parent.todo_id), (Restored, sub.todo_id)]
+    );
+    repo.delete_todo_by_id(parent.todo_id).await.unwrap();
+    let purge = Mutation::Purge { id: parent.todo_id };
+    let mutated = repo.mutate(purge, None).await.unwrap();
+    assert_eq!(
+        reported(&mutated),
+        vec![(Purged, parent.todo_id), (Purged, sub.todo_id)]
+    );
+}
+
+#[actix_web::test]
+async fn test_sqlite_tags() {
+    let repo = create_test_repo();
+    let new_user = |username: &str| NewUser {
+        username: username.to_string(),
+        password_hash: "hash".to_string(),
+    };
+    let alice = repo.create_user(new_user("alice")).await.unwrap();
+    let bob = repo.create_user(new_user("bob")).await.unwrap();
+    let tag = |name: &str| NewTag {
+        name: name.to_string(),
+        owner_id: Some(alice.user_id),
+    };
+    let ops = repo.create_tag(tag("ops")).await.unwrap();
+    let backend = repo.create_tag(tag("backend")).await.unwrap();
+    assert!(matches!(
+        repo.create_tag(tag("Ops")).await,
+        Err(RepoError::Conflict(_))
+    ));
+    assert!(matches!(
+        repo.update_tag_by_id(backend.tag_id, tag("OPS")).await,
+        Err(RepoError::Conflict(_))
+    ));
+    assert_eq!(
+        repo.get_tag_by_id(99).await.unwrap_err(),
+        RepoError::Missing("tag")
+    );
+    // Names are unique per owner, tags without owner never clash.
+    let bobs = NewTag {
+        owner_id: Some(bob.user_id),
+        ..tag("ops")
+    };
+    let bobs = repo.create_tag(bobs).await.unwrap();
+    for _ in 0..2 {
+        let legacy = NewTag {
+            owner_id: None,
+            ..tag("ops")
+        };
+        repo.create_tag(legacy).await.unwrap();
+    }
+    let visible = repo.get_tags(Some(bob.user_id)).await.unwrap();
+    assert_eq!(visible, vec![bobs.clone()]);
+    let mut new_todo = create_new_todo("Alice's", None);
+    new_todo.owner_id = Some(alice.user_id);
+    let alices = repo.create_todo(new_todo).await.unwrap();
+    repo.attach_tag(alices.todo_id, backend.tag_id)
+        .await
+        .unwrap();
+    let share = Share {
+        user_id: bob.user_id,
+        role: Role::Viewer,
+    };
+    repo.share_todo(alices.todo_id, share).await.unwrap();
+    let visible = repo.get_tags(Some(bob.user_id)).await.unwrap();
+    assert_eq!(visible, vec![backend.clone(), bobs.clone()]);
+    assert_eq!(repo.get_tags(None).await.unwrap().len(), 5);
+    repo.delete_todo_by_id(alices.todo_id).await.unwrap();
+    for id in [bobs.tag_id, bobs.tag_id + 1, bobs.tag_id + 2] {
+        repo.delete_tag_by_id(id).await.unwrap();
+    }
+
+    let both = repo
+        .create_todo(create_new_todo("both", None))
+        .await
+        .unwrap();
+    let one = repo
+        .create_todo(create_new_todo("one", None))
+        .await
+        .unwrap();
+    repo.attach_tag(both.todo_id, ops.tag_id).await.unwrap();
+    repo.attach_tag(both.todo_id, ops.tag_id).await.unwrap();
+    let tagged = repo.attach_tag(both.todo_id, backend.tag_id).await.unwrap();
+    let names: Vec<&str> = tagged.tags.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, vec!["backend", "ops"]);
+    repo.attach_tag(one.todo_id, ops.tag_id).await.unwrap();
+
+    let page = repo
+        .query_todos(TodoQuery {
+            tag: Some("OPS,backend".to_string()),
+            tag_mode: TagMode::And,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.items[0].title, "both");
+    assert_eq!(page.items[0].tags.len(), 2);
+    let page = repo
+        .query_todos(TodoQuery {
+            tag: Some("backend,ops".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.total, 2);
+
+    let detached = repo.detach_tag(both.todo_id, ops.tag_id).await.unwrap();
+    assert_eq!(detached.tags, vec![backend.clone()]);
+
+    // Deleting a tag or a todo removes the links through the foreign keys.
+    repo.delete_tag_by_id(backend.tag_id).await.unwrap();
+    assert!(repo
+        .get_todo_by_id(both.todo_id)
+        .await
+        .unwrap()
+        .tags
+        .is_empty());
+    repo.delete_todo_by_id(one.todo_id).await.unwrap();
+    repo.delete_tag_by_id(ops.tag_id).await.unwrap();
+    assert!(repo.get_tags(None).await.unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn test_sqlite_lists() {
+    let repo = create_test_repo();
+    let list = |name: &str| NewList {
+        name: name.to_string(),
+        owner_id: None,
+    };
+    let lists = repo.get_lists().await.unwrap();
+    assert_eq!(lists.len(), 1);
+    assert_eq!(lists[0].list_id, DEFAULT_LIST_ID);
+    assert_eq!(lists[0].name, DEFAULT_LIST_NAME);
+
+    let work = repo.create_list(list("Work")).await.unwrap();
+    assert_eq!(work.list_id, DEFAULT_LIST_ID + 1);
+    let renamed = repo
+        .update_list_by_id(work.list_id, list("Office"))
+        .await
+        .unwrap();
+    assert_eq!(renamed.name, "Office");
+    assert_eq!(
+        repo.update_list_by_id(99, list("x")).await.unwrap_err(),
+        RepoError::Missing("list")
+    );
+
+    let mut new_todo = create_new_todo("Report", None);
+    new_todo.list_id = work.list_id;
+    let report = repo.create_todo(new_todo).await.unwrap();
+    assert_eq!(report.list_id, work.list_id);
+    let inbox = repo
+        .create_todo(create_new_todo("Milk", None))
+        .await
+        .unwrap();
+    let mut lost = create_new_todo("Lost", None);
+    lost.list_id = 99;
+    assert_eq!(
+        repo.create_todo(lost).await.unwrap_err(),
+        RepoError::Missing("list")
+    );
+    let page = repo
+        .query_todos(TodoQuery {
+            list_id: Some(work.list_id),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.items[0].title, "Report");
+
+    let patch = TodoPatch {
+        list_id: Some(99),
+        ..Default::default()
+    };
+    assert_eq!(
+        repo.patch_todo_by_id(inbox.todo_id, patch)
+            .await
+            .unwrap_err(),
+        RepoError::Missing("list")
+    );
+    let patch = TodoPatch {
+        list_id: Some(work.list_id),
+        ..Default::default()
+    };
+    let moved = repo.patch_todo_by_id(inbox.todo_id, patch).await.unwrap();
+    assert_eq!(moved.list_id, work.list_id);
+
+    let ops = repo
+        .create_tag(NewTag {
+            name: "ops".to_string(),
+            owner_id: None,
+        })
+        .await
+        .unwrap();
+    repo.attach_tag(report.todo_id, ops.tag_id).await.unwrap();
+    assert!(matches!(
+        repo.delete_list_by_id(work.list_id, false).await,
+        Err(RepoError::Conflict(_))
+    ));
+    assert!(matches!(
+        repo.delete_list_by_id(DEFAULT_LIST_ID, true).await,
+        Err(RepoError::Conflict(_))
+    ));
+    assert_eq!(repo.delete_list_by_id(work.list_id, true).await.unwrap(), 1);
+    assert!(repo.get_todos().await.unwrap().is_empty());
+    assert_eq!(repo.get_lists().await.unwrap().len(), 1);
+    // The tag outlives the todos, and can be deleted despite the former links.
+    repo.delete_tag_by_id(ops.tag_id).await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_sqlite_cascading_list_delete_purges_each_todo() {
+    let repo: RepoBox = Arc::new(create_test_repo());
+    let audited = AuditRepo::new(repo.clone(), Some(1));
+    let list = NewList {
+        name: "Deep".to_string(),
+        owner_id: None,
+    };
+    let deep = audited.create_list(list).await.unwrap();
+    let in_list = |title: &str, parent_id: Option<i32>| NewTodo {
+        list_id: deep.list_id,
+        parent_id,
+        ..create_new_todo(title, None)
+    };
+    let root = audited.create_todo(in_list("Root", None)).await.unwrap();
+    // Deeper than MySQL follows `ON DELETE CASCADE`.
+    let mut parent = root.todo_id;
+    for level in 0..20 {
+        let todo = in_list(&format!("Level {level}"), Some(parent));
+        parent = audited.create_todo(todo).await.unwrap().todo_id;
+    }
+    let trashed = audited.create_todo(in_list("Trashed", None)).await.unwrap();
+    audited.delete_todo_by_id(trashed.todo_id).await.unwrap();
+
+    assert_eq!(
+        audited.delete_list_by_id(deep.list_id, true).await.unwrap(),
+        1
+    );
+    assert!(repo.get_todos().await.unwrap().is_empty());
+    assert!(repo.get_trash(None).await.unwrap().is_empty());
+    let query = HistoryQuery {
+        user_id: Some(1),
+        ..HistoryQuery::default()
+    };
+    let purged: Vec<i32> = repo
+        .get_history(query)
+        .await
+        .unwrap()
+        .iter()
+        .filter(|entry| entry.action == HistoryAction::Purged)
+        .map(|entry| entry.todo_id)
+        .collect();
+    assert_eq!(purged.len(), 22);
+    assert!(purged.contains(&parent));
+    assert!(purged.contains(&trashed.todo_id));
+}
+
+#[actix_web::test]
+async fn test_sqlite_subtasks() {
+    let repo = create_test_repo();
+    let subtask = |title: &str, parent_id: i32| NewTodo {
+        parent_id: Some(parent_id),
+        ..create_new_todo(title, None)
+    };
+    let mut new_todo = create_new_todo("Release", None);
+    new_todo.auto_complete = true;
+    let release = repo.create_todo(new_todo).await.unwrap();
+    let build = repo
+        .create_todo(subtask("Build", release.todo_id))
+        .await
+        .unwrap();
+    let docs = repo
+        .create_todo(subtask("Docs", release.todo_id))
+        .await
+        .unwrap();
+    let ops = repo
+        .create_tag(NewTag {
+            name: "ops".to_string(),
+            owner_id: None,
+        })
+        .await
+        .unwrap();
+    repo.attach_tag(docs.todo_id, ops.tag_id).await.unwrap();
+
+    let tree = repo.get_subtree(release.todo_id).await.unwrap();
+    let children: Vec<i32> = tree.children.iter().map(|c| c.todo.todo_id).collect();
+    assert_eq!(children, vec![build.todo_id, docs.todo_id]);
+    assert_eq!(tree.todo.progress.unwrap().total, 2);
+
+    let patch = TodoPatch {
+        parent_id: Some(Some(docs.todo_id)),
+        ..Default::default()
+    };
+    let result = repo.patch_todo_by_id(release.todo_id, patch).await;
+    assert!(matches!(result, Err(RepoError::Validation(_))));
+    assert_eq!(
+        repo.create_todo(subtask("Orphan", 99)).await.unwrap_err(),
+        RepoError::Missing("parent")
+    );
+
+    let complete = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    repo.patch_todo_by_id(build.todo_id, complete.clone())
+        .await
+        .unwrap();
+    let release_now = repo.get_todo_by_id(release.todo_id).await.unwrap();
+    assert_eq!(release_now.completed, Some(false));
+    assert_eq!(release_now.progress.unwrap().completed, 1);
+    // Deleting the last open subtask completes the parent.
+    assert_eq!(repo.delete_todo_by_id(docs.todo_id).await.unwrap(), 1);
+    let release_now = repo.get_todo_by_id(release.todo_id).await.unwrap();
+    assert_eq!(release_now.completed, Some(true));
+
+    // Deep trees are deleted as a whole.
+    let mut parent = build.todo_id;
+    for level in 0..20 {
+        parent = repo
+            .create_todo(subtask(&format!("Level {level}"), parent))
+            .await
+            .unwrap()
+            .todo_id;
+    }
+    assert_eq!(
+        repo.get_descendants(release.todo_id).await.unwrap().len(),
+        21
+    );
+    assert_eq!(repo.delete_todo_by_id(release.todo_id).await.unwrap(), 22);
+    assert!(repo.get_todos().await.unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn test_sqlite_versions() {
+    let repo = create_test_repo();
+    let mut new_todo = create_new_todo("Release", None);
+    new_todo.auto_complete = true;
+    let release = repo.create_todo(new_todo).await.unwrap();
+    let build = repo
+        .create_todo(NewTodo {
+            parent_id: Some(release.todo_id),
+            ..create_new_todo("Build", None)
+        })
+        .await
+        .unwrap();
+    assert_eq!(release.version, 1);
+
+    let complete = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    let done = repo
+        .patch_todo_if_version(build.todo_id, complete.clone(), Some(1))
+        .await
+        .unwrap();
+    assert_eq!(done.version, 2);
+    // The parent completed on its own, which is a change too.
+    let release_now = repo.get_todo_by_id(release.todo_id).await.unwrap();
+    assert_eq!(release_now.version, 2);
+    assert_eq!(
+        repo.patch_todo_if_version(build.todo_id, complete, Some(1))
+            .await
+            .unwrap_err(),
+        RepoError::stale(build.todo_id, 1)
+    );
+
+    let mut todo = done.clone();
+    todo.title = "Build it".to_string();
+    let updated = repo
+        .update_todo_if_version(build.todo_id, todo.clone(), Some(2))
+        .await
+        .unwrap();
+    assert_eq!(updated.version, 3);
+    assert_eq!(
+        repo.update_todo_if_version(build.todo_id, todo, Some(2))
+            .await
+            .unwrap_err(),
+        RepoError::stale(build.todo_id, 2)
+    );
+    assert_eq!(
+        repo.delete_todo_if_version(release.todo_id, Some(1))
+            .await
+            .unwrap_err(),
+        RepoError::stale(release.todo_id, 1)
+    );
+    assert_eq!(
+        repo.delete_todo_if_version(release.todo_id, Some(2))
+            .await
+            .unwrap(),
+        2
+    );
+}
+
+#[actix_web::test]
+async fn test_sqlite_blockers() {
+    let repo = create_test_repo();
+    let subtask = |title: &str, parent_id: i32| NewTodo {
+        parent_id: Some(parent_id),
+        ..create_new_todo(title, None)
+    };
+    let design = repo
+        .create_todo(create_new_todo("Design", None))
+        .await
+        .unwrap();
+    let build = repo
+        .create_todo(create_new_todo("Build", None))
+        .await
+        .unwrap();
+    let mut new_todo = create_new_todo("Ship", None);
+    new_todo.auto_complete = true;
+    let ship = repo.create_todo(new_todo).await.unwrap();
+    let step = repo
+        .create_todo(subtask("Step", ship.todo_id))
+        .await
+        .unwrap();
+    repo.add_blocker(ship.todo_id, build.todo_id).await.unwrap();
+    repo.add_blocker(ship.todo_id, build.todo_id).await.unwrap();
+    let todo = repo
+        .add_blocker(build.todo_id, design.todo_id)
+        .await
+        .unwrap();
+    assert_eq!(todo.blocked_by, vec![design.todo_id]);
+
+    let result = repo.add_blocker(design.todo_id, ship.todo_id).await;
+    assert!(matches!(result, Err(RepoError::Validation(_))));
+    assert_eq!(
+        repo.add_blocker(ship.todo_id, 99).await.unwrap_err(),
+        RepoError::Missing("blocker")
+    );
+
+    let ids = |todos: Vec<Todo>| todos.iter().map(|t| t.todo_id).collect::<Vec<_>>();
+    let next = repo.get_next_todos(None).await.unwrap();
+    assert_eq!(ids(next), vec![design.todo_id, step.todo_id]);
+    let ordered = repo.get_ordered_todos(DEFAULT_LIST_ID).await.unwrap();
+    assert_eq!(
+        ids(ordered),
+        vec![design.todo_id, build.todo_id, ship.todo_id, step.todo_id]
+    );
+
+    // Neither the patch nor the roll-up completes the blocked todo.
+    let complete = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    let result = repo.patch_todo_by_id(ship.todo_id, complete.clone()).await;
+    assert!(matches!(result, Err(RepoError::Conflict(_))));
+    repo.patch_todo_by_id(step.todo_id, complete.clone())
+        .await
+        .unwrap();
+    let ship_now = repo.get_todo_by_id(ship.todo_id).await.unwrap();
+    assert_eq!(ship_now.completed, Some(false));
+
+    repo.remove_blocker(ship.todo_id, build.todo_id)
+        .await
+        .unwrap();
+    let done = repo.patch_todo_by_id(ship.todo_id, complete).await.unwrap();
+    assert_eq!(done.completed, Some(true));
+    assert!(done.blocked_by.is_empty());
+
+    // The dependencies go with a deleted blocker.
+    repo.delete_todo_by_id(design.todo_id).await.unwrap();
+    let build_now = repo.get_todo_by_id(build.todo_id).await.unwrap();
+    assert!(build_now.blocked_by.is_empty());
+}
+
+#[actix_web::test]
+async fn test_sqlite_users_and_owners() {
+    let repo = create_test_repo();
+    let new_user = |username: &str| NewUser {
+        username: username.to_string(),
+        password_hash: "hash".to_string(),
+    };
+    let alice = repo.create_user(new_user("Alice")).await.unwrap();
+    assert_eq!(repo.get_user_by_id(alice.user_id).await.unwrap(), alice);
+    let result = repo.create_user(new_user("alice")).await;
+    assert!(matches!(result, Err(RepoError::Conflict(msg)) if msg.contains("alice")));
+    let record = repo.get_user_record("ALICE").await.unwrap();
+    assert_eq!(record.user, alice);
+    assert_eq!(record.password_hash, "hash");
+    assert_eq!(
+        repo.get_user_record("bob").await.unwrap_err(),
+        RepoError::Missing("user")
+    );
+
+    let mut new_todo = create_new_todo("Mine", None);
+    new_todo.owner_id = Some(alice.user_id);
+    let mine = repo.create_todo(new_todo).await.unwrap();
+    repo.create_todo(create_new_todo("Nobody's", None))
+        .await
+        .unwrap();
+    let mut todo = mine.clone();
+    todo.owner_id = None;
+    let updated = repo.update_todo_by_id(mine.todo_id, todo).await.unwrap();
+    assert_eq!(updated.owner_id, Some(alice.user_id));
+    let query = TodoQuery {
+        owner_id: Some(alice.user_id),
+        ..Default::default()
+    };
+    let page = repo.query_todos(query).await.unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.items[0].todo_id, mine.todo_id);
+}
+
+#[actix_web::test]
+async fn test_sqlite_api_keys() {
+    let repo = create_test_repo();
+    let new_user = NewUser {
+        username: "ci".to_string(),
+        password_hash: "hash".to_string(),
+    };
+    let user = repo.create_user(new_user).await.unwrap();
+    let expires_at = NaiveDate::from_ymd_opt(2030, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0);
+    let new_key = |user_id: i32| NewApiKeyRecord {
+        user_id,
+        key: NewApiKey {
+            name: "bot".to_string(),
+            scopes: vec![Scope::Read, Scope::Admin],
+            expires_at,
+        },
+        prefix: "tdk_0000".to_string(),
+        key_hash: format!("hash {user_id}"),
+    };
+    let key = repo.create_api_key(new_key(user.user_id)).await.unwrap();
+    assert_eq!(key.scopes, vec![Scope::Read, Scope::Admin]);
+    assert_eq!(key.expires_at, expires_at);
+    assert!(key.grants(Scope::Write));
+    assert_eq!(
+        repo.get_api_key_by_hash(&format!("hash {}", user.user_id))
+            .await
+            .unwrap(),
+        key
+    );
+    assert_eq!(
+        repo.get_api_keys(user.user_id).await.unwrap(),
+        vec![key.clone()]
+    );
+    assert_eq!(
+        repo.create_api_key(new_key(user.user_id + 1))
+            .await
+            .unwrap_err(),
+        RepoError::Missing("user")
+    );
+
+    assert_eq!(
+        repo.delete_api_key(user.user_id + 1, key.key_id)
+            .await
+            .unwrap_err(),
+        RepoError::NotFound
+    );
+    repo.delete_api_key(user.user_id, key.key_id).await.unwrap();
+    assert!(repo.get_api_keys(user.user_id).await.unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn test_sqlite_shares() {
+    let repo = create_test_repo();
+    let new_user = |username: &str| NewUser {
+        username: username.to_string(),
+        password_hash: "hash".to_string(),
+    };
+    let alice = repo.create_user(new_user("alice")).await.unwrap().user_id;
+    let bob = repo.create_user(new_user("bob")).await.unwrap().user_id;
+    let project = NewList {
+        name: "Project".to_string(),
+        owner_id: Some(alice),
+    };
+    let project = repo.create_list(project).await.unwrap();
+    assert_eq!(project.owner_id, Some(alice));
+    let owned_by = |title: &str, list_id: i32| NewTodo {
+        owner_id: Some(alice),
+        list_id,
+        ..create_new_todo(title, None)
+    };
+    let single = repo
+        .create_todo(owned_by("Single", DEFAULT_LIST_ID))
+        .await
+        .unwrap();
+    let planned = repo
+        .create_todo(owned_by("Planned", project.list_id))
+        .await
+        .unwrap();
+    repo.create_todo(owned_by("Hidden", DEFAULT_LIST_ID))
+        .await
+        .unwrap();
+
+    let viewer = Share {
+        user_id: bob,
+        role: Role::Viewer,
+    };
+    let editor = Share {
+        role: Role::Editor,
+        ..viewer
+    };
+    repo.share_todo(single.todo_id, viewer).await.unwrap();
+    repo.share_todo(single.todo_id, editor).await.unwrap();
+    assert_eq!(
+        repo.get_todo_shares(single.todo_id).await.unwrap(),
+        vec![editor]
+    );
+    repo.share_list(project.list_id, viewer).await.unwrap();
+    assert_eq!(
+        repo.get_list_shares(project.list_id).await.unwrap(),
+        vec![viewer]
+    );
+    let result = repo
+        .share_list(
+            project.list_id,
+            Share {
+                user_id: bob + 1,
+                ..viewer
+            },
+        )
+        .await;
+    assert_eq!(result.unwrap_err(), RepoError::Missing("user"));
+
+    let query = TodoQuery {
+        visible_to: Some(bob),
+        ..Default::default()
+    };
+    let ids: Vec<i32> = repo
+        .query_todos(query.clone())
+        .await
+        .unwrap()
+        .items
+        .iter()
+        .map(|todo| todo.todo_id)
+        .collect();
+    assert_eq!(ids, vec![single.todo_id, planned.todo_id]);
+
+    assert_eq!(repo.unshare_list(project.list_id, bob).await.unwrap(), 1);
+    assert_eq!(repo.unshare_list(project.list_id, bob).await.unwrap(), 0);
+    repo.delete_todo_by_id(single.todo_id).await.unwrap();
+    assert_eq!(repo.query_todos(query).await.unwrap().total, 0);
+}
+
+#[actix_web::test]
+async fn test_sqlite_data_survives_reopening() {
+    let dir = tempfile::tempdir().unwrap();
+    let url = format!("sqlite://{}", dir.path().join("todos.db").display());
+
+    {
+        let repo = SqliteRepo::new(&url).unwrap();
+        repo.create_todo(create_new_todo("Persistent", None))
+            .await
+            .unwrap();
+    }
+
+    let repo = repository::connect(&url).unwrap();
+    let todos = repo.get_todos().await.unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(todos[0].title, "Persistent");
+}
+
+#[actix_web::test]
+async fn test_sqlite_batches_are_atomic() {
+    let repo = create_test_repo();
+    assert!(repo.atomic_batches());
+    let todo = repo
+        .create_todo(create_new_todo("Existing", None))
+        .await
+        .unwrap();
+
+    // The failing operation is named and the ones before it are undone.
+    let ops = vec![
+        BatchOp::Create {
+            todo: create_new_todo("Rolled back", None),
+        },
+        BatchOp::Complete {
+            id: todo.todo_id,
+            version: None,
+        },
+        BatchOp::Delete {
+            id: todo.todo_id,
+            version: Some(1),
+        },
+    ];
+    let err = repo.apply_batch(ops).await.unwrap_err();
+    assert_eq!(err, RepoError::stale(todo.todo_id, 1).in_batch(2));
+    assert_eq!(err.to_string(), format!("operation 2: {}", err.cause()));
+    let todos = repo.get_todos().await.unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!((todos[0].completed, todos[0].version), (Some(false), 1));
+
+    let ops = vec![
+        BatchOp::Create {
+            todo: create_new_todo("Kept", None),
+        },
+        BatchOp::Update {
+            id: todo.todo_id,
+            patch: TodoPatch {
+                title: Some("Renamed".to_string()),
+                ..Default::default()
+            },
+            version: Some(1),
+        },
+        BatchOp::Complete {
+            id: todo.todo_id,
+            version: Some(2),
+        },
+    ];
+    let results = repo.apply_batch(ops).await.unwrap();
+    assert!(matches!(&results[0], Ok(BatchOutcome::Todo(t)) if t.title == "Kept"));
+    assert!(matches!(&results[2], Ok(BatchOutcome::Todo(t)) if t.version == 3));
+
+    let ship = repo
+        .create_todo(create_new_todo("Ship", None))
+        .await
+        .unwrap();
+    let build = repo
+        .create_todo(create_new_todo("Build", None))
+        .await
+        .unwrap();
+    repo.add_blocker(ship.todo_id, build.todo_id).await.unwrap();
+    let done = repo.complete_all(None).await.unwrap();
+    let ids: Vec<i32> = done.iter().map(|t| t.todo_id).collect();
+    assert_eq!(ids, vec![2, build.todo_id, ship.todo_id]);
+    assert_eq!(repo.delete_completed(None).await.unwrap(), 4);
+    assert!(repo.get_todos().await.unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn test_sqlite_trash() {
+    let repo = create_test_repo();
+    let subtask = |title: &str, parent_id: i32| NewTodo {
+        parent_id: Some(parent_id),
+        ..create_new_todo(title, None)
+    };
+    let mut new_todo = create_new_todo("Release", None);
+    new_todo.auto_complete = true;
+    let release = repo.create_todo(new_todo).await.unwrap();
+    let build = repo
+        .create_todo(subtask("Build", release.todo_id))
+        .await
+        .unwrap();
+    let step = repo
+        .create_todo(subtask("Step", build.todo_id))
+        .await
+        .unwrap();
+    let docs = repo
+        .create_todo(subtask("Docs", release.todo_id))
+        .await
+        .unwrap();
+    let complete = TodoPatch {
+        completed: Some(true),
+        ..Default::default()
+    };
+    repo.patch_todo_by_id(docs.todo_id, complete).await.unwrap();
+    let review = repo
+        .create_todo(create_new_todo("Review", None))
+        .await
+        .unwrap();
+    repo.add_blocker(review.todo_id, step.todo_id)
+        .await
+        .unwrap();
+
+    // Trashing the open subtask completes the parent and frees the blocked todo.
+    assert_eq!(repo.delete_todo_by_id(build.todo_id).await.unwrap(), 2);
+    let release_now = repo.get_todo_by_id(release.todo_id).await.unwrap();
+    assert_eq!(release_now.completed, Some(true));
+    assert_eq!(release_now.progress.unwrap().total, 1);
+    assert_eq!(
+        repo.get_todo_by_id(step.todo_id).await.unwrap_err(),
+        RepoError::NotFound
+    );
+    assert_eq!(repo.get_todos().await.unwrap().len(), 3);
+    let review_now = repo.get_todo_by_id(review.todo_id).await.unwrap();
+    assert!(review_now.blocked_by.is_empty());
+    let trash = repo.get_trash(None).await.unwrap();
+    let ids: Vec<i32> = trash.iter().map(|t| t.todo_id).collect();
+    assert_eq!(ids, vec![build.todo_id, step.todo_id]);
+    assert_eq!(trash[0].deleted_at, trash[1].deleted_at);
+
+    // Restoring brings the subtree back, the subtask alone cannot go first.
+    let result = repo.restore_todo(step.todo_id).await;
+    assert!(matches!(result, Err(RepoError::Conflict(_))));
+    let restored = repo.restore_todo(build.todo_id).await.unwrap();
+    assert_eq!(restored.deleted_at, None);
+    assert_eq!(restored.progress.unwrap().total, 1);
+    assert_eq!(
+        repo.get_descendants(release.todo_id).await.unwrap().len(),
+        3
+    );
+    assert_eq!(
+        repo.restore_todo(build.todo_id).await.unwrap_err(),
+        RepoError::NotFound
+    );
+
+    // Purging deletes for good, the cut-off spares todos trashed later.
+    repo.delete_todo_by_id(release.todo_id).await.unwrap();
+    assert_eq!(repo.purge_todo(step.todo_id).await.unwrap(), 1);
+    let cut_off = chrono::Local::now().naive_local();
+    repo.delete_todo_by_id(review.todo_id).await.unwrap();
+    assert_eq!(repo.purge_trash(cut_off).await.unwrap(), 3);
+    let trash = repo.get_trash(None).await.unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!(trash[0].todo_id, review.todo_id);
+    assert_eq!(repo.purge_todo(review.todo_id).await.unwrap(), 1);
+    assert!(repo.get_todos().await.unwrap().is_empty());
+}
+
+async fn history_ids(repo: &RepoBox, query: HistoryQuery) -> Vec<i32> {
+    let entries = repo.get_history(query).await.unwrap();
+    entries.iter().map(|entry| entry.history_id).collect()
+}
+
+#[actix_web::test]
+async fn test_sqlite_history() {
+    let repo: RepoBox = Arc::new(create_test_repo());
+    let new_user = NewUser {
+        username: "alice".to_string(),
+        password_hash: "hash".to_string(),
+    };
+    let alice = repo.create_user(new_user).await.unwrap().user_id;
+    let audited = AuditRepo::new(repo.clone(), Some(alice));
+    let mut new_todo = create_new_todo("Audited", None);
+    new_todo.owner_id = Some(alice);
+    let todo = audited.create_todo(new_todo).await.unwrap();
+    let patch = TodoPatch {
+        title: Some("Renamed".to_string()),
+        ..Default::default()
+    };
+    audited.patch_todo_by_id(todo.todo_id, patch).await.unwrap();
+    audited.delete_todo_by_id(todo.todo_id).await.unwrap();
+    audited.purge_todo(todo.todo_id).await.unwrap();
+    let other = AuditRepo::new(repo.clone(), None)
+        .create_todo(create_new_todo("Other", None))
+        .await
+        .unwrap();
+
+    // The snapshots survive the todo.
+    let history = repo.get_history(HistoryQuery::default()).await.unwrap();
+    let actions: Vec<HistoryAction> = history.iter().map(|entry| entry.action).collect();
+    assert_eq!(
+        actions,
+        vec![
+            HistoryAction::Created,
+            HistoryAction::Updated,
+            HistoryAction::Deleted,
+            HistoryAction::Purged,
+            HistoryAction::Created
+        ]
+    );
+    assert_eq!(history[1].before.as_ref().unwrap().title, "Audited");
+    assert_eq!(history[1].after.as_ref().unwrap().title, "Renamed");
+    assert_eq!(history[1].owner_id, Some(alice));
+    assert!(history[3].after.is_none());
+    assert!(history[3].before.as_ref().unwrap().deleted_at.is_some());
+
+    let query = HistoryQuery {
+        todo_id: Some(other.todo_id),
+        ..HistoryQuery::default()
+    };
+    assert_eq!(history_ids(&repo, query).await, vec![5]);
+    let query = HistoryQuery {
+        user_id: Some(alice),
+        offset: Some(3),
+        ..HistoryQuery::default()
+    };
+    assert_eq!(history_ids(&repo, query).await, vec![4]);
+    let query = HistoryQuery {
+        visible_to: Some(alice),
+        limit: Some(2),
+        offset: Some(1),
+        ..HistoryQuery::default()
+    };
+    assert_eq!(history_ids(&repo, query).await, vec![2, 3]);
+    let query = HistoryQuery {
+        changed_after: Some(history[4].changed_at),
+        ..HistoryQuery::default()
+    };
+    assert!(history_ids(&repo, query).await.contains(&5));
+    let query = HistoryQuery {
+        changed_before: Some(history[0].changed_at),
+        ..HistoryQuery::default()
+    };
+    assert!(history_ids(&repo, query).await.is_empty());
+}
+
+#[actix_web::test]
+async fn test_sqlite_api_workflow() {
+    let repo: RepoBox = Arc::new(create_test_repo());
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(repo))
+            .app_data(web::Data::new(AuthKeys::new(b"test secret")))
+            .configure(api::api::config),
+    )
+    .await;
+
+    let credentials = json!({ "username": "alice", "password": "correct horse" });
+    let req = test::TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(&credentials)
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::CREATED
+    );
+    let req = test::TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(&credentials)
+        .to_request();
+    let login: LoginResponse = test::call_and_read_body_json(&app, req).await;
+    let auth = (header::AUTHORIZATION, format!("Bearer {}", login.token));
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos")
+        .insert_header(auth.clone())
+        .set_json(json!({ "title": "Via API" }))
+        .to_request();
+    let created: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(created.owner_id, Some(login.user.user_id));
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/api/todos/{}", created.todo_id))
+        .insert_header(auth.clone())
+        .set_json(json!({ "completed": true }))
+        .to_request();
+    let patched: Todo = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(patched.completed, Some(true));
+
+    let req = test::TestRequest::get()
+        .uri("/api/todos?completed=true")
+        .insert_header(auth.clone())
+        .to_request();
+    let todos: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(todos.len(), 1);
+
+    // A failing operation rolls back the whole batch and is named.
+    let req = test::TestRequest::post()
+        .uri("/api/todos/bulk")
+        .insert_header(auth.clone())
+        .set_json(json!({ "operations": [
+            { "op": "create", "todo": { "title": "Rolled back" } },
+            { "op": "delete", "id": 999 },
+        ] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["message"], "operation 1: todo not found");
+    let req = test::TestRequest::get()
+        .uri("/api/todos")
+        .insert_header(auth.clone())
+        .to_request();
+    let todos: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(todos.len(), 1);
+
+    let req = test::TestRequest::post()
+        .uri("/api/todos/bulk")
+        .insert_header(auth.clone())
+        .set_json(json!({ "operations": [
+            { "op": "create", "todo": { "title": "Kept" } },
+        ] }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["atomic"], true);
+    assert_eq!(body["results"][0]["todo"]["title"], "Kept");
+    let req = test::TestRequest::get()
+        .uri("/api/todos")
+        .insert_header(auth.clone())
+        .to_request();
+    let todos: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(todos.len(), 2);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/api/todos/{}", created.todo_id))
+        .insert_header(auth.clone())
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/todos/{}", created.todo_id))
+        .insert_header(auth.clone())
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
@@ -0,0 +1,337 @@
+use chrono::{Duration, Local, NaiveDate};
+use TodoRustBackend::{
+    models::{
+        api_key::{NewApiKey, Scope},
+        list::{NewList, DEFAULT_LIST_ID},
+        tag::NewTag,
+        todo::{NewTodo, Todo, TodoPatch},
+        user::Credentials,
+    },
+    validation::{
+        Validate, API_KEY_NAME_MAX_LEN, LIST_NAME_MAX_LEN, PASSWORD_MIN_LEN, TAG_NAME_MAX_LEN,
+        TITLE_MAX_LEN, USERNAME_MAX_LEN,
+    },
+};
+
+fn new_todo(title: &str, description: Option<&str>) -> NewTodo {
+    NewTodo {
+        title: title.to_string(),
+        description: description.map(|s| s.to_string()),
+        created_at: None,
+        completed: Some(false),
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+    }
+}
+
+fn fields(errors: Vec<TodoRustBackend::validation::FieldError>) -> Vec<(String, String)> {
+    errors.into_iter().map(|e| (e.field, e.code)).collect()
+}
+
+#[test]
+fn test_valid_new_todo() {
+    assert!(new_todo("Buy milk", Some("2 litres")).validate().is_ok());
+    assert!(new_todo("Buy milk", None).validate().is_ok());
+}
+
+#[test]
+fn test_empty_description_is_allowed() {
+    assert!(new_todo("Buy milk", Some("")).validate().is_ok());
+}
+
+#[test]
+fn test_blank_title_is_rejected() {
+    for title in ["", "   ", "\t\n"] {
+        let errors = new_todo(title, None).validate().unwrap_err();
+        assert_eq!(
+            fields(errors),
+            vec![("title".to_string(), "blank".to_string())]
+        );
+    }
+}
+
+#[test]
+fn test_title_length_limit() {
+    assert!(new_todo(&"a".repeat(TITLE_MAX_LEN), None)
+        .validate()
+        .is_ok());
+
+    let errors = new_todo(&"a".repeat(TITLE_MAX_LEN + 1), None)
+        .validate()
+        .unwrap_err();
+    assert_eq!(
+        fields(errors),
+        vec![("title".to_string(), "too_long".to_string())]
+    );
+}
+
+#[test]
+fn test_title_length_counts_characters_not_bytes() {
+    // "ü" is two bytes in UTF-8 but a single character in VARCHAR(255).
+    assert!(new_todo(&"ü".repeat(TITLE_MAX_LEN), None)
+        .validate()
+        .is_ok());
+}
+
+#[test]
+fn test_whitespace_only_description_is_rejected() {
+    let errors = new_todo("Title", Some("   ")).validate().unwrap_err();
+    assert_eq!(
+        fields(errors),
+        vec![("description".to_string(), "blank".to_string())]
+    );
+}
+
+#[test]
+fn test_created_at_in_future_is_rejected() {
+    let mut todo = new_todo("Title", None);
+    todo.created_at = Some(Local::now().naive_local() + Duration::days(1));
+    let errors = todo.validate().unwrap_err();
+    assert_eq!(
+        fields(errors),
+        vec![("created_at".to_string(), "in_future".to_string())]
+    );
+
+    todo.created_at = Some(Local::now().naive_local() - Duration::days(1));
+    assert!(todo.validate().is_ok());
+}
+
+#[test]
+fn test_all_violations_are_reported() {
+    let mut todo = new_todo(" ", Some(" "));
+    todo.created_at = Some(Local::now().naive_local() + Duration::days(1));
+    let errors = todo.validate().unwrap_err();
+    assert_eq!(errors.len(), 3);
+}
+
+#[test]
+fn test_todo_is_validated_like_new_todo() {
+    let todo = Todo {
+        todo_id: 1,
+        title: "".to_string(),
+        description: Some(" ".to_string()),
+        created_at: None,
+        completed: None,
+        due_at: None,
+        remind_at: None,
+        recurrence: None,
+        list_id: DEFAULT_LIST_ID,
+        parent_id: None,
+        auto_complete: false,
+        owner_id: None,
+        version: 1,
+        deleted_at: None,
+        tags: Vec::new(),
+        blocked_by: Vec::new(),
+        progress: None,
+    };
+    assert_eq!(todo.validate().unwrap_err().len(), 2);
+}
+
+#[test]
+fn test_patch_only_checks_present_fields() {
+    assert!(TodoPatch::default().validate().is_ok());
+
+    let patch = TodoPatch {
+        description: Some(None),
+        ..Default::default()
+    };
+    assert!(patch.validate().is_ok());
+
+    let patch = TodoPatch {
+        title: Some(" ".to_string()),
+        description: Some(Some("\n".to_string())),
+        completed: Some(true),
+        ..Default::default()
+    };
+    assert_eq!(patch.validate().unwrap_err().len(), 2);
+}
+
+#[test]
+fn test_remind_at_must_not_be_after_due_at() {
+    let at = |day| NaiveDate::from_ymd_opt(2030, 1, day).and_then(|d| d.and_hms_opt(9, 0, 0));
+
+    let mut todo = new_todo("Pay rent", None);
+    todo.due_at = at(10);
+    todo.remind_at = at(10);
+    assert!(todo.validate().is_ok());
+
+    todo.remind_at = at(11);
+    let errors = todo.validate().unwrap_err();
+    assert_eq!(errors[0].field, "remind_at");
+    assert_eq!(errors[0].code, "after_due_at");
+
+    // A reminder without due date, or a due date alone, is fine.
+    todo.due_at = None;
+    assert!(todo.validate().is_ok());
+
+    let patch = TodoPatch {
+        due_at: Some(at(10)),
+        remind_at: Some(at(11)),
+        ..Default::default()
+    };
+    assert_eq!(patch.validate().unwrap_err()[0].code, "after_due_at");
+    let patch = TodoPatch {
+        remind_at: Some(at(11)),
+        ..Default::default()
+    };
+    assert!(patch.validate().is_ok());
+
+    // A single field is checked against the stored todo.
+    let mut stored = new_todo("Pay rent", None).with_id(1);
+    stored.due_at = at(10);
+    assert_eq!(
+        patch.validate_against(&stored).unwrap_err()[0].code,
+        "after_due_at"
+    );
+    stored.remind_at = at(10);
+    let patch = TodoPatch {
+        due_at: Some(at(9)),
+        ..Default::default()
+    };
+    assert_eq!(
+        patch.validate_against(&stored).unwrap_err()[0].code,
+        "after_due_at"
+    );
+    let patch = TodoPatch {
+        due_at: Some(None),
+        ..Default::default()
+    };
+    assert!(patch.validate_against(&stored).is_ok());
+}
+
+#[test]
+fn test_recurrence_rule_is_validated() {
+    let mut todo = new_todo("Standup", None);
+    todo.recurrence = Some("FREQ=WEEKLY;BYDAY=MO,FR".to_string());
+    assert!(todo.validate().is_ok());
+
+    todo.recurrence = Some("FREQ=HOURLY".to_string());
+    assert_eq!(
+        fields(todo.validate().unwrap_err()),
+        vec![("recurrence".to_string(), "invalid".to_string())]
+    );
+
+    todo.recurrence = Some(format!("FREQ=DAILY;{}", "x".repeat(300)));
+    assert_eq!(todo.validate().unwrap_err()[0].code, "too_long");
+
+    let patch = TodoPatch {
+        recurrence: Some(Some("FREQ=MONTHLY".to_string())),
+        ..Default::default()
+    };
+    assert_eq!(patch.validate().unwrap_err()[0].code, "invalid");
+    let patch = TodoPatch {
+        recurrence: Some(None),
+        ..Default::default()
+    };
+    assert!(patch.validate().is_ok());
+}
+
+#[test]
+fn test_tag_name_rules() {
+    let tag = |name: &str| NewTag {
+        name: name.to_string(),
+        owner_id: None,
+    };
+    assert!(tag("urgent").validate().is_ok());
+    assert!(tag(&"ä".repeat(TAG_NAME_MAX_LEN)).validate().is_ok());
+
+    for (name, code) in [
+        ("", "blank"),
+        ("  ", "blank"),
+        (&"x".repeat(TAG_NAME_MAX_LEN + 1), "too_long"),
+        ("ops,urgent", "invalid"),
+    ] {
+        assert_eq!(
+            fields(tag(name).validate().unwrap_err()),
+            vec![("name".to_string(), code.to_string())]
+        );
+    }
+}
+
+#[test]
+fn test_list_name_rules() {
+    let list = |name: &str| NewList {
+        name: name.to_string(),
+        owner_id: None,
+    };
+    assert!(list("Groceries, household").validate().is_ok());
+    assert!(list(&"ä".repeat(LIST_NAME_MAX_LEN)).validate().is_ok());
+
+    for (name, code) in [
+        ("", "blank"),
+        ("\t", "blank"),
+        (&"x".repeat(LIST_NAME_MAX_LEN + 1), "too_long"),
+    ] {
+        assert_eq!(
+            fields(list(name).validate().unwrap_err()),
+            vec![("name".to_string(), code.to_string())]
+        );
+    }
+}
+
+#[test]
+fn test_credentials_are_validated() {
+    let credentials = |username: &str, password: &str| Credentials {
+        username: username.to_string(),
+        password: password.to_string(),
+    };
+    assert!(credentials("alice.b-c_d", "correct horse")
+        .validate()
+        .is_ok());
+
+    let errors = credentials("", "short").validate().unwrap_err();
+    assert_eq!(
+        fields(errors),
+        vec![
+            ("username".to_string(), "blank".to_string()),
+            ("password".to_string(), "too_short".to_string()),
+        ]
+    );
+    let short = "x".repeat(PASSWORD_MIN_LEN - 1);
+    assert_eq!(
+        credentials("alice", &short).validate().unwrap_err()[0].code,
+        "too_short"
+    );
+    let long = "a".repeat(USERNAME_MAX_LEN + 1);
+    assert_eq!(
+        credentials(&long, "correct horse").validate().unwrap_err()[0].code,
+        "too_long"
+    );
+    let errors = credentials("alice smith", "correct horse")
+        .validate()
+        .unwrap_err();
+    assert_eq!(errors[0].code, "invalid");
+}
+
+#[test]
+fn test_api_keys_are_validated() {
+    let now = Local::now().naive_local();
+    let key = |name: &str, scopes: Vec<Scope>| NewApiKey {
+        name: name.to_string(),
+        scopes,
+        expires_at: Some(now + Duration::days(30)),
+    };
+    assert!(key("ci bot", vec![Scope::Write]).validate().is_ok());
+
+    let mut expired = key("", Vec::new());
+    expired.expires_at = Some(now - Duration::minutes(1));
+    assert_eq!(
+        fields(expired.validate().unwrap_err()),
+        vec![
+            ("name".to_string(), "blank".to_string()),
+            ("scopes".to_string(), "empty".to_string()),
+            ("expires_at".to_string(), "in_past".to_string()),
+        ]
+    );
+    let long = "k".repeat(API_KEY_NAME_MAX_LEN + 1);
+    assert_eq!(
+        key(&long, vec![Scope::Read]).validate().unwrap_err()[0].code,
+        "too_long"
+    );
+}
@@ -1,7 +1,40 @@
 import axios from 'axios'
-import { Todo, NewTodo } from '../types';
+import { Todo, NewTodo, Credentials, LoginResponse, ApiKey, NewApiKey, IssuedApiKey, Role, Share, TodoEvent, BatchOp, BulkResponse, HistoryEntry, HistoryQuery } from '../types';
 
 const API_URL = 'http://localhost:8080/api/todos';
+const AUTH_URL = 'http://localhost:8080/api/auth';
+const KEYS_URL = 'http://localhost:8080/api/keys';
+const LISTS_URL = 'http://localhost:8080/api/lists';
+const EVENTS_URL = 'http://localhost:8080/api/events';
+const TRASH_URL = 'http://localhost:8080/api/trash';
+const AUDIT_URL = 'http://localhost:8080/api/audit';
+
+export const register = async (credentials: Credentials) => {
+  return await axios.post(`${AUTH_URL}/register`, credentials);
+}
+
+// Sends the token with every following request.
+export const login = async (credentials: Credentials) => {
+  const response = await axios.post<LoginResponse>(`${AUTH_URL}/login`, credentials);
+  axios.defaults.headers.common['Authorization'] = `Bearer ${response.data.token}`;
+  return response;
+}
+
+export const logout = () => {
+  delete axios.defaults.headers.common['Authorization'];
+}
+
+export const getApiKeys = async () => {
+  return await axios.get<ApiKey[]>(KEYS_URL);
+}
+
+export const createApiKey = async (key: NewApiKey) => {
+  return await axios.post<IssuedApiKey>(KEYS_URL, key);
+}
+
+export const deleteApiKey = async (id: number) => {
+  return await axios.delete(`${KEYS_URL}/${id}`);
+}
 
 export const getTodos = async () => {
     return await axios.get(API_URL);
@@ -15,11 +48,90 @@ export const createTodo = async (todo: NewTodo) => {
   return await axios.post(API_URL, todo);
 }
 
-export const updateTodo = async (todo: Todo) => {
-  return await axios.put(`${API_URL}/${todo.todo_id}`, todo);
+// With the `ETag` of the todo the change fails with a 412 if someone else changed it meanwhile.
+const ifMatch = (etag?: string) => (etag ? { headers: { 'If-Match': etag } } : undefined);
+
+export const updateTodo = async (todo: Todo, etag?: string) => {
+  return await axios.put(`${API_URL}/${todo.todo_id}`, todo, ifMatch(etag));
+}
+
+export const deleteTodo = async (id: number, etag?: string) => {
+  return await axios.delete(`${API_URL}/${id}`, ifMatch(etag));
+}
+
+export const bulkTodos = async (operations: BatchOp[]) => {
+  return await axios.post<BulkResponse>(`${API_URL}/bulk`, { operations });
+}
+
+export const completeAllTodos = async (listId?: number) => {
+  return await axios.post<Todo[]>(`${API_URL}/complete-all`, null, { params: { list_id: listId } });
+}
+
+// Returns the number of deleted todos, subtasks included.
+export const deleteCompletedTodos = async (listId?: number) => {
+  return await axios.delete<number>(`${API_URL}/completed`, { params: { list_id: listId } });
+}
+
+export const getTrash = async () => {
+  return await axios.get<Todo[]>(TRASH_URL);
+}
+
+export const restoreTodo = async (id: number) => {
+  return await axios.post<Todo>(`${API_URL}/${id}/restore`);
+}
+
+// Both return the number of deleted todos, subtasks included.
+export const purgeTodo = async (id: number) => {
+  return await axios.delete<number>(`${TRASH_URL}/${id}`);
+}
+
+export const emptyTrash = async () => {
+  return await axios.delete<number>(TRASH_URL);
+}
+
+export const getTodoHistory = async (id: number, query?: HistoryQuery) => {
+  return await axios.get<HistoryEntry[]>(`${API_URL}/${id}/history`, { params: query });
+}
+
+export const getAudit = async (query?: HistoryQuery) => {
+  return await axios.get<HistoryEntry[]>(AUDIT_URL, { params: query });
 }
 
-export const deleteTodo = async (id: number) => {
-  return await axios.delete(`${API_URL}/${id}`);
+export const getTodoShares = async (id: number) => {
+  return await axios.get<Share[]>(`${API_URL}/${id}/shares`);
 }
 
+export const shareTodo = async (id: number, userId: number, role: Role) => {
+  return await axios.put<Share>(`${API_URL}/${id}/shares/${userId}`, { role });
+}
+
+export const unshareTodo = async (id: number, userId: number) => {
+  return await axios.delete(`${API_URL}/${id}/shares/${userId}`);
+}
+
+export const getListShares = async (id: number) => {
+  return await axios.get<Share[]>(`${LISTS_URL}/${id}/shares`);
+}
+
+export const shareList = async (id: number, userId: number, role: Role) => {
+  return await axios.put<Share>(`${LISTS_URL}/${id}/shares/${userId}`, { role });
+}
+
+export const unshareList = async (id: number, userId: number) => {
+  return await axios.delete(`${LISTS_URL}/${id}/shares/${userId}`);
+}
+
+// Calls onEvent for every change of a visible todo and onReset when events were missed
+// and the todos have to be loaded again. Close the returned EventSource to stop.
+export const subscribeEvents = (
+  token: string,
+  onEvent: (event: TodoEvent) => void,
+  onReset: () => void,
+) => {
+  const source = new EventSource(`${EVENTS_URL}?access_token=${encodeURIComponent(token)}`);
+  for (const kind of ['created', 'updated', 'deleted']) {
+    source.addEventListener(kind, (message) => onEvent(JSON.parse((message as MessageEvent).data)));
+  }
+  source.addEventListener('reset', onReset);
+  return source;
+}
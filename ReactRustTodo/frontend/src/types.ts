@@ -1,13 +1,162 @@
+export interface Tag {
+    tag_id: number;
+    name: string;
+}
+
+export interface TodoList {
+    list_id: number;
+    name: string;
+    owner_id?: number | null;
+}
+
+export interface Progress {
+    completed: number;
+    total: number;
+}
+
 export interface Todo {
     todo_id: number;
     title: string | null;
     description: string | null;
     createdAt: string;
     completed: boolean;
+    due_at?: string | null;
+    remind_at?: string | null;
+    recurrence?: string | null;
+    list_id?: number;
+    parent_id?: number | null;
+    auto_complete?: boolean;
+    owner_id?: number | null;
+    // Counts up with every change.
+    version?: number;
+    tags?: Tag[];
+    blocked_by?: number[];
+    progress?: Progress;
+    // Only set by GET /api/todos, true for todos of other users shared with the caller.
+    shared?: boolean;
+    // Only set for todos in the trash.
+    deleted_at?: string;
+}
+
+export interface TodoTree extends Todo {
+    children: TodoTree[];
 }
 
 export interface NewTodo {
     title: string;
     description: string | null;
     completed: boolean;
-}
\ No newline at end of file
+    due_at?: string | null;
+    remind_at?: string | null;
+    recurrence?: string | null;
+    list_id?: number;
+    parent_id?: number | null;
+    auto_complete?: boolean;
+}
+
+export interface User {
+    user_id: number;
+    username: string;
+    created_at: string;
+}
+
+export interface Credentials {
+    username: string;
+    password: string;
+}
+
+export interface LoginResponse {
+    token: string;
+    token_type: string;
+    expires_in: number;
+    user: User;
+}
+
+export type Scope = 'read' | 'write' | 'admin';
+
+export interface ApiKey {
+    key_id: number;
+    user_id: number;
+    name: string;
+    prefix: string;
+    scopes: Scope[];
+    expires_at: string | null;
+    created_at: string;
+}
+
+export interface NewApiKey {
+    name: string;
+    scopes: Scope[];
+    expires_at?: string | null;
+}
+
+// Returned once when a key is issued, the secret cannot be fetched again.
+export interface IssuedApiKey extends ApiKey {
+    secret: string;
+}
+
+export type Role = 'viewer' | 'editor' | 'owner';
+
+export interface Share {
+    user_id: number;
+    role: Role;
+}
+
+export type EventKind = 'created' | 'updated' | 'deleted';
+
+// Data of the events sent by GET /api/events, `todo` is null for deleted todos.
+export interface TodoEvent {
+    event_id: number;
+    kind: EventKind;
+    todo_id: number;
+    todo: Todo | null;
+}
+
+// One operation of POST /api/todos/bulk.
+export type BatchOp =
+    | { op: 'create'; todo: NewTodo }
+    | { op: 'update'; id: number; patch: Partial<Todo>; version?: number }
+    | { op: 'delete'; id: number; version?: number }
+    | { op: 'complete'; id: number; version?: number };
+
+export interface ErrorResponse {
+    code: string;
+    message: string;
+}
+
+export interface BulkItem {
+    index: number;
+    status: number;
+    todo?: Todo;
+    deleted?: number;
+    error?: ErrorResponse;
+}
+
+// `atomic` batches either fully succeed or fail with a single error.
+export interface BulkResponse {
+    atomic: boolean;
+    results: BulkItem[];
+}
+
+export type HistoryAction = 'created' | 'updated' | 'deleted' | 'restored' | 'purged';
+
+// One entry of the audit log, `user_id` is null for changes made by the server.
+export interface HistoryEntry {
+    history_id: number;
+    todo_id: number;
+    owner_id: number | null;
+    user_id: number | null;
+    action: HistoryAction;
+    changed_at: string;
+    before: Todo | null;
+    after: Todo | null;
+}
+
+export interface HistoryQuery {
+    todo_id?: number;
+    user_id?: number;
+    changed_after?: string;
+    changed_before?: string;
+    limit?: number;
+    offset?: number;
+}